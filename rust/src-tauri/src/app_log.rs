@@ -0,0 +1,136 @@
+//! 日志文件
+//! 测速日志写入配置目录下按大小轮转的日志文件（GUI 用户看不到 stderr，崩溃后也能排查），
+//! debug 构建同时输出到 stderr
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// 单个日志文件大小上限，超过后轮转
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+/// 保留的历史日志文件数（anyfast.log.1 ~ anyfast.log.N）
+const MAX_ROTATED_FILES: usize = 3;
+
+static APP_LOG: OnceLock<RotatingLog> = OnceLock::new();
+
+/// 日志文件路径（配置目录/logs/anyfast.log）
+pub fn log_path() -> PathBuf {
+    crate::config::data_dir().join("logs").join("anyfast.log")
+}
+
+/// 写入一行带时间戳与级别的日志
+pub fn write(level: &str, message: &str) {
+    let line = format!(
+        "[{}] [{}] {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+        level,
+        message
+    );
+    if cfg!(debug_assertions) {
+        eprintln!("{}", line);
+    }
+    APP_LOG
+        .get_or_init(|| RotatingLog::new(log_path(), MAX_LOG_BYTES, MAX_ROTATED_FILES))
+        .append(&line);
+}
+
+/// 按大小轮转的追加写日志文件
+struct RotatingLog {
+    path: PathBuf,
+    max_bytes: u64,
+    max_rotated: usize,
+    /// 当前文件句柄与已写入字节数（首次写入时打开）
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl RotatingLog {
+    fn new(path: PathBuf, max_bytes: u64, max_rotated: usize) -> Self {
+        Self {
+            path,
+            max_bytes,
+            max_rotated,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn append(&self, line: &str) {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = open_append(&self.path);
+        }
+        if guard
+            .as_ref()
+            .is_some_and(|(_, size)| *size >= self.max_bytes)
+        {
+            *guard = None; // 先关闭句柄，Windows 下才能重命名
+            rotate(&self.path, self.max_rotated);
+            *guard = open_append(&self.path);
+        }
+        if let Some((file, size)) = guard.as_mut() {
+            if writeln!(file, "{}", line).is_ok() {
+                *size += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+fn open_append(path: &Path) -> Option<(File, u64)> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).ok();
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .ok()?;
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    Some((file, size))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// anyfast.log → .1 → .2 …，超出保留数的最旧文件被覆盖
+fn rotate(path: &Path, max_rotated: usize) {
+    if max_rotated == 0 {
+        fs::remove_file(path).ok();
+        return;
+    }
+    for index in (1..max_rotated).rev() {
+        fs::rename(rotated_path(path, index), rotated_path(path, index + 1)).ok();
+    }
+    fs::rename(path, rotated_path(path, 1)).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rotates_when_size_exceeded() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("logs").join("anyfast.log");
+        let log = RotatingLog::new(path.clone(), 64, 2);
+
+        for i in 0..20 {
+            log.append(&format!("line {:02} ..............................", i));
+        }
+
+        assert!(path.exists());
+        assert!(rotated_path(&path, 1).exists());
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+
+        // 最新的行在当前文件中，且每个文件都不会无限增长
+        let current = fs::read_to_string(&path).unwrap();
+        assert!(current.contains("line 19"));
+        for file in [path.clone(), rotated_path(&path, 1), rotated_path(&path, 2)] {
+            assert!(fs::metadata(file).unwrap().len() <= 64 + 48);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Linux / macOS 开机自启动
+//! Linux 通过 XDG autostart 规范在 ~/.config/autostart 下写入/删除 .desktop 文件；
+//! macOS 在 ~/Library/LaunchAgents 下写入 LaunchAgent plist
+
+use directories::BaseDirs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 自启动 desktop 文件名
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub const DESKTOP_FILE_NAME: &str = "anyFAST.desktop";
+
+/// LaunchAgent 标识（同时作为 plist 文件名）
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub const LAUNCH_AGENT_LABEL: &str = "com.anyrouter.fast";
+
+/// XDG 自启动目录（~/.config/autostart，遵循 XDG_CONFIG_HOME）
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn autostart_dir() -> Result<PathBuf, String> {
+    BaseDirs::new()
+        .map(|dirs| dirs.config_dir().join("autostart"))
+        .ok_or_else(|| "无法获取用户主目录，无法配置开机自启动".to_string())
+}
+
+/// 按 desktop entry 规范转义 Exec 中的程序路径（含空格等字符时加引号）
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn quote_exec_path(exe: &Path) -> String {
+    let raw = exe.to_string_lossy();
+    let needs_quote = raw
+        .chars()
+        .any(|c| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c));
+    if !needs_quote {
+        return raw.into_owned();
+    }
+    let mut quoted = String::with_capacity(raw.len() + 2);
+    quoted.push('"');
+    for c in raw.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 生成自启动 desktop 文件内容
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn desktop_entry(exe: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=anyFAST\n\
+         Comment=anyFAST 中转站测速优选\n\
+         Exec={}\n\
+         Terminal=false\n\
+         X-GNOME-Autostart-enabled=true\n",
+        quote_exec_path(exe)
+    )
+}
+
+/// desktop 文件是否指向当前程序且未被禁用
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn is_entry_enabled_for(contents: &str, exe: &Path) -> bool {
+    let expected = quote_exec_path(exe);
+    let mut exec_matches = false;
+    for line in contents.lines().map(str::trim) {
+        if let Some(exec) = line.strip_prefix("Exec=") {
+            exec_matches = exec.trim() == expected;
+        } else if let Some(flag) = line.strip_prefix("X-GNOME-Autostart-enabled=") {
+            if flag.trim().eq_ignore_ascii_case("false") {
+                return false;
+            }
+        }
+    }
+    exec_matches
+}
+
+/// 写入或删除自启动 desktop 文件（目录不存在时自动创建）
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn set_enabled_in(dir: &Path, exe: &Path, enabled: bool) -> Result<(), String> {
+    let path = dir.join(DESKTOP_FILE_NAME);
+    if enabled {
+        fs::create_dir_all(dir).map_err(|e| format!("无法创建自启动目录: {}", e))?;
+        fs::write(&path, desktop_entry(exe)).map_err(|e| format!("无法写入自启动文件: {}", e))
+    } else {
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            // 文件不存在视为已关闭
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("无法删除自启动文件: {}", e)),
+        }
+    }
+}
+
+/// 自启动文件存在且 Exec 与当前程序一致时视为已开启
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn is_enabled_in(dir: &Path, exe: &Path) -> bool {
+    fs::read_to_string(dir.join(DESKTOP_FILE_NAME))
+        .map(|contents| is_entry_enabled_for(&contents, exe))
+        .unwrap_or(false)
+}
+
+// ===== macOS LaunchAgent =====
+
+/// LaunchAgents 目录（~/Library/LaunchAgents）
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn launch_agents_dir() -> Result<PathBuf, String> {
+    BaseDirs::new()
+        .map(|dirs| dirs.home_dir().join("Library").join("LaunchAgents"))
+        .ok_or_else(|| "无法获取用户主目录，无法配置开机自启动".to_string())
+}
+
+/// LaunchAgent plist 路径
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn launch_agent_path(dir: &Path) -> PathBuf {
+    dir.join(format!("{}.plist", LAUNCH_AGENT_LABEL))
+}
+
+/// XML 文本转义
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn xml_escape(raw: &str) -> String {
+    raw.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// 生成 LaunchAgent plist 内容
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn launch_agent_plist(exe: &Path) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        LAUNCH_AGENT_LABEL,
+        xml_escape(&exe.to_string_lossy())
+    )
+}
+
+/// plist 的 ProgramArguments 是否指向当前程序
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn plist_references(contents: &str, exe: &Path) -> bool {
+    let expected = format!("<string>{}</string>", xml_escape(&exe.to_string_lossy()));
+    contents
+        .split("<key>ProgramArguments</key>")
+        .nth(1)
+        .and_then(|rest| rest.split("</array>").next())
+        .map(|args| args.lines().map(str::trim).any(|line| line == expected))
+        .unwrap_or(false)
+}
+
+/// plist 存在且引用当前程序时视为已开启
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn is_launch_agent_enabled_in(dir: &Path, exe: &Path) -> bool {
+    fs::read_to_string(launch_agent_path(dir))
+        .map(|contents| plist_references(&contents, exe))
+        .unwrap_or(false)
+}
+
+/// 写入 plist（目录不存在时自动创建），覆盖旧路径生成的 plist
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn write_launch_agent_in(dir: &Path, exe: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("无法创建 LaunchAgents 目录: {}", e))?;
+    let path = launch_agent_path(dir);
+    fs::write(&path, launch_agent_plist(exe)).map_err(|e| format!("无法写入自启动文件: {}", e))?;
+    Ok(path)
+}
+
+/// 删除 plist（不存在视为已关闭）
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn remove_launch_agent_in(dir: &Path) -> Result<(), String> {
+    match fs::remove_file(launch_agent_path(dir)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("无法删除自启动文件: {}", e)),
+    }
+}
+
+/// 开启/关闭 macOS 开机自启动并同步 launchctl 注册状态
+#[cfg(target_os = "macos")]
+pub fn set_launch_agent(enabled: bool) -> Result<(), String> {
+    use std::process::Command;
+
+    let dir = launch_agents_dir()?;
+    let path = launch_agent_path(&dir);
+
+    // 先卸载已注册的旧 plist（程序移动到 /Applications 后路径会变化），忽略未加载的错误
+    if path.exists() {
+        let _ = Command::new("launchctl").arg("unload").arg(&path).output();
+    }
+
+    if !enabled {
+        return remove_launch_agent_in(&dir);
+    }
+
+    let exe_path = std::env::current_exe().map_err(|e| format!("无法获取程序路径: {}", e))?;
+    let path = write_launch_agent_in(&dir, &exe_path)?;
+    let output = Command::new("launchctl")
+        .arg("load")
+        .arg(&path)
+        .output()
+        .map_err(|e| format!("执行 launchctl load 失败: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("launchctl load 失败: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_enable_creates_dir_and_entry() {
+        let dir = TempDir::new().unwrap();
+        let autostart = dir.path().join("config").join("autostart");
+        let exe = Path::new("/opt/anyFAST/anyfast");
+
+        set_enabled_in(&autostart, exe, true).unwrap();
+        let contents = fs::read_to_string(autostart.join(DESKTOP_FILE_NAME)).unwrap();
+        assert!(contents.contains("Exec=/opt/anyFAST/anyfast\n"));
+        assert!(contents.contains("X-GNOME-Autostart-enabled=true"));
+        assert!(is_enabled_in(&autostart, exe));
+
+        set_enabled_in(&autostart, exe, false).unwrap();
+        assert!(!is_enabled_in(&autostart, exe));
+        // 重复关闭不报错
+        set_enabled_in(&autostart, exe, false).unwrap();
+    }
+
+    #[test]
+    fn test_entry_for_other_binary_is_not_enabled() {
+        let dir = TempDir::new().unwrap();
+        set_enabled_in(dir.path(), Path::new("/old/anyfast"), true).unwrap();
+        assert!(!is_enabled_in(dir.path(), Path::new("/new/anyfast")));
+    }
+
+    #[test]
+    fn test_exec_path_with_spaces_is_quoted() {
+        let exe = Path::new("/home/me/My Apps/anyfast");
+        let contents = desktop_entry(exe);
+        assert!(contents.contains("Exec=\"/home/me/My Apps/anyfast\"\n"));
+        assert!(is_entry_enabled_for(&contents, exe));
+    }
+
+    #[test]
+    fn test_disabled_flag_is_respected() {
+        let exe = Path::new("/usr/bin/anyfast");
+        let contents = desktop_entry(exe).replace(
+            "X-GNOME-Autostart-enabled=true",
+            "X-GNOME-Autostart-enabled=false",
+        );
+        assert!(!is_entry_enabled_for(&contents, exe));
+    }
+
+    #[test]
+    fn test_launch_agent_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let agents = dir.path().join("Library").join("LaunchAgents");
+        let exe = Path::new("/Applications/anyFAST.app/Contents/MacOS/anyFAST");
+
+        let path = write_launch_agent_in(&agents, exe).unwrap();
+        assert!(path.ends_with("com.anyrouter.fast.plist"));
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("<key>RunAtLoad</key>\n    <true/>"));
+        assert!(is_launch_agent_enabled_in(&agents, exe));
+
+        remove_launch_agent_in(&agents).unwrap();
+        assert!(!is_launch_agent_enabled_in(&agents, exe));
+        remove_launch_agent_in(&agents).unwrap();
+    }
+
+    #[test]
+    fn test_launch_agent_rewritten_after_binary_moves() {
+        let dir = TempDir::new().unwrap();
+        let old_exe = Path::new("/Users/me/Downloads/anyFAST.app/Contents/MacOS/anyFAST");
+        let new_exe = Path::new("/Applications/anyFAST.app/Contents/MacOS/anyFAST");
+
+        write_launch_agent_in(dir.path(), old_exe).unwrap();
+        // 程序移动后旧 plist 不再视为已开启
+        assert!(!is_launch_agent_enabled_in(dir.path(), new_exe));
+
+        write_launch_agent_in(dir.path(), new_exe).unwrap();
+        assert!(is_launch_agent_enabled_in(dir.path(), new_exe));
+        assert!(!is_launch_agent_enabled_in(dir.path(), old_exe));
+    }
+
+    #[test]
+    fn test_launch_agent_escapes_path() {
+        let exe = Path::new("/Users/me/A&B <apps>/anyFAST");
+        let contents = launch_agent_plist(exe);
+        assert!(contents.contains("<string>/Users/me/A&amp;B &lt;apps&gt;/anyFAST</string>"));
+        assert!(plist_references(&contents, exe));
+    }
+}
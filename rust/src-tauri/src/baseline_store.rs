@@ -0,0 +1,160 @@
+//! 基准延迟持久化
+//! 将各域名的基准延迟连同更新时间写入磁盘，重启后恢复，超过有效期的条目在加载时丢弃
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 基准有效期：24 小时（网络环境变化后旧基准不再可信）
+pub const BASELINE_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct StoredBaseline {
+    latency: f64,
+    /// 更新时间（Unix 秒）
+    updated_at: i64,
+}
+
+pub struct BaselineStore {
+    path: PathBuf,
+    max_age_secs: u64,
+    /// 与磁盘文件同步的条目（保留每个域名各自的更新时间）
+    entries: Mutex<HashMap<String, StoredBaseline>>,
+}
+
+impl BaselineStore {
+    pub fn new() -> Self {
+        let path = crate::config::data_dir().join("baselines.json");
+
+        Self::with_path(path, BASELINE_MAX_AGE_SECS)
+    }
+
+    /// Create a BaselineStore with a custom path
+    pub fn with_path(path: PathBuf, max_age_secs: u64) -> Self {
+        Self {
+            path,
+            max_age_secs,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn now_timestamp() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+    }
+
+    /// 加载未过期的基准（文件缺失或损坏时返回空表）
+    pub fn load(&self) -> HashMap<String, f64> {
+        self.load_at(Self::now_timestamp())
+    }
+
+    fn load_at(&self, now: i64) -> HashMap<String, f64> {
+        let stored: HashMap<String, StoredBaseline> = fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let fresh: HashMap<String, StoredBaseline> = stored
+            .into_iter()
+            .filter(|(_, entry)| {
+                let age = now.saturating_sub(entry.updated_at);
+                age >= 0 && (age as u64) < self.max_age_secs && entry.latency > 0.0
+            })
+            .collect();
+
+        let baselines = fresh
+            .iter()
+            .map(|(domain, entry)| (domain.clone(), entry.latency))
+            .collect();
+        *self.entries.lock().unwrap_or_else(|e| e.into_inner()) = fresh;
+        baselines
+    }
+
+    /// 记录一批基准更新并写盘（失败仅记录日志，不影响主流程）
+    pub fn record(&self, updates: &[(String, f64)]) {
+        self.record_at(updates, Self::now_timestamp());
+    }
+
+    fn record_at(&self, updates: &[(String, f64)], now: i64) {
+        if updates.is_empty() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        for (domain, latency) in updates {
+            entries.insert(
+                domain.clone(),
+                StoredBaseline {
+                    latency: *latency,
+                    updated_at: now,
+                },
+            );
+        }
+        match serde_json::to_string_pretty(&*entries) {
+            Ok(content) => {
+                if let Err(e) = fs::write(&self.path, content) {
+                    eprintln!("写入基准延迟失败: {}", e);
+                }
+            }
+            Err(e) => eprintln!("序列化基准延迟失败: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_missing_file_loads_empty() {
+        let dir = TempDir::new().unwrap();
+        let store = BaselineStore::with_path(dir.path().join("baselines.json"), 3600);
+        assert!(store.load().is_empty());
+    }
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("baselines.json");
+        let store = BaselineStore::with_path(path.clone(), 3600);
+        store.record_at(
+            &[("a.com".to_string(), 120.0), ("b.com".to_string(), 80.5)],
+            1_000_000,
+        );
+
+        // 模拟重启：新实例从同一文件加载
+        let reloaded = BaselineStore::with_path(path, 3600);
+        let baselines = reloaded.load_at(1_000_100);
+        assert_eq!(baselines.len(), 2);
+        assert_eq!(baselines.get("a.com"), Some(&120.0));
+        assert_eq!(baselines.get("b.com"), Some(&80.5));
+    }
+
+    #[test]
+    fn test_stale_entries_are_dropped_per_domain() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("baselines.json");
+        let store = BaselineStore::with_path(path.clone(), 3600);
+        store.record_at(&[("old.com".to_string(), 100.0)], 1_000_000);
+        store.record_at(&[("new.com".to_string(), 200.0)], 1_003_000);
+
+        let reloaded = BaselineStore::with_path(path, 3600);
+        let baselines = reloaded.load_at(1_003_600);
+        assert!(!baselines.contains_key("old.com"));
+        assert_eq!(baselines.get("new.com"), Some(&200.0));
+    }
+
+    #[test]
+    fn test_corrupt_file_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("baselines.json");
+        fs::write(&path, "not json").unwrap();
+        let store = BaselineStore::with_path(path, 3600);
+        assert!(store.load().is_empty());
+    }
+}
@@ -11,7 +11,12 @@
 //!   anyfast-helper-macos clear <domain>
 //!   anyfast-helper-macos clear-batch <json_domains>
 //!   anyfast-helper-macos clear-all
+//!   anyfast-helper-macos suspend <domain>
+//!   anyfast-helper-macos resume <domain>
+//!   anyfast-helper-macos deconflict
+//!   anyfast-helper-macos restore-deconflicted
 //!   anyfast-helper-macos flush-dns
+//!   anyfast-helper-macos version
 
 use std::collections::{HashMap, HashSet};
 use std::env;
@@ -20,13 +25,52 @@ use std::io::{Read, Write};
 use std::net::IpAddr;
 use std::path::Path;
 use std::process::{Command, ExitCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 
 const HOSTS_PATH: &str = "/etc/hosts";
-const MARKER_BEGIN: &str = "# BEGIN anyFAST";
-const MARKER_END: &str = "# END anyFAST";
-const MARKER_LINE: &str = "# anyFAST";
+const DEFAULT_MARKER_NAME: &str = "anyFAST";
+/// Same variable the GUI reads, so both sides agree on the block markers
+const HOSTS_MARKER_ENV: &str = "ANYFAST_HOSTS_MARKER";
+
+/// Block and line markers ("# BEGIN <name>", "# END <name>", "# <name>")
+struct Markers {
+    begin: String,
+    end: String,
+    line: String,
+}
+
+static MARKERS: OnceLock<Markers> = OnceLock::new();
+
+fn markers() -> &'static Markers {
+    MARKERS.get_or_init(|| {
+        let name = env::var(HOSTS_MARKER_ENV)
+            .ok()
+            .map(|n| n.trim().to_string())
+            .filter(|n| is_valid_marker_name(n))
+            .unwrap_or_else(|| DEFAULT_MARKER_NAME.to_string());
+        Markers {
+            begin: format!("# BEGIN {}", name),
+            end: format!("# END {}", name),
+            line: format!("# {}", name),
+        }
+    })
+}
+
+fn is_valid_marker_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
 
 fn main() -> ExitCode {
+    // 版本查询无需 root 权限，供 GUI 判断已安装的 helper 是否需要重新安装
+    if env::args().nth(1).as_deref() == Some("version") {
+        println!("{}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
     // Explicitly set effective UID to root (required for setuid to work)
     #[cfg(unix)]
     unsafe {
@@ -72,6 +116,15 @@ fn main() -> ExitCode {
             clear_bindings_batch(&args[2])
         }
         "clear-all" => clear_all_anyfast_bindings(),
+        "suspend" | "resume" => {
+            if args.len() != 3 {
+                eprintln!("用法: {} {} <domain>", args[0], args[1]);
+                return ExitCode::from(1);
+            }
+            set_suspended(&args[2], args[1] == "suspend")
+        }
+        "deconflict" => set_deconflicted(true),
+        "restore-deconflicted" => set_deconflicted(false),
         "flush-dns" => flush_dns(),
         _ => {
             print_usage();
@@ -99,7 +152,12 @@ fn print_usage() {
     eprintln!("  anyfast-helper-macos clear <domain>");
     eprintln!("  anyfast-helper-macos clear-batch <json_domains>");
     eprintln!("  anyfast-helper-macos clear-all");
+    eprintln!("  anyfast-helper-macos suspend <domain>");
+    eprintln!("  anyfast-helper-macos resume <domain>");
+    eprintln!("  anyfast-helper-macos deconflict");
+    eprintln!("  anyfast-helper-macos restore-deconflicted");
     eprintln!("  anyfast-helper-macos flush-dns");
+    eprintln!("  anyfast-helper-macos version");
 }
 
 // ============ Validation ============
@@ -110,6 +168,18 @@ fn validate_ip(ip: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Canonical text form of a validated IP (IPv6 compressed and lowercased)
+fn canonical_ip(ip: &str) -> String {
+    ip.parse::<IpAddr>()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|_| ip.to_string())
+}
+
+/// Whether an IP is IPv6 (AAAA); bindings of the two families are managed independently
+fn is_ipv6(ip: &str) -> bool {
+    matches!(ip.parse::<IpAddr>(), Ok(IpAddr::V6(_)))
+}
+
 fn validate_domain(domain: &str) -> Result<(), String> {
     if domain.is_empty() {
         return Err("域名不能为空".to_string());
@@ -131,7 +201,53 @@ fn validate_domain(domain: &str) -> Result<(), String> {
 struct ParsedHosts {
     before_block: Vec<String>,
     after_block: Vec<String>,
-    anyfast_bindings: HashMap<String, String>,
+    /// domain -> ips, in preference order (several IPs per domain are round-robin)
+    anyfast_bindings: HashMap<String, Vec<String>>,
+    /// Suspended (commented-out) bindings inside the block
+    suspended_bindings: HashMap<String, Vec<String>>,
+}
+
+/// Whether a line carries the line marker as a whole token
+/// ("# anyFAST" must not match "# anyFAST-deconflicted" or another variant)
+fn has_line_marker(line: &str) -> bool {
+    let marker = markers().line.as_str();
+    line.match_indices(marker).any(|(i, m)| {
+        line[i + m.len()..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+    })
+}
+
+/// Hostnames of an active entry ("1.2.3.4 a.com b.com # note"), None for comments
+fn entry_hostnames(trimmed: &str) -> Option<Vec<&str>> {
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let mut parts = trimmed.split_whitespace();
+    parts.next()?.parse::<IpAddr>().ok()?;
+    let hostnames: Vec<&str> = parts.take_while(|part| !part.starts_with('#')).collect();
+    (!hostnames.is_empty()).then_some(hostnames)
+}
+
+/// (ip, domain) of a suspended binding line ("#1.2.3.4\tdomain\t# anyFAST")
+fn suspended_binding(trimmed: &str) -> Option<(&str, &str)> {
+    let rest = trimmed.strip_prefix('#')?.trim_start();
+    if !has_line_marker(rest) {
+        return None;
+    }
+    let mut parts = rest.split_whitespace();
+    let ip = parts.next()?;
+    ip.parse::<IpAddr>().ok()?;
+    Some((ip, parts.next()?))
+}
+
+/// Add an ip to a domain's binding list, ignoring duplicates
+fn push_binding(bindings: &mut HashMap<String, Vec<String>>, domain: &str, ip: &str) {
+    let ips = bindings.entry(domain.to_string()).or_default();
+    if !ips.iter().any(|existing| existing == ip) {
+        ips.push(ip.to_string());
+    }
 }
 
 impl ParsedHosts {
@@ -139,6 +255,7 @@ impl ParsedHosts {
         let mut before_block = Vec::new();
         let mut after_block = Vec::new();
         let mut anyfast_bindings = HashMap::new();
+        let mut suspended_bindings = HashMap::new();
 
         let mut in_block = false;
         let mut found_block = false;
@@ -146,33 +263,34 @@ impl ParsedHosts {
         for line in content.lines() {
             let trimmed = line.trim();
 
-            if trimmed == MARKER_BEGIN {
+            if trimmed == markers().begin {
                 in_block = true;
                 found_block = true;
                 continue;
             }
 
-            if trimmed == MARKER_END {
+            if trimmed == markers().end {
                 in_block = false;
                 continue;
             }
 
             if in_block {
-                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                if let Some((ip, domain)) = suspended_binding(trimmed) {
+                    push_binding(&mut suspended_bindings, domain, ip);
+                } else if !trimmed.is_empty() && !trimmed.starts_with('#') {
                     let parts: Vec<&str> = trimmed.split_whitespace().collect();
                     if parts.len() >= 2 {
-                        anyfast_bindings.insert(parts[1].to_string(), parts[0].to_string());
+                        push_binding(&mut anyfast_bindings, parts[1], parts[0]);
                     }
                 }
             } else if found_block {
                 after_block.push(line.to_string());
             } else {
                 // Check for legacy line-level markers
-                if trimmed.contains(MARKER_LINE) && !trimmed.is_empty() && !trimmed.starts_with('#')
-                {
+                if has_line_marker(trimmed) && !trimmed.is_empty() && !trimmed.starts_with('#') {
                     let parts: Vec<&str> = trimmed.split_whitespace().collect();
                     if parts.len() >= 2 {
-                        anyfast_bindings.insert(parts[1].to_string(), parts[0].to_string());
+                        push_binding(&mut anyfast_bindings, parts[1], parts[0]);
                     }
                 } else {
                     before_block.push(line.to_string());
@@ -184,27 +302,167 @@ impl ParsedHosts {
             before_block,
             after_block,
             anyfast_bindings,
+            suspended_bindings,
         }
     }
 
-    fn render(&self) -> String {
-        let mut lines = self.before_block.clone();
+    /// Insert or update bindings
+    /// Each domain in the batch has its IPs of the written address families replaced by
+    /// the IPs given for it; bindings of the other family are kept (dual-stack A + AAAA)
+    /// Returns the number of bindings that were added or modified
+    fn apply_bindings(&mut self, bindings: &[(String, String)]) -> usize {
+        let mut replaced = HashMap::new();
+        for (domain, ip) in bindings {
+            push_binding(&mut replaced, domain, &canonical_ip(ip));
+        }
+        let mut changed = 0;
+        for (domain, ips) in replaced {
+            if self.replace_ips(&domain, ips.clone()) {
+                changed += ips.len();
+            }
+        }
+        changed
+    }
 
-        if !self.anyfast_bindings.is_empty() {
-            if !lines.is_empty() && !lines.last().map(|l| l.is_empty()).unwrap_or(true) {
-                lines.push(String::new());
+    /// Replace a domain's IPs of the families present in `ips`, keeping the other family
+    /// Returns whether the domain's binding list changed
+    fn replace_ips(&mut self, domain: &str, mut ips: Vec<String>) -> bool {
+        // An explicit write supersedes a suspended binding
+        self.suspended_bindings.remove(domain);
+        let existing = self.anyfast_bindings.get(domain);
+        if let Some(existing) = existing {
+            let writes_v4 = ips.iter().any(|ip| !is_ipv6(ip));
+            let writes_v6 = ips.iter().any(|ip| is_ipv6(ip));
+            ips.extend(
+                existing
+                    .iter()
+                    .filter(|ip| if is_ipv6(ip) { !writes_v6 } else { !writes_v4 })
+                    .cloned(),
+            );
+        }
+        if existing == Some(&ips) {
+            return false;
+        }
+        self.anyfast_bindings.insert(domain.to_string(), ips);
+        true
+    }
+
+    /// Comment out a domain's bindings; returns false when the domain has no active binding
+    fn suspend(&mut self, domain: &str) -> bool {
+        match self.anyfast_bindings.remove(domain) {
+            Some(ips) => {
+                self.suspended_bindings.insert(domain.to_string(), ips);
+                true
             }
+            None => false,
+        }
+    }
 
-            lines.push(MARKER_BEGIN.to_string());
+    /// Restore a suspended binding; returns false when the domain is not suspended
+    /// A binding written while suspended is newer and wins over the suspended one
+    fn resume(&mut self, domain: &str) -> bool {
+        match self.suspended_bindings.remove(domain) {
+            Some(ips) => {
+                self.anyfast_bindings
+                    .entry(domain.to_string())
+                    .or_insert(ips);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a domain's bindings, active or suspended; returns whether any existed
+    fn remove_domain(&mut self, domain: &str) -> bool {
+        let active = self.anyfast_bindings.remove(domain).is_some();
+        let suspended = self.suspended_bindings.remove(domain).is_some();
+        active || suspended
+    }
 
-            let mut sorted_bindings: Vec<_> = self.anyfast_bindings.iter().collect();
-            sorted_bindings.sort_by_key(|(domain, _)| *domain);
+    /// Whether a line outside the block binds only domains anyFAST manages
+    /// Mixed lines such as "127.0.0.1 localhost example.com" are left alone
+    fn is_conflicting(&self, line: &str) -> bool {
+        entry_hostnames(line.trim()).is_some_and(|hostnames| {
+            hostnames.iter().all(|hostname| {
+                self.anyfast_bindings
+                    .keys()
+                    .any(|domain| domain.eq_ignore_ascii_case(hostname))
+            })
+        })
+    }
 
-            for (domain, ip) in sorted_bindings {
-                lines.push(format!("{}\t{}\t{}", ip, domain, MARKER_LINE));
+    /// Comment out conflicting entries, keeping the original text so it can be restored
+    fn deconflict(&mut self) -> usize {
+        let suffix = format!("{}-deconflicted", markers().line);
+        let mut lines = std::mem::take(&mut self.before_block);
+        let mut after = std::mem::take(&mut self.after_block);
+        let mut count = 0;
+        for line in lines.iter_mut().chain(after.iter_mut()) {
+            if self.is_conflicting(line) {
+                *line = format!("# {}\t{}", line.trim(), suffix);
+                count += 1;
             }
+        }
+        self.before_block = lines;
+        self.after_block = after;
+        count
+    }
 
-            lines.push(MARKER_END.to_string());
+    /// Restore every entry commented out by deconflict
+    fn restore_deconflicted(&mut self) -> usize {
+        let suffix = format!("{}-deconflicted", markers().line);
+        let mut count = 0;
+        for line in self
+            .before_block
+            .iter_mut()
+            .chain(self.after_block.iter_mut())
+        {
+            let original = line
+                .trim()
+                .strip_prefix('#')
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .map(|rest| rest.trim().to_string());
+            if let Some(original) = original.filter(|o| entry_hostnames(o).is_some()) {
+                *line = original;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Render the anyFAST block (BEGIN..END) with bindings sorted by domain
+    fn block_lines(&self) -> Vec<String> {
+        let mut lines = vec![markers().begin.clone()];
+
+        let mut sorted_bindings: Vec<_> = self.anyfast_bindings.iter().collect();
+        sorted_bindings.sort_by_key(|(domain, _)| *domain);
+        for (domain, ips) in sorted_bindings {
+            for ip in ips {
+                lines.push(format!("{}\t{}\t{}", ip, domain, markers().line));
+            }
+        }
+
+        let mut sorted_suspended: Vec<_> = self.suspended_bindings.iter().collect();
+        sorted_suspended.sort_by_key(|(domain, _)| *domain);
+        for (domain, ips) in sorted_suspended {
+            for ip in ips {
+                lines.push(format!("#{}\t{}\t{}", ip, domain, markers().line));
+            }
+        }
+
+        lines.push(markers().end.clone());
+        lines
+    }
+
+    fn render(&self) -> String {
+        let mut lines = self.before_block.clone();
+
+        if !self.anyfast_bindings.is_empty() || !self.suspended_bindings.is_empty() {
+            if !lines.is_empty() && !lines.last().map(|l| l.is_empty()).unwrap_or(true) {
+                lines.push(String::new());
+            }
+
+            lines.extend(self.block_lines());
         }
 
         lines.extend(self.after_block.clone());
@@ -214,6 +472,52 @@ impl ParsedHosts {
 
 // ============ File Operations ============
 
+/// Error prefix for a hosts file that cannot be rewritten safely
+/// (the GUI maps it back to HostsError::UnsupportedEncoding)
+const UNSUPPORTED_ENCODING_PREFIX: &str = "unsupported hosts encoding: ";
+
+/// Label of an encoding that would be corrupted by a rewrite, None for UTF-8 (with or without BOM)
+/// Mirrors HostsEncoding::detect: UTF-16 without a BOM is recognised by NUL bytes
+/// filling one half of each code unit
+fn unsupported_encoding(raw: &[u8]) -> Option<&'static str> {
+    if raw.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return None;
+    }
+    if raw.starts_with(&[0xFF, 0xFE]) {
+        return Some("UTF-16 LE");
+    }
+    if raw.starts_with(&[0xFE, 0xFF]) {
+        return Some("UTF-16 BE");
+    }
+    if raw.len() >= 4 {
+        let units = raw.len() / 2;
+        let zero_even = raw.iter().step_by(2).filter(|b| **b == 0).count();
+        let zero_odd = raw.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+        if zero_odd * 2 > units && zero_even * 10 < units {
+            return Some("UTF-16 LE");
+        }
+        if zero_even * 2 > units && zero_odd * 10 < units {
+            return Some("UTF-16 BE");
+        }
+    }
+    std::str::from_utf8(raw)
+        .is_err()
+        .then_some("a non-UTF-8 codepage")
+}
+
+/// Decode the hosts file, refusing encodings a lossy decode would corrupt on rewrite
+fn decode_hosts_content(mut raw_content: Vec<u8>) -> Result<String, String> {
+    if let Some(label) = unsupported_encoding(&raw_content) {
+        return Err(format!("{}{}", UNSUPPORTED_ENCODING_PREFIX, label));
+    }
+    // Handle UTF-8 BOM
+    if raw_content.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        raw_content.drain(..3);
+    }
+    String::from_utf8(raw_content)
+        .map_err(|_| format!("{}a non-UTF-8 codepage", UNSUPPORTED_ENCODING_PREFIX))
+}
+
 fn read_hosts_content() -> Result<String, String> {
     let mut file = File::open(HOSTS_PATH).map_err(|e| format!("无法打开 hosts 文件: {}", e))?;
 
@@ -221,20 +525,18 @@ fn read_hosts_content() -> Result<String, String> {
     file.read_to_end(&mut raw_content)
         .map_err(|e| format!("无法读取 hosts 文件: {}", e))?;
 
-    // Handle UTF-8 BOM
-    let content = if raw_content.starts_with(&[0xEF, 0xBB, 0xBF]) {
-        String::from_utf8_lossy(&raw_content[3..]).to_string()
-    } else {
-        String::from_utf8_lossy(&raw_content).to_string()
-    };
-
-    Ok(content)
+    decode_hosts_content(raw_content)
 }
 
+/// Per-call suffix so concurrent writes in one process never share a temp path
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 fn atomic_write(content: &str) -> Result<(), String> {
     let path = Path::new(HOSTS_PATH);
     let parent = path.parent().unwrap_or(Path::new("/etc"));
-    let temp_path = parent.join(format!(".hosts.tmp.{}", std::process::id()));
+    // Temp file must live next to the hosts file for an atomic rename
+    let seq = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp_path = parent.join(format!(".hosts.tmp.{}.{}", std::process::id(), seq));
 
     // Write to temp file
     {
@@ -274,12 +576,9 @@ fn write_binding(domain: &str, ip: &str) -> Result<String, String> {
     let content = read_hosts_content()?;
     let mut parsed = ParsedHosts::parse(&content);
 
-    parsed
-        .anyfast_bindings
-        .insert(domain.to_string(), ip.to_string());
-
-    let new_content = parsed.render();
-    atomic_write(&new_content)?;
+    if parsed.replace_ips(domain, vec![canonical_ip(ip)]) {
+        atomic_write(&parsed.render())?;
+    }
 
     Ok(format!("已写入: {} -> {}", domain, ip))
 }
@@ -301,25 +600,24 @@ fn write_bindings_batch(json_bindings: &str) -> Result<String, String> {
     let content = read_hosts_content()?;
     let mut parsed = ParsedHosts::parse(&content);
 
-    let mut count = 0;
-    for binding in &bindings {
-        parsed
-            .anyfast_bindings
-            .insert(binding[0].clone(), binding[1].clone());
-        count += 1;
+    let bindings: Vec<(String, String)> = bindings
+        .into_iter()
+        .map(|binding| (binding[0].clone(), binding[1].clone()))
+        .collect();
+    // Leave the file untouched when nothing changed
+    let changed = parsed.apply_bindings(&bindings);
+    if changed > 0 {
+        atomic_write(&parsed.render())?;
     }
 
-    let new_content = parsed.render();
-    atomic_write(&new_content)?;
-
-    Ok(format!("已写入 {} 条绑定", count))
+    Ok(changed.to_string())
 }
 
 fn clear_binding(domain: &str) -> Result<String, String> {
     let content = read_hosts_content()?;
     let mut parsed = ParsedHosts::parse(&content);
 
-    if parsed.anyfast_bindings.remove(domain).is_some() {
+    if parsed.remove_domain(domain) {
         let new_content = parsed.render();
         atomic_write(&new_content)?;
         Ok(format!("已清除: {}", domain))
@@ -340,7 +638,7 @@ fn clear_bindings_batch(json_domains: &str) -> Result<String, String> {
     let mut removed_count = 0;
 
     for domain in &domains_set {
-        if parsed.anyfast_bindings.remove(*domain).is_some() {
+        if parsed.remove_domain(domain) {
             removed_count += 1;
         }
     }
@@ -357,6 +655,7 @@ fn clear_all_anyfast_bindings() -> Result<String, String> {
 
     let removed_count = parsed.anyfast_bindings.len();
     parsed.anyfast_bindings.clear();
+    parsed.suspended_bindings.clear();
 
     let new_content = parsed.render();
     atomic_write(&new_content)?;
@@ -364,6 +663,42 @@ fn clear_all_anyfast_bindings() -> Result<String, String> {
     Ok(format!("已清除所有 anyFAST 绑定 ({} 条)", removed_count))
 }
 
+/// Prints "true" when the binding changed state, "false" when it was not in the expected state
+fn set_suspended(domain: &str, suspend: bool) -> Result<String, String> {
+    validate_domain(domain)?;
+
+    let content = read_hosts_content()?;
+    let mut parsed = ParsedHosts::parse(&content);
+
+    let changed = if suspend {
+        parsed.suspend(domain)
+    } else {
+        parsed.resume(domain)
+    };
+    if changed {
+        atomic_write(&parsed.render())?;
+    }
+
+    Ok(changed.to_string())
+}
+
+/// Prints the number of entries commented out (or restored)
+fn set_deconflicted(deconflict: bool) -> Result<String, String> {
+    let content = read_hosts_content()?;
+    let mut parsed = ParsedHosts::parse(&content);
+
+    let count = if deconflict {
+        parsed.deconflict()
+    } else {
+        parsed.restore_deconflicted()
+    };
+    if count > 0 {
+        atomic_write(&parsed.render())?;
+    }
+
+    Ok(count.to_string())
+}
+
 fn flush_dns() -> Result<String, String> {
     // macOS DNS cache flush
     Command::new("/usr/bin/dscacheutil")
@@ -378,3 +713,119 @@ fn flush_dns() -> Result<String, String> {
 
     Ok("DNS 缓存已刷新".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(domain: &str, ip: &str) -> (String, String) {
+        (domain.to_string(), ip.to_string())
+    }
+
+    #[test]
+    fn test_batch_binds_several_ips_per_domain() {
+        let mut parsed = ParsedHosts::parse("127.0.0.1 localhost\n");
+        parsed.apply_bindings(&[
+            binding("api.example.com", "1.1.1.1"),
+            binding("api.example.com", "2.2.2.2"),
+        ]);
+
+        let rendered = parsed.render();
+        assert!(rendered.contains("1.1.1.1\tapi.example.com\t# anyFAST"));
+        assert!(rendered.contains("2.2.2.2\tapi.example.com\t# anyFAST"));
+
+        // Re-parsing keeps both lines, and a later single-domain write leaves the others alone
+        let mut reparsed = ParsedHosts::parse(&rendered);
+        assert_eq!(
+            reparsed.anyfast_bindings["api.example.com"],
+            vec!["1.1.1.1", "2.2.2.2"]
+        );
+        reparsed.apply_bindings(&[binding("other.example.com", "3.3.3.3")]);
+        assert_eq!(reparsed.anyfast_bindings["api.example.com"].len(), 2);
+    }
+
+    #[test]
+    fn test_batch_keeps_the_other_address_family() {
+        let mut parsed = ParsedHosts::parse("");
+        parsed.apply_bindings(&[binding("api.example.com", "1.1.1.1")]);
+        parsed.apply_bindings(&[binding("api.example.com", "2001:DB8:0:0::1")]);
+        assert_eq!(
+            parsed.anyfast_bindings["api.example.com"],
+            vec!["2001:db8::1", "1.1.1.1"]
+        );
+
+        // A new A record replaces only the IPv4 binding
+        parsed.apply_bindings(&[binding("api.example.com", "3.3.3.3")]);
+        assert_eq!(
+            parsed.anyfast_bindings["api.example.com"],
+            vec!["3.3.3.3", "2001:db8::1"]
+        );
+    }
+
+    #[test]
+    fn test_batch_counts_only_changed_bindings() {
+        let mut parsed = ParsedHosts::parse("");
+        let batch = [
+            binding("api.example.com", "1.1.1.1"),
+            binding("api.example.com", "2.2.2.2"),
+            binding("cdn.example.com", "3.3.3.3"),
+        ];
+        assert_eq!(parsed.apply_bindings(&batch), 3);
+
+        let mut reparsed = ParsedHosts::parse(&parsed.render());
+        assert_eq!(reparsed.apply_bindings(&batch), 0);
+        assert_eq!(
+            reparsed.apply_bindings(&[binding("cdn.example.com", "4.4.4.4")]),
+            1
+        );
+
+        // Rewriting a suspended binding with the same IPs re-activates it
+        assert!(reparsed.suspend("cdn.example.com"));
+        assert_eq!(
+            reparsed.apply_bindings(&[binding("cdn.example.com", "4.4.4.4")]),
+            1
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_utf16_and_legacy_codepages() {
+        let utf16: Vec<u8> = "127.0.0.1 localhost\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(
+            decode_hosts_content(utf16),
+            Err("unsupported hosts encoding: UTF-16 LE".to_string())
+        );
+        // "主机" in GBK
+        let gbk = b"# \xd6\xf7\xbb\xfa\n127.0.0.1 localhost\n".to_vec();
+        assert_eq!(
+            decode_hosts_content(gbk),
+            Err("unsupported hosts encoding: a non-UTF-8 codepage".to_string())
+        );
+
+        let bom = b"\xEF\xBB\xBF127.0.0.1 localhost\n".to_vec();
+        assert_eq!(
+            decode_hosts_content(bom),
+            Ok("127.0.0.1 localhost\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_suspend_and_resume_keep_every_ip() {
+        let mut parsed = ParsedHosts::parse("");
+        parsed.apply_bindings(&[
+            binding("api.example.com", "1.1.1.1"),
+            binding("api.example.com", "2.2.2.2"),
+        ]);
+        assert!(parsed.suspend("api.example.com"));
+
+        let mut reparsed = ParsedHosts::parse(&parsed.render());
+        assert!(reparsed.anyfast_bindings.is_empty());
+        assert!(reparsed.resume("api.example.com"));
+        assert_eq!(
+            reparsed.anyfast_bindings["api.example.com"],
+            vec!["1.1.1.1", "2.2.2.2"]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! 在线优选 IP 磁盘缓存
+//! 将在线 API 获取的 CF IP 列表连同获取时间持久化，TTL 内直接复用，网络失败时回退到过期缓存
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 默认缓存有效期：6 小时
+pub const DEFAULT_CF_IP_CACHE_TTL_SECS: u64 = 6 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedCfIps {
+    /// 获取时间（Unix 秒）
+    fetched_at: i64,
+    /// 获取来源 URL（来源变化时缓存视为过期）
+    #[serde(default)]
+    source: String,
+    ips: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CfIpCache {
+    path: PathBuf,
+    ttl_secs: u64,
+}
+
+impl CfIpCache {
+    pub fn new(ttl_secs: u64) -> Self {
+        let path = crate::config::data_dir().join("cf_ips_cache.json");
+
+        Self { path, ttl_secs }
+    }
+
+    /// Create a CfIpCache with a custom path (for testing)
+    #[cfg(test)]
+    pub fn with_path(path: PathBuf, ttl_secs: u64) -> Self {
+        Self { path, ttl_secs }
+    }
+
+    fn now_timestamp() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+    }
+
+    fn read(&self) -> Option<CachedCfIps> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        let cached: CachedCfIps = serde_json::from_str(&content).ok()?;
+        if cached.ips.is_empty() {
+            None
+        } else {
+            Some(cached)
+        }
+    }
+
+    /// 读取指定来源且未过期的缓存
+    pub fn load_fresh(&self, source: &str) -> Option<Vec<String>> {
+        self.load_fresh_at(source, Self::now_timestamp())
+    }
+
+    fn load_fresh_at(&self, source: &str, now: i64) -> Option<Vec<String>> {
+        let cached = self.read()?;
+        if cached.source != source {
+            return None;
+        }
+        let age = now.saturating_sub(cached.fetched_at);
+        if age >= 0 && (age as u64) < self.ttl_secs {
+            Some(cached.ips)
+        } else {
+            None
+        }
+    }
+
+    /// 读取指定来源的缓存（忽略 TTL），用于网络失败时的回退
+    pub fn load_stale(&self, source: &str) -> Option<Vec<String>> {
+        self.read().filter(|c| c.source == source).map(|c| c.ips)
+    }
+
+    /// 写入缓存（失败仅记录日志，不影响测速）
+    pub fn save(&self, source: &str, ips: &[String]) {
+        self.save_at(source, ips, Self::now_timestamp());
+    }
+
+    fn save_at(&self, source: &str, ips: &[String], fetched_at: i64) {
+        let cached = CachedCfIps {
+            fetched_at,
+            source: source.to_string(),
+            ips: ips.to_vec(),
+        };
+        match serde_json::to_string_pretty(&cached) {
+            Ok(content) => {
+                if let Err(e) = fs::write(&self.path, content) {
+                    eprintln!("写入优选 IP 缓存失败: {}", e);
+                }
+            }
+            Err(e) => eprintln!("序列化优选 IP 缓存失败: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SOURCE: &str = "https://ip.example.com/list";
+
+    fn ips() -> Vec<String> {
+        vec!["104.16.1.1".to_string(), "172.67.1.1".to_string()]
+    }
+
+    #[test]
+    fn test_missing_cache_returns_none() {
+        let dir = TempDir::new().unwrap();
+        let cache = CfIpCache::with_path(dir.path().join("cache.json"), 3600);
+        assert!(cache.load_fresh(SOURCE).is_none());
+        assert!(cache.load_stale(SOURCE).is_none());
+    }
+
+    #[test]
+    fn test_fresh_cache_within_ttl() {
+        let dir = TempDir::new().unwrap();
+        let cache = CfIpCache::with_path(dir.path().join("cache.json"), 3600);
+        cache.save_at(SOURCE, &ips(), 1_000_000);
+
+        assert_eq!(cache.load_fresh_at(SOURCE, 1_000_000 + 10), Some(ips()));
+        assert_eq!(cache.load_fresh_at(SOURCE, 1_000_000 + 3599), Some(ips()));
+    }
+
+    #[test]
+    fn test_cache_expires_after_ttl() {
+        let dir = TempDir::new().unwrap();
+        let cache = CfIpCache::with_path(dir.path().join("cache.json"), 3600);
+        cache.save_at(SOURCE, &ips(), 1_000_000);
+
+        assert!(cache.load_fresh_at(SOURCE, 1_000_000 + 3600).is_none());
+        // 过期缓存仍可作为回退使用
+        assert_eq!(cache.load_stale(SOURCE), Some(ips()));
+    }
+
+    #[test]
+    fn test_cache_from_other_source_is_not_fresh() {
+        let dir = TempDir::new().unwrap();
+        let cache = CfIpCache::with_path(dir.path().join("cache.json"), 3600);
+        cache.save_at(SOURCE, &ips(), 1_000_000);
+
+        assert!(cache
+            .load_fresh_at("https://other.example.com/ips", 1_000_010)
+            .is_none());
+        // 换了来源后，旧来源的缓存也不能作为回退
+        assert!(cache.load_stale("https://other.example.com/ips").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cache.json");
+        fs::write(&path, "not json").unwrap();
+        let cache = CfIpCache::with_path(path, 3600);
+        assert!(cache.load_fresh(SOURCE).is_none());
+        assert!(cache.load_stale(SOURCE).is_none());
+    }
+}
@@ -3,8 +3,9 @@
 //! This module provides a client that connects to the privileged service
 //! to perform hosts file operations without requiring admin privileges.
 
+use crate::models::ServicePipeFailure;
 use crate::service::rpc::*;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use thiserror::Error;
 use windows::core::PCSTR;
 use windows::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
@@ -24,10 +25,24 @@ const CONNECT_TIMEOUT_MS: u32 = 5000;
 /// Buffer size for communication
 const BUFFER_SIZE: usize = 65536;
 
+/// Framing support of the running service, learned from PING
+static SERVICE_FRAMING: AtomicU8 = AtomicU8::new(FRAMING_UNKNOWN);
+const FRAMING_UNKNOWN: u8 = 0;
+/// Service predates framing and only reads a single bare JSON message
+const FRAMING_LEGACY: u8 = 1;
+const FRAMING_SUPPORTED: u8 = 2;
+
+/// ERROR_ACCESS_DENIED: the pipe exists but its DACL rejects this user
+const ERROR_ACCESS_DENIED: i32 = 5;
+/// ERROR_SEM_TIMEOUT: timed out waiting for a free pipe instance
+const ERROR_SEM_TIMEOUT: i32 = 121;
+
 #[derive(Error, Debug)]
 pub enum PipeClientError {
     #[error("Service not running or pipe not available")]
     ServiceNotRunning,
+    #[error("Access to the service pipe was denied")]
+    AccessDenied,
     #[error("Connection timeout")]
     ConnectionTimeout,
     #[error("IO error: {0}")]
@@ -38,6 +53,46 @@ pub enum PipeClientError {
     Serialization(#[from] serde_json::Error),
     #[error("Invalid response")]
     InvalidResponse,
+    #[error("The installed hosts service is outdated, reinstall it to continue")]
+    ServiceOutdated,
+}
+
+impl PipeClientError {
+    /// Classify this error so the UI can suggest a remedy
+    pub fn pipe_failure(&self) -> ServicePipeFailure {
+        match self {
+            Self::ServiceNotRunning => ServicePipeFailure::NotRunning,
+            Self::ConnectionTimeout => ServicePipeFailure::Timeout,
+            Self::AccessDenied => ServicePipeFailure::AccessDenied,
+            _ => ServicePipeFailure::Other,
+        }
+    }
+}
+
+/// Map the Win32 error code of a failed connect to a client error
+fn classify_connect_error(code: Option<i32>) -> PipeClientError {
+    match code {
+        Some(ERROR_ACCESS_DENIED) => PipeClientError::AccessDenied,
+        Some(ERROR_SEM_TIMEOUT) => PipeClientError::ConnectionTimeout,
+        _ => PipeClientError::ServiceNotRunning,
+    }
+}
+
+/// Whether a framed request was rejected by a service that predates framing:
+/// such a service reads the length prefix as JSON and replies with a bare parse error
+fn is_legacy_rejection(framed_request: bool, framed_reply: bool, response: &RpcResponse) -> bool {
+    framed_request
+        && !framed_reply
+        && response
+            .error
+            .as_ref()
+            .is_some_and(|e| e.code == error_codes::PARSE_ERROR)
+}
+
+/// Extract the Win32 error code from a `windows` error (HRESULT_FROM_WIN32 form)
+fn win32_code(err: &windows::core::Error) -> Option<i32> {
+    let hr = err.code().0 as u32;
+    (hr & 0xFFFF_0000 == 0x8007_0000).then_some((hr & 0xFFFF) as i32)
 }
 
 /// Client for communicating with the anyFAST hosts service
@@ -67,11 +122,7 @@ impl PipeClient {
 
         if available.is_err() {
             let err = std::io::Error::last_os_error();
-            // ERROR_SEM_TIMEOUT (121) means timeout waiting for pipe
-            if err.raw_os_error() == Some(121) {
-                return Err(PipeClientError::ConnectionTimeout);
-            }
-            return Err(PipeClientError::ServiceNotRunning);
+            return Err(classify_connect_error(err.raw_os_error()));
         }
 
         // Open the pipe
@@ -88,9 +139,10 @@ impl PipeClient {
             )
         };
 
+        // The pipe DACL is checked here, so access denied surfaces on open
         let handle = match handle {
             Ok(h) => h,
-            Err(_) => return Err(PipeClientError::ServiceNotRunning),
+            Err(e) => return Err(classify_connect_error(win32_code(&e))),
         };
 
         if handle == INVALID_HANDLE_VALUE {
@@ -110,10 +162,25 @@ impl PipeClient {
     }
 
     /// Send a request and receive response
+    /// Uses length-prefixed frames when the service supports them, bare messages otherwise
     fn call(
         &self,
         method: &str,
         params: serde_json::Value,
+    ) -> Result<serde_json::Value, PipeClientError> {
+        let framed = match SERVICE_FRAMING.load(Ordering::Relaxed) {
+            FRAMING_SUPPORTED => true,
+            FRAMING_LEGACY => false,
+            _ => self.ping()?.framing,
+        };
+        self.call_with(method, params, framed)
+    }
+
+    fn call_with(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        framed: bool,
     ) -> Result<serde_json::Value, PipeClientError> {
         let handle = self.connect()?;
 
@@ -121,30 +188,73 @@ impl PipeClient {
         let _guard = HandleGuard(handle);
 
         let request_id = self.next_id();
-        let request = RpcRequest::new(request_id, method, params);
+        let request = RpcRequest::new(request_id, method, params)
+            .with_marker(crate::hosts_manager::marker_name());
         let request_json = serde_json::to_vec(&request)?;
 
-        // Write request
-        let mut bytes_written: u32 = 0;
-        let write_result =
-            unsafe { WriteFile(handle, Some(&request_json), Some(&mut bytes_written), None) };
-
-        if write_result.is_err() {
-            return Err(PipeClientError::Io(std::io::Error::last_os_error()));
+        // Write request (length-prefixed, split into buffer-sized writes)
+        // A legacy service reads one bare message, which must fit in its buffer
+        let frame = if framed {
+            framing::encode(&request_json).map_err(|e| {
+                PipeClientError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+            })?
+        } else if request_json.len() > BUFFER_SIZE {
+            return Err(PipeClientError::ServiceOutdated);
+        } else {
+            request_json
+        };
+        for chunk in frame.chunks(BUFFER_SIZE) {
+            let mut offset = 0;
+            while offset < chunk.len() {
+                let mut bytes_written: u32 = 0;
+                let write_result = unsafe {
+                    WriteFile(
+                        handle,
+                        Some(&chunk[offset..]),
+                        Some(&mut bytes_written),
+                        None,
+                    )
+                };
+
+                if write_result.is_err() || bytes_written == 0 {
+                    return Err(PipeClientError::Io(std::io::Error::last_os_error()));
+                }
+                offset += bytes_written as usize;
+            }
         }
 
-        // Read response
+        // Read response, looping until the whole frame has arrived
         let mut buffer = vec![0u8; BUFFER_SIZE];
-        let mut bytes_read: u32 = 0;
-        let read_result =
-            unsafe { ReadFile(handle, Some(&mut buffer), Some(&mut bytes_read), None) };
+        let mut assembler = framing::FrameAssembler::new();
+        let message = loop {
+            let mut bytes_read: u32 = 0;
+            let read_result =
+                unsafe { ReadFile(handle, Some(&mut buffer), Some(&mut bytes_read), None) };
+
+            if read_result.is_err() {
+                let err = std::io::Error::last_os_error();
+                // ERROR_MORE_DATA (234): partial message, the rest follows
+                if err.raw_os_error() != Some(234) {
+                    return Err(PipeClientError::Io(err));
+                }
+            }
+            if bytes_read == 0 {
+                return Err(PipeClientError::InvalidResponse);
+            }
 
-        if read_result.is_err() {
-            return Err(PipeClientError::Io(std::io::Error::last_os_error()));
-        }
+            match assembler.push(&buffer[..bytes_read as usize]) {
+                Ok(Some(message)) => break message,
+                Ok(None) => continue,
+                Err(_) => return Err(PipeClientError::InvalidResponse),
+            }
+        };
 
         // Parse response
-        let response: RpcResponse = serde_json::from_slice(&buffer[..bytes_read as usize])?;
+        let response: RpcResponse = serde_json::from_slice(&message.payload)?;
+        if is_legacy_rejection(framed, message.framed, &response) {
+            SERVICE_FRAMING.store(FRAMING_LEGACY, Ordering::Relaxed);
+            return Err(PipeClientError::ServiceOutdated);
+        }
 
         // Validate response matches our request
         if response.jsonrpc != "2.0" {
@@ -172,10 +282,20 @@ impl PipeClient {
         self.ping().is_ok()
     }
 
-    /// Ping the service
+    /// Ping the service and remember whether it supports framing
+    /// Sent as a bare message, which services of every version understand
     pub fn ping(&self) -> Result<PingResult, PipeClientError> {
-        let result = self.call(methods::PING, serde_json::Value::Null)?;
-        Ok(serde_json::from_value(result)?)
+        let result = self.call_with(methods::PING, serde_json::Value::Null, false)?;
+        let ping: PingResult = serde_json::from_value(result)?;
+        SERVICE_FRAMING.store(
+            if ping.framing {
+                FRAMING_SUPPORTED
+            } else {
+                FRAMING_LEGACY
+            },
+            Ordering::Relaxed,
+        );
+        Ok(ping)
     }
 
     /// Write a single binding
@@ -243,6 +363,40 @@ impl PipeClient {
         Ok(count.count)
     }
 
+    /// Comment out a binding in place, returns false when it was not active
+    pub fn suspend_binding(&self, domain: &str) -> Result<bool, PipeClientError> {
+        let params = SuspendBindingParams {
+            domain: domain.to_string(),
+        };
+        let result = self.call(methods::SUSPEND_BINDING, serde_json::to_value(params)?)?;
+        let changed: ChangedResult = serde_json::from_value(result)?;
+        Ok(changed.changed)
+    }
+
+    /// Re-enable a suspended binding, returns false when it was not suspended
+    pub fn resume_binding(&self, domain: &str) -> Result<bool, PipeClientError> {
+        let params = SuspendBindingParams {
+            domain: domain.to_string(),
+        };
+        let result = self.call(methods::RESUME_BINDING, serde_json::to_value(params)?)?;
+        let changed: ChangedResult = serde_json::from_value(result)?;
+        Ok(changed.changed)
+    }
+
+    /// Comment out entries outside the anyFAST block that shadow its bindings
+    pub fn deconflict_bindings(&self) -> Result<u32, PipeClientError> {
+        let result = self.call(methods::DECONFLICT_BINDINGS, serde_json::Value::Null)?;
+        let count: CountResult = serde_json::from_value(result)?;
+        Ok(count.count)
+    }
+
+    /// Restore entries commented out by deconflict_bindings
+    pub fn restore_deconflicted(&self) -> Result<u32, PipeClientError> {
+        let result = self.call(methods::RESTORE_DECONFLICTED, serde_json::Value::Null)?;
+        let count: CountResult = serde_json::from_value(result)?;
+        Ok(count.count)
+    }
+
     /// Read a binding
     pub fn read_binding(&self, domain: &str) -> Result<Option<String>, PipeClientError> {
         let params = ReadBindingParams {
@@ -301,4 +455,54 @@ mod tests {
         // Just test that we can create a client
         assert!(client.next_id() > 0);
     }
+
+    #[test]
+    fn test_legacy_rejection_detection() {
+        let parse_error = RpcResponse::error(0, error_codes::PARSE_ERROR, "Parse error");
+        assert!(is_legacy_rejection(true, false, &parse_error));
+        // A framed reply comes from a service that understood the frame
+        assert!(!is_legacy_rejection(true, true, &parse_error));
+        // Bare requests are what a legacy service expects
+        assert!(!is_legacy_rejection(false, false, &parse_error));
+        let other = RpcResponse::error(1, error_codes::METHOD_NOT_FOUND, "Method not found");
+        assert!(!is_legacy_rejection(true, false, &other));
+    }
+
+    #[test]
+    fn test_connect_error_classification() {
+        assert!(matches!(
+            classify_connect_error(Some(5)),
+            PipeClientError::AccessDenied
+        ));
+        assert!(matches!(
+            classify_connect_error(Some(121)),
+            PipeClientError::ConnectionTimeout
+        ));
+        // ERROR_FILE_NOT_FOUND (2): the pipe does not exist
+        assert!(matches!(
+            classify_connect_error(Some(2)),
+            PipeClientError::ServiceNotRunning
+        ));
+        assert!(matches!(
+            classify_connect_error(None),
+            PipeClientError::ServiceNotRunning
+        ));
+
+        assert_eq!(
+            PipeClientError::AccessDenied.pipe_failure(),
+            ServicePipeFailure::AccessDenied
+        );
+        assert_eq!(
+            PipeClientError::ConnectionTimeout.pipe_failure(),
+            ServicePipeFailure::Timeout
+        );
+        assert_eq!(
+            PipeClientError::ServiceNotRunning.pipe_failure(),
+            ServicePipeFailure::NotRunning
+        );
+        assert_eq!(
+            PipeClientError::InvalidResponse.pipe_failure(),
+            ServicePipeFailure::Other
+        );
+    }
 }
@@ -1,11 +1,65 @@
 //! Configuration manager
 
-use crate::models::AppConfig;
+use crate::models::{AppConfig, ConfigImportSummary};
 use directories::ProjectDirs;
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
 use thiserror::Error;
 
+/// 配置读-改-写的进程内互斥锁：后台任务（订阅刷新）与前端保存不会互相覆盖
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 系统配置目录不可用时的回退目录名
+const FALLBACK_DIR_NAME: &str = "anyfast-data";
+
+/// 应用数据目录（配置、历史、缓存、日志等文件所在目录）
+/// ProjectDirs 不可用时回退到可执行文件旁的 anyfast-data，不可写则使用系统临时目录，
+/// 避免从任意工作目录启动时把文件散落在 cwd
+pub fn data_dir() -> PathBuf {
+    static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+    DATA_DIR
+        .get_or_init(|| {
+            let dir = match ProjectDirs::from("com", "anyrouter", "fast") {
+                Some(dirs) => dirs.config_dir().to_path_buf(),
+                None => {
+                    let exe_dir = std::env::current_exe()
+                        .ok()
+                        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+                    let dir = fallback_data_dir(exe_dir, std::env::temp_dir(), is_writable_dir);
+                    eprintln!("无法获取系统配置目录，数据文件保存到: {}", dir.display());
+                    dir
+                }
+            };
+            fs::create_dir_all(&dir).ok();
+            dir
+        })
+        .clone()
+}
+
+/// 回退目录选择：优先可执行文件旁（可写时），否则系统临时目录
+fn fallback_data_dir<F>(exe_dir: Option<PathBuf>, temp_dir: PathBuf, writable: F) -> PathBuf
+where
+    F: Fn(&Path) -> bool,
+{
+    exe_dir
+        .map(|dir| dir.join(FALLBACK_DIR_NAME))
+        .filter(|dir| writable(dir))
+        .unwrap_or_else(|| temp_dir.join(FALLBACK_DIR_NAME))
+}
+
+/// 目录可创建且能写入文件（安装在只读位置时为 false）
+fn is_writable_dir(dir: &Path) -> bool {
+    if fs::create_dir_all(dir).is_err() {
+        return false;
+    }
+    let probe = dir.join(".write_test");
+    let writable = fs::write(&probe, b"").is_ok();
+    fs::remove_file(&probe).ok();
+    writable
+}
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("IO error: {0}")]
@@ -15,6 +69,8 @@ pub enum ConfigError {
     #[allow(dead_code)]
     #[error("Config directory not found")]
     NoDirs,
+    #[error("Invalid config: {0}")]
+    Invalid(String),
 }
 
 #[derive(Clone)]
@@ -24,15 +80,9 @@ pub struct ConfigManager {
 
 impl ConfigManager {
     pub fn new() -> Self {
-        let path = if let Some(dirs) = ProjectDirs::from("com", "anyrouter", "fast") {
-            let config_dir = dirs.config_dir();
-            fs::create_dir_all(config_dir).ok();
-            config_dir.join("config.json")
-        } else {
-            PathBuf::from("config.json")
-        };
-
-        Self { path }
+        Self {
+            path: data_dir().join("config.json"),
+        }
     }
 
     /// Create a ConfigManager with a custom path (for testing)
@@ -56,11 +106,91 @@ impl ConfigManager {
         }
     }
 
+    /// 获取配置写锁；load → 修改 → save 需在持有期间完成（不要跨 await 持有）
+    pub fn lock_writes(&self) -> MutexGuard<'static, ()> {
+        WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     pub fn save(&self, config: &AppConfig) -> Result<(), ConfigError> {
         let content = serde_json::to_string_pretty(config)?;
         fs::write(&self.path, content)?;
         Ok(())
     }
+
+    /// 导出当前配置到指定文件
+    pub fn export_to(&self, dest: &Path) -> Result<(), ConfigError> {
+        let config = self.load()?;
+        let content = serde_json::to_string_pretty(&config)?;
+        fs::write(dest, content)?;
+        Ok(())
+    }
+
+    /// 读取并校验待导入的配置文件，返回合并后的配置（不写盘，由调用方规范化后保存）
+    /// 与 load 不同，解析失败直接报错，不回退到默认配置
+    /// AppConfig 各字段都有默认值，任意 JSON 对象都能解析，因此要求文件带有 endpoints 数组
+    pub fn prepare_import(
+        &self,
+        src: &Path,
+        merge: bool,
+    ) -> Result<(AppConfig, ConfigImportSummary), ConfigError> {
+        let content = fs::read_to_string(src)?;
+        let value: serde_json::Value =
+            serde_json::from_str(content.trim_start_matches('\u{feff}'))?;
+        if !value
+            .get("endpoints")
+            .is_some_and(serde_json::Value::is_array)
+        {
+            return Err(ConfigError::Invalid(
+                "不是 anyFAST 配置文件（缺少 endpoints 列表）".into(),
+            ));
+        }
+        let imported: AppConfig = serde_json::from_value(value)?;
+        let current = self.load()?;
+        merge_imported_config(current, imported, merge)
+    }
+}
+
+/// 合并导入的配置：端点按 domain 去重
+/// merge = true 时保留当前设置和端点（含启用状态），只追加新域名；否则以导入的配置为准
+fn merge_imported_config(
+    current: AppConfig,
+    mut imported: AppConfig,
+    merge: bool,
+) -> Result<(AppConfig, ConfigImportSummary), ConfigError> {
+    if let Some(ep) = imported
+        .endpoints
+        .iter()
+        .find(|e| e.domain.trim().is_empty())
+    {
+        return Err(ConfigError::Invalid(format!(
+            "端点 \"{}\" 缺少域名",
+            ep.name
+        )));
+    }
+
+    let imported_endpoints = std::mem::take(&mut imported.endpoints);
+    let mut config = if merge { current } else { imported };
+    let mut endpoints = std::mem::take(&mut config.endpoints);
+    if !merge {
+        endpoints.clear();
+    }
+    let mut seen: HashSet<String> = endpoints
+        .iter()
+        .map(|e| e.domain.trim().to_lowercase())
+        .collect();
+
+    let mut summary = ConfigImportSummary::default();
+    for endpoint in imported_endpoints {
+        if seen.insert(endpoint.domain.trim().to_lowercase()) {
+            endpoints.push(endpoint);
+            summary.imported_endpoints += 1;
+        } else {
+            summary.skipped_duplicates += 1;
+        }
+    }
+    config.endpoints = endpoints;
+
+    Ok((config, summary))
 }
 
 #[cfg(test)]
@@ -68,6 +198,30 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_fallback_data_dir_prefers_writable_exe_dir() {
+        let exe_dir = PathBuf::from("/opt/anyfast");
+        let temp_dir = PathBuf::from("/tmp");
+
+        assert_eq!(
+            fallback_data_dir(Some(exe_dir.clone()), temp_dir.clone(), |_| true),
+            exe_dir.join(FALLBACK_DIR_NAME)
+        );
+        // 安装目录只读或无法获取可执行文件路径时使用临时目录，而不是 cwd
+        assert_eq!(
+            fallback_data_dir(Some(exe_dir), temp_dir.clone(), |_| false),
+            temp_dir.join(FALLBACK_DIR_NAME)
+        );
+        assert_eq!(
+            fallback_data_dir(None, temp_dir.clone(), |_| true),
+            temp_dir.join(FALLBACK_DIR_NAME)
+        );
+
+        let dir = TempDir::new().unwrap();
+        assert!(is_writable_dir(&dir.path().join("nested")));
+        assert!(!dir.path().join("nested").join(".write_test").exists());
+    }
+
     #[test]
     fn test_config_manager_load_default_when_missing() {
         let temp_dir = TempDir::new().unwrap();
@@ -108,6 +262,7 @@ mod tests {
             url: "https://custom.com/api".into(),
             domain: "custom.com".into(),
             enabled: false,
+            ..Default::default()
         });
 
         manager.save(&config).unwrap();
@@ -136,4 +291,112 @@ mod tests {
         assert_eq!(config.check_interval, 120);
         assert_eq!(config.endpoints.len(), 2);
     }
+
+    fn endpoint(domain: &str, enabled: bool) -> crate::models::Endpoint {
+        crate::models::Endpoint {
+            name: domain.into(),
+            url: format!("https://{}/v1", domain),
+            domain: domain.into(),
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = ConfigManager::with_path(temp_dir.path().join("config.json"));
+        let src = temp_dir.path().join("import.json");
+
+        fs::write(&src, "not json").unwrap();
+        assert!(matches!(
+            manager.prepare_import(&src, false),
+            Err(ConfigError::Json(_))
+        ));
+
+        // 类型错误同样报错，而不是回退默认配置
+        fs::write(&src, r#"{"endpoints":[],"check_interval":"fast"}"#).unwrap();
+        assert!(manager.prepare_import(&src, false).is_err());
+
+        // 任意 JSON 对象都能按默认值解析，缺少 endpoints 列表的文件不是配置文件
+        for content in [r#"{}"#, r#"{"foo":1}"#, r#"{"endpoints":{}}"#, "[]"] {
+            fs::write(&src, content).unwrap();
+            assert!(matches!(
+                manager.prepare_import(&src, false),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_endpoint_without_domain() {
+        let imported = AppConfig {
+            endpoints: vec![endpoint(" ", true)],
+            ..Default::default()
+        };
+        assert!(matches!(
+            merge_imported_config(AppConfig::default(), imported, false),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_dedupes_endpoints_by_domain() {
+        let imported = AppConfig {
+            endpoints: vec![
+                endpoint("a.com", true),
+                endpoint("A.com", false),
+                endpoint("b.com", true),
+            ],
+            ..Default::default()
+        };
+
+        let (config, summary) =
+            merge_imported_config(AppConfig::default(), imported, false).unwrap();
+        assert_eq!(summary.imported_endpoints, 2);
+        assert_eq!(summary.skipped_duplicates, 1);
+        let domains: Vec<_> = config.endpoints.iter().map(|e| e.domain.as_str()).collect();
+        assert_eq!(domains, vec!["a.com", "b.com"]);
+    }
+
+    #[test]
+    fn test_import_merge_keeps_current_settings() {
+        let current = AppConfig {
+            check_interval: 120,
+            endpoints: vec![endpoint("a.com", false)],
+            ..Default::default()
+        };
+        let imported = AppConfig {
+            check_interval: 300,
+            endpoints: vec![endpoint("a.com", true), endpoint("b.com", true)],
+            ..Default::default()
+        };
+
+        let (config, summary) = merge_imported_config(current, imported, true).unwrap();
+        assert_eq!(summary.imported_endpoints, 1);
+        assert_eq!(summary.skipped_duplicates, 1);
+        assert_eq!(config.check_interval, 120); // 合并只追加端点，不覆盖当前设置
+        assert_eq!(config.endpoints.len(), 2);
+        assert!(!config.endpoints[0].enabled); // 保留当前启用状态
+        assert_eq!(config.endpoints[1].domain, "b.com");
+    }
+
+    #[test]
+    fn test_export_then_import_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = ConfigManager::with_path(temp_dir.path().join("config.json"));
+        let config = AppConfig {
+            check_interval: 45,
+            ..Default::default()
+        };
+        manager.save(&config).unwrap();
+
+        let dest = temp_dir.path().join("export.json");
+        manager.export_to(&dest).unwrap();
+
+        let (imported, summary) = manager.prepare_import(&dest, false).unwrap();
+        assert_eq!(imported.check_interval, 45);
+        assert_eq!(summary.imported_endpoints, config.endpoints.len() as u32);
+        assert_eq!(summary.skipped_duplicates, 0);
+    }
 }
@@ -1,6 +1,12 @@
 //! Endpoint speed tester with Cloudflare IP optimization
 
-use crate::models::{Endpoint, EndpointResult, TestProgressEvent, TestProgressEventType};
+use crate::cf_ip_cache::CfIpCache;
+use crate::models::{
+    normalize_user_agent, AppConfig, CandidateIps, DnsBenchmarkResult, Endpoint, EndpointResult,
+    IpVersionPreference, ProbeErrorKind, TestProgressEvent, TestProgressEventType, UrlProbeResult,
+    DEFAULT_ENDPOINT_PORT, DEFAULT_HTTP_PORT, DEFAULT_USER_AGENT, MAX_IPS_PER_DOMAIN,
+};
+use crate::proxy::ProbeProxy;
 use hickory_resolver::config::{
     LookupIpStrategy, NameServerConfig, Protocol, ResolverConfig, ResolverOpts,
 };
@@ -8,15 +14,16 @@ use hickory_resolver::TokioAsyncResolver;
 use rand::seq::SliceRandom;
 use rand::Rng;
 use reqwest::Client;
-use std::collections::HashSet;
-use std::net::SocketAddr;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 #[cfg(feature = "tauri-runtime")]
 use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpSocket;
+use tokio::net::{TcpSocket, TcpStream};
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 use tokio_native_tls::TlsConnector;
@@ -24,25 +31,25 @@ use tokio_native_tls::TlsConnector;
 /// 日志宏：输出带时间戳的调试日志到 stderr
 macro_rules! debug_log {
     ($($arg:tt)*) => {
-        eprintln!("[{}] [DEBUG] {}", chrono::Local::now().format("%H:%M:%S%.3f"), format!($($arg)*));
+        crate::app_log::write("DEBUG", &format!($($arg)*));
     };
 }
 
 macro_rules! info_log {
     ($($arg:tt)*) => {
-        eprintln!("[{}] [INFO] {}", chrono::Local::now().format("%H:%M:%S%.3f"), format!($($arg)*));
+        crate::app_log::write("INFO", &format!($($arg)*));
     };
 }
 
 macro_rules! warn_log {
     ($($arg:tt)*) => {
-        eprintln!("[{}] [WARN] {}", chrono::Local::now().format("%H:%M:%S%.3f"), format!($($arg)*));
+        crate::app_log::write("WARN", &format!($($arg)*));
     };
 }
 
 macro_rules! error_log {
     ($($arg:tt)*) => {
-        eprintln!("[{}] [ERROR] {}", chrono::Local::now().format("%H:%M:%S%.3f"), format!($($arg)*));
+        crate::app_log::write("ERROR", &format!($($arg)*));
     };
 }
 
@@ -65,20 +72,26 @@ const DEFAULT_CF_IPS: &[&str] = &[
 const IPDB_API_URL: &str = "https://ip.164746.xyz/ipTop10.html";
 
 /// Max concurrent endpoint tests (fallback, overridden by TestStrategy)
-const MAX_ENDPOINT_CONCURRENCY: usize = 3;
+pub(crate) const MAX_ENDPOINT_CONCURRENCY: usize = 3;
 /// DNS lookup timeout for each endpoint
 const DNS_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
 /// Timeout for a single IP test
 const SINGLE_IP_TEST_TIMEOUT: Duration = Duration::from_secs(8);
 /// Timeout for TCP-only probe (fast fail detection)
 const TCP_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// 吞吐量测量：响应体读取时限与字节上限（防止分块响应无限读取）
+const THROUGHPUT_READ_TIMEOUT: Duration = Duration::from_secs(3);
+const THROUGHPUT_MAX_BYTES: usize = 2 * 1024 * 1024;
+/// 吞吐量读取需在单 IP 超时前结束，预留的收尾余量
+const THROUGHPUT_DEADLINE_MARGIN: Duration = Duration::from_millis(200);
 /// Total timeout for all IP tests within one endpoint
 const IP_TEST_TOTAL_TIMEOUT: Duration = Duration::from_secs(45);
-/// End-to-end workflow timeout bounds (used for dynamic estimation)
+/// Lower bound of the end-to-end workflow timeout (used for dynamic estimation)
 const MIN_WORKFLOW_TIMEOUT: Duration = Duration::from_secs(60);
-const MAX_WORKFLOW_TIMEOUT: Duration = Duration::from_secs(180);
-/// Reserve some headroom for outer workflow timeout
-const COLLECT_TIMEOUT_HEADROOM: Duration = Duration::from_secs(5);
+/// Extra headroom on top of each endpoint's own test budget
+const ENDPOINT_DEADLINE_HEADROOM: Duration = Duration::from_secs(5);
+/// How often long-running awaits (e.g. online IP fetch) re-check the cancel flag
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /// 测速策略参数：控制并发度、错开间隔、批间冷却等
 /// 通过 `from_aggressiveness(level)` 获取预设，或手动构造
@@ -143,6 +156,39 @@ impl TestStrategy {
         }
     }
 
+    /// 用配置中的并发上限覆盖预设值（None 保持预设，其余限制在 1..=MAX_CONFIGURABLE_CONCURRENCY）
+    pub fn with_concurrency_overrides(
+        mut self,
+        max_endpoint_concurrency: Option<u32>,
+        max_ip_concurrency: Option<u32>,
+    ) -> Self {
+        if let Some(limit) = max_endpoint_concurrency {
+            self.max_endpoint_concurrency = clamp_concurrency(limit);
+        }
+        if let Some(limit) = max_ip_concurrency {
+            self.max_ip_concurrency = clamp_concurrency(limit);
+            self.min_ip_concurrency = self.min_ip_concurrency.min(self.max_ip_concurrency);
+        }
+        self
+    }
+
+    /// 用配置中的候选 IP 上限覆盖预设值（None 保持预设，其余限制在 1..=MAX_CONFIGURABLE_TEST_IPS）
+    /// 单端点耗时已由 IP_TEST_TOTAL_TIMEOUT 封顶，因此不影响整轮超时估算
+    pub fn with_max_test_ips(mut self, max_test_ips: Option<u32>) -> Self {
+        if let Some(limit) = max_test_ips {
+            self.max_test_ips = clamp_test_ips(limit);
+        }
+        self
+    }
+
+    /// 按本策略的端点并发数与端点间冷却估算整轮测速的超时预算
+    pub fn estimate_timeout(&self, endpoint_count: usize) -> Duration {
+        let cooldown = Duration::from_millis(self.inter_batch_cooldown_ms)
+            * endpoint_count.saturating_sub(1) as u32;
+        estimate_test_timeout_with_concurrency(endpoint_count, self.max_endpoint_concurrency)
+            + cooldown
+    }
+
     /// 根据当前降级级别返回调整后的参数
     fn effective_ip_concurrency(&self, degradation_level: u32) -> usize {
         let mut concurrency = self.max_ip_concurrency;
@@ -168,8 +214,10 @@ impl Default for TestStrategy {
 }
 
 /// Estimate a realistic timeout budget for testing `endpoint_count` endpoints.
-/// This prevents long endpoint lists from starving later rows and being marked as 9999ms early.
-pub fn estimate_test_timeout(endpoint_count: usize) -> Duration {
+/// Not capped: each endpoint runs under its own deadline, so the outer budget must cover
+/// every batch or long endpoint lists would have their later rows marked as failed early.
+#[cfg(test)]
+fn estimate_test_timeout(endpoint_count: usize) -> Duration {
     estimate_test_timeout_with_concurrency(endpoint_count, MAX_ENDPOINT_CONCURRENCY)
 }
 
@@ -185,19 +233,110 @@ pub fn estimate_test_timeout_with_concurrency(
     let concurrency = endpoint_count.clamp(1, max_concurrency);
     let batches = endpoint_count.div_ceil(concurrency) as u64;
 
-    // Worst-case per endpoint phase:
-    // DNS lookup + original IP probe + optimized IP candidate probing.
-    let per_endpoint_budget = DNS_LOOKUP_TIMEOUT.as_secs()
-        + SINGLE_IP_TEST_TIMEOUT.as_secs()
-        + IP_TEST_TOTAL_TIMEOUT.as_secs();
+    // Worst-case per endpoint phase: the per-endpoint deadline
+    // (DNS lookup + original IP probe + optimized IP candidate probing + headroom).
+    let per_endpoint_budget = (DNS_LOOKUP_TIMEOUT
+        + SINGLE_IP_TEST_TIMEOUT
+        + IP_TEST_TOTAL_TIMEOUT
+        + ENDPOINT_DEADLINE_HEADROOM)
+        .as_secs();
 
     // Add fixed scheduling overhead to avoid premature timeout in loaded environments.
     let estimated_secs = batches * per_endpoint_budget + 15;
 
-    Duration::from_secs(estimated_secs.clamp(
-        MIN_WORKFLOW_TIMEOUT.as_secs(),
-        MAX_WORKFLOW_TIMEOUT.as_secs(),
-    ))
+    Duration::from_secs(estimated_secs.max(MIN_WORKFLOW_TIMEOUT.as_secs()))
+}
+
+/// Deadline for a single endpoint, counted from when it gets a concurrency slot
+fn endpoint_test_deadline(endpoint: &Endpoint) -> Duration {
+    endpoint.scaled_timeout(DNS_LOOKUP_TIMEOUT + SINGLE_IP_TEST_TIMEOUT + IP_TEST_TOTAL_TIMEOUT)
+        + ENDPOINT_DEADLINE_HEADROOM
+}
+
+/// 以有限并发依次启动任务（任务之间间隔 cooldown）。每个任务从拿到并发槽位起单独计时，
+/// 只有超过自己的时限才被放弃，排在后面的任务不会因前面任务的耗时被提前判定失败。
+/// 返回已启动的任务及其结果（超时、panic 或被取消时为 None），保持输入顺序
+async fn run_with_deadlines<T, R, D, F, Fut>(
+    items: &[T],
+    limit: usize,
+    cooldown: Duration,
+    cancelled: &AtomicBool,
+    deadline: D,
+    probe: F,
+) -> Vec<(T, Option<R>)>
+where
+    T: Clone,
+    R: Send + 'static,
+    D: Fn(&T) -> Duration,
+    F: Fn(usize, T) -> Fut,
+    Fut: Future<Output = R> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(limit.max(1)));
+    let mut join_set = JoinSet::new();
+    let mut spawned: Vec<T> = Vec::new();
+
+    for (idx, item) in items.iter().enumerate() {
+        if cancelled.load(Ordering::SeqCst) {
+            warn_log!("测试已取消，停止添加新任务");
+            break;
+        }
+        // 任务间冷却：第二个任务开始添加间隔，避免突发流量
+        if idx > 0 && !cooldown.is_zero() {
+            tokio::time::sleep(cooldown).await;
+        }
+
+        // 等待空闲槽位：运行中的任务都有各自时限，不会无限等待；期间仍响应取消
+        let permit = loop {
+            if cancelled.load(Ordering::SeqCst) {
+                break None;
+            }
+            match tokio::time::timeout(
+                Duration::from_millis(500),
+                semaphore.clone().acquire_owned(),
+            )
+            .await
+            {
+                Ok(Ok(permit)) => break Some(permit),
+                Ok(Err(_)) => {
+                    error_log!("信号量关闭，停止测试");
+                    break None;
+                }
+                Err(_) => continue,
+            }
+        };
+        let Some(permit) = permit else {
+            break;
+        };
+
+        let limit = deadline(item);
+        let task = probe(idx, item.clone());
+        let slot = spawned.len();
+        spawned.push(item.clone());
+        join_set.spawn(async move {
+            let _permit = permit;
+            (slot, tokio::time::timeout(limit, task).await.ok())
+        });
+    }
+
+    let mut outcomes: Vec<Option<R>> = (0..spawned.len()).map(|_| None).collect();
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            warn_log!("测试已取消，中止所有任务");
+            join_set.abort_all();
+            break;
+        }
+        match tokio::time::timeout(Duration::from_secs(1), join_set.join_next()).await {
+            Ok(Some(Ok((slot, outcome)))) => outcomes[slot] = outcome,
+            Ok(Some(Err(e))) => {
+                error_log!("任务 panic: {:?}", e);
+            }
+            Ok(None) => break,
+            // 继续等待，期间检查取消
+            Err(_) => {}
+        }
+    }
+
+    spawned.into_iter().zip(outcomes).collect()
 }
 
 /// 主 DNS 解析器列表（国内 DNS 优先，降低境外域名解析延迟）
@@ -234,6 +373,37 @@ pub fn is_cloudflare_ip(ip: &str) -> bool {
     CF_RANGES.iter().any(|r| ip.starts_with(r))
 }
 
+/// 由 IP 字面量和端口构造 SocketAddr
+/// 直接解析 IpAddr 而非拼接 "ip:port" 字符串，IPv6 字面量（如 2606:4700::1）无需方括号
+fn build_socket_addr(ip: &str, port: u16) -> Result<SocketAddr, String> {
+    let ip = ip.trim().trim_start_matches('[').trim_end_matches(']');
+    let addr: IpAddr = ip.parse().map_err(|e| format!("Invalid IP: {}", e))?;
+    Ok(SocketAddr::new(addr, port))
+}
+
+/// 构造 HTTP Host 头：非协议默认端口（HTTPS 443 / HTTP 80）需附带端口号
+fn host_header(domain: &str, port: u16, tls: bool) -> String {
+    let default_port = if tls {
+        DEFAULT_ENDPOINT_PORT
+    } else {
+        DEFAULT_HTTP_PORT
+    };
+    if port == default_port {
+        domain.to_string()
+    } else {
+        format!("{}:{}", domain, port)
+    }
+}
+
+/// 从响应状态行（如 "HTTP/1.1 502 Bad Gateway"）解析状态码
+fn parse_status_code(response: &str) -> Option<u16> {
+    let status_line = response.lines().next()?;
+    if !status_line.starts_with("HTTP/") {
+        return None;
+    }
+    status_line.split_whitespace().nth(1)?.parse().ok()
+}
+
 /// IP 测试错误分类
 #[derive(Debug, Clone, PartialEq)]
 enum IpTestErrorCategory {
@@ -263,6 +433,63 @@ fn categorize_error(error: &str) -> IpTestErrorCategory {
     }
 }
 
+/// 证书与域名不匹配的错误关键字（OpenSSL / SChannel / Security.framework）
+/// 只收录具体短语，避免误判其他恰好含 "does not match" 之类字样的错误
+const TLS_NAME_MISMATCH_KEYWORDS: &[&str] = &[
+    "hostname mismatch",
+    "host name mismatch",
+    "principal name is incorrect",
+    "certificate name does not match",
+    "not valid for the requested host",
+];
+
+/// 证书校验失败的错误关键字（不受信任、过期、自签名等）
+/// 不收录单独的 "certificate"：alert certificate required 等握手错误也带该词
+const TLS_CERT_INVALID_KEYWORDS: &[&str] = &[
+    "certificate verify failed",
+    "certificate has expired",
+    "not within its validity period",
+    "unable to get local issuer certificate",
+    "certificate for this server is invalid",
+    "not trusted",
+    "untrusted",
+    "self signed",
+    "self-signed",
+    "unknown ca",
+];
+
+/// TLS 握手错误映射：区分证书域名不匹配、证书校验失败与普通握手失败
+fn map_tls_error(err: &str) -> String {
+    let lower = err.to_lowercase();
+    if TLS_NAME_MISMATCH_KEYWORDS.iter().any(|k| lower.contains(k)) {
+        format!("TLS_CERT_MISMATCH: {}", err)
+    } else if TLS_CERT_INVALID_KEYWORDS.iter().any(|k| lower.contains(k)) {
+        format!("TLS_CERT: {}", err)
+    } else {
+        format!("TLS: {}", err)
+    }
+}
+
+/// 根据错误前缀推断失败分类
+/// CF IP 上的证书域名不匹配说明 IP 可达（边缘节点未托管该证书），不按不可达处理
+fn probe_error_kind(ip: &str, error: &str) -> Option<ProbeErrorKind> {
+    if error.starts_with("TLS_CERT_MISMATCH:") {
+        if is_cloudflare_ip(ip) {
+            Some(ProbeErrorKind::CertMismatch)
+        } else {
+            Some(ProbeErrorKind::CertInvalid)
+        }
+    } else if error.starts_with("TLS_CERT:") {
+        Some(ProbeErrorKind::CertInvalid)
+    } else if error.starts_with("TLS:") {
+        Some(ProbeErrorKind::TlsHandshake)
+    } else if error.starts_with("TCP_") {
+        Some(ProbeErrorKind::Connect)
+    } else {
+        None
+    }
+}
+
 /// Merge candidate IPs in stable order and deduplicate.
 /// Priority: online CF IP list first, then current DNS IPs.
 fn merge_candidate_ips(cf_ips: Vec<String>, dns_ips: &[String], limit: usize) -> Vec<String> {
@@ -281,22 +508,35 @@ fn merge_candidate_ips(cf_ips: Vec<String>, dns_ips: &[String], limit: usize) ->
     merged
 }
 
-/// 并发查询多个公共 DNS 解析器，收集域名的所有唯一 IP
-async fn resolve_via_multi_dns(domain: &str) -> Vec<String> {
+/// 构造只使用单个 DNS 服务器的解析器（UDP，2 秒超时，不重试）
+fn single_server_resolver(addr: IpAddr, ip_strategy: LookupIpStrategy) -> TokioAsyncResolver {
+    let ns = NameServerConfig::new(SocketAddr::new(addr, 53), Protocol::Udp);
+    let config = ResolverConfig::from_parts(None, vec![], vec![ns]);
+    let mut opts = ResolverOpts::default();
+    opts.timeout = Duration::from_secs(2);
+    opts.attempts = 1;
+    opts.ip_strategy = ip_strategy;
+    TokioAsyncResolver::tokio(config, opts)
+}
+
+/// 并发查询多个公共 DNS 解析器，返回 (IP, 解析器地址) 列表（不去重，保留每个解析器的归属）
+async fn resolve_via_multi_dns(
+    domain: &str,
+    preference: IpVersionPreference,
+) -> Vec<(String, String)> {
     let mut join_set = JoinSet::new();
+    let ip_strategy = lookup_strategy_for(preference);
 
     for &dns_server in PUBLIC_DNS_SERVERS {
         let domain = domain.to_string();
         let addr: std::net::IpAddr = dns_server.parse().unwrap();
         join_set.spawn(async move {
-            let ns = NameServerConfig::new(SocketAddr::new(addr, 53), Protocol::Udp);
-            let config = ResolverConfig::from_parts(None, vec![], vec![ns]);
-            let mut opts = ResolverOpts::default();
-            opts.timeout = Duration::from_secs(2);
-            opts.attempts = 1;
-            let resolver = TokioAsyncResolver::tokio(config, opts);
+            let resolver = single_server_resolver(addr, ip_strategy);
             match resolver.lookup_ip(&domain).await {
-                Ok(lookup) => lookup.iter().map(|ip| ip.to_string()).collect::<Vec<_>>(),
+                Ok(lookup) => lookup
+                    .iter()
+                    .map(|ip| (ip.to_string(), dns_server.to_string()))
+                    .collect::<Vec<_>>(),
                 Err(_) => vec![],
             }
         });
@@ -315,63 +555,619 @@ async fn resolve_via_multi_dns(domain: &str) -> Vec<String> {
             all_ips.extend(ips);
         }
     }
+    all_ips
+}
 
-    // 去重（保持顺序）
+/// 去掉解析器归属并去重（保持顺序），用于实际测速
+fn unique_resolved_ips(resolved: &[(String, String)]) -> Vec<String> {
     let mut seen = HashSet::new();
-    all_ips.retain(|ip| seen.insert(ip.clone()));
-    all_ips
+    resolved
+        .iter()
+        .filter(|(ip, _)| seen.insert(ip.clone()))
+        .map(|(ip, _)| ip.clone())
+        .collect()
 }
 
-/// Fetch optimized Cloudflare IPs from online API
-/// Returns IPs from cf-speed-dns, falls back to default IPs on failure
-pub async fn fetch_online_cf_ips() -> Vec<String> {
-    info_log!("从在线 API 获取优选 IP...");
+/// 按解析器分组：每个公共 DNS 都有条目，未返回结果（失败或超时）的解析器对应空列表
+fn group_by_resolver(resolved: &[(String, String)]) -> BTreeMap<String, Vec<String>> {
+    let mut grouped: BTreeMap<String, Vec<String>> = PUBLIC_DNS_SERVERS
+        .iter()
+        .map(|server| (server.to_string(), Vec::new()))
+        .collect();
+    for (ip, resolver) in resolved {
+        let ips = grouped.entry(resolver.clone()).or_default();
+        if !ips.contains(ip) {
+            ips.push(ip.clone());
+        }
+    }
+    grouped
+}
+
+/// 诊断多 DNS 解析：返回 解析器地址 → IP 列表，便于发现返回污染结果的解析器
+pub async fn diagnose_multi_dns(
+    domain: &str,
+    preference: IpVersionPreference,
+) -> BTreeMap<String, Vec<String>> {
+    group_by_resolver(&resolve_via_multi_dns(domain, preference).await)
+}
+
+/// 逐个公共 DNS 解析器测速：记录响应耗时与返回的 IP
+/// 成功结果按延迟升序，失败结果排在最后（保持服务器列表顺序）
+pub async fn benchmark_dns_servers(
+    domain: &str,
+    preference: IpVersionPreference,
+) -> Vec<DnsBenchmarkResult> {
+    let mut join_set = JoinSet::new();
+    let ip_strategy = lookup_strategy_for(preference);
+
+    for (index, &dns_server) in PUBLIC_DNS_SERVERS.iter().enumerate() {
+        let domain = domain.to_string();
+        let addr: IpAddr = dns_server.parse().unwrap();
+        join_set.spawn(async move {
+            let resolver = single_server_resolver(addr, ip_strategy);
+            let start = Instant::now();
+            let lookup = tokio::time::timeout(MULTI_DNS_TIMEOUT, resolver.lookup_ip(&domain)).await;
+            let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+            let (ips, error) = match lookup {
+                Ok(Ok(lookup)) => (lookup.iter().map(|ip| ip.to_string()).collect(), None),
+                Ok(Err(e)) => (Vec::new(), Some(e.to_string())),
+                Err(_) => (Vec::new(), Some("超时".to_string())),
+            };
+            (
+                index,
+                DnsBenchmarkResult {
+                    server: dns_server.to_string(),
+                    latency_ms,
+                    ips,
+                    error,
+                },
+            )
+        });
+    }
 
-    let client = match Client::builder().timeout(Duration::from_secs(10)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            warn_log!("创建 HTTP 客户端失败: {}, 使用默认 IP", e);
-            return DEFAULT_CF_IPS.iter().map(|s| s.to_string()).collect();
+    let mut results = Vec::with_capacity(PUBLIC_DNS_SERVERS.len());
+    while let Some(joined) = join_set.join_next().await {
+        if let Ok(item) = joined {
+            results.push(item);
         }
-    };
+    }
+    sort_dns_benchmark(&mut results);
+    results.into_iter().map(|(_, result)| result).collect()
+}
 
-    match client.get(IPDB_API_URL).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                match resp.text().await {
-                    Ok(text) => {
-                        // Support both comma-separated and newline-separated formats
-                        let ips: Vec<String> = text
-                            .split([',', '\n', '\r'])
-                            .map(|s| s.trim().to_string())
-                            .filter(|s| !s.is_empty() && !s.starts_with('#'))
-                            .collect();
-
-                        if ips.is_empty() {
-                            warn_log!("在线 API 返回空列表，使用默认 IP");
-                            DEFAULT_CF_IPS.iter().map(|s| s.to_string()).collect()
-                        } else {
-                            info_log!("从在线 API 获取到 {} 个优选 IP", ips.len());
-                            ips
-                        }
-                    }
-                    Err(e) => {
-                        warn_log!("读取在线 API 响应失败: {}, 使用默认 IP", e);
-                        DEFAULT_CF_IPS.iter().map(|s| s.to_string()).collect()
-                    }
-                }
+/// DNS 测速结果排序：成功在前按延迟升序，失败在后按服务器列表顺序
+fn sort_dns_benchmark(results: &mut [(usize, DnsBenchmarkResult)]) {
+    results.sort_by(|(ia, a), (ib, b)| {
+        let a_ok = a.error.is_none();
+        let b_ok = b.error.is_none();
+        b_ok.cmp(&a_ok).then_with(|| {
+            if a_ok {
+                a.latency_ms.total_cmp(&b.latency_ms)
             } else {
-                warn_log!("在线 API 返回状态码 {}, 使用默认 IP", resp.status());
-                DEFAULT_CF_IPS.iter().map(|s| s.to_string()).collect()
+                ia.cmp(ib)
+            }
+        })
+    });
+}
+
+/// 最优 IP 之外的其余成功 IP，按延迟升序（最多 MAX_IPS_PER_DOMAIN - 1 个）
+fn backup_ips(successes: &[(String, f64)], best_ip: &str) -> Vec<String> {
+    let mut others: Vec<&(String, f64)> =
+        successes.iter().filter(|(ip, _)| ip != best_ip).collect();
+    others.sort_by(|a, b| a.1.total_cmp(&b.1));
+    others
+        .into_iter()
+        .take(MAX_IPS_PER_DOMAIN as usize - 1)
+        .map(|(ip, _)| ip.clone())
+        .collect()
+}
+
+/// 用户可配置的端点/IP 并发上限
+const MAX_CONFIGURABLE_CONCURRENCY: u32 = 32;
+/// 可配置的每端点候选 IP 数上限
+const MAX_CONFIGURABLE_TEST_IPS: u32 = 50;
+/// 基准测试并发上限
+const MAX_BENCHMARK_CONCURRENCY: u32 = 64;
+/// 每个 IP 的测试轮数范围
+const MIN_TEST_ROUNDS: u32 = 1;
+const MAX_TEST_ROUNDS: u32 = 5;
+
+/// 将配置中的测速参数修正为测速时实际生效的值（供 get_effective_config 展示）
+pub(crate) fn apply_effective_limits(config: &mut AppConfig) {
+    config.test_count = config.test_count.clamp(MIN_TEST_ROUNDS, MAX_TEST_ROUNDS);
+    if !matches!(config.test_aggressiveness, 1..=3) {
+        config.test_aggressiveness = 2; // from_aggressiveness 对其他值使用标准模式
+    }
+    config.benchmark_concurrency = config
+        .benchmark_concurrency
+        .clamp(1, MAX_BENCHMARK_CONCURRENCY);
+    config.max_endpoint_concurrency = config
+        .max_endpoint_concurrency
+        .map(|limit| clamp_concurrency(limit) as u32);
+    config.max_ip_concurrency = config
+        .max_ip_concurrency
+        .map(|limit| clamp_concurrency(limit) as u32);
+    config.max_test_ips = config
+        .max_test_ips
+        .map(|limit| clamp_test_ips(limit) as u32);
+}
+
+fn clamp_concurrency(configured: u32) -> usize {
+    configured.clamp(1, MAX_CONFIGURABLE_CONCURRENCY) as usize
+}
+
+fn clamp_test_ips(configured: u32) -> usize {
+    configured.clamp(1, MAX_CONFIGURABLE_TEST_IPS) as usize
+}
+
+/// 基准测试并发上限：限制在 1..=64，且不超过 IP 数量
+fn benchmark_concurrency_limit(configured: u32, ip_count: usize) -> usize {
+    (configured.clamp(1, MAX_BENCHMARK_CONCURRENCY) as usize).min(ip_count.max(1))
+}
+
+/// 校验自定义优选 IP 来源 URL：仅接受 http/https，空值返回 None
+pub fn validate_cf_ip_source_url(raw: &str) -> Result<Option<String>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    let parsed = url::Url::parse(trimmed).map_err(|e| format!("优选 IP 来源 URL 无效: {}", e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(Some(trimmed.to_string())),
+        other => Err(format!(
+            "优选 IP 来源 URL 仅支持 http/https，当前为 {}",
+            other
+        )),
+    }
+}
+
+/// 测速结果排序：收藏端点置顶，其次成功优先，最后按延迟升序
+fn compare_results(a: &EndpointResult, b: &EndpointResult) -> std::cmp::Ordering {
+    b.endpoint
+        .favorite
+        .cmp(&a.endpoint.favorite)
+        .then_with(|| b.success.cmp(&a.success))
+        .then_with(|| {
+            a.latency
+                .partial_cmp(&b.latency)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// 单次 HTTPS 探测耗时（毫秒）
+#[derive(Debug, Clone, Copy)]
+struct HttpsTiming {
+    /// 连接延迟：TCP 连接 + TLS 握手
+    latency: f64,
+    /// 首字节时间：从开始连接到收到首个响应字节
+    ttfb: f64,
+    /// 响应体下载吞吐量（仅开启 measure_throughput 时测量）
+    throughput_kbps: Option<f64>,
+    /// 实际使用的应用层协议（"h2" / "http/1.1"）
+    protocol: &'static str,
+    /// HTTP 状态码（HTTP/2 探测不发请求，为 None）
+    status: Option<u16>,
+}
+
+/// test_endpoint 选出（或未能选出）IP 的分支，用于生成 selection_reason
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Selection {
+    /// DNS 解析失败 / 超时 / 无结果
+    DnsFailed,
+    /// CF 风控冷却中，跳过候选 IP，只看原始 IP
+    CfThrottled { original_ok: bool },
+    /// TCP 预探测全部不可达
+    NetworkUnreachable,
+    /// 最优候选相对原始 IP 加速不显著，保留原始 IP
+    NotSignificant { speedup_percent: f64 },
+    /// 候选 IP 中最快的一个
+    Fastest {
+        candidates: usize,
+        whitelisted: bool,
+    },
+    /// 候选 IP 全部失败，回退原始 IP
+    FallbackOriginal,
+    /// 原始 IP 与候选 IP 全部失败
+    AllFailed,
+}
+
+impl Selection {
+    fn reason(self) -> String {
+        match self {
+            Self::DnsFailed => "DNS 解析失败，未选择 IP".into(),
+            Self::CfThrottled { original_ok: true } => {
+                "CF 风控冷却中，跳过候选 IP，使用 DNS 原始 IP".into()
+            }
+            Self::CfThrottled { original_ok: false } => {
+                "CF 风控冷却中，且 DNS 原始 IP 不可用".into()
+            }
+            Self::NetworkUnreachable => "所有候选 IP TCP 不可达".into(),
+            Self::NotSignificant { speedup_percent } => {
+                format!("最优候选仅快 {:.0}%，保留 DNS 原始 IP", speedup_percent)
+            }
+            Self::Fastest {
+                candidates,
+                whitelisted,
+            } => format!(
+                "在 {} 个候选 IP 中最快{}",
+                candidates,
+                if whitelisted {
+                    "（优选白名单 IP）"
+                } else {
+                    ""
+                }
+            ),
+            Self::FallbackOriginal => "候选 IP 全部失败，回退至 DNS 原始 IP".into(),
+            Self::AllFailed => "原始 IP 与全部候选 IP 均失败".into(),
+        }
+    }
+}
+
+/// HTTP/2 连接前言（RFC 9113 §3.4）
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const HTTP2_FRAME_HEADERS: u8 = 0x1;
+const HTTP2_FRAME_RST_STREAM: u8 = 0x3;
+const HTTP2_FRAME_SETTINGS: u8 = 0x4;
+const HTTP2_FRAME_GOAWAY: u8 = 0x7;
+const HTTP2_FLAG_ACK: u8 = 0x1;
+const HTTP2_FLAG_END_STREAM: u8 = 0x1;
+const HTTP2_FLAG_END_HEADERS: u8 = 0x4;
+const HTTP2_FLAG_PADDED: u8 = 0x8;
+const HTTP2_FLAG_PRIORITY: u8 = 0x20;
+/// 等待服务端 SETTINGS 与响应头时最多读取的帧数
+const HTTP2_MAX_PROBE_FRAMES: usize = 8;
+/// HPACK 静态表第 8-14 项（:status）对应的状态码
+const HPACK_STATIC_STATUS: [u16; 7] = [200, 204, 206, 304, 400, 404, 500];
+
+/// 客户端 HTTP/2 开场：连接前言 + 空 SETTINGS 帧
+fn http2_client_preamble() -> Vec<u8> {
+    let mut buf = HTTP2_PREFACE.to_vec();
+    // 帧头：24 位长度(0) + 类型 + 标志 + 31 位流 ID(0)
+    buf.extend_from_slice(&[0, 0, 0, HTTP2_FRAME_SETTINGS, 0, 0, 0, 0, 0]);
+    buf
+}
+
+/// 解析 HTTP/2 帧头，返回 (负载长度, 帧类型, 标志)
+fn parse_http2_frame_header(header: &[u8; 9]) -> (usize, u8, u8) {
+    let len = ((header[0] as usize) << 16) | ((header[1] as usize) << 8) | header[2] as usize;
+    (len, header[3], header[4])
+}
+
+/// HPACK 整数编码（RFC 7541 §5.1），flags 为首字节中前缀之外的高位
+fn hpack_encode_int(buf: &mut Vec<u8>, flags: u8, prefix_bits: u8, mut value: usize) {
+    let max = (1usize << prefix_bits) - 1;
+    if value < max {
+        buf.push(flags | value as u8);
+        return;
+    }
+    buf.push(flags | max as u8);
+    value -= max;
+    while value >= 0x80 {
+        buf.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// 不索引的字面量头部：名称取静态表索引，值不做 Huffman 编码
+fn hpack_encode_literal(buf: &mut Vec<u8>, name_index: usize, value: &str) {
+    hpack_encode_int(buf, 0x00, 4, name_index);
+    hpack_encode_int(buf, 0x00, 7, value.len());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// 流 1 上的 HEAD / 请求（HEADERS 帧，END_STREAM | END_HEADERS）
+fn http2_probe_request(authority: &str, ua: &str) -> Vec<u8> {
+    let mut block = Vec::new();
+    // :method HEAD（静态表名称 2）、:scheme https（7）、:path /（4）
+    hpack_encode_literal(&mut block, 2, "HEAD");
+    block.extend_from_slice(&[0x87, 0x84]);
+    // :authority（1）、user-agent（58）
+    hpack_encode_literal(&mut block, 1, authority);
+    hpack_encode_literal(&mut block, 58, ua);
+
+    let len = block.len();
+    let mut frame = vec![
+        (len >> 16) as u8,
+        (len >> 8) as u8,
+        len as u8,
+        HTTP2_FRAME_HEADERS,
+        HTTP2_FLAG_END_STREAM | HTTP2_FLAG_END_HEADERS,
+        0,
+        0,
+        0,
+        1,
+    ];
+    frame.extend_from_slice(&block);
+    frame
+}
+
+/// HPACK 整数解码，pos 前移到整数之后
+fn hpack_decode_int(data: &[u8], pos: &mut usize, prefix_bits: u8) -> Option<usize> {
+    let max = (1usize << prefix_bits) - 1;
+    let mut value = *data.get(*pos)? as usize & max;
+    *pos += 1;
+    if value < max {
+        return Some(value);
+    }
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value += ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+/// 解码只含数字的 HPACK Huffman 串（:status 的值），含其他符号时返回 None
+fn hpack_huffman_digits(raw: &[u8]) -> Option<Vec<u8>> {
+    let total = raw.len() * 8;
+    let bit = |i: usize| (raw[i / 8] >> (7 - i % 8)) & 1;
+    let bits = |from: usize, n: usize| (from..from + n).fold(0u8, |acc, i| (acc << 1) | bit(i));
+    let mut out = Vec::new();
+    let mut i = 0;
+    while total - i >= 5 {
+        // '0'-'2' 为 5 位码 00000-00010，'3'-'9' 为 6 位码 011001-011111
+        let code = bits(i, 5);
+        if code < 3 {
+            out.push(b'0' + code);
+            i += 5;
+        } else if total - i >= 6 && (25..=31).contains(&bits(i, 6)) {
+            out.push(b'3' + bits(i, 6) - 25);
+            i += 6;
+        } else {
+            break;
+        }
+    }
+    // 剩余不足 8 位且全为 1 的是 EOS 填充
+    (total - i < 8 && (i..total).all(|k| bit(k) == 1)).then_some(out)
+}
+
+/// HPACK 字符串解码（长度前缀 + 可选 Huffman）
+fn hpack_decode_string(data: &[u8], pos: &mut usize) -> Option<Vec<u8>> {
+    let huffman = *data.get(*pos)? & 0x80 != 0;
+    let len = hpack_decode_int(data, pos, 7)?;
+    let raw = data.get(*pos..pos.checked_add(len)?)?;
+    *pos += len;
+    if huffman {
+        hpack_huffman_digits(raw)
+    } else {
+        Some(raw.to_vec())
+    }
+}
+
+/// 从 HEADERS 帧负载中解析 :status（伪头部必须位于头部块首位）
+/// 新连接上的首个响应尚无动态表可引用，只需处理静态表索引与字面量
+fn parse_http2_status(payload: &[u8], flags: u8) -> Option<u16> {
+    let mut start = 0;
+    let mut end = payload.len();
+    if flags & HTTP2_FLAG_PADDED != 0 {
+        end = end.checked_sub(*payload.first()? as usize)?;
+        start = 1;
+    }
+    if flags & HTTP2_FLAG_PRIORITY != 0 {
+        start += 5;
+    }
+    let block = payload.get(start..end)?;
+
+    let mut pos = 0;
+    // 跳过动态表大小更新
+    while *block.get(pos)? & 0xE0 == 0x20 {
+        hpack_decode_int(block, &mut pos, 5)?;
+    }
+    let first = block[pos];
+    let value = if first & 0x80 != 0 {
+        let index = hpack_decode_int(block, &mut pos, 7)?;
+        return HPACK_STATIC_STATUS.get(index.checked_sub(8)?).copied();
+    } else {
+        let prefix_bits = if first & 0x40 != 0 { 6 } else { 4 };
+        let name_index = hpack_decode_int(block, &mut pos, prefix_bits)?;
+        if name_index == 0 {
+            if hpack_decode_string(block, &mut pos)? != b":status" {
+                return None;
             }
+        } else if !(8..=14).contains(&name_index) {
+            return None;
+        }
+        hpack_decode_string(block, &mut pos)?
+    };
+    std::str::from_utf8(&value).ok()?.parse().ok()
+}
+
+/// 将 ALPN 协商结果映射为协议名（未协商时按 HTTP/1.1 处理）
+fn alpn_protocol_name(alpn: Option<&[u8]>) -> &'static str {
+    match alpn {
+        Some(b"h2") => "h2",
+        _ => "http/1.1",
+    }
+}
+
+/// 构造探测请求（Chrome 风格请求头，User-Agent 可配置）
+fn build_probe_request(method: &str, path: &str, host: &str, ua: &str) -> String {
+    // Browser-like HTTP headers (order and content match Chrome to reduce WAF scoring)
+    // Platform-specific values must match TLS fingerprint (Schannel=Windows, SecureTransport=macOS)
+    #[cfg(target_os = "macos")]
+    let platform = "macOS";
+    #[cfg(not(target_os = "macos"))]
+    let platform = "Windows";
+    format!(
+        "{} {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Connection: close\r\n\
+         sec-ch-ua: \"Google Chrome\";v=\"131\", \"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\"\r\n\
+         sec-ch-ua-mobile: ?0\r\n\
+         sec-ch-ua-platform: \"{}\"\r\n\
+         User-Agent: {}\r\n\
+         Accept: text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8\r\n\
+         Accept-Encoding: gzip, deflate, br, zstd\r\n\
+         Accept-Language: en-US,en;q=0.9\r\n\
+         \r\n",
+        method, path, host, platform, ua
+    )
+}
+
+/// 吞吐量（KB/s），耗时不足 1ms 时按 1ms 计算，避免除零
+fn throughput_kbps(bytes: usize, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    bytes as f64 / 1024.0 / secs
+}
+
+/// 规范化吞吐量测量路径：空值为 "/"，缺少前导斜杠时补齐
+pub fn normalize_throughput_path(path: &str) -> String {
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else if trimmed.starts_with('/') {
+        trimmed.to_string()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// 取中位数（会对输入排序），空列表返回 0
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    values[values.len() / 2]
+}
+
+/// 按 IP 协议族偏好选择 DNS 查询策略（只请求需要的记录类型）
+fn lookup_strategy_for(preference: IpVersionPreference) -> LookupIpStrategy {
+    match preference {
+        IpVersionPreference::Auto => LookupIpStrategy::Ipv4thenIpv6,
+        IpVersionPreference::Ipv4 => LookupIpStrategy::Ipv4Only,
+        IpVersionPreference::Ipv6 => LookupIpStrategy::Ipv6Only,
+    }
+}
+
+/// 内置默认 CF IP 列表
+fn default_cf_ips() -> Vec<String> {
+    DEFAULT_CF_IPS.iter().map(|s| s.to_string()).collect()
+}
+
+/// Fetch optimized Cloudflare IPs from online API
+/// 失败时返回错误（由调用方决定回退策略：过期缓存 > 默认 IP）
+/// proxy 为测速代理 URL，设置时在线获取同样经由该代理
+async fn try_fetch_online_cf_ips(
+    source_url: &str,
+    proxy: Option<&str>,
+    user_agent: &str,
+) -> Result<Vec<String>, String> {
+    info_log!("从在线 API 获取优选 IP: {}", source_url);
+
+    let mut builder = Client::builder()
+        .user_agent(user_agent)
+        .timeout(Duration::from_secs(10));
+    if let Some(proxy_url) = proxy {
+        let proxy = reqwest::Proxy::all(proxy_url).map_err(|e| format!("代理地址无效: {}", e))?;
+        builder = builder.proxy(proxy);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+
+    let resp = client
+        .get(source_url)
+        .send()
+        .await
+        .map_err(|e| format!("请求在线 API 失败: {}", e))?;
+
+    if !resp.status().is_success() {
+        return Err(format!("在线 API 返回状态码 {}", resp.status()));
+    }
+
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("读取在线 API 响应失败: {}", e))?;
+
+    // Support both comma-separated and newline-separated formats
+    let ips: Vec<String> = text
+        .split([',', '\n', '\r'])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty() && !s.starts_with('#'))
+        .collect();
+
+    if ips.is_empty() {
+        return Err("在线 API 返回空列表".into());
+    }
+
+    info_log!("从在线 API 获取到 {} 个优选 IP", ips.len());
+    Ok(ips)
+}
+
+/// 等待 future 完成，期间定期检查取消标志；已取消时放弃等待并返回 None
+async fn run_unless_cancelled<F: Future>(cancelled: &AtomicBool, fut: F) -> Option<F::Output> {
+    tokio::pin!(fut);
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            return None;
+        }
+        if let Ok(output) = tokio::time::timeout(CANCEL_POLL_INTERVAL, &mut fut).await {
+            return Some(output);
+        }
+    }
+}
+
+/// 在线获取失败时的回退：优先使用过期的磁盘缓存，其次内置默认列表
+fn fallback_cf_ips(stale: Option<Vec<String>>) -> Vec<String> {
+    match stale {
+        Some(ips) if !ips.is_empty() => {
+            warn_log!("使用过期的优选 IP 缓存 ({} 个)", ips.len());
+            ips
         }
-        Err(e) => {
-            warn_log!("请求在线 API 失败: {}, 使用默认 IP", e);
-            DEFAULT_CF_IPS.iter().map(|s| s.to_string()).collect()
+        _ => default_cf_ips(),
+    }
+}
+
+/// 最近测速失败的端点（小写域名 → 失败时间），跨测速轮次保留
+static RECENT_FAILURES: OnceLock<std::sync::Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+fn recent_failures() -> std::sync::MutexGuard<'static, HashMap<String, Instant>> {
+    RECENT_FAILURES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 记录本轮实际测试的结果：失败的端点记下失败时间，成功的清除记录
+fn record_recent_failures(results: &[EndpointResult], now: Instant) {
+    let mut failures = recent_failures();
+    for result in results.iter().filter(|r| !r.skipped) {
+        let domain = result.endpoint.domain.to_lowercase();
+        if result.success {
+            failures.remove(&domain);
+        } else {
+            failures.insert(domain, now);
         }
     }
 }
 
+/// 端点在 window 内测速失败过时返回距上次失败的时长（window 为 0 时不跳过）
+fn recently_failed(domain: &str, window: Duration, now: Instant) -> Option<Duration> {
+    if window.is_zero() {
+        return None;
+    }
+    recent_failures()
+        .get(&domain.to_lowercase())
+        .map(|failed_at| now.saturating_duration_since(*failed_at))
+        .filter(|age| *age < window)
+}
+
+/// 逐个接收 test_all 完成的端点结果（流式测速用），在测速任务内同步调用
+pub type ResultSink = Arc<dyn Fn(&EndpointResult) + Send + Sync>;
+
+/// 测速运行 ID 计数器：每个新建的 tester 分配一个，clone 共享
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
+
 /// Reusable endpoint tester with connection pooling
 #[derive(Clone)]
 pub struct EndpointTester {
@@ -391,10 +1187,70 @@ pub struct EndpointTester {
     cf_throttle_until: Arc<Mutex<Option<Instant>>>,
     /// 当前降级级别（0=正常，每次限流+1）
     degradation_level: Arc<AtomicU32>,
+    /// 在线优选 IP 磁盘缓存（None 时仅使用内存缓存）
+    cf_ip_cache: Option<Arc<CfIpCache>>,
+    /// 在线优选 IP 来源 URL（默认 IPDB_API_URL）
+    cf_ip_source_url: Arc<String>,
+    /// 是否测量下载吞吐量（GET 读取响应体）
+    measure_throughput: bool,
+    /// 吞吐量测量请求路径
+    throughput_path: Arc<String>,
+    /// IP 协议族偏好（多 DNS 解析只查询对应记录类型）
+    ip_version_preference: IpVersionPreference,
+    /// 测速代理（None 时直连）
+    proxy: Option<Arc<ProbeProxy>>,
+    /// 是否通过 ALPN 协商 HTTP/2（默认仅 HTTP/1.1）
+    http2: bool,
+    /// 探测请求与在线 IP 获取使用的 User-Agent
+    user_agent: Arc<String>,
+    /// 严格 CF 模式：忽略 CF 检测结果，始终走 CF 候选路径
+    cf_only: bool,
+    /// test_all 跳过在此时长内刚失败过的端点（0 表示不跳过）
+    skip_recently_failed: Duration,
+    /// 诊断探测：任何 HTTP 状态码都视为可达（429 也不按 CF 风控处理），仅 probe_once 使用
+    accept_any_status: bool,
+    /// 本次测速的运行 ID（clone 共享，用于定向取消）
+    run_id: u64,
+    /// 每完成一个端点即回调（None 时只在 test_all 结束后统一返回）
+    result_sink: Option<ResultSink>,
 }
 
 use tokio::sync::Mutex;
 
+/// 进程内共享的 TLS 连接器：每种 ALPN 配置只创建一次，各 tester clone 使用
+/// 探测走系统原生 TLS（Schannel / Security Framework / OpenSSL），不依赖 rustls 的全局 CryptoProvider，
+/// 因此与 reqwest 内部使用的 rustls 互不干扰
+static HTTP1_TLS_CONNECTOR: OnceLock<Result<TlsConnector, String>> = OnceLock::new();
+static HTTP2_TLS_CONNECTOR: OnceLock<Result<TlsConnector, String>> = OnceLock::new();
+
+fn shared_tls_connector(http2: bool) -> Result<TlsConnector, String> {
+    let cell = if http2 {
+        &HTTP2_TLS_CONNECTOR
+    } else {
+        &HTTP1_TLS_CONNECTOR
+    };
+    cell.get_or_init(|| {
+        let mut builder = native_tls::TlsConnector::builder();
+        if http2 {
+            builder.request_alpns(&["h2", "http/1.1"]);
+        }
+        let result = builder
+            .build()
+            .map(TlsConnector::from)
+            .map_err(|e| e.to_string());
+        match &result {
+            Ok(_) => {
+                debug_log!("已初始化原生 TLS 连接器（HTTP/2: {}）", http2);
+            }
+            Err(e) => {
+                warn_log!("初始化原生 TLS 连接器失败（HTTP/2: {}）: {}", http2, e);
+            }
+        }
+        result
+    })
+    .clone()
+}
+
 impl EndpointTester {
     #[allow(dead_code)]
     pub fn new(custom_cf_ips: Vec<String>, test_rounds: u32) -> Self {
@@ -408,9 +1264,8 @@ impl EndpointTester {
     ) -> Self {
         // Use native TLS (Schannel on Windows, Security Framework on macOS)
         // for authentic OS-level TLS fingerprints instead of rustls's identifiable JA3
-        let native_connector =
-            native_tls::TlsConnector::new().expect("Failed to create native TLS connector");
-        let tls_connector = TlsConnector::from(native_connector);
+        let tls_connector =
+            shared_tls_connector(false).expect("Failed to create native TLS connector");
 
         // Pre-create DNS resolver with domestic DNS servers for faster resolution
         let mut name_servers = Vec::new();
@@ -431,7 +1286,7 @@ impl EndpointTester {
         let resolver = TokioAsyncResolver::tokio(config, opts);
 
         // Clamp test rounds to 1..=5
-        let test_rounds = test_rounds.clamp(1, 5);
+        let test_rounds = test_rounds.clamp(MIN_TEST_ROUNDS, MAX_TEST_ROUNDS);
 
         Self {
             custom_cf_ips: Arc::new(custom_cf_ips),
@@ -445,46 +1300,221 @@ impl EndpointTester {
             strategy,
             cf_throttle_until: Arc::new(Mutex::new(None)),
             degradation_level: Arc::new(AtomicU32::new(0)),
+            cf_ip_cache: None,
+            cf_ip_source_url: Arc::new(IPDB_API_URL.to_string()),
+            measure_throughput: false,
+            throughput_path: Arc::new("/".to_string()),
+            ip_version_preference: IpVersionPreference::Auto,
+            proxy: None,
+            http2: false,
+            user_agent: Arc::new(DEFAULT_USER_AGENT.to_string()),
+            cf_only: false,
+            skip_recently_failed: Duration::ZERO,
+            accept_any_status: false,
+            run_id: NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed),
+            result_sink: None,
         }
     }
 
-    /// 创建带 AppHandle 的 EndpointTester（用于向前端推送测速进度）
-    #[cfg(feature = "tauri-runtime")]
-    #[allow(dead_code)]
-    pub fn with_app_handle(
-        custom_cf_ips: Vec<String>,
-        test_rounds: u32,
-        app_handle: Option<AppHandle>,
-    ) -> Self {
-        let mut tester = Self::new(custom_cf_ips, test_rounds);
-        tester.app_handle = app_handle;
-        tester
+    /// 设置逐端点结果回调：test_all 每得到一个结果（含跳过与异常）立即调用
+    pub fn with_result_sink(mut self, sink: ResultSink) -> Self {
+        self.result_sink = Some(sink);
+        self
     }
 
-    /// 创建带 AppHandle 和 TestStrategy 的 EndpointTester
-    #[cfg(feature = "tauri-runtime")]
-    pub fn with_app_handle_and_strategy(
-        custom_cf_ips: Vec<String>,
-        test_rounds: u32,
-        app_handle: Option<AppHandle>,
-        strategy: TestStrategy,
-    ) -> Self {
-        let mut tester = Self::with_strategy(custom_cf_ips, test_rounds, strategy);
-        tester.app_handle = app_handle;
-        tester
+    fn report_result(&self, result: &EndpointResult) {
+        if let Some(sink) = &self.result_sink {
+            sink(result);
+        }
     }
 
-    /// 设置 CF 限流冷却期
-    async fn set_cf_cooldown(&self, duration: Duration) {
-        let mut throttle = self.cf_throttle_until.lock().await;
-        *throttle = Some(Instant::now() + duration);
+    /// 测速时跳过在 secs 秒内刚失败过的端点（0 表示不跳过）
+    pub fn with_skip_recently_failed(mut self, secs: u64) -> Self {
+        self.skip_recently_failed = Duration::from_secs(secs);
+        self
     }
 
-    /// 检查 CF 是否处于限流冷却期
-    async fn is_cf_throttled(&self) -> bool {
-        let throttle = self.cf_throttle_until.lock().await;
-        match *throttle {
-            Some(until) => Instant::now() < until,
+    /// 开启严格 CF 模式（所有端点按 CF 站点测速，不做多 DNS 解析）
+    pub fn with_cf_only(mut self, enabled: bool) -> Self {
+        self.cf_only = enabled;
+        self
+    }
+
+    /// 是否按 CF 站点测速：严格 CF 模式下恒为 true，否则看 DNS 结果中是否有 CF IP
+    fn treat_as_cf(&self, dns_ips: &[String]) -> bool {
+        self.cf_only || dns_ips.iter().any(|ip| is_cloudflare_ip(ip))
+    }
+
+    /// 设置探测请求与在线 IP 获取使用的 User-Agent（为空或无效时保持内置 UA）
+    pub fn with_user_agent(mut self, user_agent: Option<&str>) -> Self {
+        match user_agent.map(normalize_user_agent) {
+            Some(Ok(Some(ua))) => self.user_agent = Arc::new(ua),
+            Some(Err(e)) => {
+                warn_log!("{}，使用内置 User-Agent", e);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// 启用在线优选 IP 的磁盘缓存
+    pub fn with_cf_ip_cache(mut self, cache: CfIpCache) -> Self {
+        self.cf_ip_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// 设置在线优选 IP 来源 URL（无效或为空时保持内置地址）
+    pub fn with_cf_ip_source_url(mut self, source_url: Option<&str>) -> Self {
+        match source_url.map(validate_cf_ip_source_url) {
+            Some(Ok(Some(url))) => self.cf_ip_source_url = Arc::new(url),
+            Some(Err(e)) => {
+                warn_log!("{}，使用内置来源", e);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// 开启吞吐量测量（GET 指定路径并读取响应体）
+    pub fn with_throughput(mut self, enabled: bool, path: &str) -> Self {
+        self.measure_throughput = enabled;
+        self.throughput_path = Arc::new(normalize_throughput_path(path));
+        self
+    }
+
+    /// 按配置构建测速器：优选 IP、测试轮数、激进程度及全部可选测速参数
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::with_strategy(
+            config.preferred_ips.clone(),
+            config.test_count,
+            TestStrategy::from_aggressiveness(config.test_aggressiveness),
+        )
+        .with_config_options(config)
+    }
+
+    /// 应用 AppConfig 中的可选测速参数（并发上限、磁盘缓存、IP 来源、吞吐量等）
+    pub fn with_config_options(mut self, config: &AppConfig) -> Self {
+        self.strategy = self
+            .strategy
+            .with_concurrency_overrides(config.max_endpoint_concurrency, config.max_ip_concurrency)
+            .with_max_test_ips(config.max_test_ips);
+        self.with_cf_ip_cache(CfIpCache::new(config.cf_ip_cache_ttl_secs))
+            .with_cf_ip_source_url(config.cf_ip_source_url.as_deref())
+            .with_throughput(config.measure_throughput, &config.throughput_path)
+            .with_ip_version_preference(config.ip_version_preference)
+            .with_proxy(config.proxy.as_deref())
+            .with_http2(config.enable_http2)
+            .with_user_agent(config.user_agent.as_deref())
+            .with_cf_only(config.cf_only)
+            .with_skip_recently_failed(config.skip_recently_failed_secs)
+    }
+
+    /// 构建测速器所读取的全部配置项（with_strategy 参数 + with_config_options）的指纹
+    /// 缓存 EndpointTester 的调用方据此判断配置是否变化、是否需要重建
+    pub fn config_fingerprint(config: &AppConfig) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        config.preferred_ips.hash(&mut hasher);
+        config.test_count.hash(&mut hasher);
+        config.test_aggressiveness.hash(&mut hasher);
+        config.max_endpoint_concurrency.hash(&mut hasher);
+        config.max_ip_concurrency.hash(&mut hasher);
+        config.max_test_ips.hash(&mut hasher);
+        config.cf_ip_cache_ttl_secs.hash(&mut hasher);
+        config.cf_ip_source_url.hash(&mut hasher);
+        config.measure_throughput.hash(&mut hasher);
+        config.throughput_path.hash(&mut hasher);
+        config.ip_version_preference.hash(&mut hasher);
+        config.proxy.hash(&mut hasher);
+        config.enable_http2.hash(&mut hasher);
+        config.user_agent.hash(&mut hasher);
+        config.cf_only.hash(&mut hasher);
+        config.skip_recently_failed_secs.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// 本次探测是否协商 h2
+    /// 诊断探测需要完整的响应头（CF 相关头、原始状态行），吞吐量测量需要 GET 读取响应体，
+    /// 而 HTTP/2 探测只发 HEAD，这两种情况固定回退到 HTTP/1.1
+    fn negotiates_http2(&self) -> bool {
+        self.http2 && !self.accept_any_status && !self.measure_throughput
+    }
+
+    /// 开启 HTTP/2 探测：TLS 握手时通过 ALPN 声明 ["h2", "http/1.1"]
+    pub fn with_http2(mut self, enabled: bool) -> Self {
+        if enabled == self.http2 {
+            return self;
+        }
+        match shared_tls_connector(enabled) {
+            Ok(connector) => {
+                self.tls_connector = connector;
+                self.http2 = enabled;
+            }
+            Err(e) => {
+                warn_log!("创建 HTTP/2 TLS 连接器失败，继续使用 HTTP/1.1: {}", e);
+            }
+        }
+        self
+    }
+
+    /// 设置测速代理（为空或无效时直连）
+    pub fn with_proxy(mut self, proxy: Option<&str>) -> Self {
+        self.proxy = match proxy.map(str::trim).filter(|p| !p.is_empty()) {
+            Some(raw) => match ProbeProxy::parse(raw) {
+                Ok(proxy) => Some(Arc::new(proxy)),
+                Err(e) => {
+                    warn_log!("{}，测速将直连", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        self
+    }
+
+    /// 设置 IP 协议族偏好
+    pub fn with_ip_version_preference(mut self, preference: IpVersionPreference) -> Self {
+        self.ip_version_preference = preference;
+        self
+    }
+
+    /// 创建带 AppHandle 的 EndpointTester（用于向前端推送测速进度）
+    #[cfg(feature = "tauri-runtime")]
+    #[allow(dead_code)]
+    pub fn with_app_handle(
+        custom_cf_ips: Vec<String>,
+        test_rounds: u32,
+        app_handle: Option<AppHandle>,
+    ) -> Self {
+        let mut tester = Self::new(custom_cf_ips, test_rounds);
+        tester.app_handle = app_handle;
+        tester
+    }
+
+    /// 创建带 AppHandle 和 TestStrategy 的 EndpointTester
+    #[cfg(feature = "tauri-runtime")]
+    pub fn with_app_handle_and_strategy(
+        custom_cf_ips: Vec<String>,
+        test_rounds: u32,
+        app_handle: Option<AppHandle>,
+        strategy: TestStrategy,
+    ) -> Self {
+        let mut tester = Self::with_strategy(custom_cf_ips, test_rounds, strategy);
+        tester.app_handle = app_handle;
+        tester
+    }
+
+    /// 设置 CF 限流冷却期
+    async fn set_cf_cooldown(&self, duration: Duration) {
+        let mut throttle = self.cf_throttle_until.lock().await;
+        *throttle = Some(Instant::now() + duration);
+    }
+
+    /// 检查 CF 是否处于限流冷却期
+    async fn is_cf_throttled(&self) -> bool {
+        let throttle = self.cf_throttle_until.lock().await;
+        match *throttle {
+            Some(until) => Instant::now() < until,
             None => false,
         }
     }
@@ -541,12 +1571,35 @@ impl EndpointTester {
         self.cancelled.store(true, Ordering::SeqCst);
     }
 
-    /// TCP-only 探测：仅建立 TCP 连接到 443 端口，不做 TLS/HTTP
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 整轮测速的兜底超时：按策略估算，并按端点中最大的超时倍率放大
+    pub fn estimate_timeout(&self, endpoints: &[Endpoint]) -> Duration {
+        let multiplier = endpoints
+            .iter()
+            .map(|e| e.scaled_timeout(Duration::from_secs(1)).as_secs_f64())
+            .fold(1.0, f64::max);
+        self.strategy
+            .estimate_timeout(endpoints.len())
+            .mul_f64(multiplier)
+    }
+
+    /// 本次测速的运行 ID
+    pub fn run_id(&self) -> u64 {
+        self.run_id
+    }
+
+    /// 是否为同一次测速（clone 共享取消标记）
+    pub fn is_same_run(&self, other: &EndpointTester) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+
+    /// TCP-only 探测：仅建立 TCP 连接到端点端口（默认 443），不做 TLS/HTTP
     /// 用于快速判断 IP 是否网络可达
-    async fn tcp_probe(ip: &str) -> Result<Duration, String> {
-        let addr: SocketAddr = format!("{}:443", ip)
-            .parse()
-            .map_err(|e| format!("Invalid IP: {}", e))?;
+    async fn tcp_probe(ip: &str, port: u16) -> Result<Duration, String> {
+        let addr = build_socket_addr(ip, port)?;
 
         let socket = if addr.is_ipv4() {
             TcpSocket::new_v4()
@@ -578,7 +1631,7 @@ impl EndpointTester {
         }
     }
 
-    #[allow(dead_code)]
+    /// 仅测试指定 IP（不做优选）
     pub async fn test_ip(&self, endpoint: &Endpoint, ip: String) -> EndpointResult {
         self.test_single_ip(endpoint, ip).await
     }
@@ -600,8 +1653,47 @@ impl EndpointTester {
             }
         }
 
-        // 3. 从在线 API 获取并缓存
-        let online_ips = fetch_online_cf_ips().await;
+        // 3. 尝试未过期的磁盘缓存
+        let source_url = self.cf_ip_source_url.as_str();
+        if let Some(disk_ips) = self
+            .cf_ip_cache
+            .as_ref()
+            .and_then(|c| c.load_fresh(source_url))
+        {
+            debug_log!("使用磁盘缓存的在线优选 IP ({} 个)", disk_ips.len());
+            let mut cached = self.online_cf_ips.lock().await;
+            *cached = Some(disk_ips.clone());
+            return disk_ips;
+        }
+
+        // 4. 从在线 API 获取并缓存；失败时回退到过期缓存或默认 IP
+        // 测速被取消时立即放弃获取，且不缓存回退结果（下次测速重新获取）
+        let proxy_url = self.proxy.as_ref().map(|p| p.to_url());
+        let fetch = try_fetch_online_cf_ips(source_url, proxy_url.as_deref(), &self.user_agent);
+        let online_ips = match run_unless_cancelled(&self.cancelled, fetch).await {
+            Some(Ok(ips)) => {
+                if let Some(cache) = &self.cf_ip_cache {
+                    cache.save(source_url, &ips);
+                }
+                ips
+            }
+            Some(Err(e)) => {
+                warn_log!("{}", e);
+                fallback_cf_ips(
+                    self.cf_ip_cache
+                        .as_ref()
+                        .and_then(|c| c.load_stale(source_url)),
+                )
+            }
+            None => {
+                warn_log!("测试已取消，放弃在线获取优选 IP");
+                return fallback_cf_ips(
+                    self.cf_ip_cache
+                        .as_ref()
+                        .and_then(|c| c.load_stale(source_url)),
+                );
+            }
+        };
         {
             let mut cached = self.online_cf_ips.lock().await;
             *cached = Some(online_ips.clone());
@@ -609,6 +1701,140 @@ impl EndpointTester {
         online_ips
     }
 
+    /// 收集候选 IP（不做探测），返回 (CF 候选源, 合并后的待测列表)
+    /// User-configured preferred IPs take highest priority regardless of CF detection
+    async fn collect_candidate_ips(
+        &self,
+        domain: &str,
+        dns_ips: &[String],
+        is_cf: bool,
+    ) -> (Vec<String>, Vec<String>) {
+        if !self.custom_cf_ips.is_empty() {
+            debug_log!("  使用用户白名单 IP（优先级最高），不合并 DNS IP");
+            let ips = self.custom_cf_ips.to_vec();
+            (ips.clone(), ips)
+        } else if is_cf {
+            let cf_ips = self.get_cf_ips().await;
+            let merged = merge_candidate_ips(cf_ips.clone(), dns_ips, self.strategy.max_test_ips);
+            (cf_ips, merged)
+        } else {
+            // 非 CF 站点：并发查询多个公共 DNS，收集更多候选 IP
+            debug_log!("  非CF站点，启用多DNS解析器优选");
+            let multi_dns_ips = unique_resolved_ips(
+                &resolve_via_multi_dns(domain, self.ip_version_preference).await,
+            );
+            if multi_dns_ips.len() > dns_ips.len() {
+                debug_log!(
+                    "  多DNS解析发现 {} 个唯一IP（原DNS {} 个）",
+                    multi_dns_ips.len(),
+                    dns_ips.len()
+                );
+            }
+            // 合并：DNS IP 优先，然后追加多 DNS 发现的新 IP，限制总数
+            let merged =
+                merge_candidate_ips(dns_ips.to_vec(), &multi_dns_ips, self.strategy.max_test_ips);
+            (Vec::new(), merged)
+        }
+    }
+
+    /// 预览某域名的候选 IP 集合：执行 DNS 解析 + 合并逻辑，但不做任何探测
+    pub async fn preview_candidate_ips(&self, domain: &str) -> Result<CandidateIps, String> {
+        let dns_ips: Vec<String> =
+            match tokio::time::timeout(DNS_LOOKUP_TIMEOUT, self.resolver.lookup_ip(domain)).await {
+                Ok(Ok(lookup)) => lookup.iter().map(|ip| ip.to_string()).collect(),
+                Ok(Err(e)) => return Err(format!("DNS失败: {}", e)),
+                Err(_) => return Err("DNS超时".into()),
+            };
+
+        let is_cf = self.treat_as_cf(&dns_ips);
+        let (cf_ips, merged) = self.collect_candidate_ips(domain, &dns_ips, is_cf).await;
+
+        Ok(CandidateIps {
+            dns_ips,
+            cf_ips,
+            merged,
+            is_cf,
+        })
+    }
+
+    /// 批量基准测试一组 IP（独立并发上限，每个 IP 完成后推送进度）
+    /// ips 为空时使用 CF 候选源（白名单 > 在线 > 默认）
+    pub async fn benchmark_cf_ips(
+        &self,
+        endpoint: &Endpoint,
+        ips: Vec<String>,
+        concurrency: u32,
+    ) -> Vec<EndpointResult> {
+        let ips = if ips.is_empty() {
+            self.get_cf_ips().await
+        } else {
+            ips
+        };
+        if ips.is_empty() {
+            return Vec::new();
+        }
+
+        let total = ips.len();
+        let concurrency = benchmark_concurrency_limit(concurrency, total);
+        info_log!(
+            "开始基准测试 {} 个 IP ({}，并发 {})",
+            total,
+            endpoint.domain,
+            concurrency
+        );
+
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let mut join_set = JoinSet::new();
+        for ip in ips {
+            if self.cancelled.load(Ordering::SeqCst) {
+                break;
+            }
+            let permit = match semaphore.clone().acquire_owned().await {
+                Ok(p) => p,
+                Err(_) => break,
+            };
+            let tester = self.clone();
+            let endpoint = endpoint.clone();
+            join_set.spawn(async move {
+                let _permit = permit;
+                tester.test_single_ip(&endpoint, ip).await
+            });
+        }
+
+        let mut results = Vec::with_capacity(total);
+        while let Some(joined) = join_set.join_next().await {
+            let Ok(result) = joined else { continue };
+            results.push(result);
+            let last = &results[results.len() - 1];
+            self.emit_progress(
+                TestProgressEventType::BenchmarkIpComplete,
+                if last.success { "success" } else { "warning" },
+                Some(&endpoint.name),
+                format!(
+                    "[{}/{}] {} {}",
+                    results.len(),
+                    total,
+                    last.ip,
+                    if last.success {
+                        format!("{:.0}ms", last.latency)
+                    } else {
+                        last.error.clone().unwrap_or_else(|| "失败".into())
+                    }
+                ),
+            );
+        }
+
+        results.sort_by(|a, b| match (a.success, b.success) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => a
+                .latency
+                .partial_cmp(&b.latency)
+                .unwrap_or(std::cmp::Ordering::Equal),
+        });
+        results
+    }
+
     /// Test all endpoints concurrently with controlled parallelism
     pub async fn test_all(&self, endpoints: &[Endpoint]) -> Vec<EndpointResult> {
         info_log!("开始测试 {} 个端点", endpoints.len());
@@ -621,6 +1847,36 @@ impl EndpointTester {
         // 每次 test_all 调用重置降级级别
         self.degradation_level.store(0, Ordering::SeqCst);
 
+        // 跳过最近刚失败过的端点（很可能仍不可用），直接记为跳过
+        let now = Instant::now();
+        let mut skipped = Vec::new();
+        let endpoints: Vec<Endpoint> = endpoints
+            .iter()
+            .filter(|endpoint| {
+                match recently_failed(&endpoint.domain, self.skip_recently_failed, now) {
+                    Some(age) => {
+                        info_log!(
+                            "跳过端点 {} ({})：{} 秒前测速失败",
+                            endpoint.name,
+                            endpoint.domain,
+                            age.as_secs()
+                        );
+                        skipped.push(EndpointResult::skipped_recent_failure(
+                            (*endpoint).clone(),
+                            age.as_secs(),
+                        ));
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .cloned()
+            .collect();
+        let endpoints = endpoints.as_slice();
+        for result in &skipped {
+            self.report_result(result);
+        }
+
         // 使用 strategy 的端点并发数
         let max_concurrency = endpoints
             .len()
@@ -642,176 +1898,90 @@ impl EndpointTester {
                 max_concurrency
             ),
         );
-        let semaphore = Arc::new(Semaphore::new(max_concurrency));
-        let mut join_set = JoinSet::new();
-
-        // 追踪已 spawn 的端点，用于处理 panic 情况
-        let mut spawned_endpoints: Vec<Endpoint> = Vec::new();
-
-        for (idx, endpoint) in endpoints.iter().enumerate() {
-            if self.cancelled.load(Ordering::SeqCst) {
-                warn_log!("测试已取消，停止添加新任务");
-                break;
-            }
-
-            // 端点间冷却：第二个端点开始添加间隔，避免突发流量
-            if idx > 0 {
-                tokio::time::sleep(Duration::from_millis(self.strategy.inter_batch_cooldown_ms))
-                    .await;
-            }
-
-            debug_log!(
-                "[{}/{}] 准备测试端点: {} ({})",
-                idx + 1,
-                endpoints.len(),
-                endpoint.name,
-                endpoint.domain
-            );
-
-            let endpoint = endpoint.clone();
-            let tester = self.clone();
-
-            // 获取信号量，添加 5 秒超时防止死锁
-            let permit = match tokio::time::timeout(
-                Duration::from_secs(5),
-                semaphore.clone().acquire_owned(),
-            )
-            .await
-            {
-                Ok(Ok(permit)) => permit,
-                Ok(Err(_)) => {
-                    error_log!("信号量关闭，停止测试");
-                    break;
-                }
-                Err(_) => {
-                    error_log!("获取信号量超时，跳过端点: {}", endpoint.name);
-                    continue;
-                }
-            };
-
-            // 记录已 spawn 的端点
-            spawned_endpoints.push(endpoint.clone());
-
-            let idx_copy = idx;
-            let total = endpoints.len();
-            join_set.spawn(async move {
-                let _permit = permit;
-                debug_log!("[{}/{}] 开始测试: {}", idx_copy + 1, total, endpoint.name);
-                let start = Instant::now();
-                let result = tester.test_endpoint(&endpoint).await;
+        let total = endpoints.len();
+        let outcomes = run_with_deadlines(
+            endpoints,
+            max_concurrency,
+            Duration::from_millis(self.strategy.inter_batch_cooldown_ms),
+            &self.cancelled,
+            endpoint_test_deadline,
+            |idx, endpoint| {
+                let tester = self.clone();
                 debug_log!(
-                    "[{}/{}] 测试完成: {} - {} (耗时 {:.1}s)",
-                    idx_copy + 1,
+                    "[{}/{}] 准备测试端点: {} ({})",
+                    idx + 1,
                     total,
                     endpoint.name,
-                    if result.success {
-                        format!("成功 {:.0}ms", result.latency)
-                    } else {
-                        format!("失败: {}", result.error.as_deref().unwrap_or("unknown"))
-                    },
-                    start.elapsed().as_secs_f64()
-                );
-                result
-            });
-        }
-
-        info_log!("已添加 {} 个测试任务，等待完成...", join_set.len());
-
-        let mut results = Vec::with_capacity(endpoints.len());
-        let collect_start = Instant::now();
-        let collect_timeout =
-            estimate_test_timeout(spawned_endpoints.len()).saturating_sub(COLLECT_TIMEOUT_HEADROOM);
-        let mut panic_count = 0usize;
-
-        // 收集结果，使用动态预算而不是固定 30 秒，避免后排端点饥饿
-        loop {
-            // 检查总体超时
-            if collect_start.elapsed() > collect_timeout {
-                warn_log!(
-                    "收集结果超时（{}秒），已收集 {} 个结果，中止剩余任务",
-                    collect_timeout.as_secs(),
-                    results.len(),
+                    endpoint.domain
                 );
-                join_set.abort_all();
-                break;
-            }
-
-            // 检查是否取消
-            if self.cancelled.load(Ordering::SeqCst) {
-                warn_log!("测试已取消，中止所有任务");
-                join_set.abort_all();
-                break;
-            }
-
-            // 使用超时等待下一个结果
-            match tokio::time::timeout(Duration::from_secs(5), join_set.join_next()).await {
-                Ok(Some(Ok(result))) => {
-                    results.push(result);
+                async move {
+                    debug_log!("[{}/{}] 开始测试: {}", idx + 1, total, endpoint.name);
+                    let start = Instant::now();
+                    let result = tester.test_endpoint(&endpoint).await;
                     debug_log!(
-                        "已收集 {}/{} 个结果",
-                        results.len(),
-                        spawned_endpoints.len()
+                        "[{}/{}] 测试完成: {} - {} (耗时 {:.1}s)",
+                        idx + 1,
+                        total,
+                        endpoint.name,
+                        if result.success {
+                            format!("成功 {:.0}ms", result.latency)
+                        } else {
+                            format!("失败: {}", result.error.as_deref().unwrap_or("unknown"))
+                        },
+                        start.elapsed().as_secs_f64()
                     );
+                    tester.report_result(&result);
+                    result
                 }
-                Ok(Some(Err(e))) => {
-                    panic_count += 1;
-                    error_log!("任务 panic: {:?}", e);
-                }
-                Ok(None) => {
-                    // 所有任务完成
-                    info_log!(
-                        "所有任务完成，共 {} 个结果，{} 个 panic",
-                        results.len(),
-                        panic_count
+            },
+        )
+        .await;
+
+        // 为没有返回结果的端点（超过自身时限、panic 或取消）创建失败记录
+        let mut results = Vec::with_capacity(outcomes.len());
+        for (endpoint, outcome) in outcomes {
+            match outcome {
+                Some(result) => results.push(result),
+                None => {
+                    warn_log!(
+                        "端点 {} ({}) 测试异常，未返回结果",
+                        endpoint.name,
+                        endpoint.domain
                     );
-                    break;
-                }
-                Err(_) => {
-                    // 单个等待超时，继续等待
-                    debug_log!("等待下一个结果超时，继续...");
+                    let result = EndpointResult::failure(
+                        endpoint,
+                        String::new(),
+                        "测试异常（任务崩溃或超时）".into(),
+                    );
+                    self.report_result(&result);
+                    results.push(result);
                 }
             }
         }
 
-        // 为没有返回结果的端点（panic 或超时）创建失败记录
-        let returned_domains: std::collections::HashSet<String> =
-            results.iter().map(|r| r.endpoint.domain.clone()).collect();
-
-        for endpoint in &spawned_endpoints {
-            if !returned_domains.contains(&endpoint.domain) {
-                warn_log!(
-                    "端点 {} ({}) 测试异常，未返回结果",
-                    endpoint.name,
-                    endpoint.domain
-                );
-                results.push(EndpointResult::failure(
-                    endpoint.clone(),
-                    String::new(),
-                    "测试异常（任务崩溃或超时）".into(),
-                ));
-            }
+        // 取消导致的失败不代表端点不可用，不计入最近失败
+        if !self.is_cancelled() {
+            record_recent_failures(&results, Instant::now());
         }
+        results.extend(skipped);
 
-        // Sort by latency (成功的排前面，失败的排后面)
-        results.sort_by(|a, b| match (a.success, b.success) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a
-                .latency
-                .partial_cmp(&b.latency)
-                .unwrap_or(std::cmp::Ordering::Equal),
-        });
+        // 收藏端点置顶，组内成功的排前面、按延迟升序
+        results.sort_by(compare_results);
 
         let success_count = results.iter().filter(|r| r.success).count();
+        let best_latency = results
+            .iter()
+            .filter(|r| r.success)
+            .map(|r| r.latency)
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
         info_log!(
             "测速完成: {}/{} 成功, 最佳延迟: {:.0}ms",
             success_count,
             results.len(),
-            results.first().map(|r| r.latency).unwrap_or(0.0)
+            best_latency.unwrap_or(0.0)
         );
 
         // 发射 TestFinished 事件
-        let best_latency = results.first().filter(|r| r.success).map(|r| r.latency);
         let finish_msg = if let Some(lat) = best_latency {
             format!(
                 "测速完成: {}/{} 成功, 最佳延迟 {:.0}ms",
@@ -852,11 +2022,9 @@ impl EndpointTester {
         // Resolve DNS using cached resolver
         debug_log!("  DNS 解析: {}", endpoint.domain);
         let dns_start = Instant::now();
-        let dns_result = tokio::time::timeout(
-            DNS_LOOKUP_TIMEOUT,
-            self.resolver.lookup_ip(&endpoint.domain),
-        )
-        .await;
+        let dns_timeout = endpoint.scaled_timeout(DNS_LOOKUP_TIMEOUT);
+        let dns_result =
+            tokio::time::timeout(dns_timeout, self.resolver.lookup_ip(&endpoint.domain)).await;
 
         let dns_ips: Vec<String> = match dns_result {
             Ok(Ok(lookup)) => {
@@ -895,27 +2063,30 @@ impl EndpointTester {
                     endpoint.clone(),
                     String::new(),
                     format!("DNS失败: {}", e),
-                );
+                )
+                .with_selection_reason(Selection::DnsFailed.reason());
             }
             Err(_) => {
-                error_log!("  DNS 超时 ({}s)", DNS_LOOKUP_TIMEOUT.as_secs());
+                error_log!("  DNS 超时 ({:.1}s)", dns_timeout.as_secs_f64());
                 self.emit_progress(
                     TestProgressEventType::DnsFailed,
                     "error",
                     Some(&endpoint.name),
                     format!(
-                        "[{}] DNS 解析超时 ({}s)",
+                        "[{}] DNS 解析超时 ({:.1}s)",
                         endpoint.name,
-                        DNS_LOOKUP_TIMEOUT.as_secs()
+                        dns_timeout.as_secs_f64()
                     ),
                 );
-                return EndpointResult::failure(endpoint.clone(), String::new(), "DNS超时".into());
+                return EndpointResult::failure(endpoint.clone(), String::new(), "DNS超时".into())
+                    .with_selection_reason(Selection::DnsFailed.reason());
             }
         };
 
         if dns_ips.is_empty() {
             error_log!("  DNS 无结果");
-            return EndpointResult::failure(endpoint.clone(), String::new(), "DNS无结果".into());
+            return EndpointResult::failure(endpoint.clone(), String::new(), "DNS无结果".into())
+                .with_selection_reason(Selection::DnsFailed.reason());
         }
 
         // 记录原始 IP（DNS 解析的第一个 IP）
@@ -957,9 +2128,13 @@ impl EndpointTester {
         };
 
         // Check if Cloudflare
-        let is_cf = dns_ips.iter().any(|ip| is_cloudflare_ip(ip));
+        let is_cf = self.treat_as_cf(&dns_ips);
         if is_cf {
-            debug_log!("  检测到 Cloudflare IP，启用 CF 优选");
+            if self.cf_only {
+                debug_log!("  严格 CF 模式，启用 CF 优选");
+            } else {
+                debug_log!("  检测到 Cloudflare IP，启用 CF 优选");
+            }
         }
 
         // 封锁快速跳过：如果 CF 处于限流冷却期，跳过候选 IP 测试
@@ -985,7 +2160,9 @@ impl EndpointTester {
                     original_result.latency,
                     original_ip,
                     original_latency,
-                );
+                )
+                .with_metrics_from(&original_result)
+                .with_selection_reason(Selection::CfThrottled { original_ok: true }.reason());
                 self.emit_progress(
                     TestProgressEventType::EndpointComplete,
                     "success",
@@ -1001,7 +2178,8 @@ impl EndpointTester {
                     endpoint.clone(),
                     original_ip,
                     "CF风控+原始IP失败".into(),
-                );
+                )
+                .with_selection_reason(Selection::CfThrottled { original_ok: false }.reason());
                 self.emit_progress(
                     TestProgressEventType::EndpointComplete,
                     "error",
@@ -1013,43 +2191,16 @@ impl EndpointTester {
         }
 
         // Collect IPs to test
-        // User-configured preferred IPs take highest priority regardless of CF detection
-        let test_ips: Vec<String> = if !self.custom_cf_ips.is_empty() {
-            debug_log!("  使用用户白名单 IP（优先级最高），不合并 DNS IP");
-            self.custom_cf_ips.to_vec()
-        } else if is_cf {
-            let cf_ips = self.get_cf_ips().await;
-            merge_candidate_ips(cf_ips, &dns_ips, self.strategy.max_test_ips)
-        } else {
-            // 非 CF 站点：并发查询多个公共 DNS，收集更多候选 IP
-            debug_log!("  非CF站点，启用多DNS解析器优选");
-            let multi_dns_ips = resolve_via_multi_dns(&endpoint.domain).await;
-            if multi_dns_ips.len() > dns_ips.len() {
-                debug_log!(
-                    "  多DNS解析发现 {} 个唯一IP（原DNS {} 个）",
-                    multi_dns_ips.len(),
-                    dns_ips.len()
-                );
-            }
-            // 合并：DNS IP 优先，然后追加多 DNS 发现的新 IP，限制总数
-            let mut seen = HashSet::new();
-            let mut merged = Vec::with_capacity(self.strategy.max_test_ips);
-            for ip in dns_ips.iter().chain(multi_dns_ips.iter()) {
-                if seen.insert(ip.clone()) {
-                    merged.push(ip.clone());
-                    if merged.len() >= self.strategy.max_test_ips {
-                        break;
-                    }
-                }
-            }
-            merged
-        };
+        let (_, test_ips) = self
+            .collect_candidate_ips(&endpoint.domain, &dns_ips, is_cf)
+            .await;
 
         debug_log!("  准备测试 {} 个 IP", test_ips.len());
 
         // TCP 预探测：当原始 IP 失败时，先快速检测候选 IP 的 TCP 连通性
+        // 配置了代理时直连结果不代表经代理的可达性，跳过预探测
         let mut test_ips = test_ips;
-        if !original_result.success && !test_ips.is_empty() {
+        if !original_result.success && !test_ips.is_empty() && self.proxy.is_none() {
             debug_log!("  原始IP失败，启动TCP预探测 ({} 个候选IP)", test_ips.len());
             self.emit_progress(
                 TestProgressEventType::TcpProbeStarted,
@@ -1064,10 +2215,11 @@ impl EndpointTester {
 
             let probe_start = Instant::now();
             let mut probe_set = JoinSet::new();
+            let port = endpoint.effective_port();
             for ip in test_ips.iter() {
                 let ip_clone = ip.clone();
                 probe_set.spawn(async move {
-                    let result = Self::tcp_probe(&ip_clone).await;
+                    let result = Self::tcp_probe(&ip_clone, port).await;
                     (ip_clone, result)
                 });
             }
@@ -1148,7 +2300,8 @@ impl EndpointTester {
                     endpoint.clone(),
                     original_ip,
                     "网络不可达: 所有候选IP TCP连接失败".into(),
-                );
+                )
+                .with_selection_reason(Selection::NetworkUnreachable.reason());
                 self.emit_progress(
                     TestProgressEventType::EndpointComplete,
                     "error",
@@ -1187,12 +2340,16 @@ impl EndpointTester {
         );
 
         let mut best_result: Option<EndpointResult> = None;
+        // 所有成功 IP 及其延迟（用于多 IP 绑定）
+        let mut successful_ips: Vec<(String, f64)> = Vec::new();
         let ip_test_start = Instant::now();
         let ip_test_timeout = IP_TEST_TOTAL_TIMEOUT;
         let mut ip_success_count: usize = 0;
         let mut ip_tested_count: usize = 0;
         let mut timeout_count: usize = 0;
         let mut cf_blocked_count: usize = 0;
+        // CF IP 可达但证书不匹配：不计入不可达
+        let mut cert_mismatch_count: usize = 0;
         let mut early_exit = false;
 
         for (batch_index, batch) in test_ips.chunks(batch_size).enumerate() {
@@ -1254,6 +2411,7 @@ impl EndpointTester {
                         ip_tested_count += 1;
                         if result.success {
                             ip_success_count += 1;
+                            successful_ips.push((result.ip.clone(), result.latency));
                             if best_result.is_none()
                                 || result.latency < best_result.as_ref().unwrap().latency
                             {
@@ -1266,6 +2424,9 @@ impl EndpointTester {
                             } else {
                                 debug_log!("    IP {} 延迟 {:.0}ms", result.ip, result.latency);
                             }
+                        } else if result.error_kind == Some(ProbeErrorKind::CertMismatch) {
+                            cert_mismatch_count += 1;
+                            debug_log!("    IP {} 可达但证书与域名不匹配", result.ip);
                         } else {
                             let err_msg = result.error.as_deref().unwrap_or("unknown");
                             debug_log!("    IP {} 失败: {}", result.ip, err_msg);
@@ -1292,8 +2453,9 @@ impl EndpointTester {
             }
 
             // 批级别智能检测
-            if ip_tested_count >= 5 && ip_success_count * 5 < ip_tested_count {
-                let fail_count = ip_tested_count - ip_success_count;
+            let reachable_count = ip_success_count + cert_mismatch_count;
+            if ip_tested_count >= 5 && reachable_count * 5 < ip_tested_count {
+                let fail_count = ip_tested_count - reachable_count;
                 if cf_blocked_count > timeout_count && cf_blocked_count * 2 >= fail_count && is_cf {
                     // CF 封锁占多数 → 触发降级 + 60s 冷却期（可恢复）
                     warn_log!(
@@ -1414,6 +2576,13 @@ impl EndpointTester {
                     original_latency,
                     original_ip,
                     original_latency,
+                )
+                .with_metrics_from(&original_result)
+                .with_selection_reason(
+                    Selection::NotSignificant {
+                        speedup_percent: speedup,
+                    }
+                    .reason(),
                 );
                 result.warning = Some("当前网络已是最优，无需优选".to_string());
                 result
@@ -1427,11 +2596,20 @@ impl EndpointTester {
                 );
                 EndpointResult::success_with_comparison(
                     endpoint.clone(),
-                    best.ip,
+                    best.ip.clone(),
                     best.latency,
                     original_ip,
                     original_latency,
                 )
+                .with_metrics_from(&best)
+                .with_backup_ips(backup_ips(&successful_ips, &best.ip))
+                .with_selection_reason(
+                    Selection::Fastest {
+                        candidates: test_ips.len(),
+                        whitelisted: self.custom_cf_ips.contains(&best.ip),
+                    }
+                    .reason(),
+                )
             }
         } else if original_result.success {
             // 如果优化 IP 都失败，但原始 IP 成功，使用原始 IP
@@ -1447,7 +2625,9 @@ impl EndpointTester {
                 original_result.latency,
                 original_ip.clone(),
                 original_latency,
-            );
+            )
+            .with_metrics_from(&original_result)
+            .with_selection_reason(Selection::FallbackOriginal.reason());
 
             // 用户设置了优选 IP 白名单但全部失败，设置警告
             if !self.custom_cf_ips.is_empty() {
@@ -1462,7 +2642,18 @@ impl EndpointTester {
             result
         } else {
             error_log!("  端点 {} 全部失败", endpoint.name);
-            EndpointResult::failure(endpoint.clone(), original_ip, "全部超时".into())
+            let result = if cert_mismatch_count > 0 {
+                // 候选 IP 可达，只是证书不覆盖该域名：单独提示，避免误判为网络不可达
+                EndpointResult::failure(
+                    endpoint.clone(),
+                    original_ip,
+                    format!("{} 个候选 IP 可达但证书与域名不匹配", cert_mismatch_count),
+                )
+                .with_error_kind(Some(ProbeErrorKind::CertMismatch))
+            } else {
+                EndpointResult::failure(endpoint.clone(), original_ip, "全部超时".into())
+            };
+            result.with_selection_reason(Selection::AllFailed.reason())
         };
 
         debug_log!("test_endpoint 完成: {}", endpoint.name);
@@ -1511,18 +2702,34 @@ impl EndpointTester {
     async fn test_single_ip(&self, endpoint: &Endpoint, ip: String) -> EndpointResult {
         let rounds = self.test_rounds as usize;
         let mut latencies: Vec<f64> = Vec::with_capacity(rounds);
+        let mut ttfbs: Vec<f64> = Vec::with_capacity(rounds);
+        let mut throughputs: Vec<f64> = Vec::new();
+        let mut protocol = None;
+        let ip_timeout = endpoint.scaled_timeout(SINGLE_IP_TEST_TIMEOUT);
 
         for round in 0..rounds {
-            match tokio::time::timeout(SINGLE_IP_TEST_TIMEOUT, self.do_https_test(endpoint, &ip))
-                .await
-            {
-                Ok(Ok(latency)) => {
-                    latencies.push(latency);
+            let probe = async {
+                if endpoint.uses_tls() {
+                    self.do_https_test(endpoint, &ip).await
+                } else {
+                    self.do_plain_http_test(endpoint, &ip).await
+                }
+            };
+            match tokio::time::timeout(ip_timeout, probe).await {
+                Ok(Ok(timing)) => {
+                    latencies.push(timing.latency);
+                    ttfbs.push(timing.ttfb);
+                    if let Some(kbps) = timing.throughput_kbps {
+                        throughputs.push(kbps);
+                    }
+                    protocol = Some(timing.protocol.to_string());
                 }
                 Ok(Err(e)) => {
                     // 首轮失败直接放弃（IP 大概率不可达），保留原始错误信息
                     if round == 0 {
-                        return EndpointResult::failure(endpoint.clone(), ip, e);
+                        let kind = probe_error_kind(&ip, &e);
+                        return EndpointResult::failure(endpoint.clone(), ip, e)
+                            .with_error_kind(kind);
                     }
                     // 后续轮次失败忽略，用已有数据
                 }
@@ -1533,7 +2740,8 @@ impl EndpointTester {
                             endpoint.clone(),
                             ip,
                             "TCP_TIMEOUT: 测试超时".into(),
-                        );
+                        )
+                        .with_error_kind(Some(ProbeErrorKind::Connect));
                     }
                 }
             }
@@ -1543,21 +2751,143 @@ impl EndpointTester {
             return EndpointResult::failure(endpoint.clone(), ip, "全部超时".into());
         }
 
-        // 取中位数（排序后取中间值，抗抖动）
-        latencies.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let median = latencies[latencies.len() / 2];
+        // 连接延迟与 TTFB 分别取中位数（排序后取中间值，抗抖动）
+        let latency = median(&mut latencies);
+        let ttfb = median(&mut ttfbs);
+
+        let mut result = EndpointResult::success(endpoint.clone(), ip, latency, ttfb);
+        result.throughput_kbps = median(&mut throughputs);
+        result.protocol = protocol;
+        result
+    }
 
-        EndpointResult::success(endpoint.clone(), ip, median)
+    /// 单次诊断探测：解析 DNS 后对首个 IP 测一次连接 / TLS / 首字节耗时
+    /// 任何状态码都视为可达（诊断需要看到 5xx），整体受单 IP 超时约束
+    pub async fn probe_once(&self, endpoint: &Endpoint) -> Result<UrlProbeResult, String> {
+        let dns_start = Instant::now();
+        let lookup = tokio::time::timeout(
+            endpoint.scaled_timeout(DNS_LOOKUP_TIMEOUT),
+            self.resolver.lookup_ip(&endpoint.domain),
+        )
+        .await
+        .map_err(|_| "DNS 解析超时".to_string())?
+        .map_err(|e| format!("DNS失败: {}", e))?;
+        let dns_ms = dns_start.elapsed().as_secs_f64() * 1000.0;
+        let ip = lookup
+            .iter()
+            .next()
+            .ok_or_else(|| "DNS 未返回任何 IP".to_string())?
+            .to_string();
+
+        // 诊断只关心连通性与状态码：不读响应体测吞吐量，任何状态码都如实返回
+        let tester = Self {
+            accept_any_status: true,
+            measure_throughput: false,
+            ..self.clone()
+        };
+        let probe = async {
+            if endpoint.uses_tls() {
+                tester.do_https_test(endpoint, &ip).await
+            } else {
+                tester.do_plain_http_test(endpoint, &ip).await
+            }
+        };
+        let timing = tokio::time::timeout(endpoint.scaled_timeout(SINGLE_IP_TEST_TIMEOUT), probe)
+            .await
+            .map_err(|_| "TCP_TIMEOUT: 测试超时".to_string())??;
+
+        Ok(UrlProbeResult {
+            ip,
+            dns_ms,
+            connect_ms: timing.latency,
+            ttfb_ms: timing.ttfb,
+            status: timing.status,
+        })
     }
 
-    async fn do_https_test(&self, endpoint: &Endpoint, ip: &str) -> Result<f64, String> {
-        let addr: SocketAddr = format!("{}:443", ip)
-            .parse()
-            .map_err(|e| format!("Invalid IP: {}", e))?;
+    /// HTTP/2 探测：发送连接前言 + SETTINGS 与一个 HEAD 请求，以收到服务端 SETTINGS 的时间
+    /// 作为 TTFB，再读取响应头的 :status，按与 HTTP/1.1 相同的规则判定健康
+    async fn do_http2_probe<S>(
+        &self,
+        mut stream: S,
+        endpoint: &Endpoint,
+        start: Instant,
+        latency: f64,
+    ) -> Result<HttpsTiming, String>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let mut request = http2_client_preamble();
+        request.extend_from_slice(&http2_probe_request(
+            &host_header(&endpoint.domain, endpoint.effective_port(), true),
+            &self.user_agent,
+        ));
+        stream
+            .write_all(&request)
+            .await
+            .map_err(|e| format!("Write: {}", e))?;
 
-        let start = Instant::now();
+        let mut ttfb = None;
+        let mut status = None;
+        for _ in 0..HTTP2_MAX_PROBE_FRAMES {
+            let mut header = [0u8; 9];
+            stream
+                .read_exact(&mut header)
+                .await
+                .map_err(|e| format!("Read: {}", e))?;
+            let (len, frame_type, flags) = parse_http2_frame_header(&header);
+            let mut payload = vec![0u8; len];
+            stream
+                .read_exact(&mut payload)
+                .await
+                .map_err(|e| format!("Read: {}", e))?;
+
+            match frame_type {
+                HTTP2_FRAME_GOAWAY => return Err("H2: 服务端发送 GOAWAY".into()),
+                HTTP2_FRAME_RST_STREAM => return Err("H2: 服务端重置请求流".into()),
+                HTTP2_FRAME_SETTINGS if flags & HTTP2_FLAG_ACK == 0 => {
+                    ttfb.get_or_insert(start.elapsed().as_secs_f64() * 1000.0);
+                    let _ = stream
+                        .write_all(&[0, 0, 0, HTTP2_FRAME_SETTINGS, HTTP2_FLAG_ACK, 0, 0, 0, 0])
+                        .await;
+                }
+                HTTP2_FRAME_HEADERS => {
+                    status = Some(parse_http2_status(&payload, flags));
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let _ = stream.shutdown().await;
+
+        let ttfb = ttfb.ok_or_else(|| "H2: 未收到服务端 SETTINGS".to_string())?;
+        let status = status.ok_or_else(|| "H2: 未收到响应头".to_string())?;
+        if let Some(status) = status {
+            if status == 429 && !self.accept_any_status {
+                warn_log!(
+                    "  [CF风控] {} | HTTP {} (h2) | ttfb={:.0}ms",
+                    endpoint.domain,
+                    status,
+                    ttfb
+                );
+                return Err(format!("CF_BLOCKED: HTTP {}", status));
+            }
+            if !self.accept_any_status && !endpoint.is_healthy_status(status) {
+                return Err(format!("HTTP {}", status));
+            }
+        }
+        Ok(HttpsTiming {
+            latency,
+            ttfb,
+            throughput_kbps: None,
+            protocol: "h2",
+            status,
+        })
+    }
 
-        // TCP connect with SO_REUSEADDR to avoid TIME_WAIT port conflicts on rapid retests
+    /// 直连目标地址（TCP connect with SO_REUSEADDR）
+    async fn direct_connect(addr: SocketAddr) -> Result<TcpStream, String> {
         let socket = if addr.is_ipv4() {
             TcpSocket::new_v4()
         } else {
@@ -1565,7 +2895,7 @@ impl EndpointTester {
         }
         .map_err(|e| format!("Socket: {}", e))?;
         socket.set_reuseaddr(true).ok();
-        let stream = socket.connect(addr).await.map_err(|e| {
+        socket.connect(addr).await.map_err(|e| {
             let kind = e.kind();
             match kind {
                 std::io::ErrorKind::TimedOut => format!("TCP_TIMEOUT: {}", e),
@@ -1574,123 +2904,237 @@ impl EndpointTester {
                 std::io::ErrorKind::ConnectionAborted => format!("TCP_RESET: {}", e),
                 _ => format!("TCP: {}", e),
             }
-        })?;
+        })
+    }
+
+    async fn do_https_test(&self, endpoint: &Endpoint, ip: &str) -> Result<HttpsTiming, String> {
+        // TCP 连接使用端点端口，TLS SNI 仍使用 endpoint.domain
+        let addr = build_socket_addr(ip, endpoint.effective_port())?;
+
+        let start = Instant::now();
+
+        // 配置了代理时经代理隧道连接，TLS 仍在隧道内与目标直接握手
+        let stream = if let Some(proxy) = &self.proxy {
+            proxy.connect(addr).await?
+        } else {
+            Self::direct_connect(addr).await?
+        };
+
+        let use_http2 = self.negotiates_http2();
 
         // TLS handshake using native TLS (OS-native fingerprint)
-        let connector = self.tls_connector.clone();
+        let connector = if self.http2 && !use_http2 {
+            shared_tls_connector(false)?
+        } else {
+            self.tls_connector.clone()
+        };
 
         let mut tls_stream = connector
             .connect(&endpoint.domain, stream)
             .await
-            .map_err(|e| format!("TLS: {}", e))?;
-
-        // Browser-like HTTP headers (order and content match Chrome to reduce WAF scoring)
-        // Platform-specific values must match TLS fingerprint (Schannel=Windows, SecureTransport=macOS)
-        #[cfg(target_os = "macos")]
-        let (platform, ua) = (
-            "macOS",
-            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
-        );
-        #[cfg(not(target_os = "macos"))]
-        let (platform, ua) = (
-            "Windows",
-            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
-        );
-        let request = format!(
-            "HEAD / HTTP/1.1\r\n\
-             Host: {}\r\n\
-             Connection: close\r\n\
-             sec-ch-ua: \"Google Chrome\";v=\"131\", \"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\"\r\n\
-             sec-ch-ua-mobile: ?0\r\n\
-             sec-ch-ua-platform: \"{}\"\r\n\
-             User-Agent: {}\r\n\
-             Accept: text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8\r\n\
-             Accept-Encoding: gzip, deflate, br, zstd\r\n\
-             Accept-Language: en-US,en;q=0.9\r\n\
-             \r\n",
-            endpoint.domain, platform, ua
+            .map_err(|e| map_tls_error(&e.to_string()))?;
+
+        // 连接延迟：TCP + TLS 握手完成
+        let latency = start.elapsed().as_secs_f64() * 1000.0;
+
+        // 协商到 h2 时改为测量 HTTP/2 SETTINGS 交换
+        if use_http2 {
+            let negotiated = tls_stream.get_ref().negotiated_alpn().ok().flatten();
+            if alpn_protocol_name(negotiated.as_deref()) == "h2" {
+                return self
+                    .do_http2_probe(tls_stream, endpoint, start, latency)
+                    .await;
+            }
+        }
+
+        let result = self
+            .http1_exchange(&mut tls_stream, endpoint, ip, start, latency)
+            .await;
+
+        // Properly shutdown TLS (sends close_notify) to ensure clean socket release.
+        // Without this, sockets accumulate in TIME_WAIT/CLOSE_WAIT on Windows,
+        // causing subsequent tests to fail with timeouts.
+        let _ = tls_stream.shutdown().await;
+        result
+    }
+
+    /// 明文 HTTP 探测（http:// 端点）：连接延迟仅为 TCP 建连，TTFB 为首个响应字节
+    async fn do_plain_http_test(
+        &self,
+        endpoint: &Endpoint,
+        ip: &str,
+    ) -> Result<HttpsTiming, String> {
+        let addr = build_socket_addr(ip, endpoint.effective_port())?;
+
+        let start = Instant::now();
+        let mut stream = if let Some(proxy) = &self.proxy {
+            proxy.connect(addr).await?
+        } else {
+            Self::direct_connect(addr).await?
+        };
+        let latency = start.elapsed().as_secs_f64() * 1000.0;
+
+        let result = self
+            .http1_exchange(&mut stream, endpoint, ip, start, latency)
+            .await;
+        let _ = stream.shutdown().await;
+        result
+    }
+
+    /// 在已建立的连接上发送 HTTP/1.1 探测请求并解析响应（TLS 与明文共用）
+    async fn http1_exchange<S>(
+        &self,
+        stream: &mut S,
+        endpoint: &Endpoint,
+        ip: &str,
+        start: Instant,
+        latency: f64,
+    ) -> Result<HttpsTiming, String>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        // 开启吞吐量测量时改用 GET 读取响应体，否则保持 HEAD 快速路径
+        let (method, path) = if self.measure_throughput {
+            ("GET", self.throughput_path.as_str())
+        } else {
+            ("HEAD", "/")
+        };
+        let request = build_probe_request(
+            method,
+            path,
+            &host_header(
+                &endpoint.domain,
+                endpoint.effective_port(),
+                endpoint.uses_tls(),
+            ),
+            &self.user_agent,
         );
 
-        tls_stream
+        stream
             .write_all(request.as_bytes())
             .await
             .map_err(|e| format!("Write: {}", e))?;
 
         // Read response header
         let mut buf = [0u8; 1024];
-        let n = tls_stream
+        let n = stream
             .read(&mut buf)
             .await
             .map_err(|e| format!("Read: {}", e))?;
 
-        let latency = start.elapsed().as_secs_f64() * 1000.0;
-
-        // Properly shutdown TLS (sends close_notify) to ensure clean socket release.
-        // Without this, sockets accumulate in TIME_WAIT/CLOSE_WAIT on Windows,
-        // causing subsequent tests to fail with timeouts.
-        let _ = tls_stream.shutdown().await;
+        // TTFB：收到首个响应字节
+        let first_byte_at = Instant::now();
+        let ttfb = (first_byte_at - start).as_secs_f64() * 1000.0;
+
+        // 吞吐量：从首个响应体字节开始计时，继续读取直到 EOF / 字节上限 / 读取时限
+        // 与计时起点同时到达的数据（含随响应头一起缓冲的部分）没有耗时可言，不计入
+        let throughput_kbps = if self.measure_throughput {
+            let body_buffered = buf[..n]
+                .windows(4)
+                .position(|w| w == b"\r\n\r\n")
+                .is_some_and(|end| end + 4 < n);
+            let mut timer_start = body_buffered.then_some(first_byte_at);
+            let mut last_read_at = first_byte_at;
+            let mut timed_bytes = 0;
+            let mut total = n;
+            let mut chunk = [0u8; 16 * 1024];
+            // 读取时限同时受单 IP 超时剩余预算约束，否则整轮会被外层超时判为失败
+            let budget_deadline = tokio::time::Instant::from_std(start)
+                + endpoint
+                    .scaled_timeout(SINGLE_IP_TEST_TIMEOUT)
+                    .saturating_sub(THROUGHPUT_DEADLINE_MARGIN);
+            let deadline =
+                budget_deadline.min(tokio::time::Instant::now() + THROUGHPUT_READ_TIMEOUT);
+            while total < THROUGHPUT_MAX_BYTES {
+                match tokio::time::timeout_at(deadline, stream.read(&mut chunk)).await {
+                    Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+                    Ok(Ok(read)) => {
+                        total += read;
+                        last_read_at = Instant::now();
+                        if timer_start.is_some() {
+                            timed_bytes += read;
+                        } else {
+                            timer_start = Some(last_read_at);
+                        }
+                    }
+                }
+            }
+            timer_start
+                .filter(|_| timed_bytes > 0)
+                .map(|since| throughput_kbps(timed_bytes, last_read_at - since))
+        } else {
+            None
+        };
 
         // Verify HTTP response and check for CF blocking
         let response = String::from_utf8_lossy(&buf[..n]);
         if response.starts_with("HTTP/") {
             // 解析状态码，检测 CF 风控
-            if let Some(status_str) = response.get(9..12) {
-                if let Ok(status) = status_str.trim().parse::<u16>() {
-                    // 提取 CF 风控相关响应头用于压测分析
-                    let cf_ray = Self::extract_header(&response, "cf-ray");
-                    let cf_mitigated = Self::extract_header(&response, "cf-mitigated");
-                    let cf_chl_bypass = Self::extract_header(&response, "cf-chl-bypass");
-                    let server = Self::extract_header(&response, "server");
-                    let retry_after = Self::extract_header(&response, "retry-after");
-
-                    if status == 429 {
-                        warn_log!(
-                            "  [CF风控] {} -> {} | HTTP {} | latency={:.0}ms | server={} | cf-ray={} | cf-mitigated={} | retry-after={}",
-                            endpoint.domain, ip, status, latency,
-                            server.as_deref().unwrap_or("-"),
-                            cf_ray.as_deref().unwrap_or("-"),
-                            cf_mitigated.as_deref().unwrap_or("-"),
-                            retry_after.as_deref().unwrap_or("-"),
-                        );
-                        return Err(format!("CF_BLOCKED: HTTP {}", status));
-                    }
+            let status = parse_status_code(&response);
+            if let Some(status) = status {
+                // 提取 CF 风控相关响应头用于压测分析
+                let cf_ray = Self::extract_header(&response, "cf-ray");
+                let cf_mitigated = Self::extract_header(&response, "cf-mitigated");
+                let cf_chl_bypass = Self::extract_header(&response, "cf-chl-bypass");
+                let server = Self::extract_header(&response, "server");
+                let retry_after = Self::extract_header(&response, "retry-after");
+
+                if status == 429 && !self.accept_any_status {
+                    warn_log!(
+                        "  [CF风控] {} -> {} | HTTP {} | ttfb={:.0}ms | server={} | cf-ray={} | cf-mitigated={} | retry-after={}",
+                        endpoint.domain, ip, status, ttfb,
+                        server.as_deref().unwrap_or("-"),
+                        cf_ray.as_deref().unwrap_or("-"),
+                        cf_mitigated.as_deref().unwrap_or("-"),
+                        retry_after.as_deref().unwrap_or("-"),
+                    );
+                    return Err(format!("CF_BLOCKED: HTTP {}", status));
+                }
 
-                    if status == 403 {
-                        warn_log!(
-                            "  [CF挑战] {} -> {} | HTTP {} | latency={:.0}ms | server={} | cf-ray={} | cf-mitigated={} | cf-chl-bypass={}",
-                            endpoint.domain, ip, status, latency,
-                            server.as_deref().unwrap_or("-"),
-                            cf_ray.as_deref().unwrap_or("-"),
-                            cf_mitigated.as_deref().unwrap_or("-"),
-                            cf_chl_bypass.as_deref().unwrap_or("-"),
-                        );
-                        // 403 仍视为连通成功（Turnstile/JS challenge 不影响 API 流量）
-                    } else if status >= 400 {
-                        debug_log!(
-                            "  [HTTP] {} -> {} | HTTP {} | latency={:.0}ms | server={} | cf-ray={}",
-                            endpoint.domain,
-                            ip,
-                            status,
-                            latency,
-                            server.as_deref().unwrap_or("-"),
-                            cf_ray.as_deref().unwrap_or("-"),
-                        );
-                    } else {
-                        debug_log!(
-                            "  [HTTP] {} -> {} | HTTP {} | latency={:.0}ms | cf-ray={}",
-                            endpoint.domain,
-                            ip,
-                            status,
-                            latency,
-                            cf_ray.as_deref().unwrap_or("-"),
-                        );
-                    }
-                    // 403 (CF Turnstile challenge, origin 403, etc.) confirms IP connectivity:
-                    // TCP connected, TLS handshake succeeded, HTTP response received.
-                    // API traffic bypasses challenges via proper headers/API keys.
+                if status == 403 {
+                    warn_log!(
+                        "  [CF挑战] {} -> {} | HTTP {} | ttfb={:.0}ms | server={} | cf-ray={} | cf-mitigated={} | cf-chl-bypass={}",
+                        endpoint.domain, ip, status, ttfb,
+                        server.as_deref().unwrap_or("-"),
+                        cf_ray.as_deref().unwrap_or("-"),
+                        cf_mitigated.as_deref().unwrap_or("-"),
+                        cf_chl_bypass.as_deref().unwrap_or("-"),
+                    );
+                    // 403 仍视为连通成功（Turnstile/JS challenge 不影响 API 流量）
+                } else if status >= 400 {
+                    debug_log!(
+                        "  [HTTP] {} -> {} | HTTP {} | ttfb={:.0}ms | server={} | cf-ray={}",
+                        endpoint.domain,
+                        ip,
+                        status,
+                        ttfb,
+                        server.as_deref().unwrap_or("-"),
+                        cf_ray.as_deref().unwrap_or("-"),
+                    );
+                } else {
+                    debug_log!(
+                        "  [HTTP] {} -> {} | HTTP {} | ttfb={:.0}ms | cf-ray={}",
+                        endpoint.domain,
+                        ip,
+                        status,
+                        ttfb,
+                        cf_ray.as_deref().unwrap_or("-"),
+                    );
+                }
+                // 403 (CF Turnstile challenge, origin 403, etc.) confirms IP connectivity:
+                // TCP connected, TLS handshake succeeded, HTTP response received.
+                // API traffic bypasses challenges via proper headers/API keys.
+                if !self.accept_any_status && !endpoint.is_healthy_status(status) {
+                    return Err(format!("HTTP {}", status));
                 }
             }
-            Ok(latency)
+            Ok(HttpsTiming {
+                latency,
+                ttfb,
+                throughput_kbps,
+                protocol: "http/1.1",
+                status,
+            })
         } else {
             Err("Invalid response".into())
         }
@@ -1791,6 +3235,661 @@ mod tests {
         assert_eq!(merged, vec!["1.1.1.1", "2.2.2.2"]);
     }
 
+    #[test]
+    fn test_multi_dns_resolver_attribution() {
+        let resolved = vec![
+            ("1.2.3.4".to_string(), "1.1.1.1".to_string()),
+            ("10.0.0.1".to_string(), "8.8.8.8".to_string()),
+            ("1.2.3.4".to_string(), "9.9.9.9".to_string()),
+            ("1.2.3.4".to_string(), "1.1.1.1".to_string()),
+        ];
+
+        let grouped = group_by_resolver(&resolved);
+        assert_eq!(grouped["1.1.1.1"], vec!["1.2.3.4"]);
+        assert_eq!(grouped["8.8.8.8"], vec!["10.0.0.1"]);
+        assert_eq!(grouped["9.9.9.9"], vec!["1.2.3.4"]);
+        // 没有返回结果的解析器也列出，便于区分"无响应"与"未查询"
+        assert!(grouped["223.5.5.5"].is_empty());
+        assert_eq!(grouped.len(), PUBLIC_DNS_SERVERS.len());
+
+        // 实际测速仍使用去重后的 IP 列表
+        assert_eq!(unique_resolved_ips(&resolved), vec!["1.2.3.4", "10.0.0.1"]);
+    }
+
+    #[test]
+    fn test_build_socket_addr_ipv4() {
+        let addr = build_socket_addr("104.16.1.1", 443).unwrap();
+        assert!(addr.is_ipv4());
+        assert_eq!(addr.to_string(), "104.16.1.1:443");
+    }
+
+    #[test]
+    fn test_build_socket_addr_ipv6() {
+        let addr = build_socket_addr("2606:4700::1", 443).unwrap();
+        assert!(addr.is_ipv6());
+        assert_eq!(addr.port(), 443);
+        assert_eq!(addr.to_string(), "[2606:4700::1]:443");
+
+        // 带方括号的字面量同样可用
+        let bracketed = build_socket_addr("[2606:4700::1]", 443).unwrap();
+        assert_eq!(bracketed, addr);
+    }
+
+    #[test]
+    fn test_host_header_port() {
+        assert_eq!(host_header("test.com", 443, true), "test.com");
+        assert_eq!(host_header("test.com", 8443, true), "test.com:8443");
+        assert_eq!(host_header("test.com", 80, false), "test.com");
+        assert_eq!(host_header("test.com", 443, false), "test.com:443");
+    }
+
+    #[test]
+    fn test_build_socket_addr_invalid() {
+        let err = build_socket_addr("not-an-ip", 443).unwrap_err();
+        assert!(err.starts_with("Invalid IP"));
+    }
+
+    #[test]
+    fn test_validate_cf_ip_source_url() {
+        assert_eq!(validate_cf_ip_source_url("  "), Ok(None));
+        assert_eq!(
+            validate_cf_ip_source_url(" https://example.com/ips.txt "),
+            Ok(Some("https://example.com/ips.txt".to_string()))
+        );
+        assert!(validate_cf_ip_source_url("http://example.com/ips").is_ok());
+        assert!(validate_cf_ip_source_url("ftp://example.com/ips").is_err());
+        assert!(validate_cf_ip_source_url("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_cf_ip_source_url_ignores_invalid() {
+        let tester =
+            EndpointTester::new(vec![], 1).with_cf_ip_source_url(Some("ftp://example.com/ips"));
+        assert_eq!(tester.cf_ip_source_url.as_str(), IPDB_API_URL);
+
+        let tester =
+            EndpointTester::new(vec![], 1).with_cf_ip_source_url(Some("https://example.com/ips"));
+        assert_eq!(tester.cf_ip_source_url.as_str(), "https://example.com/ips");
+    }
+
+    #[test]
+    fn test_compare_results_groups_favorites_first() {
+        let ep = |domain: &str, favorite: bool| Endpoint {
+            name: domain.into(),
+            url: format!("https://{}", domain),
+            domain: domain.into(),
+            favorite,
+            ..Default::default()
+        };
+        let mut results = [
+            EndpointResult::success(ep("fast.com", false), "1.1.1.1".into(), 50.0, 50.0),
+            EndpointResult::failure(ep("fav-down.com", true), String::new(), "x".into()),
+            EndpointResult::success(ep("fav-slow.com", true), "2.2.2.2".into(), 300.0, 300.0),
+            EndpointResult::success(ep("fav-fast.com", true), "3.3.3.3".into(), 100.0, 100.0),
+            EndpointResult::failure(ep("down.com", false), String::new(), "x".into()),
+        ];
+        results.sort_by(compare_results);
+
+        let order: Vec<&str> = results.iter().map(|r| r.endpoint.domain.as_str()).collect();
+        assert_eq!(
+            order,
+            vec![
+                "fav-fast.com",
+                "fav-slow.com",
+                "fav-down.com",
+                "fast.com",
+                "down.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_median_independent_per_metric() {
+        let mut latencies = vec![90.0, 30.0, 60.0];
+        let mut ttfbs = vec![200.0, 400.0, 100.0];
+        assert_eq!(median(&mut latencies), 60.0);
+        assert_eq!(median(&mut ttfbs), 200.0);
+        assert_eq!(median(&mut []), 0.0);
+    }
+
+    #[test]
+    fn test_build_probe_request_method_and_path() {
+        let head = build_probe_request("HEAD", "/", "test.com", DEFAULT_USER_AGENT);
+        assert!(head.starts_with("HEAD / HTTP/1.1\r\n"));
+        assert!(head.contains("Host: test.com\r\n"));
+        assert!(head.contains(&format!("User-Agent: {}\r\n", DEFAULT_USER_AGENT)));
+        assert!(head.ends_with("\r\n\r\n"));
+
+        let get = build_probe_request("GET", "/favicon.ico", "test.com:8443", "curl/8.5.0");
+        assert!(get.starts_with("GET /favicon.ico HTTP/1.1\r\n"));
+        assert!(get.contains("Host: test.com:8443\r\n"));
+        assert!(get.contains("User-Agent: curl/8.5.0\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_probe_skips_tls() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let endpoint = Endpoint {
+            name: "plain".into(),
+            url: format!("http://test.com:{}/v1", port),
+            domain: "test.com".into(),
+            port: Some(port),
+            ..Default::default()
+        };
+        let tester = EndpointTester::new(vec![], 1);
+        let result = tester.test_ip(&endpoint, "127.0.0.1".into()).await;
+
+        // 明文请求直接以 HTTP 请求行开头（没有 TLS ClientHello），Host 带非默认端口
+        let request = server.await.unwrap();
+        assert!(request.starts_with("HEAD / HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("Host: test.com:{}\r\n", port)));
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.protocol.as_deref(), Some("http/1.1"));
+    }
+
+    /// 本地明文站点：先等待 header_delay 再发响应头，随后按 body_writes 依次等待并写入响应体
+    async fn spawn_slow_body_server(
+        header_delay: Duration,
+        body_writes: Vec<(Duration, usize)>,
+    ) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            tokio::time::sleep(header_delay).await;
+            if socket
+                .write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n")
+                .await
+                .is_err()
+            {
+                return;
+            }
+            for (delay, len) in body_writes {
+                tokio::time::sleep(delay).await;
+                if socket.write_all(&vec![b'x'; len]).await.is_err() {
+                    return;
+                }
+            }
+        });
+        port
+    }
+
+    fn plain_endpoint(port: u16, timeout_multiplier: f64) -> Endpoint {
+        Endpoint {
+            name: "plain".into(),
+            url: format!("http://test.com:{}/v1", port),
+            domain: "test.com".into(),
+            port: Some(port),
+            timeout_multiplier,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throughput_clock_starts_at_first_body_byte() {
+        // 响应头之后空等 500ms 才有响应体：这段等待不应拉低吞吐量
+        let port = spawn_slow_body_server(
+            Duration::ZERO,
+            vec![
+                (Duration::from_millis(500), 16 * 1024),
+                (Duration::from_millis(100), 16 * 1024),
+            ],
+        )
+        .await;
+        let tester = EndpointTester::new(vec![], 1).with_throughput(true, "/");
+        let result = tester
+            .test_ip(&plain_endpoint(port, 1.0), "127.0.0.1".into())
+            .await;
+
+        assert!(result.success, "{:?}", result.error);
+        // 计时从首块响应体开始：约 16KB / 100ms；若从响应头算起只有约 32KB / 600ms
+        assert!(result.throughput_kbps > 80.0, "{}", result.throughput_kbps);
+    }
+
+    #[tokio::test]
+    async fn test_throughput_read_stays_within_ip_budget() {
+        // 单 IP 预算 4s（0.5 倍），响应头 2.5s 后才到，之后响应体源源不断
+        let trickle = vec![(Duration::from_millis(100), 1024); 100];
+        let port = spawn_slow_body_server(Duration::from_millis(2500), trickle).await;
+        let tester = EndpointTester::new(vec![], 1).with_throughput(true, "/");
+        let result = tester
+            .test_ip(&plain_endpoint(port, 0.5), "127.0.0.1".into())
+            .await;
+
+        // 读取时限按剩余预算截断，本轮照常完成而不是整轮超时
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.throughput_kbps > 0.0);
+    }
+
+    /// 本地假 HTTP 代理：按 CONNECT 目标延迟建立隧道（None 表示拒绝），隧道内直接充当目标站点
+    /// 经代理时连接延迟包含 CONNECT 往返，借此为不同 IP 制造可控的延迟差
+    async fn spawn_fake_proxy(targets: Vec<(&'static str, Option<u64>)>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let targets = targets.clone();
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut byte = [0u8; 1];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        if socket.read(&mut byte).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        head.push(byte[0]);
+                    }
+                    let head = String::from_utf8_lossy(&head).to_string();
+                    let target = head.split_whitespace().nth(1).unwrap_or_default();
+                    let delay = targets
+                        .iter()
+                        .find(|(t, _)| *t == target)
+                        .and_then(|(_, delay)| *delay);
+                    let Some(delay_ms) = delay else {
+                        let _ = socket.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+                        return;
+                    };
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                        .await;
+                    let mut buf = vec![0u8; 4096];
+                    let _ = socket.read(&mut buf).await;
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                        .await;
+                });
+            }
+        });
+        format!("http://127.0.0.1:{}", port)
+    }
+
+    /// 原始 IP 10.0.0.1（域名即 IP 字面量，无需真实 DNS）与白名单候选 10.0.0.2 经假代理完整优选
+    async fn select_via_fake_proxy(
+        original: Option<u64>,
+        candidate: Option<u64>,
+    ) -> EndpointResult {
+        let proxy = spawn_fake_proxy(vec![
+            ("10.0.0.1:8080", original),
+            ("10.0.0.2:8080", candidate),
+        ])
+        .await;
+        let endpoint = Endpoint {
+            name: "relay".into(),
+            url: "http://10.0.0.1:8080/v1".into(),
+            domain: "10.0.0.1".into(),
+            port: Some(8080),
+            ..Default::default()
+        };
+        let tester = EndpointTester::new(vec!["10.0.0.2".into()], 1).with_proxy(Some(&proxy));
+        tester.test_endpoint(&endpoint).await
+    }
+
+    #[tokio::test]
+    async fn test_selection_reason_per_branch() {
+        // 候选明显更快：选用候选 IP
+        let result = select_via_fake_proxy(Some(300), Some(0)).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.ip, "10.0.0.2");
+        assert_eq!(
+            result.selection_reason,
+            "在 1 个候选 IP 中最快（优选白名单 IP）"
+        );
+
+        // 两者相当：保留原始 IP
+        let result = select_via_fake_proxy(Some(300), Some(300)).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.ip, "10.0.0.1");
+        assert!(
+            result.selection_reason.starts_with("最优候选仅快")
+                && result.selection_reason.ends_with("保留 DNS 原始 IP"),
+            "{}",
+            result.selection_reason
+        );
+
+        // 候选全部失败：回退原始 IP
+        let result = select_via_fake_proxy(Some(0), None).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.ip, "10.0.0.1");
+        assert_eq!(
+            result.selection_reason,
+            Selection::FallbackOriginal.reason()
+        );
+
+        // 原始与候选都失败
+        let result = select_via_fake_proxy(None, None).await;
+        assert!(!result.success);
+        assert_eq!(result.selection_reason, Selection::AllFailed.reason());
+    }
+
+    #[test]
+    fn test_selection_reason_serialization() {
+        // 原因随结果一起序列化，未经完整优选的结果不带该字段
+        let endpoint = Endpoint {
+            name: "test".into(),
+            url: "https://test.com".into(),
+            domain: "test.com".into(),
+            ..Default::default()
+        };
+        let plain = EndpointResult::success(endpoint.clone(), "1.1.1.1".into(), 10.0, 10.0);
+        assert!(serde_json::to_value(&plain)
+            .unwrap()
+            .get("selection_reason")
+            .is_none());
+        let tagged = plain.with_selection_reason(Selection::AllFailed.reason());
+        assert_eq!(
+            serde_json::to_value(&tagged).unwrap()["selection_reason"],
+            "原始 IP 与全部候选 IP 均失败"
+        );
+    }
+
+    #[test]
+    fn test_status_code_health_rule() {
+        let mut endpoint = Endpoint {
+            name: "test".into(),
+            url: "https://test.com/v1".into(),
+            domain: "test.com".into(),
+            ..Default::default()
+        };
+        let ok = parse_status_code("HTTP/1.1 200 OK\r\nServer: nginx\r\n\r\n").unwrap();
+        let forbidden = parse_status_code("HTTP/1.1 403 Forbidden\r\n\r\n").unwrap();
+        let bad_gateway = parse_status_code("HTTP/1.1 502 Bad Gateway\r\n\r\n").unwrap();
+        assert_eq!((ok, forbidden, bad_gateway), (200, 403, 502));
+        assert_eq!(parse_status_code("garbage"), None);
+
+        // 默认：1xx-4xx 可用，5xx 故障
+        assert!(endpoint.is_healthy_status(ok));
+        assert!(endpoint.is_healthy_status(forbidden));
+        assert!(!endpoint.is_healthy_status(bad_gateway));
+
+        // 自定义：仅列出的状态码可用
+        endpoint.healthy_status_codes = vec![200, 502];
+        assert!(endpoint.is_healthy_status(ok));
+        assert!(!endpoint.is_healthy_status(forbidden));
+        assert!(endpoint.is_healthy_status(bad_gateway));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_online_fetch_returns_quickly() {
+        // 只接受连接、从不响应的来源，模拟卡住的在线获取
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let source = format!("http://127.0.0.1:{}/ips.txt", port);
+        let tester = EndpointTester::new(vec![], 1).with_cf_ip_source_url(Some(&source));
+        let canceller = tester.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            canceller.cancel();
+        });
+
+        let start = Instant::now();
+        let ips = tester.get_cf_ips().await;
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(ips, default_cf_ips());
+        // 取消后的回退结果不缓存
+        assert!(tester.online_cf_ips.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_probe_fails_on_server_error() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let endpoint = Endpoint {
+            name: "broken".into(),
+            url: format!("http://test.com:{}/v1", port),
+            domain: "test.com".into(),
+            port: Some(port),
+            ..Default::default()
+        };
+        let result = EndpointTester::new(vec![], 1)
+            .test_ip(&endpoint, "127.0.0.1".into())
+            .await;
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_probe_once_reports_any_status() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let mut endpoint = plain_endpoint(port, 1.0);
+        endpoint.domain = "127.0.0.1".into();
+        // 即使开启了吞吐量测量，诊断探测也只发 HEAD
+        let tester = EndpointTester::new(vec![], 1).with_throughput(true, "/big.bin");
+        let probe = tester.probe_once(&endpoint).await;
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("HEAD / HTTP/1.1\r\n"), "{}", request);
+        // 429 如实返回状态码，不按 CF 风控判为失败
+        assert_eq!(probe.unwrap().status, Some(429));
+    }
+
+    #[test]
+    fn test_http2_client_preamble() {
+        let preamble = http2_client_preamble();
+        assert!(preamble.starts_with(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"));
+        // 空 SETTINGS 帧：长度 0、类型 4、流 ID 0
+        let header: [u8; 9] = preamble[HTTP2_PREFACE.len()..].try_into().unwrap();
+        assert_eq!(
+            parse_http2_frame_header(&header),
+            (0, HTTP2_FRAME_SETTINGS, 0)
+        );
+    }
+
+    #[test]
+    fn test_parse_http2_frame_header() {
+        let header = [0x00, 0x01, 0x02, 0x04, 0x01, 0, 0, 0, 0];
+        assert_eq!(parse_http2_frame_header(&header), (258, 0x04, 0x01));
+    }
+
+    #[test]
+    fn test_http2_probe_request_encodes_head() {
+        let frame = http2_probe_request("api.example.com", "ua");
+        let header: [u8; 9] = frame[..9].try_into().unwrap();
+        let (len, frame_type, flags) = parse_http2_frame_header(&header);
+        assert_eq!(len, frame.len() - 9);
+        assert_eq!(frame_type, HTTP2_FRAME_HEADERS);
+        assert_eq!(flags, HTTP2_FLAG_END_STREAM | HTTP2_FLAG_END_HEADERS);
+        assert_eq!(&frame[5..9], &[0, 0, 0, 1]);
+        assert_eq!(&frame[9..15], b"\x02\x04HEAD");
+
+        // 超过前缀上限的整数按多字节编码
+        let mut buf = Vec::new();
+        hpack_encode_int(&mut buf, 0x00, 4, 58);
+        assert_eq!(buf, [0x0f, 0x2b]);
+        let mut pos = 0;
+        assert_eq!(hpack_decode_int(&buf, &mut pos, 4), Some(58));
+    }
+
+    #[test]
+    fn test_parse_http2_status() {
+        // 静态表索引：8 = 200，14 = 500
+        assert_eq!(parse_http2_status(&[0x88], 0), Some(200));
+        assert_eq!(parse_http2_status(&[0x8e], 0), Some(500));
+        // 字面量（不索引 / 增量索引），名称取静态表 :status
+        assert_eq!(parse_http2_status(b"\x08\x03502", 0), Some(502));
+        assert_eq!(parse_http2_status(b"\x48\x03503", 0), Some(503));
+        // Huffman 编码的 "200" 与 "503"
+        assert_eq!(parse_http2_status(&[0x08, 0x82, 0x10, 0x01], 0), Some(200));
+        assert_eq!(
+            parse_http2_status(&[0x08, 0x83, 0x6c, 0x0c, 0xff], 0),
+            Some(503)
+        );
+        // 动态表大小更新后接 :status，带填充与优先级字段
+        assert_eq!(
+            parse_http2_status(&[2, 0, 0, 0, 0, 0, 0x3f, 0xe1, 0x1f, 0x8d, 0, 0], 0x28),
+            Some(404)
+        );
+        // 首个字段不是 :status
+        assert_eq!(parse_http2_status(&[0x82], 0), None);
+        assert_eq!(parse_http2_status(b"\x0f\x2b\x02ua", 0), None);
+    }
+
+    /// 假 HTTP/2 服务端：忽略客户端请求，回 SETTINGS 与给定 :status 的 HEADERS
+    async fn http2_probe_with_status(
+        status_block: &[u8],
+        healthy_status_codes: Vec<u16>,
+    ) -> Result<HttpsTiming, String> {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let mut response = vec![0, 0, 0, HTTP2_FRAME_SETTINGS, 0, 0, 0, 0, 0];
+        response.extend_from_slice(&[
+            0,
+            0,
+            status_block.len() as u8,
+            HTTP2_FRAME_HEADERS,
+            HTTP2_FLAG_END_STREAM | HTTP2_FLAG_END_HEADERS,
+            0,
+            0,
+            0,
+            1,
+        ]);
+        response.extend_from_slice(status_block);
+        server.write_all(&response).await.unwrap();
+
+        let mut endpoint = plain_endpoint(443, 1.0);
+        endpoint.healthy_status_codes = healthy_status_codes;
+        let tester = EndpointTester::new(vec![], 1);
+        let result = tester
+            .do_http2_probe(client, &endpoint, Instant::now(), 1.0)
+            .await;
+        drop(server);
+        result
+    }
+
+    #[test]
+    fn test_throughput_measurement_falls_back_to_http1() {
+        let tester = EndpointTester::new(vec![], 1).with_http2(true);
+        assert!(tester.negotiates_http2());
+        assert!(!tester
+            .clone()
+            .with_throughput(true, "/big.bin")
+            .negotiates_http2());
+        let diagnostic = EndpointTester {
+            accept_any_status: true,
+            ..tester
+        };
+        assert!(!diagnostic.negotiates_http2());
+    }
+
+    #[tokio::test]
+    async fn test_http2_probe_applies_status_rule() {
+        // 默认规则：5xx 视为不可用
+        assert_eq!(
+            http2_probe_with_status(b"\x08\x03502", vec![]).await.err(),
+            Some("HTTP 502".to_string())
+        );
+        let ok = http2_probe_with_status(&[0x88], vec![]).await.unwrap();
+        assert_eq!((ok.protocol, ok.status), ("h2", Some(200)));
+        // 自定义健康状态码同样作用于 h2
+        assert!(http2_probe_with_status(b"\x08\x03502", vec![502])
+            .await
+            .is_ok());
+        assert_eq!(
+            http2_probe_with_status(&[0x88], vec![204]).await.err(),
+            Some("HTTP 200".to_string())
+        );
+    }
+
+    #[test]
+    fn test_alpn_protocol_name() {
+        assert_eq!(alpn_protocol_name(Some(b"h2")), "h2");
+        assert_eq!(alpn_protocol_name(Some(b"http/1.1")), "http/1.1");
+        assert_eq!(alpn_protocol_name(None), "http/1.1");
+    }
+
+    #[test]
+    fn test_throughput_kbps() {
+        assert_eq!(throughput_kbps(1024 * 100, Duration::from_secs(1)), 100.0);
+        assert_eq!(
+            throughput_kbps(1024 * 50, Duration::from_millis(500)),
+            100.0
+        );
+        // 耗时 0 时不会除零
+        assert!(throughput_kbps(1024, Duration::ZERO).is_finite());
+    }
+
+    #[test]
+    fn test_normalize_throughput_path() {
+        assert_eq!(normalize_throughput_path(""), "/");
+        assert_eq!(
+            normalize_throughput_path(" /static/1mb.bin "),
+            "/static/1mb.bin"
+        );
+        assert_eq!(normalize_throughput_path("robots.txt"), "/robots.txt");
+    }
+
+    #[test]
+    fn test_lookup_strategy_for_preference() {
+        assert_eq!(
+            lookup_strategy_for(IpVersionPreference::Auto),
+            LookupIpStrategy::Ipv4thenIpv6
+        );
+        assert_eq!(
+            lookup_strategy_for(IpVersionPreference::Ipv4),
+            LookupIpStrategy::Ipv4Only
+        );
+        assert_eq!(
+            lookup_strategy_for(IpVersionPreference::Ipv6),
+            LookupIpStrategy::Ipv6Only
+        );
+    }
+
+    #[test]
+    fn test_benchmark_concurrency_limit() {
+        assert_eq!(benchmark_concurrency_limit(12, 50), 12);
+        assert_eq!(benchmark_concurrency_limit(12, 5), 5);
+        assert_eq!(benchmark_concurrency_limit(0, 50), 1);
+        assert_eq!(benchmark_concurrency_limit(1000, 200), 64);
+    }
+
+    #[test]
+    fn test_fallback_cf_ips_prefers_stale_cache() {
+        let stale = vec!["104.16.9.9".to_string()];
+        assert_eq!(fallback_cf_ips(Some(stale.clone())), stale);
+        assert_eq!(fallback_cf_ips(None), default_cf_ips());
+        assert_eq!(fallback_cf_ips(Some(Vec::new())), default_cf_ips());
+    }
+
     #[test]
     fn test_cf_ranges_coverage() {
         // Verify that CF_RANGES covers expected prefixes
@@ -1822,6 +3921,34 @@ mod tests {
         assert_eq!(tester.custom_cf_ips[0], "1.2.3.4");
     }
 
+    #[tokio::test]
+    async fn test_collect_candidate_ips_prefers_custom_ips() {
+        let custom_ips = vec!["1.2.3.4".to_string(), "5.6.7.8".to_string()];
+        let tester = EndpointTester::new(custom_ips.clone(), 1);
+        let dns_ips = vec!["104.16.1.1".to_string()];
+
+        // 白名单优先，不合并 DNS IP，也不触发在线获取
+        let (cf_ips, merged) = tester
+            .collect_candidate_ips("test.com", &dns_ips, true)
+            .await;
+        assert_eq!(cf_ips, custom_ips);
+        assert_eq!(merged, custom_ips);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_tester_construction() {
+        let handles: Vec<_> = (0..16)
+            .map(|i| {
+                tokio::spawn(
+                    async move { EndpointTester::new(vec![], 1).with_http2(i % 2 == 0).http2 },
+                )
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.await.unwrap(), i % 2 == 0);
+        }
+    }
+
     #[tokio::test]
     async fn test_endpoint_tester_cancel() {
         let tester = EndpointTester::new(vec![], 3);
@@ -1854,6 +3981,61 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_recently_failed_endpoint_is_skipped() {
+        let endpoint = Endpoint {
+            name: "flaky".into(),
+            url: "https://recently-failed.invalid".into(),
+            domain: "recently-failed.invalid".into(),
+            port: Some(443),
+            ..Default::default()
+        };
+        let now = Instant::now();
+        record_recent_failures(
+            &[EndpointResult::failure(
+                endpoint.clone(),
+                String::new(),
+                "TCP_TIMEOUT".into(),
+            )],
+            now,
+        );
+
+        let window = Duration::from_secs(60);
+        assert!(recently_failed("Recently-Failed.invalid", window, now).is_some());
+        assert!(recently_failed(&endpoint.domain, Duration::ZERO, now).is_none());
+        assert!(recently_failed(&endpoint.domain, window, now + window).is_none());
+
+        // 窗口内不再实际测试，直接返回跳过结果（同样推送给结果回调）
+        let streamed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink_streamed = streamed.clone();
+        let tester = EndpointTester::new(vec![], 1)
+            .with_skip_recently_failed(60)
+            .with_result_sink(Arc::new(move |r: &EndpointResult| {
+                sink_streamed
+                    .lock()
+                    .unwrap()
+                    .push(r.endpoint.domain.clone());
+            }));
+        let results = tester.test_all(std::slice::from_ref(&endpoint)).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].skipped && !results[0].success);
+        assert_eq!(*streamed.lock().unwrap(), vec![endpoint.domain.clone()]);
+        // 跳过结果不刷新失败时间
+        assert!(recently_failed(&endpoint.domain, window, now + window).is_none());
+
+        // 成功后清除记录
+        record_recent_failures(
+            &[EndpointResult::success(
+                endpoint.clone(),
+                "1.2.3.4".into(),
+                10.0,
+                10.0,
+            )],
+            now,
+        );
+        assert!(recently_failed(&endpoint.domain, window, now).is_none());
+    }
+
     #[test]
     fn test_strategy_from_aggressiveness() {
         let conservative = TestStrategy::from_aggressiveness(1);
@@ -1934,4 +4116,268 @@ mod tests {
         assert_eq!(tester.strategy.max_ip_concurrency, 2);
         assert_eq!(tester.strategy.max_endpoint_concurrency, 1);
     }
+
+    #[test]
+    fn test_concurrency_overrides_are_clamped() {
+        let standard = TestStrategy::from_aggressiveness(2);
+        let unchanged = standard.clone().with_concurrency_overrides(None, None);
+        assert_eq!(unchanged.max_endpoint_concurrency, 2);
+        assert_eq!(unchanged.max_ip_concurrency, 3);
+
+        let tuned = standard
+            .clone()
+            .with_concurrency_overrides(Some(12), Some(8));
+        assert_eq!(tuned.max_endpoint_concurrency, 12);
+        assert_eq!(tuned.max_ip_concurrency, 8);
+
+        let clamped = standard.with_concurrency_overrides(Some(0), Some(1000));
+        assert_eq!(clamped.max_endpoint_concurrency, 1);
+        assert_eq!(
+            clamped.max_ip_concurrency,
+            MAX_CONFIGURABLE_CONCURRENCY as usize
+        );
+
+        let config = AppConfig {
+            max_endpoint_concurrency: Some(64),
+            max_ip_concurrency: Some(0),
+            ..AppConfig::default()
+        };
+        let tester = EndpointTester::new(vec![], 1).with_config_options(&config);
+        assert_eq!(tester.strategy.max_endpoint_concurrency, 32);
+        assert_eq!(tester.strategy.max_ip_concurrency, 1);
+        assert_eq!(tester.strategy.effective_ip_concurrency(2), 1);
+    }
+
+    #[test]
+    fn test_config_fingerprint_tracks_tester_options() {
+        let base = AppConfig::default();
+        let fingerprint = EndpointTester::config_fingerprint(&base);
+        assert_eq!(
+            EndpointTester::config_fingerprint(&base.clone()),
+            fingerprint
+        );
+
+        // 与测速器无关的配置项不触发重建
+        let unrelated = AppConfig {
+            check_interval: base.check_interval + 60,
+            ..base.clone()
+        };
+        assert_eq!(EndpointTester::config_fingerprint(&unrelated), fingerprint);
+
+        let changed = [
+            AppConfig {
+                proxy: Some("socks5://127.0.0.1:1080".into()),
+                ..base.clone()
+            },
+            AppConfig {
+                enable_http2: !base.enable_http2,
+                ..base.clone()
+            },
+            AppConfig {
+                user_agent: Some("curl/8.5.0".into()),
+                ..base.clone()
+            },
+            AppConfig {
+                cf_only: !base.cf_only,
+                ..base.clone()
+            },
+            AppConfig {
+                max_test_ips: Some(3),
+                ..base.clone()
+            },
+            AppConfig {
+                max_ip_concurrency: Some(1),
+                ..base.clone()
+            },
+            AppConfig {
+                cf_ip_source_url: Some("https://ip.example.com/list".into()),
+                ..base.clone()
+            },
+            AppConfig {
+                ip_version_preference: IpVersionPreference::Ipv6,
+                ..base.clone()
+            },
+        ];
+        for config in &changed {
+            assert_ne!(EndpointTester::config_fingerprint(config), fingerprint);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cf_only_forces_cf_candidate_branch() {
+        let dns_ips = vec!["93.184.216.34".to_string()];
+        let tester = EndpointTester::new(vec![], 1);
+        assert!(!tester.treat_as_cf(&dns_ips));
+
+        let config = AppConfig {
+            cf_only: true,
+            ..AppConfig::default()
+        };
+        let tester = EndpointTester::new(vec![], 1).with_config_options(&config);
+        assert!(tester.treat_as_cf(&dns_ips));
+
+        // 预置在线优选 IP，避免测试访问网络；非 CF 分支会返回空的 CF 候选源
+        *tester.online_cf_ips.lock().await = Some(vec!["104.16.0.1".to_string()]);
+        let (cf_ips, merged) = tester
+            .collect_candidate_ips("example.com", &dns_ips, tester.treat_as_cf(&dns_ips))
+            .await;
+        assert_eq!(cf_ips, vec!["104.16.0.1"]);
+        assert_eq!(merged, vec!["104.16.0.1", "93.184.216.34"]);
+    }
+
+    #[test]
+    fn test_max_test_ips_override_caps_candidates() {
+        let standard = TestStrategy::from_aggressiveness(2);
+        assert_eq!(standard.clone().with_max_test_ips(None).max_test_ips, 8);
+        assert_eq!(standard.clone().with_max_test_ips(Some(0)).max_test_ips, 1);
+        assert_eq!(
+            standard.with_max_test_ips(Some(500)).max_test_ips,
+            MAX_CONFIGURABLE_TEST_IPS as usize
+        );
+
+        let config = AppConfig {
+            max_test_ips: Some(3),
+            ..AppConfig::default()
+        };
+        let tester = EndpointTester::new(vec![], 1).with_config_options(&config);
+        assert_eq!(tester.strategy.max_test_ips, 3);
+
+        let cf_ips: Vec<String> = (1..=20).map(|i| format!("104.16.0.{}", i)).collect();
+        let dns_ips = vec!["1.2.3.4".to_string()];
+        let merged = merge_candidate_ips(cf_ips, &dns_ips, tester.strategy.max_test_ips);
+        assert_eq!(merged.len(), 3);
+    }
+
+    #[test]
+    fn test_estimate_test_timeout_scales_with_endpoints() {
+        assert_eq!(estimate_test_timeout(0), MIN_WORKFLOW_TIMEOUT);
+        assert_eq!(estimate_test_timeout(1), Duration::from_secs(78));
+        // 30 个端点 / 3 并发 = 10 批，不再封顶，保证后排端点也有完整预算
+        assert_eq!(estimate_test_timeout(30), Duration::from_secs(10 * 63 + 15));
+
+        // 策略估算包含端点间冷却
+        let strategy = TestStrategy::from_aggressiveness(2);
+        assert_eq!(
+            strategy.estimate_timeout(30),
+            estimate_test_timeout_with_concurrency(30, 2) + Duration::from_millis(29 * 500)
+        );
+    }
+
+    #[test]
+    fn test_config_tester_estimate_follows_timeout_multiplier() {
+        let endpoint = |timeout_multiplier: f64| Endpoint {
+            name: "test".into(),
+            url: "https://test.com".into(),
+            domain: "test.com".into(),
+            timeout_multiplier,
+            ..Default::default()
+        };
+        let config = AppConfig {
+            max_endpoint_concurrency: Some(1),
+            ..AppConfig::default()
+        };
+        let tester = EndpointTester::from_config(&config);
+        let base = tester.estimate_timeout(&[endpoint(1.0), endpoint(1.0)]);
+        // 并发覆盖生效：两个端点串行
+        assert_eq!(
+            base,
+            TestStrategy::from_aggressiveness(config.test_aggressiveness)
+                .with_concurrency_overrides(Some(1), None)
+                .estimate_timeout(2)
+        );
+        // 任一端点放宽超时，整体估算随之放大
+        assert_eq!(
+            tester.estimate_timeout(&[endpoint(1.0), endpoint(2.0)]),
+            base * 2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_with_deadlines_does_not_starve_late_items() {
+        let cancelled = AtomicBool::new(false);
+        // 40 个任务、并发 4、每个 30ms：总耗时约 300ms，远超单个任务的 150ms 时限
+        let mut items: Vec<u64> = vec![30; 40];
+        items[5] = 5_000; // 一个真正卡住的任务
+
+        let started = Instant::now();
+        let outcomes = run_with_deadlines(
+            &items,
+            4,
+            Duration::ZERO,
+            &cancelled,
+            |_| Duration::from_millis(150),
+            |idx, delay_ms| async move {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                idx
+            },
+        )
+        .await;
+
+        assert!(started.elapsed() > Duration::from_millis(150));
+        assert_eq!(outcomes.len(), 40);
+        for (idx, (_, outcome)) in outcomes.iter().enumerate() {
+            if idx == 5 {
+                assert_eq!(*outcome, None);
+            } else {
+                assert_eq!(*outcome, Some(idx), "item {} failed early", idx);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_dns_benchmark_orders_success_by_latency() {
+        let entry = |server: &str, latency_ms: f64, error: Option<&str>| DnsBenchmarkResult {
+            server: server.into(),
+            latency_ms,
+            ips: Vec::new(),
+            error: error.map(String::from),
+        };
+        let mut results = vec![
+            (0, entry("8.8.8.8", 120.0, None)),
+            (1, entry("8.8.4.4", 3000.0, Some("超时"))),
+            (2, entry("1.1.1.1", 30.0, None)),
+            (3, entry("9.9.9.9", 5.0, Some("refused"))),
+        ];
+        sort_dns_benchmark(&mut results);
+        let order: Vec<&str> = results.iter().map(|(_, r)| r.server.as_str()).collect();
+        assert_eq!(order, ["1.1.1.1", "8.8.8.8", "8.8.4.4", "9.9.9.9"]);
+    }
+
+    #[test]
+    fn test_map_tls_error_distinguishes_cert_problems() {
+        let openssl = "error:0A000086:SSL routines:tls_post_process_server_certificate:certificate verify failed (hostname mismatch)";
+        assert!(map_tls_error(openssl).starts_with("TLS_CERT_MISMATCH:"));
+        let schannel = "The target principal name is incorrect. (os error -2146893022)";
+        assert!(map_tls_error(schannel).starts_with("TLS_CERT_MISMATCH:"));
+        let expired = "certificate verify failed (certificate has expired)";
+        assert!(map_tls_error(expired).starts_with("TLS_CERT:"));
+        let handshake = "unexpected EOF during handshake";
+        assert!(map_tls_error(handshake).starts_with("TLS:"));
+        let schannel_expired =
+            "A required certificate is not within its validity period (os error -2146762495)";
+        assert!(map_tls_error(schannel_expired).starts_with("TLS_CERT:"));
+        // 仅出现 "certificate" / "does not match" 字样的握手错误不算证书问题
+        let alert = "error:0A00045C:SSL routines::tlsv13 alert certificate required";
+        assert!(map_tls_error(alert).starts_with("TLS:"));
+        let framing = "record length does not match the expected size";
+        assert!(map_tls_error(framing).starts_with("TLS:"));
+    }
+
+    #[test]
+    fn test_probe_error_kind_cert_mismatch_only_reachable_on_cf() {
+        let err = map_tls_error("hostname mismatch");
+        assert_eq!(
+            probe_error_kind("104.16.1.1", &err),
+            Some(ProbeErrorKind::CertMismatch)
+        );
+        assert_eq!(
+            probe_error_kind("8.8.8.8", &err),
+            Some(ProbeErrorKind::CertInvalid)
+        );
+        assert_eq!(
+            probe_error_kind("104.16.1.1", "TCP_TIMEOUT: x"),
+            Some(ProbeErrorKind::Connect)
+        );
+        assert_eq!(probe_error_kind("104.16.1.1", "CF_BLOCKED: HTTP 403"), None);
+    }
 }
@@ -1,13 +1,23 @@
 //! 健康检查模块
 //! 基准延迟跟踪 + 持续优化后台任务
 
+use crate::baseline_store::BaselineStore;
 use crate::config::ConfigManager;
-use crate::endpoint_tester::{EndpointTester, TestStrategy};
-use crate::hosts_manager::HostsBinding;
+use crate::endpoint_tester::{EndpointTester, MAX_ENDPOINT_CONCURRENCY};
+use crate::hosts_manager::{HostsBinding, HostsError};
 use crate::hosts_ops;
-use crate::models::{Endpoint, EndpointResult, OptimizationEvent, OptimizationEventType};
+use crate::models::{
+    AppConfig, CheckIntervalSuggestion, DailySummary, Endpoint, EndpointResult,
+    HealthCheckerRestart, HealthLoopStopped, OptimizationEvent, OptimizationEventType,
+    OptimizationStatus,
+};
+use crate::notification::{self, SwitchNotice};
+use crate::tray::{self, TrayStatus};
+use chrono::NaiveDate;
 use rand::Rng;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::sync::Arc;
 #[cfg(feature = "tauri-runtime")]
 use tauri::{AppHandle, Emitter};
@@ -15,15 +25,28 @@ use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
 /// 基准延迟跟踪器
-/// 记录每个域名的基准延迟，用于测速结果展示
+/// 记录每个域名的基准延迟，用于测速结果展示；配置持久化后重启可恢复
+#[derive(Clone)]
 pub struct BaselineTracker {
     baselines: Arc<Mutex<HashMap<String, f64>>>,
+    store: Option<Arc<BaselineStore>>,
 }
 
 impl BaselineTracker {
+    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
             baselines: Arc::new(Mutex::new(HashMap::new())),
+            store: None,
+        }
+    }
+
+    /// 创建带磁盘持久化的跟踪器，并加载未过期的基准
+    pub fn with_store(store: BaselineStore) -> Self {
+        let loaded = store.load();
+        Self {
+            baselines: Arc::new(Mutex::new(loaded)),
+            store: Some(Arc::new(store)),
         }
     }
 
@@ -31,17 +54,346 @@ impl BaselineTracker {
     pub fn get_baselines_arc(&self) -> Arc<Mutex<HashMap<String, f64>>> {
         self.baselines.clone()
     }
+
+    /// 批量更新基准延迟（只获取一次锁），启用持久化时同步写盘
+    pub async fn update(&self, updates: Vec<(String, f64)>) {
+        if updates.is_empty() {
+            return;
+        }
+        {
+            let mut b = self.baselines.lock().await;
+            for (domain, latency) in &updates {
+                b.insert(domain.clone(), *latency);
+            }
+        }
+        if let Some(store) = &self.store {
+            store.record(&updates);
+        }
+    }
 }
 
 /// 持续优化后台任务
 pub struct HealthChecker {
     cancel_token: CancellationToken,
     task_handle: Option<tokio::task::JoinHandle<()>>,
+    /// 暂停标志：暂停期间跳过检查，但任务与失败计数等内存状态保留
+    paused: Arc<AtomicBool>,
+    /// 当前轮次使用的测速器，停止或手动取消时调用其 cancel() 让进行中的探测尽快结束
+    active_tester: ActiveTester,
+}
+
+/// 后台循环与 HealthChecker 共享的当前测速器
+type ActiveTester = Arc<std::sync::Mutex<Option<EndpointTester>>>;
+
+/// 取消当前轮次的测速，返回是否确实取消了一次进行中的测速
+fn cancel_active_tester(active_tester: &ActiveTester) -> bool {
+    let guard = active_tester.lock().unwrap_or_else(|e| e.into_inner());
+    match guard.as_ref() {
+        Some(tester) if !tester.is_cancelled() => {
+            tester.cancel();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 登记本轮检查使用的测速器，离开作用域（本轮结束、continue 或 break）时清空登记
+/// 否则轮次之间被缓存复用的空闲测速器仍会被 cancel_in_flight 当作进行中的测速取消
+struct ActiveCheck<'a> {
+    slot: &'a ActiveTester,
+}
+
+impl<'a> ActiveCheck<'a> {
+    fn begin(slot: &'a ActiveTester, tester: &EndpointTester) -> Self {
+        *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(tester.clone());
+        Self { slot }
+    }
+}
+
+impl Drop for ActiveCheck<'_> {
+    fn drop(&mut self) {
+        *self.slot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// 候选 IP 确认：首测与确认探测均成功才接受，返回两次中较差的延迟（保守比较）
+fn confirmed_latency(candidate: &EndpointResult, confirm: &EndpointResult) -> Option<f64> {
+    (candidate.success && confirm.success && confirm.ip == candidate.ip)
+        .then(|| candidate.latency.max(confirm.latency))
+}
+
+/// 看门狗轮询间隔
+const WATCHDOG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// 看门狗重启前的等待时间（避免持续崩溃时疯狂重启）
+const WATCHDOG_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// 看门狗是否还能再重启一次（restart_count 为已重启次数）
+fn restart_allowed(restart_count: u32, max_restarts: u32) -> bool {
+    restart_count < max_restarts
+}
+
+/// 核心循环连续运行超过该时长才出问题时，视为已恢复稳定，重启次数从零计
+const WATCHDOG_STABLE_RUN: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+/// 本轮核心循环运行了 ran_for 后出问题：运行足够久则清零已重启次数，否则沿用
+fn restart_count_after_run(restart_count: u32, ran_for: std::time::Duration) -> u32 {
+    if ran_for >= WATCHDOG_STABLE_RUN {
+        0
+    } else {
+        restart_count
+    }
+}
+
+/// 单轮检查（含全量优选）允许的额外耗时，超过即视为卡死
+const WATCHDOG_CHECK_BUDGET_SECS: i64 = 600;
+
+/// 检查间隔下限（抖动后也不低于该值）
+const MIN_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// 检查间隔抖动比例上限（%）
+const MAX_CHECK_JITTER_PERCENT: u32 = 50;
+
+/// 轻量检查中各端点首次探测的最大错开时间（ms）
+const MAX_PROBE_STAGGER_MS: u64 = 3000;
+
+/// 在检查间隔上叠加 ±jitter_percent% 的随机抖动，避免大量客户端同步探测；
+/// 结果不低于 MIN_CHECK_INTERVAL_SECS
+fn jittered_interval<R: Rng>(
+    interval_secs: u64,
+    jitter_percent: u32,
+    rng: &mut R,
+) -> std::time::Duration {
+    let base_ms = interval_secs.max(MIN_CHECK_INTERVAL_SECS) * 1000;
+    let spread_ms = base_ms * jitter_percent.min(MAX_CHECK_JITTER_PERCENT) as u64 / 100;
+    let jittered_ms = if spread_ms == 0 {
+        base_ms
+    } else {
+        (base_ms - spread_ms) + rng.gen_range(0..=spread_ms * 2)
+    };
+    std::time::Duration::from_millis(jittered_ms.max(MIN_CHECK_INTERVAL_SECS * 1000))
+}
+
+/// 自动切换记录保留时长（秒），与 suggest_check_interval 的统计窗口一致
+pub(crate) const SWITCH_LOG_WINDOW_SECS: i64 = 24 * 3600;
+
+/// 持续优化实际切换了 IP 的时间戳（每个被切换的域名一条），跨任务重启保留
+static SWITCH_LOG: std::sync::Mutex<std::collections::VecDeque<i64>> =
+    std::sync::Mutex::new(std::collections::VecDeque::new());
+
+/// 记录 count 次实际切换，并丢弃统计窗口之外的旧记录
+fn record_switches(log: &mut std::collections::VecDeque<i64>, now: i64, count: usize) {
+    log.extend(std::iter::repeat_n(now, count));
+    while log
+        .front()
+        .is_some_and(|&t| now - t > SWITCH_LOG_WINDOW_SECS)
+    {
+        log.pop_front();
+    }
+}
+
+/// 最近 SWITCH_LOG_WINDOW_SECS 内持续优化实际切换 IP 的次数
+pub(crate) fn recent_switch_count(now: i64) -> usize {
+    let log = SWITCH_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    log.iter()
+        .filter(|&&t| now - t <= SWITCH_LOG_WINDOW_SECS)
+        .count()
+}
+
+/// 根据近期稳定性推荐检查间隔：切换越频繁、失败率越高，检查越频繁
+/// switches_per_hour 为平均每个域名每小时的切换次数，failure_rate 为最近测速的失败比例（0-1）
+pub(crate) fn suggest_check_interval(
+    switches_per_hour: f64,
+    failure_rate: f64,
+) -> CheckIntervalSuggestion {
+    let (secs, level) = if failure_rate >= 0.3 || switches_per_hour >= 1.0 {
+        (60, "端点很不稳定，建议频繁检查以便及时切换")
+    } else if failure_rate >= 0.1 || switches_per_hour >= 0.25 {
+        (120, "端点偶有波动，建议保持默认检查频率")
+    } else if switches_per_hour >= 0.05 {
+        (300, "端点较稳定，可以适当降低检查频率")
+    } else {
+        (600, "端点非常稳定，降低检查频率可减少探测流量")
+    };
+    CheckIntervalSuggestion {
+        suggested_secs: (secs as u64).max(MIN_CHECK_INTERVAL_SECS),
+        rationale: format!(
+            "{}（每个域名平均每小时切换 {:.2} 次，最近测速失败率 {:.0}%）",
+            level,
+            switches_per_hour,
+            failure_rate * 100.0
+        ),
+    }
+}
+
+/// 将配置中的持续优化参数修正为实际生效的值（供 get_effective_config 展示）
+pub(crate) fn apply_effective_limits(config: &mut AppConfig) {
+    config.check_interval = config.check_interval.max(MIN_CHECK_INTERVAL_SECS);
+    config.check_jitter_percent = config.check_jitter_percent.min(MAX_CHECK_JITTER_PERCENT);
+    config.failure_threshold = config.failure_threshold.max(1); // 0 与 1 等效：失败一次即触发
+
+    let thresholds = SwitchThresholds::from_config(config);
+    config.severe_abs_threshold_ms = thresholds.severe_abs_threshold_ms;
+    config.full_test_cooldown_secs = thresholds.full_test_cooldown.as_secs();
+    config.switch_min_improvement_percent = thresholds.min_improvement_percent;
+    config.switch_min_improvement_ms = thresholds.min_improvement_ms;
+}
+
+/// 全量优选冷却期下限（避免配置过小导致频繁全量测速触发 CF 风控）
+const MIN_FULL_TEST_COOLDOWN_SECS: u64 = 60;
+
+/// 切换判定阈值（来自 AppConfig，已按下限修正）
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SwitchThresholds {
+    /// 比基准慢的百分比
+    slow_threshold_percent: f64,
+    /// 比基准增加的绝对延迟（ms）
+    severe_abs_threshold_ms: f64,
+    /// 同一域名两次全量优选的最小间隔
+    full_test_cooldown: std::time::Duration,
+    /// 当前 IP 可用时，候选 IP 需至少快多少百分比才切换
+    min_improvement_percent: f64,
+    /// 当前 IP 可用时，候选 IP 需至少快多少 ms 才切换
+    min_improvement_ms: f64,
+}
+
+impl SwitchThresholds {
+    fn from_config(config: &AppConfig) -> Self {
+        Self {
+            slow_threshold_percent: config.slow_threshold as f64,
+            severe_abs_threshold_ms: config.severe_abs_threshold_ms.max(0.0),
+            full_test_cooldown: std::time::Duration::from_secs(
+                config
+                    .full_test_cooldown_secs
+                    .max(MIN_FULL_TEST_COOLDOWN_SECS),
+            ),
+            min_improvement_percent: config.switch_min_improvement_percent.clamp(0.0, 100.0),
+            min_improvement_ms: config.switch_min_improvement_ms.max(0.0),
+        }
+    }
+
+    /// 延迟是否严重恶化（比基准高 slow_threshold% 且绝对增加超过阈值）
+    fn is_severely_degraded(&self, latency: f64, baseline: f64) -> bool {
+        if baseline <= 0.0 {
+            return false;
+        }
+        let threshold_latency = baseline * (1.0 + self.slow_threshold_percent / 100.0);
+        latency > threshold_latency && latency - baseline > self.severe_abs_threshold_ms
+    }
+
+    /// 当前 IP 能通但延迟恶化时，候选 IP 是否明显更好
+    fn is_worth_switching(&self, current: f64, candidate: f64) -> bool {
+        if current <= 0.0 {
+            return false;
+        }
+        let improvement_pct = (current - candidate) / current * 100.0;
+        let improvement_abs = current - candidate;
+        improvement_pct > self.min_improvement_percent && improvement_abs > self.min_improvement_ms
+    }
+}
+
+/// 心跳超过「两个检查间隔 + 单轮预算」未更新则判定核心循环卡死
+fn is_stalled(last_heartbeat: i64, now: i64, check_interval_secs: u64) -> bool {
+    let allowed = (check_interval_secs as i64).saturating_mul(2) + WATCHDOG_CHECK_BUDGET_SECS;
+    now.saturating_sub(last_heartbeat) > allowed
+}
+
+/// 提取 panic 信息用于日志与前端提示
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "未知错误".to_string()
+    }
+}
+
+/// 以有限并发执行各端点的探测，结果按输入顺序返回
+/// 单个慢端点只占用一个并发槽位，不会拖住其余端点
+async fn run_bounded<T, R, F, Fut>(items: Vec<T>, limit: usize, probe: F) -> Vec<R>
+where
+    R: Send + 'static,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R> + Send + 'static,
+{
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(limit.max(1)));
+    let mut join_set = tokio::task::JoinSet::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let semaphore = semaphore.clone();
+        let task = probe(item);
+        join_set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, task.await)
+        });
+    }
+
+    let mut results = Vec::with_capacity(join_set.len());
+    while let Some(joined) = join_set.join_next().await {
+        if let Ok(item) = joined {
+            results.push(item);
+        }
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
 }
 
 /// 记录每个域名当前 IP 连续失败的次数
 type FailureCounter = HashMap<String, u32>;
 
+/// 每日汇总计数器（以 chrono::Local 日期为边界）
+struct DailyStats {
+    day: NaiveDate,
+    checks: u32,
+    switches: u32,
+    /// 域名 → (延迟总和, 样本数)
+    latency_sums: HashMap<String, (f64, u32)>,
+}
+
+impl DailyStats {
+    fn new(day: NaiveDate) -> Self {
+        Self {
+            day,
+            checks: 0,
+            switches: 0,
+            latency_sums: HashMap::new(),
+        }
+    }
+
+    /// 记录一轮检查（仅统计成功的延迟样本）
+    fn record_check(&mut self, samples: &[(String, f64)], switches: u32) {
+        self.checks += 1;
+        self.switches += switches;
+        for (domain, latency) in samples {
+            let entry = self.latency_sums.entry(domain.clone()).or_insert((0.0, 0));
+            entry.0 += latency;
+            entry.1 += 1;
+        }
+    }
+
+    /// 跨日时返回前一天的汇总并重置计数
+    fn roll_over(&mut self, today: NaiveDate) -> Option<DailySummary> {
+        if today == self.day {
+            return None;
+        }
+        let finished = std::mem::replace(self, Self::new(today));
+        if finished.checks == 0 {
+            return None;
+        }
+        let avg_latency = finished
+            .latency_sums
+            .into_iter()
+            .map(|(domain, (sum, count))| (domain, sum / count as f64))
+            .collect();
+        Some(DailySummary {
+            date: finished.day.format("%Y-%m-%d").to_string(),
+            checks: finished.checks,
+            switches: finished.switches,
+            avg_latency,
+        })
+    }
+}
+
 impl HealthChecker {
     /// 启动持续优化后台任务
     #[cfg(feature = "tauri-runtime")]
@@ -49,24 +401,59 @@ impl HealthChecker {
         app_handle: AppHandle,
         config_manager: ConfigManager,
         results: Arc<Mutex<Vec<EndpointResult>>>,
-        baselines: Arc<Mutex<HashMap<String, f64>>>,
+        baselines: BaselineTracker,
     ) -> Self {
         let cancel_token = CancellationToken::new();
         let token = cancel_token.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let paused_flag = paused.clone();
+        let active_tester: ActiveTester = Arc::new(std::sync::Mutex::new(None));
+        let tester_slot = active_tester.clone();
 
         let task_handle = tokio::spawn(async move {
-            Self::run_loop(app_handle, config_manager, results, baselines, token).await;
+            Self::supervise(
+                app_handle,
+                config_manager,
+                results,
+                baselines,
+                token,
+                paused_flag,
+                tester_slot,
+            )
+            .await;
         });
 
         Self {
             cancel_token,
             task_handle: Some(task_handle),
+            paused,
+            active_tester,
+        }
+    }
+
+    /// 暂停检查（不取消任务），返回状态是否发生变化
+    pub fn pause(&self) -> bool {
+        !self.paused.swap(true, Ordering::SeqCst)
+    }
+
+    /// 恢复检查，返回状态是否发生变化
+    pub fn resume(&self) -> bool {
+        self.paused.swap(false, Ordering::SeqCst)
+    }
+
+    /// 当前运行/暂停状态（未运行时不视为暂停）
+    pub fn status(&self) -> OptimizationStatus {
+        let is_running = self.is_running();
+        OptimizationStatus {
+            is_running,
+            is_paused: is_running && self.paused.load(Ordering::SeqCst),
         }
     }
 
     /// 停止后台任务（带 10 秒超时保护，防止永久阻塞）
     pub async fn stop(&mut self) {
         self.cancel_token.cancel();
+        cancel_active_tester(&self.active_tester);
         if let Some(handle) = self.task_handle.take() {
             let timeout = tokio::time::timeout(std::time::Duration::from_secs(10), handle);
             match timeout.await {
@@ -78,6 +465,11 @@ impl HealthChecker {
         }
     }
 
+    /// 取消当前这一轮进行中的测速（不停止持续优化），返回是否有测速被取消
+    pub fn cancel_in_flight(&self) -> bool {
+        cancel_active_tester(&self.active_tester)
+    }
+
     /// 查询是否正在运行
     pub fn is_running(&self) -> bool {
         if let Some(handle) = &self.task_handle {
@@ -87,14 +479,120 @@ impl HealthChecker {
         }
     }
 
+    /// 看门狗：运行核心循环，崩溃（panic）或长时间无心跳时自动重启，并发送 health-checker-restarted 事件；
+    /// 连续运行超过 WATCHDOG_STABLE_RUN 后重启次数清零；超过配置的重启次数后停止并发送 health-loop-stopped 事件（任务结束，is_running 随之变为 false）
+    #[cfg(feature = "tauri-runtime")]
+    async fn supervise(
+        app_handle: AppHandle,
+        config_manager: ConfigManager,
+        results: Arc<Mutex<Vec<EndpointResult>>>,
+        baselines: BaselineTracker,
+        cancel_token: CancellationToken,
+        paused: Arc<AtomicBool>,
+        active_tester: ActiveTester,
+    ) {
+        let mut restart_count: u32 = 0;
+
+        loop {
+            let heartbeat = Arc::new(AtomicI64::new(chrono::Utc::now().timestamp()));
+            let loop_token = cancel_token.child_token();
+            let started = std::time::Instant::now();
+            let mut task = tokio::spawn(Self::run_loop(
+                app_handle.clone(),
+                config_manager.clone(),
+                results.clone(),
+                baselines.clone(),
+                loop_token.clone(),
+                heartbeat.clone(),
+                paused.clone(),
+                active_tester.clone(),
+            ));
+
+            let reason = loop {
+                tokio::select! {
+                    joined = &mut task => match joined {
+                        Err(e) if e.is_panic() => {
+                            break format!("后台任务崩溃: {}", panic_message(&*e.into_panic()));
+                        }
+                        // 正常退出（已停止或关闭了持续优化）
+                        _ => return,
+                    },
+                    _ = cancel_token.cancelled() => {
+                        // 子 token 已随之取消；同时中止进行中的测速，等待核心循环自行退出
+                        cancel_active_tester(&active_tester);
+                        let _ = task.await;
+                        return;
+                    }
+                    _ = tokio::time::sleep(WATCHDOG_POLL_INTERVAL) => {
+                        let check_interval = config_manager
+                            .load()
+                            .map(|c| c.check_interval)
+                            .unwrap_or_default();
+                        let last = heartbeat.load(Ordering::Relaxed);
+                        if is_stalled(last, chrono::Utc::now().timestamp(), check_interval) {
+                            loop_token.cancel();
+                            task.abort();
+                            break "后台任务长时间无响应".to_string();
+                        }
+                    }
+                }
+            };
+
+            restart_count = restart_count_after_run(restart_count, started.elapsed());
+            let max_restarts = config_manager
+                .load()
+                .map(|c| c.health_loop_max_restarts)
+                .unwrap_or_default();
+            if !restart_allowed(restart_count, max_restarts) {
+                eprintln!(
+                    "HealthChecker: {}，已重启 {} 次，不再重启",
+                    reason, restart_count
+                );
+                tray::update(&app_handle, TrayStatus::Idle);
+                let _ = app_handle.emit(
+                    "health-loop-stopped",
+                    HealthLoopStopped {
+                        reason,
+                        restart_count,
+                    },
+                );
+                return;
+            }
+
+            restart_count += 1;
+            eprintln!(
+                "HealthChecker: {}，{}s 后重启（第 {} 次）",
+                reason,
+                WATCHDOG_RESTART_DELAY.as_secs(),
+                restart_count
+            );
+            let _ = app_handle.emit(
+                "health-checker-restarted",
+                HealthCheckerRestart {
+                    reason,
+                    restart_count,
+                },
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(WATCHDOG_RESTART_DELAY) => {}
+                _ = cancel_token.cancelled() => return,
+            }
+        }
+    }
+
     /// 核心循环
     #[cfg(feature = "tauri-runtime")]
+    #[allow(clippy::too_many_arguments)]
     async fn run_loop(
         app_handle: AppHandle,
         config_manager: ConfigManager,
         results: Arc<Mutex<Vec<EndpointResult>>>,
-        baselines: Arc<Mutex<HashMap<String, f64>>>,
+        baselines: BaselineTracker,
         cancel_token: CancellationToken,
+        heartbeat: Arc<AtomicI64>,
+        paused: Arc<AtomicBool>,
+        active_tester: ActiveTester,
     ) {
         // 通知前端已启动
         let _ = app_handle.emit(
@@ -105,21 +603,34 @@ impl HealthChecker {
                 ..Default::default()
             },
         );
+        tray::update(
+            &app_handle,
+            TrayStatus::Running {
+                healthy: 0,
+                total: 0,
+            },
+        );
 
         // 连续失败计数器：域名 → 连续失败次数
         let mut failure_counts: FailureCounter = HashMap::new();
 
+        // anyFAST 区块被外部修改后自动暂停；用户恢复即视为确认，下次写入时覆盖该区块
+        let mut force_next_write = false;
+
         // 跨循环复用 EndpointTester（TLS connector + DNS resolver 开销大）
         let mut cached_tester: Option<EndpointTester> = None;
-        let mut cached_preferred_ips: Vec<String> = Vec::new();
-        let mut cached_test_count: u32 = 0;
-        let mut cached_aggressiveness: u32 = 0;
+        let mut cached_fingerprint: u64 = 0;
+
+        // 每日汇总计数
+        let mut daily_stats = DailyStats::new(chrono::Local::now().date_naive());
 
         // 全量优选冷却期追踪：域名 → 上次全量优选时间
         let mut last_full_test: HashMap<String, std::time::Instant> = HashMap::new();
-        const FULL_TEST_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(600); // 10 分钟
 
         loop {
+            // 心跳：供看门狗判断循环是否卡死
+            heartbeat.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+
             // 每次循环开始重新加载 config
             let config = match config_manager.load() {
                 Ok(c) => c,
@@ -137,10 +648,11 @@ impl HealthChecker {
                 break;
             }
 
-            let interval_secs = config.check_interval;
-            // Add 0~20% random jitter to avoid bot-like precise periodic patterns
-            let jitter = rand::thread_rng().gen_range(0..=interval_secs / 5);
-            let interval = std::time::Duration::from_secs(interval_secs + jitter);
+            let interval = jittered_interval(
+                config.check_interval,
+                config.check_jitter_percent,
+                &mut rand::thread_rng(),
+            );
 
             // 等待检查间隔或取消信号
             tokio::select! {
@@ -152,6 +664,23 @@ impl HealthChecker {
                 break;
             }
 
+            // 暂停中：跳过本轮检查，失败计数、冷却期等状态原样保留
+            if paused.load(Ordering::SeqCst) {
+                tray::update(&app_handle, TrayStatus::Paused);
+                continue;
+            }
+
+            // 跨日：发送前一天的汇总
+            if let Some(summary) = daily_stats.roll_over(chrono::Local::now().date_naive()) {
+                if config.daily_summary {
+                    eprintln!(
+                        "HealthChecker: {} 每日汇总: 检查 {} 轮，切换 {} 次",
+                        summary.date, summary.checks, summary.switches
+                    );
+                    let _ = app_handle.emit("daily-summary", summary);
+                }
+            }
+
             // 找出已绑定的端点
             let bound_endpoints: Vec<(Endpoint, String)> = config
                 .endpoints
@@ -163,60 +692,48 @@ impl HealthChecker {
                 continue;
             }
 
-            // 复用 EndpointTester：仅在配置变化时重建
+            // 复用 EndpointTester：仅在测速相关配置变化或上一轮被取消时重建
+            let fingerprint = EndpointTester::config_fingerprint(&config);
             let tester = match &cached_tester {
-                Some(t)
-                    if cached_preferred_ips == config.preferred_ips
-                        && cached_test_count == config.test_count
-                        && cached_aggressiveness == config.test_aggressiveness =>
-                {
-                    t.clone()
-                }
+                Some(t) if !t.is_cancelled() && cached_fingerprint == fingerprint => t.clone(),
                 _ => {
-                    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
-                    let t = EndpointTester::with_strategy(
-                        config.preferred_ips.clone(),
-                        config.test_count,
-                        strategy,
-                    );
-                    cached_preferred_ips = config.preferred_ips.clone();
-                    cached_test_count = config.test_count;
-                    cached_aggressiveness = config.test_aggressiveness;
+                    let t = EndpointTester::from_config(&config);
+                    cached_fingerprint = fingerprint;
                     cached_tester = Some(t.clone());
                     t
                 }
             };
+            let _active_check = ActiveCheck::begin(&active_tester, &tester);
 
             // === Phase 1: 轻量级检查 — 仅测当前绑定 IP（每端点 1 次 TLS 连接） ===
-            let mut join_set = tokio::task::JoinSet::new();
-            for (ep, current_ip) in &bound_endpoints {
-                let tester_clone = tester.clone();
-                let ep_clone = ep.clone();
-                let current_ip_clone = current_ip.clone();
-                join_set.spawn(async move {
-                    let current_result = tester_clone
-                        .test_ip(&ep_clone, current_ip_clone.clone())
-                        .await;
-                    (ep_clone, current_ip_clone, current_result)
-                });
-            }
-
-            // 收集轻量级检查结果
-            let mut light_results: Vec<(Endpoint, String, EndpointResult)> = Vec::new();
-            while let Some(result) = join_set.join_next().await {
-                if cancel_token.is_cancelled() {
-                    break;
-                }
-                let Ok(item) = result else { continue };
-                light_results.push(item);
-            }
-
-            if cancel_token.is_cancelled() {
-                break;
+            // 有限并发探测，结果保持配置中的端点顺序；各端点随机错开几秒，避免瞬时突发
+            let light_probe = run_bounded(
+                bound_endpoints.clone(),
+                MAX_ENDPOINT_CONCURRENCY,
+                |(ep, current_ip)| {
+                    let tester = tester.clone();
+                    let stagger = std::time::Duration::from_millis(
+                        rand::thread_rng().gen_range(0..=MAX_PROBE_STAGGER_MS),
+                    );
+                    async move {
+                        tokio::time::sleep(stagger).await;
+                        let current_result = tester.test_ip(&ep, current_ip.clone()).await;
+                        (ep, current_ip, current_result)
+                    }
+                },
+            );
+            let light_results: Vec<(Endpoint, String, EndpointResult)> = tokio::select! {
+                results = light_probe => results,
+                _ = cancel_token.cancelled() => break,
+            };
+            // 本轮被手动取消：探测结果不可信，不计入失败计数
+            if tester.is_cancelled() {
+                continue;
             }
 
             // === Phase 2: 判断哪些端点需要全量优选 ===
-            let baselines_snapshot = baselines.lock().await.clone();
+            let baselines_snapshot = baselines.get_baselines_arc().lock().await.clone();
+            let thresholds = SwitchThresholds::from_config(&config);
             let mut needs_full_test: Vec<(Endpoint, String)> = Vec::new();
 
             for (ep, current_ip, current_result) in &light_results {
@@ -224,15 +741,10 @@ impl HealthChecker {
                     // 当前 IP 成功 — 重置失败计数
                     failure_counts.remove(&ep.domain);
 
-                    // 检查延迟是否严重恶化（比基准高 slow_threshold% 且绝对增加超 300ms）
+                    // 检查延迟是否严重恶化（比基准高 slow_threshold% 且绝对增加超阈值）
                     if let Some(&baseline) = baselines_snapshot.get(&ep.domain) {
-                        if baseline > 0.0 {
-                            let threshold_latency =
-                                baseline * (1.0 + config.slow_threshold as f64 / 100.0);
-                            let abs_increase = current_result.latency - baseline;
-                            if current_result.latency > threshold_latency && abs_increase > 300.0 {
-                                needs_full_test.push((ep.clone(), current_ip.clone()));
-                            }
+                        if thresholds.is_severely_degraded(current_result.latency, baseline) {
+                            needs_full_test.push((ep.clone(), current_ip.clone()));
                         }
                     }
                 } else {
@@ -245,10 +757,10 @@ impl HealthChecker {
                 }
             }
 
-            // 应用冷却期过滤：每个域名全量优选后 10 分钟内不重复触发
+            // 应用冷却期过滤：每个域名全量优选后冷却期内不重复触发
             let now = std::time::Instant::now();
             needs_full_test.retain(|(ep, _)| match last_full_test.get(&ep.domain) {
-                Some(last_time) => now.duration_since(*last_time) >= FULL_TEST_COOLDOWN,
+                Some(last_time) => now.duration_since(*last_time) >= thresholds.full_test_cooldown,
                 None => true,
             });
 
@@ -265,34 +777,44 @@ impl HealthChecker {
             let mut switch_actions: Vec<SwitchAction> = Vec::new();
 
             if !needs_full_test.is_empty() {
-                let mut full_join_set = tokio::task::JoinSet::new();
-                for (ep, current_ip) in &needs_full_test {
-                    // 记录全量优选时间（冷却期起点）
+                // 记录全量优选时间（冷却期起点）
+                for (ep, _) in &needs_full_test {
                     last_full_test.insert(ep.domain.clone(), now);
-
-                    let tester_clone = tester.clone();
-                    let ep_clone = ep.clone();
-                    let current_ip_clone = current_ip.clone();
-                    full_join_set.spawn(async move {
-                        let best_result = tester_clone.test_endpoint(&ep_clone).await;
-                        (ep_clone, current_ip_clone, best_result)
-                    });
                 }
 
-                while let Some(result) = full_join_set.join_next().await {
-                    if cancel_token.is_cancelled() {
-                        break;
-                    }
-                    let Ok((ep, current_ip, best_result)) = result else {
-                        continue;
-                    };
+                let full_probe = run_bounded(
+                    needs_full_test,
+                    MAX_ENDPOINT_CONCURRENCY,
+                    |(ep, current_ip)| {
+                        let tester = tester.clone();
+                        async move {
+                            let best_result = tester.test_endpoint(&ep).await;
+                            // 候选 IP 与当前不同时追加一次确认探测，避免切到仅偶尔可用的 IP
+                            let confirm_result =
+                                if best_result.success && best_result.ip != current_ip {
+                                    Some(tester.test_ip(&ep, best_result.ip.clone()).await)
+                                } else {
+                                    None
+                                };
+                            (ep, current_ip, best_result, confirm_result)
+                        }
+                    },
+                );
+                let full_results = tokio::select! {
+                    results = full_probe => results,
+                    _ = cancel_token.cancelled() => break,
+                };
+                if tester.is_cancelled() {
+                    continue;
+                }
 
+                // 失败计数等状态只在本循环内顺序更新，无需额外同步
+                for (ep, current_ip, best_result, confirm_result) in full_results {
                     if !best_result.success {
                         continue;
                     }
 
                     let new_ip = &best_result.ip;
-                    let new_latency = best_result.latency;
 
                     // 从轻量检查结果获取当前延迟
                     let current_latency = light_results
@@ -312,15 +834,21 @@ impl HealthChecker {
                         continue;
                     }
 
+                    // 候选 IP 必须通过确认探测，防止在两个同样不稳定的 IP 间来回切换
+                    let Some(new_latency) = confirm_result
+                        .as_ref()
+                        .and_then(|confirm| confirmed_latency(&best_result, confirm))
+                    else {
+                        eprintln!(
+                            "HealthChecker: {} 候选 IP {} 确认探测失败，放弃切换",
+                            ep.domain, new_ip
+                        );
+                        continue;
+                    };
+
                     let should_switch = if let Some(cur_lat) = current_latency {
                         // 当前 IP 能通但延迟恶化 — 需要明显更好才切换
-                        if cur_lat <= 0.0 {
-                            false
-                        } else {
-                            let improvement_pct = (cur_lat - new_latency) / cur_lat * 100.0;
-                            let improvement_abs = cur_lat - new_latency;
-                            improvement_pct > 20.0 && improvement_abs > 50.0
-                        }
+                        thresholds.is_worth_switching(cur_lat, new_latency)
                     } else {
                         // 当前 IP 不可达 — 有可用候选就切换
                         failure_counts.remove(&ep.domain);
@@ -354,20 +882,29 @@ impl HealthChecker {
                     })
                     .collect();
 
-                match hosts_ops::write_bindings_batch(&bindings) {
+                match hosts_ops::write_bindings_batch(&bindings, force_next_write) {
                     Ok(count) => {
+                        force_next_write = false;
+                        // count 只包含 IP 确实变化的域名
+                        record_switches(
+                            &mut SWITCH_LOG.lock().unwrap_or_else(|e| e.into_inner()),
+                            chrono::Utc::now().timestamp(),
+                            count,
+                        );
                         if count > 0 {
                             let _ = hosts_ops::flush_dns();
                         }
 
                         // 批量更新状态 + 通知前端
                         // 先批量更新 baselines（只获取一次锁）
-                        {
-                            let mut b = baselines.lock().await;
-                            for action in &switch_actions {
-                                b.insert(action.domain.clone(), action.new_latency);
-                            }
-                        }
+                        baselines
+                            .update(
+                                switch_actions
+                                    .iter()
+                                    .map(|a| (a.domain.clone(), a.new_latency))
+                                    .collect(),
+                            )
+                            .await;
 
                         // 再批量更新 results（只获取一次锁）
                         {
@@ -378,6 +915,7 @@ impl HealthChecker {
                                     .find(|r| r.endpoint.domain == action.domain)
                                 {
                                     *existing = action.best_result.clone();
+                                    existing.apply_rating(&config.latency_thresholds);
                                 }
                             }
                         }
@@ -407,8 +945,39 @@ impl HealthChecker {
                             );
                         }
 
+                        // 窗口隐藏到托盘时前端事件可能被忽略，同轮切换合并为一条系统通知
+                        if config.notify_on_switch && count > 0 {
+                            let notices: Vec<SwitchNotice> = switch_actions
+                                .iter()
+                                .map(|a| SwitchNotice {
+                                    domain: a.domain.clone(),
+                                    old_ip: a.old_ip.clone(),
+                                    new_ip: a.new_ip.clone(),
+                                    new_latency: a.new_latency,
+                                })
+                                .collect();
+                            if let Some((title, body)) = notification::switch_summary(&notices) {
+                                notification::notify(title, body);
+                            }
+                        }
+
                         count
                     }
+                    Err(HostsError::ExternalModification) => {
+                        // 不暂停的话每轮都会以同样的错误失败
+                        paused.store(true, Ordering::SeqCst);
+                        force_next_write = true;
+                        tray::update(&app_handle, TrayStatus::Paused);
+                        let _ = app_handle.emit(
+                            "optimization-event",
+                            OptimizationEvent {
+                                event_type: OptimizationEventType::ExternalModification,
+                                message: "hosts 中的 anyFAST 区块已被手动修改，持续优化已暂停；恢复后将以新的测速结果覆盖该区块".into(),
+                                ..Default::default()
+                            },
+                        );
+                        0
+                    }
                     Err(e) => {
                         eprintln!("HealthChecker: 批量写入绑定失败: {}", e);
                         0
@@ -418,6 +987,22 @@ impl HealthChecker {
                 0
             };
 
+            let latency_samples: Vec<(String, f64)> = light_results
+                .iter()
+                .filter(|(_, _, r)| r.success)
+                .map(|(ep, _, r)| (ep.domain.clone(), r.latency))
+                .collect();
+            daily_stats.record_check(&latency_samples, switched_count as u32);
+
+            // 托盘图标反映本轮轻量检查的健康状况
+            tray::update(
+                &app_handle,
+                TrayStatus::Running {
+                    healthy: latency_samples.len(),
+                    total: light_results.len(),
+                },
+            );
+
             // 通知前端本轮检查完成
             let _ = app_handle.emit(
                 "optimization-event",
@@ -442,6 +1027,7 @@ impl HealthChecker {
                 ..Default::default()
             },
         );
+        tray::update(&app_handle, TrayStatus::Idle);
     }
 }
 
@@ -449,6 +1035,88 @@ impl HealthChecker {
 mod tests {
     use super::*;
 
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, d).unwrap()
+    }
+
+    fn endpoint() -> Endpoint {
+        Endpoint {
+            name: "a".into(),
+            url: "https://a.com".into(),
+            domain: "a.com".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_stalled_uses_interval_and_budget() {
+        let interval = 300;
+        let allowed = 2 * 300 + WATCHDOG_CHECK_BUDGET_SECS;
+        assert!(!is_stalled(1_000, 1_000 + allowed, interval));
+        assert!(is_stalled(1_000, 1_000 + allowed + 1, interval));
+        // 时钟回拨不误判
+        assert!(!is_stalled(1_000, 500, interval));
+    }
+
+    #[test]
+    fn test_panic_message_extracts_payload() {
+        let payload = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "boom");
+
+        let payload = std::panic::catch_unwind(|| panic!("{} failed", "check")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "check failed");
+    }
+
+    #[test]
+    fn test_confirmed_latency_requires_both_probes() {
+        let candidate = EndpointResult::success(endpoint(), "1.1.1.1".into(), 80.0, 80.0);
+
+        // 确认探测成功：取较差的延迟
+        let confirm = EndpointResult::success(endpoint(), "1.1.1.1".into(), 120.0, 120.0);
+        assert_eq!(confirmed_latency(&candidate, &confirm), Some(120.0));
+
+        // 确认探测失败：拒绝切换（间歇可用的 IP）
+        let confirm = EndpointResult::failure(endpoint(), "1.1.1.1".into(), "timeout".into());
+        assert_eq!(confirmed_latency(&candidate, &confirm), None);
+
+        // 确认的不是同一个 IP：拒绝
+        let confirm = EndpointResult::success(endpoint(), "2.2.2.2".into(), 60.0, 60.0);
+        assert_eq!(confirmed_latency(&candidate, &confirm), None);
+    }
+
+    #[test]
+    fn test_daily_stats_same_day_no_summary() {
+        let mut stats = DailyStats::new(day(1));
+        stats.record_check(&[("a.com".into(), 100.0)], 0);
+        assert!(stats.roll_over(day(1)).is_none());
+        assert_eq!(stats.checks, 1);
+    }
+
+    #[test]
+    fn test_daily_stats_roll_over_summarizes_previous_day() {
+        let mut stats = DailyStats::new(day(1));
+        stats.record_check(&[("a.com".into(), 100.0), ("b.com".into(), 50.0)], 1);
+        stats.record_check(&[("a.com".into(), 200.0)], 2);
+
+        let summary = stats.roll_over(day(2)).expect("should summarize");
+        assert_eq!(summary.date, "2025-01-01");
+        assert_eq!(summary.checks, 2);
+        assert_eq!(summary.switches, 3);
+        assert_eq!(summary.avg_latency.get("a.com"), Some(&150.0));
+        assert_eq!(summary.avg_latency.get("b.com"), Some(&50.0));
+
+        // 计数已重置到新的一天
+        assert_eq!(stats.day, day(2));
+        assert_eq!(stats.checks, 0);
+    }
+
+    #[test]
+    fn test_daily_stats_empty_day_skipped() {
+        let mut stats = DailyStats::new(day(1));
+        assert!(stats.roll_over(day(2)).is_none());
+        assert_eq!(stats.day, day(2));
+    }
+
     #[tokio::test]
     async fn test_baseline_tracker_new() {
         let tracker = BaselineTracker::new();
@@ -468,4 +1136,270 @@ mod tests {
         let b = baselines.lock().await;
         assert_eq!(b.get("test.com"), Some(&100.0));
     }
+
+    #[tokio::test]
+    async fn test_baseline_tracker_persists_updates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("baselines.json");
+
+        let tracker = BaselineTracker::with_store(BaselineStore::with_path(path.clone(), 3600));
+        tracker.update(vec![("test.com".to_string(), 150.0)]).await;
+
+        // 模拟重启后重新加载
+        let restored = BaselineTracker::with_store(BaselineStore::with_path(path, 3600));
+        let baselines = restored.get_baselines_arc();
+        assert_eq!(baselines.lock().await.get("test.com"), Some(&150.0));
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_slow_probe_does_not_block_fast_one() {
+        let finished = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let items = vec![("slow", 300u64), ("fast", 10), ("fast2", 10)];
+
+        let started = std::time::Instant::now();
+        let results = run_bounded(items, 2, |(name, delay_ms)| {
+            let finished = finished.clone();
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+                finished.lock().unwrap().push(name);
+                name
+            }
+        })
+        .await;
+
+        // 快端点先完成，且不用等慢端点释放槽位
+        assert_eq!(*finished.lock().unwrap(), ["fast", "fast2", "slow"]);
+        assert!(started.elapsed() < std::time::Duration::from_millis(600));
+        // 返回结果保持输入顺序
+        assert_eq!(results, ["slow", "fast", "fast2"]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume_transitions() {
+        let cancel_token = CancellationToken::new();
+        let token = cancel_token.clone();
+        let mut checker = HealthChecker {
+            cancel_token,
+            task_handle: Some(tokio::spawn(async move { token.cancelled().await })),
+            paused: Arc::new(AtomicBool::new(false)),
+            active_tester: Arc::new(std::sync::Mutex::new(None)),
+        };
+        let status = |running, paused| OptimizationStatus {
+            is_running: running,
+            is_paused: paused,
+        };
+
+        assert_eq!(checker.status(), status(true, false));
+        assert!(checker.pause());
+        assert!(!checker.pause());
+        assert_eq!(checker.status(), status(true, true));
+
+        assert!(checker.resume());
+        assert!(!checker.resume());
+        assert_eq!(checker.status(), status(true, false));
+
+        // 停止后不再报告暂停
+        checker.pause();
+        checker.stop().await;
+        assert_eq!(checker.status(), status(false, false));
+    }
+
+    #[tokio::test]
+    async fn test_panicked_loop_is_not_running() {
+        let mut checker = HealthChecker {
+            cancel_token: CancellationToken::new(),
+            task_handle: Some(tokio::spawn(async {
+                panic!("simulated health loop panic");
+            })),
+            paused: Arc::new(AtomicBool::new(false)),
+            active_tester: Arc::new(std::sync::Mutex::new(None)),
+        };
+        while checker
+            .task_handle
+            .as_ref()
+            .is_some_and(|h| !h.is_finished())
+        {
+            tokio::task::yield_now().await;
+        }
+        assert!(!checker.is_running());
+        assert!(!checker.status().is_running);
+        checker.stop().await;
+    }
+
+    #[test]
+    fn test_restart_allowed_respects_limit() {
+        assert!(!restart_allowed(0, 0));
+        assert!(restart_allowed(0, 2));
+        assert!(restart_allowed(1, 2));
+        assert!(!restart_allowed(2, 2));
+    }
+
+    #[test]
+    fn test_restart_count_resets_after_stable_run() {
+        let short = WATCHDOG_STABLE_RUN - std::time::Duration::from_secs(1);
+        assert_eq!(restart_count_after_run(3, short), 3);
+        assert_eq!(restart_count_after_run(3, WATCHDOG_STABLE_RUN), 0);
+        // 清零后仍可继续重启
+        assert!(restart_allowed(
+            restart_count_after_run(2, WATCHDOG_STABLE_RUN),
+            2
+        ));
+    }
+
+    #[test]
+    fn test_suggest_check_interval_follows_stability() {
+        // 失败率高或频繁切换：缩短间隔
+        assert_eq!(suggest_check_interval(0.0, 0.5).suggested_secs, 60);
+        assert_eq!(suggest_check_interval(2.0, 0.0).suggested_secs, 60);
+        // 偶有波动：默认间隔
+        assert_eq!(suggest_check_interval(0.3, 0.0).suggested_secs, 120);
+        assert_eq!(suggest_check_interval(0.0, 0.15).suggested_secs, 120);
+        // 稳定：放宽间隔
+        assert_eq!(suggest_check_interval(0.1, 0.0).suggested_secs, 300);
+        assert_eq!(suggest_check_interval(0.0, 0.0).suggested_secs, 600);
+
+        let suggestion = suggest_check_interval(0.5, 0.2);
+        assert!(suggestion.suggested_secs >= MIN_CHECK_INTERVAL_SECS);
+        assert!(suggestion.rationale.contains("0.50"));
+        assert!(suggestion.rationale.contains("20%"));
+    }
+
+    #[test]
+    fn test_record_switches_prunes_outside_window() {
+        let mut log = std::collections::VecDeque::new();
+        record_switches(&mut log, 1_000, 2);
+        record_switches(&mut log, 1_000 + SWITCH_LOG_WINDOW_SECS, 0);
+        assert_eq!(log.len(), 2);
+        record_switches(&mut log, 1_001 + SWITCH_LOG_WINDOW_SECS, 1);
+        assert_eq!(log, [1_001 + SWITCH_LOG_WINDOW_SECS]);
+    }
+
+    #[tokio::test]
+    async fn test_stop_cancels_in_flight_check() {
+        let cancel_token = CancellationToken::new();
+        let token = cancel_token.clone();
+        let tester = EndpointTester::new(vec![], 1);
+        let mut checker = HealthChecker {
+            cancel_token,
+            task_handle: Some(tokio::spawn(async move { token.cancelled().await })),
+            paused: Arc::new(AtomicBool::new(false)),
+            active_tester: Arc::new(std::sync::Mutex::new(None)),
+        };
+
+        // 模拟一轮耗时很长、只在测速器取消时提前结束的检查
+        let slot = checker.active_tester.clone();
+        let check = tokio::spawn(async move {
+            let _active_check = ActiveCheck::begin(&slot, &tester);
+            for _ in 0..600 {
+                if tester.is_cancelled() {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            false
+        });
+        while checker.active_tester.lock().unwrap().is_none() {
+            tokio::task::yield_now().await;
+        }
+
+        let started = std::time::Instant::now();
+        checker.stop().await;
+        let interrupted = tokio::time::timeout(std::time::Duration::from_secs(2), check)
+            .await
+            .expect("check should stop promptly")
+            .unwrap();
+        assert!(interrupted);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        // 已取消的测速不会被重复计为一次取消
+        assert!(!checker.cancel_in_flight());
+    }
+
+    #[test]
+    fn test_finished_check_releases_cached_tester() {
+        let checker = HealthChecker {
+            cancel_token: CancellationToken::new(),
+            task_handle: None,
+            paused: Arc::new(AtomicBool::new(false)),
+            active_tester: Arc::new(std::sync::Mutex::new(None)),
+        };
+        // 与核心循环相同：跨轮复用同一个测速器
+        let cached = EndpointTester::new(vec![], 1);
+
+        {
+            let _active_check = ActiveCheck::begin(&checker.active_tester, &cached);
+            assert!(checker
+                .active_tester
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|t| !t.is_cancelled()));
+        }
+
+        // 两轮之间没有进行中的测速：不报告取消，也不会取消缓存的测速器
+        assert!(!checker.cancel_in_flight());
+        assert!(!cached.is_cancelled());
+
+        let _active_check = ActiveCheck::begin(&checker.active_tester, &cached);
+        assert!(checker.cancel_in_flight());
+        assert!(cached.is_cancelled());
+    }
+
+    #[test]
+    fn test_switch_thresholds_follow_config() {
+        let defaults = SwitchThresholds::from_config(&AppConfig::default());
+        // 默认：比基准慢 150% 且绝对增加超 300ms
+        assert!(defaults.is_severely_degraded(800.0, 200.0));
+        assert!(!defaults.is_severely_degraded(300.0, 100.0));
+        // 默认：至少快 20% 且 50ms 才切换
+        assert!(defaults.is_worth_switching(400.0, 300.0));
+        assert!(!defaults.is_worth_switching(400.0, 360.0));
+        assert_eq!(defaults.full_test_cooldown.as_secs(), 600);
+
+        let tuned = SwitchThresholds::from_config(&AppConfig {
+            severe_abs_threshold_ms: 100.0,
+            switch_min_improvement_percent: 5.0,
+            switch_min_improvement_ms: 10.0,
+            full_test_cooldown_secs: 120,
+            ..AppConfig::default()
+        });
+        assert!(tuned.is_severely_degraded(300.0, 100.0));
+        assert!(tuned.is_worth_switching(400.0, 360.0));
+        assert_eq!(tuned.full_test_cooldown.as_secs(), 120);
+
+        // 过小的配置按下限修正
+        let clamped = SwitchThresholds::from_config(&AppConfig {
+            severe_abs_threshold_ms: -5.0,
+            switch_min_improvement_percent: 250.0,
+            full_test_cooldown_secs: 0,
+            ..AppConfig::default()
+        });
+        assert_eq!(clamped.severe_abs_threshold_ms, 0.0);
+        assert_eq!(clamped.min_improvement_percent, 100.0);
+        assert_eq!(
+            clamped.full_test_cooldown.as_secs(),
+            MIN_FULL_TEST_COOLDOWN_SECS
+        );
+    }
+
+    #[test]
+    fn test_jittered_interval_stays_within_bounds() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let interval = jittered_interval(120, 10, &mut rng).as_millis();
+            assert!((108_000..=132_000).contains(&interval), "{}", interval);
+        }
+
+        // 抖动不会把间隔压到下限以下
+        for _ in 0..500 {
+            let interval = jittered_interval(MIN_CHECK_INTERVAL_SECS, 50, &mut rng);
+            assert!(interval.as_secs() >= MIN_CHECK_INTERVAL_SECS);
+        }
+
+        // 关闭抖动时间隔固定，过小的间隔按下限修正
+        assert_eq!(jittered_interval(90, 0, &mut rng).as_secs(), 90);
+        assert_eq!(
+            jittered_interval(5, 0, &mut rng).as_secs(),
+            MIN_CHECK_INTERVAL_SECS
+        );
+    }
 }
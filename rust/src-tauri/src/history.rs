@@ -1,15 +1,30 @@
 //! History record manager
 //! 存储测试历史记录，支持统计分析
+//! 记录以 JSON Lines（每行一条）追加写入，定期整体重写以丢弃过期记录
 
-use crate::models::{HistoryRecord, HistoryStats};
-use directories::ProjectDirs;
-use std::fs;
+use crate::models::{DailyHistory, DomainHistory, HistoryInfo, HistoryRecord, HistoryStats};
+use fs2::FileExt;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
-/// 历史记录保留天数
-const HISTORY_RETENTION_DAYS: i64 = 7;
+/// 默认历史记录保留天数
+pub const DEFAULT_HISTORY_RETENTION_DAYS: u32 = 7;
+
+/// 最短保留天数（避免误配置为 0 清空全部记录）
+pub const MIN_HISTORY_RETENTION_DAYS: u32 = 1;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// 距上次压缩超过该时长（秒）后，下次追加时重写文件、丢弃过期记录
+const COMPACTION_INTERVAL_SECS: i64 = 60 * 60;
+
+/// get_stats 默认返回的最近记录条数
+pub const DEFAULT_STATS_RECORD_LIMIT: u32 = 100;
 
 #[derive(Error, Debug)]
 pub enum HistoryError {
@@ -21,19 +36,44 @@ pub enum HistoryError {
 
 pub struct HistoryManager {
     path: PathBuf,
+    /// 保留天数（来自 AppConfig.history_retention_days，保存配置时更新）
+    retention_days: AtomicU32,
+    /// 上次压缩的时间戳（0 表示本进程尚未压缩，下次追加时立即压缩）
+    last_compaction: AtomicI64,
 }
 
 impl HistoryManager {
     pub fn new() -> Self {
-        let path = if let Some(dirs) = ProjectDirs::from("com", "anyrouter", "fast") {
-            let config_dir = dirs.config_dir();
-            fs::create_dir_all(config_dir).ok();
-            config_dir.join("history.json")
-        } else {
-            PathBuf::from("history.json")
-        };
+        let path = crate::config::data_dir().join("history.jsonl");
+
+        Self::with_retention(path)
+    }
 
-        Self { path }
+    fn with_retention(path: PathBuf) -> Self {
+        Self {
+            path,
+            retention_days: AtomicU32::new(DEFAULT_HISTORY_RETENTION_DAYS),
+            last_compaction: AtomicI64::new(0),
+        }
+    }
+
+    /// Create a HistoryManager with a custom path (for testing)
+    #[cfg(test)]
+    pub fn with_path(path: PathBuf) -> Self {
+        Self::with_retention(path)
+    }
+
+    /// 设置保留天数（不足 MIN_HISTORY_RETENTION_DAYS 时按最小值处理），下次追加时立即压缩
+    pub fn set_retention_days(&self, days: u32) {
+        self.retention_days
+            .store(days.max(MIN_HISTORY_RETENTION_DAYS), Ordering::Relaxed);
+        self.last_compaction.store(0, Ordering::Relaxed);
+    }
+
+    /// 早于该时间戳的记录视为过期
+    fn retention_cutoff(&self) -> i64 {
+        let days = self.retention_days.load(Ordering::Relaxed) as i64;
+        Self::now_timestamp() - days * 24 * 60 * 60
     }
 
     /// 获取当前 Unix 时间戳（秒）
@@ -44,71 +84,151 @@ impl HistoryManager {
             .unwrap_or(0)
     }
 
-    /// 加载所有历史记录
+    /// 旧版（整个 JSON 数组）历史文件路径
+    /// 数据文件本身就是 .json 时没有单独的旧版文件，返回 None，避免把刚写入的文件当作旧版删掉
+    fn legacy_path(&self) -> Option<PathBuf> {
+        let legacy = self.path.with_extension("json");
+        (legacy != self.path).then_some(legacy)
+    }
+
+    /// 读取文件中的全部记录（含尚未压缩掉的过期记录）
+    /// 跳过无法解析的行（如写入中途崩溃留下的半行）；新格式文件不存在时回退读取旧版文件
+    fn read_all_records(&self) -> Result<Vec<HistoryRecord>, HistoryError> {
+        if !self.path.exists() {
+            if let Some(legacy) = self.legacy_path().filter(|p| p.exists()) {
+                let content = fs::read_to_string(&legacy)?;
+                return Ok(serde_json::from_str(&content)?);
+            }
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)?;
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// 加载保留期内的历史记录（过期但尚未压缩掉的记录不参与统计）
     fn load_records(&self) -> Result<Vec<HistoryRecord>, HistoryError> {
-        if self.path.exists() {
-            let content = fs::read_to_string(&self.path)?;
-            let records: Vec<HistoryRecord> = serde_json::from_str(&content)?;
-            Ok(records)
-        } else {
-            Ok(Vec::new())
+        let cutoff = self.retention_cutoff();
+        let mut records = self.read_all_records()?;
+        records.retain(|r| r.timestamp > cutoff);
+        Ok(records)
+    }
+
+    fn to_lines(records: &[HistoryRecord]) -> Result<String, HistoryError> {
+        let mut content = String::new();
+        for record in records {
+            content.push_str(&serde_json::to_string(record)?);
+            content.push('\n');
         }
+        Ok(content)
     }
 
-    /// 保存历史记录
+    /// 整体重写历史记录：先写临时文件再原子替换，读者不会读到写了一半的文件
+    /// 写入成功后删除旧版文件（完成迁移）
     fn save_records(&self, records: &[HistoryRecord]) -> Result<(), HistoryError> {
-        let content = serde_json::to_string_pretty(records)?;
-        fs::write(&self.path, content)?;
+        let content = Self::to_lines(records)?;
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &self.path)?;
+        if let Some(legacy) = self.legacy_path().filter(|p| p.exists()) {
+            fs::remove_file(legacy)?;
+        }
+        Ok(())
+    }
+
+    /// 在文件末尾追加记录，不重写已有内容
+    /// 上次写入中途崩溃时末行可能缺少换行，先补上，避免新记录与半行粘在一起
+    fn append_records(&self, records: &[HistoryRecord]) -> Result<(), HistoryError> {
+        let mut content = Self::to_lines(records)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                content.insert(0, '\n');
+            }
+        }
+        file.write_all(content.as_bytes())?;
         Ok(())
     }
 
+    /// 获取跨进程写锁
+    /// 锁加在独立的 .lock 文件上：数据文件会被原子替换，锁不能挂在它身上
+    fn lock(&self) -> Result<File, HistoryError> {
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("jsonl.lock"))?;
+        lock_file.lock_exclusive()?;
+        Ok(lock_file)
+    }
+
+    /// 在文件锁保护下完成「读取-修改-保存」，避免并发写入（含其他进程）互相覆盖
+    /// 返回值为 None 时表示无需保存
+    fn update_records<T>(
+        &self,
+        modify: impl FnOnce(&mut Vec<HistoryRecord>) -> Option<T>,
+    ) -> Result<Option<T>, HistoryError> {
+        let _lock = self.lock()?;
+
+        let mut records = self.read_all_records()?;
+        let outcome = modify(&mut records);
+        if outcome.is_some() {
+            self.save_records(&records)?;
+        }
+        Ok(outcome)
+        // 锁在此释放
+    }
+
     /// 添加一条历史记录
     #[allow(dead_code)]
     pub fn add_record(&self, record: HistoryRecord) -> Result<(), HistoryError> {
-        let mut records = self.load_records()?;
-        records.push(record);
-
-        // 自动清理过期记录
-        let cutoff = Self::now_timestamp() - (HISTORY_RETENTION_DAYS * 24 * 60 * 60);
-        records.retain(|r| r.timestamp > cutoff);
-
-        self.save_records(&records)
+        self.add_records(vec![record])
     }
 
-    /// 批量添加历史记录
+    /// 批量添加历史记录：通常直接追加；仍是旧版文件或到了压缩时间时整体重写并清理过期记录
     pub fn add_records(&self, new_records: Vec<HistoryRecord>) -> Result<(), HistoryError> {
         if new_records.is_empty() {
             return Ok(());
         }
 
-        let mut records = self.load_records()?;
-        records.extend(new_records);
+        let now = Self::now_timestamp();
+        let _lock = self.lock()?;
+        let needs_migration = !self.path.exists() && self.legacy_path().is_some_and(|p| p.exists());
+        let compaction_due =
+            now - self.last_compaction.load(Ordering::Relaxed) >= COMPACTION_INTERVAL_SECS;
+        if !needs_migration && !compaction_due {
+            return self.append_records(&new_records);
+        }
 
-        // 自动清理过期记录
-        let cutoff = Self::now_timestamp() - (HISTORY_RETENTION_DAYS * 24 * 60 * 60);
+        let cutoff = self.retention_cutoff();
+        let mut records = self.read_all_records()?;
+        records.extend(new_records);
         records.retain(|r| r.timestamp > cutoff);
-
-        self.save_records(&records)
+        self.save_records(&records)?;
+        self.last_compaction.store(now, Ordering::Relaxed);
+        Ok(())
     }
 
     /// 获取指定时间段内的统计数据
     /// hours: 过去多少小时的数据，0 表示全部
-    pub fn get_stats(&self, hours: u32) -> Result<HistoryStats, HistoryError> {
+    /// limit: 返回的最近记录条数上限（汇总统计仍覆盖整个时间段）
+    pub fn get_stats(&self, hours: u32, limit: u32) -> Result<HistoryStats, HistoryError> {
         let records = self.load_records()?;
 
         // 累计节省时间：使用全部记录计算（不受时间范围过滤，反映自启用以来的总效果）
         let total_speedup_ms = Self::calculate_cumulative_speedup(&records);
 
-        let cutoff = if hours > 0 {
-            Self::now_timestamp() - (hours as i64 * 60 * 60)
-        } else {
-            0
-        };
-
-        let filtered: Vec<HistoryRecord> = records
-            .into_iter()
-            .filter(|r| r.timestamp > cutoff)
-            .collect();
+        let filtered = Self::within_hours(records, hours);
 
         if filtered.is_empty() {
             return Ok(HistoryStats {
@@ -139,8 +259,8 @@ impl HistoryManager {
 
         // 返回最近的记录（最多 100 条，按时间倒序）
         let mut recent_records = filtered;
-        recent_records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-        recent_records.truncate(100);
+        recent_records.sort_by_key(|r| std::cmp::Reverse(r.timestamp));
+        recent_records.truncate(limit as usize);
 
         Ok(HistoryStats {
             total_tests,
@@ -150,6 +270,63 @@ impl HistoryManager {
         })
     }
 
+    /// 获取单个域名在指定时间段内的记录（按时间升序）及优化后延迟统计
+    /// hours: 过去多少小时的数据，0 表示全部
+    pub fn get_domain_history(
+        &self,
+        domain: &str,
+        hours: u32,
+    ) -> Result<DomainHistory, HistoryError> {
+        let mut records: Vec<HistoryRecord> = Self::within_hours(self.load_records()?, hours)
+            .into_iter()
+            .filter(|r| r.domain == domain)
+            .collect();
+        records.sort_by_key(|r| r.timestamp);
+
+        let latencies = records.iter().map(|r| r.optimized_latency);
+        let (min_latency, max_latency, avg_latency) = if records.is_empty() {
+            (0.0, 0.0, 0.0)
+        } else {
+            (
+                latencies.clone().fold(f64::INFINITY, f64::min),
+                latencies.clone().fold(f64::NEG_INFINITY, f64::max),
+                latencies.sum::<f64>() / records.len() as f64,
+            )
+        };
+
+        Ok(DomainHistory {
+            domain: domain.to_string(),
+            min_latency,
+            max_latency,
+            avg_latency,
+            records,
+        })
+    }
+
+    /// 按 UTC 自然日汇总最近 days 天（含今天）的记录，0 表示全部，按日期升序
+    pub fn get_daily(&self, days: u32) -> Result<Vec<DailyHistory>, HistoryError> {
+        let since_day = if days > 0 {
+            Self::now_timestamp().div_euclid(SECONDS_PER_DAY) - (days as i64 - 1)
+        } else {
+            i64::MIN
+        };
+        Ok(bucket_daily(&self.load_records()?, since_day))
+    }
+
+    /// 保留过去 hours 小时内的记录，0 表示全部
+    fn within_hours(records: Vec<HistoryRecord>, hours: u32) -> Vec<HistoryRecord> {
+        let cutoff = if hours > 0 {
+            Self::now_timestamp() - (hours as i64 * 60 * 60)
+        } else {
+            0
+        };
+
+        records
+            .into_iter()
+            .filter(|r| r.timestamp > cutoff)
+            .collect()
+    }
+
     /// 计算累计节省时间（基于绑定持续时间的估算）
     ///
     /// 核心思路：每条 applied 记录表示"在该时刻，优化绑定有效"。
@@ -188,29 +365,385 @@ impl HistoryManager {
         total
     }
 
+    /// 存储概况：记录数、文件大小与时间范围（只读取一次记录）
+    /// 记录数包含尚未压缩掉的过期记录，反映文件实际内容
+    pub fn get_info(&self) -> Result<HistoryInfo, HistoryError> {
+        let records = self.read_all_records()?;
+        let file_size_bytes = match fs::metadata(&self.path) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(HistoryInfo {
+            record_count: records.len() as u32,
+            file_size_bytes,
+            oldest_timestamp: records.iter().map(|r| r.timestamp).min(),
+            newest_timestamp: records.iter().map(|r| r.timestamp).max(),
+        })
+    }
+
     /// 清理过期记录
-    #[allow(dead_code)]
     pub fn clear_old(&self) -> Result<u32, HistoryError> {
-        let records = self.load_records()?;
-        let original_count = records.len();
+        let cutoff = self.retention_cutoff();
+        let removed = self.update_records(|records| {
+            let original_count = records.len();
+            records.retain(|r| r.timestamp > cutoff);
+            let removed_count = (original_count - records.len()) as u32;
+            (removed_count > 0).then_some(removed_count)
+        })?;
+
+        Ok(removed.unwrap_or(0))
+    }
 
-        let cutoff = Self::now_timestamp() - (HISTORY_RETENTION_DAYS * 24 * 60 * 60);
-        let filtered: Vec<HistoryRecord> = records
-            .into_iter()
-            .filter(|r| r.timestamp > cutoff)
-            .collect();
+    /// 清空所有历史记录
+    pub fn clear_all(&self) -> Result<(), HistoryError> {
+        self.update_records(|records| {
+            records.clear();
+            Some(())
+        })?;
+        Ok(())
+    }
+}
+
+/// 单次遍历按 UTC 日分桶（since_day 为起始日序号，即时间戳 / 86400）
+/// avg_speedup_percent 与 get_stats 一致，只统计正向加速；
+/// total_speedup_ms 为当日已应用记录节省延迟之和
+fn bucket_daily(records: &[HistoryRecord], since_day: i64) -> Vec<DailyHistory> {
+    #[derive(Default)]
+    struct Bucket {
+        test_count: u32,
+        speedup_sum: f64,
+        speedup_count: u32,
+        saved_ms: f64,
+    }
+
+    let mut buckets: BTreeMap<i64, Bucket> = BTreeMap::new();
+    for r in records {
+        let day = r.timestamp.div_euclid(SECONDS_PER_DAY);
+        if day < since_day {
+            continue;
+        }
+        let bucket = buckets.entry(day).or_default();
+        bucket.test_count += 1;
+        if r.speedup_percent > 0.0 {
+            bucket.speedup_sum += r.speedup_percent;
+            bucket.speedup_count += 1;
+        }
+        if r.applied {
+            bucket.saved_ms += (r.original_latency - r.optimized_latency).max(0.0);
+        }
+    }
 
-        let removed_count = (original_count - filtered.len()) as u32;
+    buckets
+        .into_iter()
+        .map(|(day, bucket)| DailyHistory {
+            date: chrono::DateTime::from_timestamp(day * SECONDS_PER_DAY, 0)
+                .map(|dt| dt.date_naive().to_string())
+                .unwrap_or_default(),
+            test_count: bucket.test_count,
+            avg_speedup_percent: if bucket.speedup_count > 0 {
+                bucket.speedup_sum / bucket.speedup_count as f64
+            } else {
+                0.0
+            },
+            total_speedup_ms: bucket.saved_ms,
+        })
+        .collect()
+}
 
-        if removed_count > 0 {
-            self.save_records(&filtered)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(timestamp: i64) -> HistoryRecord {
+        HistoryRecord {
+            timestamp,
+            domain: "test.com".into(),
+            original_latency: 200.0,
+            optimized_latency: 100.0,
+            speedup_percent: 50.0,
+            applied: true,
         }
+    }
 
-        Ok(removed_count)
+    #[test]
+    fn test_concurrent_add_records_keeps_all() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.json");
+        let now = HistoryManager::now_timestamp();
+
+        // 各线程使用独立的 HistoryManager，模拟不同的记录来源同时写入
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let manager = HistoryManager::with_path(path);
+                    for batch in 0..5 {
+                        manager
+                            .add_records(vec![
+                                record(now - thread * 100 - batch * 2),
+                                record(now - thread * 100 - batch * 2 - 1),
+                            ])
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let manager = HistoryManager::with_path(path.clone());
+        assert_eq!(manager.load_records().unwrap().len(), 8 * 5 * 2);
+        assert!(path.exists());
+        assert!(!dir.path().join("history.jsonl.tmp").exists());
     }
 
-    /// 清空所有历史记录
-    pub fn clear_all(&self) -> Result<(), HistoryError> {
-        self.save_records(&[])
+    fn line_count(path: &std::path::Path) -> usize {
+        fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn test_add_records_appends_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let manager = HistoryManager::with_path(path.clone());
+        let now = HistoryManager::now_timestamp();
+
+        manager.add_records(vec![record(now - 2)]).unwrap();
+        let first = fs::read_to_string(&path).unwrap();
+        manager
+            .add_records(vec![record(now - 1), record(now)])
+            .unwrap();
+
+        // 已有内容原样保留，新记录逐行追加在末尾
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(&first));
+        assert_eq!(line_count(&path), 3);
+        let records = manager.load_records().unwrap();
+        let timestamps: Vec<i64> = records.iter().map(|r| r.timestamp).collect();
+        assert_eq!(timestamps, [now - 2, now - 1, now]);
+    }
+
+    #[test]
+    fn test_read_skips_corrupted_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let now = HistoryManager::now_timestamp();
+        let line = serde_json::to_string(&record(now)).unwrap();
+        // 中间一行是崩溃留下的半行
+        fs::write(&path, format!("{}\n{{\"timestamp\": 1\n\n{}\n", line, line)).unwrap();
+
+        let manager = HistoryManager::with_path(path);
+        assert_eq!(manager.load_records().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_append_after_truncated_line_starts_new_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let manager = HistoryManager::with_path(path.clone());
+        let now = HistoryManager::now_timestamp();
+        manager.add_record(record(now - 1)).unwrap();
+        // 模拟崩溃：末行只写了一半，没有换行
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"timestamp\": 1").unwrap();
+        drop(file);
+
+        manager.add_record(record(now)).unwrap();
+        let timestamps: Vec<i64> = manager
+            .load_records()
+            .unwrap()
+            .iter()
+            .map(|r| r.timestamp)
+            .collect();
+        assert_eq!(timestamps, [now - 1, now]);
+    }
+
+    #[test]
+    fn test_compaction_drops_expired_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let manager = HistoryManager::with_path(path.clone());
+        let now = HistoryManager::now_timestamp();
+        let day = 24 * 60 * 60;
+
+        // 刚压缩过：过期记录先追加在文件中，但不参与统计
+        manager.last_compaction.store(now, Ordering::Relaxed);
+        manager
+            .add_records(vec![record(now - 30 * day), record(now)])
+            .unwrap();
+        assert_eq!(line_count(&path), 2);
+        assert_eq!(manager.get_stats(0, 10).unwrap().total_tests, 1);
+
+        // 到了压缩时间：下次追加时整体重写，过期行被丢弃
+        manager
+            .last_compaction
+            .store(now - COMPACTION_INTERVAL_SECS, Ordering::Relaxed);
+        manager.add_record(record(now - 1)).unwrap();
+        assert_eq!(line_count(&path), 2);
+        assert!(!dir.path().join("history.jsonl.tmp").exists());
+    }
+
+    #[test]
+    fn test_migrates_legacy_json_array() {
+        let dir = TempDir::new().unwrap();
+        let legacy = dir.path().join("history.json");
+        let now = HistoryManager::now_timestamp();
+        fs::write(
+            &legacy,
+            serde_json::to_string_pretty(&vec![record(now - 60), record(now - 30)]).unwrap(),
+        )
+        .unwrap();
+
+        let path = dir.path().join("history.jsonl");
+        let manager = HistoryManager::with_path(path.clone());
+        // 迁移前即可读取旧数据
+        assert_eq!(manager.load_records().unwrap().len(), 2);
+
+        manager.add_record(record(now)).unwrap();
+        assert!(!legacy.exists());
+        assert_eq!(line_count(&path), 3);
+        assert_eq!(manager.load_records().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_get_info_reports_counts_and_bounds() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.json");
+        let manager = HistoryManager::with_path(path.clone());
+        assert_eq!(manager.get_info().unwrap(), HistoryInfo::default());
+
+        let now = HistoryManager::now_timestamp();
+        // 乱序写入，边界仍取最早与最新
+        manager
+            .add_records(vec![record(now - 60), record(now), record(now - 3600)])
+            .unwrap();
+
+        let info = manager.get_info().unwrap();
+        assert_eq!(info.record_count, 3);
+        assert_eq!(info.file_size_bytes, fs::metadata(&path).unwrap().len());
+        assert!(info.file_size_bytes > 0);
+        assert_eq!(info.oldest_timestamp, Some(now - 3600));
+        assert_eq!(info.newest_timestamp, Some(now));
+    }
+
+    #[test]
+    fn test_get_stats_limit_only_caps_records() {
+        let dir = TempDir::new().unwrap();
+        let manager = HistoryManager::with_path(dir.path().join("history.json"));
+        let now = HistoryManager::now_timestamp();
+        manager
+            .add_records((0..5).map(|i| record(now - i * 60)).collect())
+            .unwrap();
+
+        let stats = manager.get_stats(0, 2).unwrap();
+        assert_eq!(stats.total_tests, 5);
+        assert_eq!(stats.records.len(), 2);
+        // 按时间倒序返回最近的记录
+        assert_eq!(stats.records[0].timestamp, now);
+
+        let stats = manager.get_stats(0, DEFAULT_STATS_RECORD_LIMIT).unwrap();
+        assert_eq!(stats.records.len(), 5);
+    }
+
+    #[test]
+    fn test_get_domain_history_isolates_domains() {
+        let dir = TempDir::new().unwrap();
+        let manager = HistoryManager::with_path(dir.path().join("history.json"));
+        let now = HistoryManager::now_timestamp();
+        let at = |domain: &str, timestamp: i64, latency: f64| HistoryRecord {
+            domain: domain.into(),
+            optimized_latency: latency,
+            ..record(timestamp)
+        };
+        manager
+            .add_records(vec![
+                at("a.com", now - 60, 80.0),
+                at("b.com", now - 120, 500.0),
+                at("a.com", now - 1800, 120.0),
+                at("a.com", now - 3 * 3600, 40.0),
+            ])
+            .unwrap();
+
+        let history = manager.get_domain_history("a.com", 1).unwrap();
+        let timestamps: Vec<i64> = history.records.iter().map(|r| r.timestamp).collect();
+        assert_eq!(timestamps, [now - 1800, now - 60]);
+        assert_eq!(history.min_latency, 80.0);
+        assert_eq!(history.max_latency, 120.0);
+        assert_eq!(history.avg_latency, 100.0);
+
+        let all = manager.get_domain_history("a.com", 0).unwrap();
+        assert_eq!(all.records.len(), 3);
+        assert_eq!(all.min_latency, 40.0);
+
+        let empty = manager.get_domain_history("c.com", 0).unwrap();
+        assert!(empty.records.is_empty());
+        assert_eq!(empty.avg_latency, 0.0);
+    }
+
+    #[test]
+    fn test_bucket_daily_splits_at_utc_midnight() {
+        // 2024-03-01 00:00:00 UTC
+        let midnight = 1_709_251_200;
+        let records = vec![
+            record(midnight - 1),
+            HistoryRecord {
+                speedup_percent: 20.0,
+                original_latency: 150.0,
+                ..record(midnight)
+            },
+            record(midnight + SECONDS_PER_DAY - 1),
+            HistoryRecord {
+                speedup_percent: -5.0,
+                applied: false,
+                ..record(midnight + 3600)
+            },
+        ];
+
+        let daily = bucket_daily(&records, i64::MIN);
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].date, "2024-02-29");
+        assert_eq!(daily[0].test_count, 1);
+        assert_eq!(daily[0].total_speedup_ms, 100.0);
+        assert_eq!(daily[1].date, "2024-03-01");
+        assert_eq!(daily[1].test_count, 3);
+        // 负向加速不计入平均值，未应用的记录不计入节省时间
+        assert_eq!(daily[1].avg_speedup_percent, 35.0);
+        assert_eq!(daily[1].total_speedup_ms, 150.0);
+
+        // 起始日之前的记录被排除
+        let since = bucket_daily(&records, midnight / SECONDS_PER_DAY);
+        assert_eq!(since.len(), 1);
+        assert_eq!(since[0].date, "2024-03-01");
+    }
+
+    #[test]
+    fn test_retention_days_boundary() {
+        let dir = TempDir::new().unwrap();
+        let manager = HistoryManager::with_path(dir.path().join("history.json"));
+        manager.set_retention_days(30);
+        let now = HistoryManager::now_timestamp();
+        let day = 24 * 60 * 60;
+
+        manager
+            .add_records(vec![
+                record(now - 29 * day),
+                record(now - 30 * day + 60),
+                record(now - 30 * day - 60),
+            ])
+            .unwrap();
+        assert_eq!(manager.get_stats(0, 10).unwrap().total_tests, 2);
+
+        // 缩短保留期后清理
+        manager.set_retention_days(1);
+        manager.add_record(record(now - 60)).unwrap();
+        assert_eq!(manager.get_stats(0, 10).unwrap().total_tests, 1);
+
+        // 0 天按最小 1 天处理，不会清空当天记录
+        manager.set_retention_days(0);
+        assert_eq!(manager.clear_old().unwrap(), 0);
+        assert_eq!(manager.get_stats(0, 10).unwrap().total_tests, 1);
     }
 }
@@ -4,14 +4,16 @@
 //! - Block-based management with BEGIN/END markers
 //! - Atomic file writes using temp file + fsync + rename
 //! - Exclusive file locking for concurrent access safety
-//! - UTF-8 BOM handling
+//! - UTF-8 BOM handling, refusing UTF-16 / non-UTF-8 files instead of lossy rewrites
 
 use fs2::FileExt;
 use std::collections::HashSet;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read as IoRead, Seek, Write};
 use std::net::IpAddr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
 use thiserror::Error;
 
 #[cfg(windows)]
@@ -20,10 +22,83 @@ const HOSTS_PATH: &str = r"C:\Windows\System32\drivers\etc\hosts";
 #[cfg(not(windows))]
 const HOSTS_PATH: &str = "/etc/hosts";
 
-// Block markers for identifying anyFAST-managed entries
-const MARKER_BEGIN: &str = "# BEGIN anyFAST";
-const MARKER_END: &str = "# END anyFAST";
-const MARKER_LINE: &str = "# anyFAST";
+/// Default marker name (`# BEGIN anyFAST` / `# END anyFAST` / `# anyFAST`)
+const DEFAULT_MARKER_NAME: &str = "anyFAST";
+
+/// Environment variable overriding the marker name, so forks or side-by-side
+/// variants don't clobber each other's blocks. The GUI sends its marker name
+/// with every Service request; the macOS helper inherits the GUI's environment.
+pub const HOSTS_MARKER_ENV: &str = "ANYFAST_HOSTS_MARKER";
+
+/// Marker strings identifying the managed block and its entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostsConfig {
+    pub begin: String,
+    pub end: String,
+    pub line: String,
+}
+
+impl HostsConfig {
+    /// Build `# BEGIN <name>` / `# END <name>` / `# <name>`
+    pub fn from_name(name: &str) -> Self {
+        Self {
+            begin: format!("# BEGIN {}", name),
+            end: format!("# END {}", name),
+            line: format!("# {}", name),
+        }
+    }
+}
+
+impl Default for HostsConfig {
+    fn default() -> Self {
+        Self::from_name(DEFAULT_MARKER_NAME)
+    }
+}
+
+fn is_valid_marker_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+static MARKER_NAME: OnceLock<String> = OnceLock::new();
+
+thread_local! {
+    /// Per-request marker override (the Service applies the marker sent by the GUI)
+    static MARKER_OVERRIDE: std::cell::RefCell<Option<HostsConfig>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Process-wide marker name, read once from HOSTS_MARKER_ENV with fallback to the default
+/// Names must be a single token of alphanumerics, '-', '_' or '.'
+pub fn marker_name() -> &'static str {
+    MARKER_NAME.get_or_init(|| match std::env::var(HOSTS_MARKER_ENV) {
+        Ok(name) if is_valid_marker_name(name.trim()) => name.trim().to_string(),
+        _ => DEFAULT_MARKER_NAME.to_string(),
+    })
+}
+
+/// Run `f` with the given marker name for hosts operations on the current thread
+/// Invalid or missing names keep the process-wide marker (only the Windows Service needs this)
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn with_marker<R>(name: Option<&str>, f: impl FnOnce() -> R) -> R {
+    let config = name
+        .map(str::trim)
+        .filter(|name| is_valid_marker_name(name))
+        .map(HostsConfig::from_name);
+    let previous = MARKER_OVERRIDE.with(|cell| cell.replace(config));
+    let result = f();
+    MARKER_OVERRIDE.with(|cell| *cell.borrow_mut() = previous);
+    result
+}
+
+/// Marker configuration for the current operation
+fn hosts_config() -> HostsConfig {
+    MARKER_OVERRIDE
+        .with(|cell| cell.borrow().clone())
+        .unwrap_or_else(|| HostsConfig::from_name(marker_name()))
+}
 
 #[derive(Error, Debug)]
 pub enum HostsError {
@@ -35,6 +110,68 @@ pub enum HostsError {
     InvalidIp(String),
     #[error("Invalid domain: {0}")]
     InvalidDomain(String),
+    #[error("anyFAST block was modified externally since the last write")]
+    ExternalModification,
+    #[error("hosts file is encoded as {0}; convert it to UTF-8 before anyFAST can modify it")]
+    UnsupportedEncoding(&'static str),
+}
+
+/// Text encoding of the hosts file as found on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostsEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Not valid UTF-8, most likely a legacy codepage such as GBK
+    NonUtf8,
+}
+
+impl HostsEncoding {
+    /// Detect the encoding from the raw file bytes
+    /// UTF-16 without a BOM is recognised by NUL bytes filling one half of each code unit
+    pub fn detect(raw: &[u8]) -> Self {
+        if raw.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return Self::Utf8Bom;
+        }
+        if raw.starts_with(&[0xFF, 0xFE]) {
+            return Self::Utf16Le;
+        }
+        if raw.starts_with(&[0xFE, 0xFF]) {
+            return Self::Utf16Be;
+        }
+        if raw.len() >= 4 {
+            let units = raw.len() / 2;
+            let zero_even = raw.iter().step_by(2).filter(|b| **b == 0).count();
+            let zero_odd = raw.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+            if zero_odd * 2 > units && zero_even * 10 < units {
+                return Self::Utf16Le;
+            }
+            if zero_even * 2 > units && zero_odd * 10 < units {
+                return Self::Utf16Be;
+            }
+        }
+        if std::str::from_utf8(raw).is_ok() {
+            Self::Utf8
+        } else {
+            Self::NonUtf8
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf8Bom => "UTF-8 with BOM",
+            Self::Utf16Le => "UTF-16 LE",
+            Self::Utf16Be => "UTF-16 BE",
+            Self::NonUtf8 => "a non-UTF-8 codepage",
+        }
+    }
+
+    /// Whether anyFAST can read and rewrite the file without corrupting it
+    pub fn is_supported(self) -> bool {
+        matches!(self, Self::Utf8 | Self::Utf8Bom)
+    }
 }
 
 /// Validate IP address
@@ -44,8 +181,21 @@ fn validate_ip(ip: &str) -> Result<(), HostsError> {
     Ok(())
 }
 
+/// Canonical text form of a validated IP (IPv6 compressed and lowercased),
+/// so the same address always renders and compares identically
+fn canonical_ip(ip: &str) -> String {
+    ip.parse::<IpAddr>()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|_| ip.to_string())
+}
+
+/// Whether an IP is IPv6 (AAAA); bindings of the two families are managed independently
+pub fn is_ipv6(ip: &str) -> bool {
+    matches!(ip.parse::<IpAddr>(), Ok(IpAddr::V6(_)))
+}
+
 /// Validate domain name (no whitespace, control chars, or newlines)
-fn validate_domain(domain: &str) -> Result<(), HostsError> {
+pub fn validate_domain(domain: &str) -> Result<(), HostsError> {
     if domain.is_empty() {
         return Err(HostsError::InvalidDomain("empty domain".to_string()));
     }
@@ -70,26 +220,94 @@ fn validate_domain(domain: &str) -> Result<(), HostsError> {
 }
 
 /// Binding entry for batch operations
+/// Several entries with the same domain bind that domain to multiple IPs (round-robin)
 pub struct HostsBinding {
     pub domain: String,
     pub ip: String,
 }
 
+impl HostsBinding {
+    /// Validate the IP and domain the same way a write would
+    pub fn validate(&self) -> Result<(), HostsError> {
+        validate_ip(&self.ip)?;
+        validate_domain(&self.domain)
+    }
+}
+
 /// Internal structure to hold parsed hosts file content
 struct ParsedHosts {
     /// Lines before the anyFAST block
     before_block: Vec<String>,
     /// Lines after the anyFAST block
     after_block: Vec<String>,
-    /// Current anyFAST bindings (domain -> ip)
-    anyrouter_bindings: std::collections::HashMap<String, String>,
+    /// Current anyFAST bindings (domain -> ips, in preference order)
+    anyrouter_bindings: std::collections::HashMap<String, Vec<String>>,
+    /// Suspended bindings: kept commented out inside the block, ignored by lookups
+    suspended_bindings: std::collections::HashMap<String, Vec<String>>,
+    /// Markers used to parse and render the block
+    markers: HostsConfig,
+}
+
+/// Whether a line carries the line marker as a whole token
+/// ("# anyFAST" must not match "# anyFAST-pro" from another variant)
+fn has_line_marker(line: &str, marker: &str) -> bool {
+    line.match_indices(marker).any(|(i, m)| {
+        line[i + m.len()..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+    })
+}
+
+/// Parse a suspended binding line ("#1.2.3.4\tdomain\t# anyFAST") into (ip, domain)
+/// Only commented-out lines that still carry the line marker and a valid IP qualify,
+/// so ordinary comments inside the block are never mistaken for bindings
+fn parse_suspended_line<'a>(trimmed: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let rest = trimmed.strip_prefix('#')?.trim_start();
+    if !has_line_marker(rest, marker) {
+        return None;
+    }
+    let mut parts = rest.split_whitespace();
+    let ip = parts.next()?;
+    let domain = parts.next()?;
+    ip.parse::<IpAddr>().ok()?;
+    Some((ip, domain))
+}
+
+/// Hostnames of an active (uncommented) hosts entry, None for comments and non-entries
+/// Trailing "# ..." comments on the line are not hostnames
+fn entry_hostnames(trimmed: &str) -> Option<Vec<&str>> {
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let mut parts = trimmed.split_whitespace();
+    parts.next()?.parse::<IpAddr>().ok()?;
+    let hostnames: Vec<&str> = parts.take_while(|part| !part.starts_with('#')).collect();
+    (!hostnames.is_empty()).then_some(hostnames)
+}
+
+/// Add an ip to a domain's binding list, ignoring duplicates
+fn push_binding(
+    bindings: &mut std::collections::HashMap<String, Vec<String>>,
+    domain: &str,
+    ip: &str,
+) {
+    let ips = bindings.entry(domain.to_string()).or_default();
+    if !ips.iter().any(|existing| existing == ip) {
+        ips.push(ip.to_string());
+    }
 }
 
 impl ParsedHosts {
     fn parse(content: &str) -> Self {
+        Self::parse_with(content, hosts_config())
+    }
+
+    fn parse_with(content: &str, markers: HostsConfig) -> Self {
         let mut before_block = Vec::new();
         let mut after_block = Vec::new();
         let mut anyrouter_bindings = std::collections::HashMap::new();
+        let mut suspended_bindings = std::collections::HashMap::new();
 
         let mut in_block = false;
         let mut found_block = false;
@@ -99,23 +317,25 @@ impl ParsedHosts {
         for line in content.lines() {
             let trimmed = line.trim();
 
-            if trimmed == MARKER_BEGIN {
+            if trimmed == markers.begin {
                 in_block = true;
                 found_block = true;
                 continue;
             }
 
-            if trimmed == MARKER_END {
+            if trimmed == markers.end {
                 in_block = false;
                 continue;
             }
 
             if in_block {
                 // Parse binding inside the block
-                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                if let Some((ip, domain)) = parse_suspended_line(trimmed, &markers.line) {
+                    push_binding(&mut suspended_bindings, domain, ip);
+                } else if !trimmed.is_empty() && !trimmed.starts_with('#') {
                     let parts: Vec<&str> = trimmed.split_whitespace().collect();
                     if parts.len() >= 2 {
-                        anyrouter_bindings.insert(parts[1].to_string(), parts[0].to_string());
+                        push_binding(&mut anyrouter_bindings, parts[1], parts[0]);
                     }
                 }
                 // Track raw lines in case block is unclosed
@@ -124,11 +344,15 @@ impl ParsedHosts {
                 after_block.push(line.to_string());
             } else {
                 // Also check for legacy line-level markers (for backward compatibility)
-                if trimmed.contains(MARKER_LINE) && !trimmed.is_empty() && !trimmed.starts_with('#')
+                // Only the configured line marker counts, so a variant with custom markers
+                // never claims another variant's entries
+                if has_line_marker(trimmed, &markers.line)
+                    && !trimmed.is_empty()
+                    && !trimmed.starts_with('#')
                 {
                     let parts: Vec<&str> = trimmed.split_whitespace().collect();
                     if parts.len() >= 2 {
-                        anyrouter_bindings.insert(parts[1].to_string(), parts[0].to_string());
+                        push_binding(&mut anyrouter_bindings, parts[1], parts[0]);
                     }
                 } else {
                     before_block.push(line.to_string());
@@ -144,6 +368,9 @@ impl ParsedHosts {
             for line in unclosed_block_lines {
                 let trimmed = line.trim();
                 // Skip lines that were already parsed as bindings
+                if parse_suspended_line(trimmed, &markers.line).is_some() {
+                    continue;
+                }
                 if trimmed.is_empty() || trimmed.starts_with('#') {
                     after_block.push(line);
                 } else {
@@ -160,30 +387,191 @@ impl ParsedHosts {
             before_block,
             after_block,
             anyrouter_bindings,
+            suspended_bindings,
+            markers,
+        }
+    }
+
+    /// Render the anyFAST block (BEGIN..END) with bindings sorted by domain
+    fn block_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.markers.begin.clone()];
+
+        // Sort bindings by domain for consistent output
+        let mut sorted_bindings: Vec<_> = self.anyrouter_bindings.iter().collect();
+        sorted_bindings.sort_by_key(|(domain, _)| *domain);
+
+        for (domain, ips) in sorted_bindings {
+            for ip in ips {
+                lines.push(format!("{}\t{}\t{}", ip, domain, self.markers.line));
+            }
+        }
+
+        // Suspended bindings follow, commented out
+        let mut sorted_suspended: Vec<_> = self.suspended_bindings.iter().collect();
+        sorted_suspended.sort_by_key(|(domain, _)| *domain);
+        for (domain, ips) in sorted_suspended {
+            for ip in ips {
+                lines.push(format!("#{}\t{}\t{}", ip, domain, self.markers.line));
+            }
+        }
+
+        lines.push(self.markers.end.clone());
+        lines
+    }
+
+    /// Insert or update bindings
+    /// Each domain in the batch has its IPs of the written address families replaced by
+    /// the IPs given for it; bindings of the other family are kept (dual-stack A + AAAA)
+    /// Returns the number of bindings that were added or modified
+    /// (domains whose IP list is already identical are not counted)
+    fn apply_bindings(&mut self, bindings: &[HostsBinding]) -> usize {
+        let mut replaced = std::collections::HashMap::new();
+        for binding in bindings {
+            push_binding(&mut replaced, &binding.domain, &canonical_ip(&binding.ip));
+        }
+        let mut changed = 0;
+        for (domain, ips) in replaced {
+            if self.replace_ips(&domain, ips.clone()) {
+                changed += ips.len();
+            }
+        }
+        changed
+    }
+
+    /// Replace a domain's IPs of the families present in `ips`, keeping the other family
+    /// Returns whether the domain's binding list changed
+    fn replace_ips(&mut self, domain: &str, mut ips: Vec<String>) -> bool {
+        // An explicit write supersedes a suspended binding
+        self.suspended_bindings.remove(domain);
+        let writes_v4 = ips.iter().any(|ip| !is_ipv6(ip));
+        let writes_v6 = ips.iter().any(|ip| is_ipv6(ip));
+        let existing = self.anyrouter_bindings.get(domain);
+        if let Some(existing) = existing {
+            ips.extend(
+                existing
+                    .iter()
+                    .filter(|ip| if is_ipv6(ip) { !writes_v6 } else { !writes_v4 })
+                    .cloned(),
+            );
+        }
+        if existing == Some(&ips) {
+            return false;
+        }
+        self.anyrouter_bindings.insert(domain.to_string(), ips);
+        true
+    }
+
+    /// Comment out a domain's bindings; returns false when the domain has no active binding
+    fn suspend(&mut self, domain: &str) -> bool {
+        match self.anyrouter_bindings.remove(domain) {
+            Some(ips) => {
+                self.suspended_bindings.insert(domain.to_string(), ips);
+                true
+            }
+            None => false,
         }
     }
 
+    /// Restore a suspended binding; returns false when the domain is not suspended
+    /// A binding written while suspended is newer and wins over the suspended one
+    fn resume(&mut self, domain: &str) -> bool {
+        match self.suspended_bindings.remove(domain) {
+            Some(ips) => {
+                self.anyrouter_bindings
+                    .entry(domain.to_string())
+                    .or_insert(ips);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a domain's bindings, active or suspended; returns whether any existed
+    fn remove_domain(&mut self, domain: &str) -> bool {
+        let active = self.anyrouter_bindings.remove(domain).is_some();
+        let suspended = self.suspended_bindings.remove(domain).is_some();
+        active || suspended
+    }
+
+    /// Suffix appended to entries commented out by deconflict ("# anyFAST-deconflicted")
+    fn deconflict_suffix(&self) -> String {
+        format!("{}-deconflicted", self.markers.line)
+    }
+
+    /// Whether a line outside the block is an entry for domains anyFAST has bound
+    /// Only entries whose hostnames are all bound count: commenting out a line such as
+    /// "127.0.0.1 localhost example.com" would also break the other names on it
+    fn is_conflicting(&self, line: &str) -> bool {
+        entry_hostnames(line.trim()).is_some_and(|hostnames| {
+            hostnames.iter().all(|hostname| {
+                self.anyrouter_bindings
+                    .keys()
+                    .any(|domain| domain.eq_ignore_ascii_case(hostname))
+            })
+        })
+    }
+
+    /// Entries outside the block that conflict with anyFAST bindings
+    fn conflicting_lines(&self) -> Vec<String> {
+        self.before_block
+            .iter()
+            .chain(&self.after_block)
+            .filter(|line| self.is_conflicting(line))
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    /// Comment out conflicting entries, keeping the original text so it can be restored
+    /// Returns the number of entries commented out
+    fn deconflict(&mut self) -> usize {
+        let suffix = self.deconflict_suffix();
+        let mut lines = std::mem::take(&mut self.before_block);
+        let mut after = std::mem::take(&mut self.after_block);
+        let mut count = 0;
+        for line in lines.iter_mut().chain(after.iter_mut()) {
+            if self.is_conflicting(line) {
+                *line = format!("# {}\t{}", line.trim(), suffix);
+                count += 1;
+            }
+        }
+        self.before_block = lines;
+        self.after_block = after;
+        count
+    }
+
+    /// Restore every entry commented out by deconflict; returns the number restored
+    fn restore_deconflicted(&mut self) -> usize {
+        let suffix = self.deconflict_suffix();
+        let mut count = 0;
+        for line in self
+            .before_block
+            .iter_mut()
+            .chain(self.after_block.iter_mut())
+        {
+            let original = line
+                .trim()
+                .strip_prefix('#')
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .map(|rest| rest.trim().to_string());
+            if let Some(original) = original.filter(|o| entry_hostnames(o).is_some()) {
+                *line = original;
+                count += 1;
+            }
+        }
+        count
+    }
+
     fn render(&self) -> String {
         let mut lines = self.before_block.clone();
 
         // Add anyFAST block if there are bindings
-        if !self.anyrouter_bindings.is_empty() {
+        if !self.anyrouter_bindings.is_empty() || !self.suspended_bindings.is_empty() {
             // Ensure there's a blank line before the block
             if !lines.is_empty() && !lines.last().map(|l| l.is_empty()).unwrap_or(true) {
                 lines.push(String::new());
             }
 
-            lines.push(MARKER_BEGIN.to_string());
-
-            // Sort bindings by domain for consistent output
-            let mut sorted_bindings: Vec<_> = self.anyrouter_bindings.iter().collect();
-            sorted_bindings.sort_by_key(|(domain, _)| *domain);
-
-            for (domain, ip) in sorted_bindings {
-                lines.push(format!("{}\t{}\t{}", ip, domain, MARKER_LINE));
-            }
-
-            lines.push(MARKER_END.to_string());
+            lines.extend(self.block_lines());
         }
 
         // Add lines after the block
@@ -194,18 +582,86 @@ impl ParsedHosts {
     }
 }
 
+/// Hash of the rendered anyFAST block (formatting-insensitive, order-stable)
+fn block_hash(parsed: &ParsedHosts) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    parsed.block_lines().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Remembers the anyFAST block written last, to detect edits made outside anyFAST
+/// before the next write overwrites them
+pub struct BlockHashTracker {
+    last: Mutex<Option<u64>>,
+}
+
+impl BlockHashTracker {
+    pub const fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Record the block currently on disk (call after a successful write)
+    pub fn record(&self) {
+        self.record_from_path(Path::new(HOSTS_PATH));
+    }
+
+    /// Fail with ExternalModification if the on-disk block differs from the last recorded one
+    /// Passes when nothing has been recorded yet in this session
+    pub fn verify(&self) -> Result<(), HostsError> {
+        self.verify_from_path(Path::new(HOSTS_PATH))
+    }
+
+    fn current_hash(path: &Path) -> Option<u64> {
+        let mut file = File::open(path).ok()?;
+        let content = read_hosts_content(&mut file).ok()?;
+        Some(block_hash(&ParsedHosts::parse(&content)))
+    }
+
+    fn record_from_path(&self, path: &Path) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Self::current_hash(path);
+    }
+
+    fn verify_from_path(&self, path: &Path) -> Result<(), HostsError> {
+        let expected = *self.last.lock().unwrap_or_else(|e| e.into_inner());
+        match (expected, Self::current_hash(path)) {
+            (Some(expected), Some(current)) if expected != current => {
+                Err(HostsError::ExternalModification)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Read file content handling UTF-8 BOM
+/// UTF-16 and non-UTF-8 files are rejected: a lossy decode would corrupt them on rewrite
 fn read_hosts_content(file: &mut File) -> Result<String, HostsError> {
     let mut raw_content = Vec::new();
     file.read_to_end(&mut raw_content).map_err(HostsError::Io)?;
+    decode_hosts_content(raw_content)
+}
 
-    let content = if raw_content.starts_with(&[0xEF, 0xBB, 0xBF]) {
-        String::from_utf8_lossy(&raw_content[3..]).to_string()
-    } else {
-        String::from_utf8_lossy(&raw_content).to_string()
-    };
+fn decode_hosts_content(mut raw_content: Vec<u8>) -> Result<String, HostsError> {
+    match HostsEncoding::detect(&raw_content) {
+        HostsEncoding::Utf8Bom => {
+            raw_content.drain(..3);
+        }
+        HostsEncoding::Utf8 => {}
+        unsupported => return Err(HostsError::UnsupportedEncoding(unsupported.label())),
+    }
+    String::from_utf8(raw_content)
+        .map_err(|_| HostsError::UnsupportedEncoding(HostsEncoding::NonUtf8.label()))
+}
+
+/// Per-call suffix for temp files, so concurrent writes in one process never share a path
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
 
-    Ok(content)
+/// Unique temp path next to the target (same directory is required for atomic rename)
+fn unique_temp_path(parent: &Path) -> PathBuf {
+    let seq = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    parent.join(format!(".hosts.tmp.{}.{}", std::process::id(), seq))
 }
 
 /// Atomic write: write to temp file, fsync, then rename.
@@ -216,7 +672,7 @@ fn read_hosts_content(file: &mut File) -> Result<String, HostsError> {
 fn atomic_write(path: &Path, content: &str) -> Result<(), HostsError> {
     // Create temp file in the same directory (required for atomic rename)
     let parent = path.parent().unwrap_or(Path::new("."));
-    let temp_path = parent.join(format!(".hosts.tmp.{}", std::process::id()));
+    let temp_path = unique_temp_path(parent);
 
     // Write to temp file
     {
@@ -260,6 +716,108 @@ fn write_locked(file: &mut File, content: &str) -> Result<(), HostsError> {
     Ok(())
 }
 
+/// Lines of unchanged context shown around a preview diff
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// Line-level LCS diff of two small slices (' ' keep, '-' remove, '+' add)
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<String> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j] = LCS length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            out.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    out.extend(old[i..].iter().map(|line| format!("-{}", line)));
+    out.extend(new[j..].iter().map(|line| format!("+{}", line)));
+    out
+}
+
+/// Unified diff with a single hunk around the changed region (empty when identical)
+///
+/// Only the region between the common prefix and suffix goes through the LCS diff,
+/// so large hosts files (e.g. ad-block lists) stay cheap to preview.
+fn unified_diff(old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    if old_lines == new_lines {
+        return String::new();
+    }
+
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = old_lines.len().min(new_lines.len()) - prefix;
+    let suffix = old_lines
+        .iter()
+        .rev()
+        .zip(new_lines.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_changed_end = old_lines.len() - suffix;
+    let new_changed_end = new_lines.len() - suffix;
+    let start = prefix.saturating_sub(DIFF_CONTEXT_LINES);
+    let old_end = (old_changed_end + DIFF_CONTEXT_LINES).min(old_lines.len());
+    let new_end = (new_changed_end + DIFF_CONTEXT_LINES).min(new_lines.len());
+
+    let mut body: Vec<String> = old_lines[start..prefix]
+        .iter()
+        .map(|line| format!(" {}", line))
+        .collect();
+    body.extend(diff_lines(
+        &old_lines[prefix..old_changed_end],
+        &new_lines[prefix..new_changed_end],
+    ));
+    body.extend(
+        old_lines[old_changed_end..old_end]
+            .iter()
+            .map(|line| format!(" {}", line)),
+    );
+
+    let range = |count: usize| {
+        if count == 0 {
+            format!("{},0", start)
+        } else {
+            format!("{},{}", start + 1, count)
+        }
+    };
+    let mut diff = format!(
+        "--- hosts\n+++ hosts (preview)\n@@ -{} +{} @@\n",
+        range(old_end - start),
+        range(new_end - start)
+    );
+    for line in body {
+        diff.push_str(&line);
+        diff.push('\n');
+    }
+    diff
+}
+
 pub struct HostsManager;
 
 impl HostsManager {
@@ -273,8 +831,12 @@ impl HostsManager {
         let content = fs::read_to_string(path).ok()?;
         let parsed = ParsedHosts::parse(&content);
 
-        // First check anyFAST bindings
-        if let Some(ip) = parsed.anyrouter_bindings.get(domain) {
+        // First check anyFAST bindings (the first IP of a multi-IP binding)
+        if let Some(ip) = parsed
+            .anyrouter_bindings
+            .get(domain)
+            .and_then(|ips| ips.first())
+        {
             return Some(ip.clone());
         }
 
@@ -294,6 +856,44 @@ impl HostsManager {
         None
     }
 
+    /// Read every IP anyFAST has bound for a domain, in preference order
+    pub fn read_binding_ips(domain: &str) -> Vec<String> {
+        Self::read_binding_ips_from_path(Path::new(HOSTS_PATH), domain)
+    }
+
+    /// Internal: read bound IPs from custom path (for testing)
+    fn read_binding_ips_from_path(path: &Path, domain: &str) -> Vec<String> {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        ParsedHosts::parse(&content)
+            .anyrouter_bindings
+            .remove(domain)
+            .unwrap_or_default()
+    }
+
+    /// Read all anyFAST-managed bindings in a single pass, sorted by domain
+    /// A domain bound to several IPs yields one pair per IP
+    pub fn read_all_anyfast_bindings() -> Vec<(String, String)> {
+        Self::read_all_anyfast_bindings_from_path(Path::new(HOSTS_PATH))
+    }
+
+    /// Internal: read all anyFAST bindings from custom path (for testing)
+    fn read_all_anyfast_bindings_from_path(path: &Path) -> Vec<(String, String)> {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        let mut domains: Vec<(String, Vec<String>)> = ParsedHosts::parse(&content)
+            .anyrouter_bindings
+            .into_iter()
+            .collect();
+        domains.sort_by(|(a, _), (b, _)| a.cmp(b));
+        domains
+            .into_iter()
+            .flat_map(|(domain, ips)| ips.into_iter().map(move |ip| (domain.clone(), ip)))
+            .collect()
+    }
+
     /// Write or update binding in hosts file
     pub fn write_binding(domain: &str, ip: &str) -> Result<(), HostsError> {
         Self::write_binding_to_path(Path::new(HOSTS_PATH), domain, ip)
@@ -325,10 +925,8 @@ impl HostsManager {
         let content = read_hosts_content(&mut file)?;
         let mut parsed = ParsedHosts::parse(&content);
 
-        // Update or add binding
-        parsed
-            .anyrouter_bindings
-            .insert(domain.to_string(), ip.to_string());
+        // Update or add binding (replaces any multi-IP binding of the same address family)
+        parsed.replace_ips(domain, vec![canonical_ip(ip)]);
 
         // Generate new content
         let new_content = parsed.render();
@@ -381,13 +979,10 @@ impl HostsManager {
         let content = read_hosts_content(&mut file)?;
         let mut parsed = ParsedHosts::parse(&content);
 
-        // Update bindings
-        let mut updated_count = 0;
-        for binding in bindings {
-            parsed
-                .anyrouter_bindings
-                .insert(binding.domain.clone(), binding.ip.clone());
-            updated_count += 1;
+        // Update bindings; leave the file untouched when nothing changed
+        let updated_count = parsed.apply_bindings(bindings);
+        if updated_count == 0 {
+            return Ok(0);
         }
 
         // Generate new content
@@ -400,6 +995,31 @@ impl HostsManager {
         Ok(updated_count)
     }
 
+    /// Preview a batch write as a unified diff without touching the hosts file
+    /// Returns an empty string when nothing would change
+    pub fn preview_bindings_batch(bindings: &[HostsBinding]) -> Result<String, HostsError> {
+        Self::preview_bindings_batch_from_path(Path::new(HOSTS_PATH), bindings)
+    }
+
+    /// Internal: preview batch write against custom path (for testing)
+    fn preview_bindings_batch_from_path(
+        path: &Path,
+        bindings: &[HostsBinding],
+    ) -> Result<String, HostsError> {
+        for binding in bindings {
+            validate_ip(&binding.ip)?;
+            validate_domain(&binding.domain)?;
+        }
+
+        let mut file = File::open(path)?;
+        let content = read_hosts_content(&mut file)?;
+        let mut parsed = ParsedHosts::parse(&content);
+        parsed.apply_bindings(bindings);
+
+        // Same render path as the real write, so the preview matches exactly
+        Ok(unified_diff(&content, &parsed.render()))
+    }
+
     /// Clear binding for a domain
     #[allow(dead_code)]
     pub fn clear_binding(domain: &str) -> Result<(), HostsError> {
@@ -431,7 +1051,7 @@ impl HostsManager {
         let mut parsed = ParsedHosts::parse(&content);
 
         // Remove binding
-        parsed.anyrouter_bindings.remove(domain);
+        parsed.remove_domain(domain);
 
         // Generate new content
         let new_content = parsed.render();
@@ -478,7 +1098,7 @@ impl HostsManager {
         // Remove bindings and count
         let mut removed_count = 0;
         for domain in &domains_set {
-            if parsed.anyrouter_bindings.remove(*domain).is_some() {
+            if parsed.remove_domain(domain) {
                 removed_count += 1;
             }
         }
@@ -520,9 +1140,15 @@ impl HostsManager {
         let content = read_hosts_content(&mut file)?;
         let mut parsed = ParsedHosts::parse(&content);
 
-        // Count and clear all bindings
-        let removed_count = parsed.anyrouter_bindings.len();
+        // Count and clear all bindings (suspended ones included)
+        let removed_count = parsed
+            .anyrouter_bindings
+            .keys()
+            .chain(parsed.suspended_bindings.keys())
+            .collect::<HashSet<_>>()
+            .len();
         parsed.anyrouter_bindings.clear();
+        parsed.suspended_bindings.clear();
 
         // Generate new content (will not include anyFAST block since bindings is empty)
         let new_content = parsed.render();
@@ -533,6 +1159,197 @@ impl HostsManager {
         Ok(removed_count)
     }
 
+    /// Temporarily disable a domain's binding by commenting it out inside the block
+    /// Returns false when the domain has no active anyFAST binding
+    pub fn suspend_binding(domain: &str) -> Result<bool, HostsError> {
+        Self::set_suspended_in_path(Path::new(HOSTS_PATH), domain, true)
+    }
+
+    /// Re-enable a binding previously disabled by suspend_binding
+    /// Returns false when the domain is not suspended
+    pub fn resume_binding(domain: &str) -> Result<bool, HostsError> {
+        Self::set_suspended_in_path(Path::new(HOSTS_PATH), domain, false)
+    }
+
+    /// Internal: suspend or resume a binding in custom path (for testing)
+    fn set_suspended_in_path(path: &Path, domain: &str, suspend: bool) -> Result<bool, HostsError> {
+        validate_domain(domain)?;
+
+        // Open file with exclusive lock for atomic read-modify-write
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    HostsError::PermissionDenied
+                } else {
+                    HostsError::Io(e)
+                }
+            })?;
+
+        // Acquire exclusive lock (blocks until available)
+        file.lock_exclusive().map_err(HostsError::Io)?;
+
+        let content = read_hosts_content(&mut file)?;
+        let mut parsed = ParsedHosts::parse(&content);
+        let changed = if suspend {
+            parsed.suspend(domain)
+        } else {
+            parsed.resume(domain)
+        };
+        if changed {
+            write_locked(&mut file, &parsed.render())?;
+        }
+        Ok(changed)
+    }
+
+    /// Encoding of the hosts file on disk (unsupported encodings block all writes)
+    pub fn detect_encoding() -> Result<HostsEncoding, HostsError> {
+        Self::detect_encoding_from_path(Path::new(HOSTS_PATH))
+    }
+
+    /// Internal: detect encoding of custom path (for testing)
+    fn detect_encoding_from_path(path: &Path) -> Result<HostsEncoding, HostsError> {
+        Ok(HostsEncoding::detect(&fs::read(path)?))
+    }
+
+    /// Entries outside the anyFAST block that bind domains anyFAST also manages
+    pub fn find_conflicts() -> Vec<String> {
+        Self::find_conflicts_from_path(Path::new(HOSTS_PATH))
+    }
+
+    /// Internal: find conflicting entries in custom path (for testing)
+    fn find_conflicts_from_path(path: &Path) -> Vec<String> {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        ParsedHosts::parse(&content).conflicting_lines()
+    }
+
+    /// Comment out entries outside the block that conflict with anyFAST bindings
+    /// The originals are kept as tagged comments; returns the number commented out
+    pub fn deconflict_bindings() -> Result<usize, HostsError> {
+        Self::set_deconflicted_in_path(Path::new(HOSTS_PATH), true)
+    }
+
+    /// Restore entries previously commented out by deconflict_bindings
+    pub fn restore_deconflicted() -> Result<usize, HostsError> {
+        Self::set_deconflicted_in_path(Path::new(HOSTS_PATH), false)
+    }
+
+    /// Internal: deconflict or restore in custom path (for testing)
+    fn set_deconflicted_in_path(path: &Path, deconflict: bool) -> Result<usize, HostsError> {
+        // Open file with exclusive lock for atomic read-modify-write
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    HostsError::PermissionDenied
+                } else {
+                    HostsError::Io(e)
+                }
+            })?;
+
+        // Acquire exclusive lock (blocks until available)
+        file.lock_exclusive().map_err(HostsError::Io)?;
+
+        let content = read_hosts_content(&mut file)?;
+        let mut parsed = ParsedHosts::parse(&content);
+        let changed = if deconflict {
+            parsed.deconflict()
+        } else {
+            parsed.restore_deconflicted()
+        };
+        if changed > 0 {
+            write_locked(&mut file, &parsed.render())?;
+        }
+        Ok(changed)
+    }
+
+    /// Domains whose bindings are currently suspended, sorted
+    pub fn read_suspended_domains() -> Vec<String> {
+        Self::read_suspended_domains_from_path(Path::new(HOSTS_PATH))
+    }
+
+    /// Internal: read suspended domains from custom path (for testing)
+    fn read_suspended_domains_from_path(path: &Path) -> Vec<String> {
+        let Ok(content) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        let mut domains: Vec<String> = ParsedHosts::parse(&content)
+            .suspended_bindings
+            .into_keys()
+            .collect();
+        domains.sort();
+        domains
+    }
+
+    /// Export the current anyFAST block to a separate file (hosts file untouched)
+    /// Returns the number of exported bindings
+    pub fn export_block(dest: &Path) -> Result<usize, HostsError> {
+        Self::export_block_from_path(Path::new(HOSTS_PATH), dest)
+    }
+
+    /// Internal: export anyFAST block from custom path (for testing)
+    fn export_block_from_path(path: &Path, dest: &Path) -> Result<usize, HostsError> {
+        let (block, count) = Self::read_block_from_path(path)?;
+        fs::write(dest, block)?;
+        Ok(count)
+    }
+
+    /// Current anyFAST block as text (BEGIN..END markers included), hosts file untouched
+    pub fn read_block() -> Result<String, HostsError> {
+        Self::read_block_from_path(Path::new(HOSTS_PATH)).map(|(block, _)| block)
+    }
+
+    /// Internal: render the block from custom path, with the number of bound domains
+    fn read_block_from_path(path: &Path) -> Result<(String, usize), HostsError> {
+        let mut file = File::open(path)?;
+        let content = read_hosts_content(&mut file)?;
+        let parsed = ParsedHosts::parse(&content);
+
+        let mut block = parsed.block_lines().join("\n");
+        block.push('\n');
+        Ok((block, parsed.anyrouter_bindings.len()))
+    }
+
+    /// Parse a hosts-format snippet (e.g. an exported block) into bindings
+    /// Blank lines, comments and markers are ignored; a line may list several hostnames.
+    /// Returns the valid bindings and the number of rejected entries
+    pub fn parse_snippet(content: &str) -> (Vec<HostsBinding>, usize) {
+        let mut bindings = Vec::new();
+        let mut rejected = 0;
+
+        for line in content.lines() {
+            let entry = line.split('#').next().unwrap_or_default().trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let mut parts = entry.split_whitespace();
+            let ip = parts.next().unwrap_or_default();
+            let domains: Vec<&str> = parts.collect();
+            if domains.is_empty() || validate_ip(ip).is_err() {
+                rejected += 1;
+                continue;
+            }
+            for domain in domains {
+                if validate_domain(domain).is_ok() {
+                    bindings.push(HostsBinding {
+                        domain: domain.to_string(),
+                        ip: ip.to_string(),
+                    });
+                } else {
+                    rejected += 1;
+                }
+            }
+        }
+
+        (bindings, rejected)
+    }
+
     /// Flush DNS cache
     pub fn flush_dns() -> Result<(), HostsError> {
         #[cfg(windows)]
@@ -592,6 +1409,54 @@ impl TestableHostsManager {
     pub fn clear_bindings_batch(&self, domains: &[&str]) -> Result<usize, HostsError> {
         HostsManager::clear_bindings_batch_from_path(&self.path, domains)
     }
+
+    pub fn read_binding_ips(&self, domain: &str) -> Vec<String> {
+        HostsManager::read_binding_ips_from_path(&self.path, domain)
+    }
+
+    pub fn read_all_anyfast_bindings(&self) -> Vec<(String, String)> {
+        HostsManager::read_all_anyfast_bindings_from_path(&self.path)
+    }
+
+    pub fn export_block(&self, dest: &Path) -> Result<usize, HostsError> {
+        HostsManager::export_block_from_path(&self.path, dest)
+    }
+
+    pub fn read_block(&self) -> Result<String, HostsError> {
+        HostsManager::read_block_from_path(&self.path).map(|(block, _)| block)
+    }
+
+    pub fn preview_bindings_batch(&self, bindings: &[HostsBinding]) -> Result<String, HostsError> {
+        HostsManager::preview_bindings_batch_from_path(&self.path, bindings)
+    }
+
+    pub fn suspend_binding(&self, domain: &str) -> Result<bool, HostsError> {
+        HostsManager::set_suspended_in_path(&self.path, domain, true)
+    }
+
+    pub fn resume_binding(&self, domain: &str) -> Result<bool, HostsError> {
+        HostsManager::set_suspended_in_path(&self.path, domain, false)
+    }
+
+    pub fn read_suspended_domains(&self) -> Vec<String> {
+        HostsManager::read_suspended_domains_from_path(&self.path)
+    }
+
+    pub fn detect_encoding(&self) -> Result<HostsEncoding, HostsError> {
+        HostsManager::detect_encoding_from_path(&self.path)
+    }
+
+    pub fn find_conflicts(&self) -> Vec<String> {
+        HostsManager::find_conflicts_from_path(&self.path)
+    }
+
+    pub fn deconflict_bindings(&self) -> Result<usize, HostsError> {
+        HostsManager::set_deconflicted_in_path(&self.path, true)
+    }
+
+    pub fn restore_deconflicted(&self) -> Result<usize, HostsError> {
+        HostsManager::set_deconflicted_in_path(&self.path, false)
+    }
 }
 
 #[cfg(test)]
@@ -599,6 +1464,10 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    const MARKER_BEGIN: &str = "# BEGIN anyFAST";
+    const MARKER_END: &str = "# END anyFAST";
+    const MARKER_LINE: &str = "# anyFAST";
+
     fn create_hosts_file(dir: &TempDir, content: &str) -> std::path::PathBuf {
         let path = dir.path().join("hosts");
         fs::write(&path, content).unwrap();
@@ -707,6 +1576,44 @@ mod tests {
         assert!(result.contains(MARKER_END));
     }
 
+    #[test]
+    fn test_write_bindings_batch_counts_only_changes() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(&dir, "127.0.0.1\tlocalhost");
+        let manager = TestableHostsManager::new(path.clone());
+
+        let bindings = vec![
+            HostsBinding {
+                domain: "test1.com".into(),
+                ip: "1.1.1.1".into(),
+            },
+            HostsBinding {
+                domain: "test2.com".into(),
+                ip: "2.2.2.2".into(),
+            },
+        ];
+        assert_eq!(manager.write_bindings_batch(&bindings).unwrap(), 2);
+        let written = fs::read_to_string(&path).unwrap();
+
+        // Identical bindings: nothing changed, so callers skip the DNS flush
+        assert_eq!(manager.write_bindings_batch(&bindings).unwrap(), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), written);
+
+        // Only the modified domain is counted
+        let modified = vec![
+            HostsBinding {
+                domain: "test1.com".into(),
+                ip: "1.1.1.1".into(),
+            },
+            HostsBinding {
+                domain: "test2.com".into(),
+                ip: "3.3.3.3".into(),
+            },
+        ];
+        assert_eq!(manager.write_bindings_batch(&modified).unwrap(), 1);
+        assert_eq!(manager.read_binding("test2.com"), Some("3.3.3.3".into()));
+    }
+
     #[test]
     fn test_write_bindings_batch_empty() {
         let dir = TempDir::new().unwrap();
@@ -751,6 +1658,126 @@ mod tests {
         assert!(result.contains("localhost"));
     }
 
+    #[test]
+    fn test_parse_domain_with_multiple_ips() {
+        let content = "# BEGIN anyFAST\n1.1.1.1\ttest.com\t# anyFAST\n2.2.2.2\ttest.com\t# anyFAST\n3.3.3.3\ttest.com\t# anyFAST\n1.1.1.1\ttest.com\t# anyFAST\n# END anyFAST";
+        let parsed = ParsedHosts::parse(content);
+
+        // 保持原有顺序并去重
+        assert_eq!(
+            parsed.anyrouter_bindings.get("test.com"),
+            Some(&vec![
+                "1.1.1.1".to_string(),
+                "2.2.2.2".to_string(),
+                "3.3.3.3".to_string()
+            ])
+        );
+        assert_eq!(
+            parsed.block_lines(),
+            vec![
+                MARKER_BEGIN.to_string(),
+                "1.1.1.1\ttest.com\t# anyFAST".to_string(),
+                "2.2.2.2\ttest.com\t# anyFAST".to_string(),
+                "3.3.3.3\ttest.com\t# anyFAST".to_string(),
+                MARKER_END.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_ip_binding_read_and_clear() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(&dir, "127.0.0.1\tlocalhost");
+        let manager = TestableHostsManager::new(path.clone());
+
+        let bindings: Vec<HostsBinding> = ["1.1.1.1", "2.2.2.2", "3.3.3.3"]
+            .iter()
+            .map(|ip| HostsBinding {
+                domain: "test.com".into(),
+                ip: ip.to_string(),
+            })
+            .chain(std::iter::once(HostsBinding {
+                domain: "other.com".into(),
+                ip: "4.4.4.4".into(),
+            }))
+            .collect();
+        assert_eq!(manager.write_bindings_batch(&bindings).unwrap(), 4);
+
+        assert_eq!(manager.read_binding("test.com"), Some("1.1.1.1".into()));
+        assert_eq!(
+            manager.read_binding_ips("test.com"),
+            vec!["1.1.1.1", "2.2.2.2", "3.3.3.3"]
+        );
+        assert_eq!(manager.read_all_anyfast_bindings().len(), 4);
+
+        // 再次批量写入时替换该域名的整个 IP 列表
+        manager
+            .write_bindings_batch(&[HostsBinding {
+                domain: "test.com".into(),
+                ip: "5.5.5.5".into(),
+            }])
+            .unwrap();
+        assert_eq!(manager.read_binding_ips("test.com"), vec!["5.5.5.5"]);
+        manager.write_bindings_batch(&bindings).unwrap();
+
+        // 清除一个域名会删除它的全部行
+        assert_eq!(manager.clear_bindings_batch(&["test.com"]).unwrap(), 1);
+        let result = fs::read_to_string(&path).unwrap();
+        assert!(!result.contains("test.com"));
+        assert!(result.contains("4.4.4.4\tother.com"));
+    }
+
+    #[test]
+    fn test_custom_markers() {
+        let markers = HostsConfig::from_name("myFAST");
+        let content = "127.0.0.1\tlocalhost\n# BEGIN anyFAST\n1.1.1.1\tother.com\t# anyFAST\n# END anyFAST\n# BEGIN myFAST\n2.2.2.2\ttest.com\t# myFAST\n# END myFAST\n3.3.3.3\tlegacy.com\t# myFAST";
+        let mut parsed = ParsedHosts::parse_with(content, markers);
+
+        // 只认自己的区块；另一变体的区块原样保留
+        let mut domains: Vec<_> = parsed.anyrouter_bindings.keys().cloned().collect();
+        domains.sort();
+        assert_eq!(domains, ["test.com"]);
+
+        parsed.apply_bindings(&[HostsBinding {
+            domain: "new.com".into(),
+            ip: "4.4.4.4".into(),
+        }]);
+        let rendered = parsed.render();
+        assert!(rendered.contains("# BEGIN anyFAST\n1.1.1.1\tother.com\t# anyFAST\n# END anyFAST"));
+        assert!(rendered.contains(
+            "# BEGIN myFAST\n4.4.4.4\tnew.com\t# myFAST\n2.2.2.2\ttest.com\t# myFAST\n# END myFAST"
+        ));
+
+        // 清空自己的区块不影响另一变体
+        parsed.anyrouter_bindings.clear();
+        let rendered = parsed.render();
+        assert!(!rendered.contains("myFAST\n"));
+        assert!(rendered.contains("1.1.1.1\tother.com\t# anyFAST"));
+
+        // 默认配置不会把 "# anyFAST-pro" 行当作旧版行级标记
+        let parsed = ParsedHosts::parse_with(
+            "5.5.5.5\tpro.com\t# anyFAST-pro\n6.6.6.6\told.com\t# anyFAST",
+            HostsConfig::default(),
+        );
+        assert!(!parsed.anyrouter_bindings.contains_key("pro.com"));
+        assert!(parsed.anyrouter_bindings.contains_key("old.com"));
+    }
+
+    #[test]
+    fn test_with_marker_overrides_current_thread() {
+        let content = "# BEGIN anyFAST\n1.1.1.1\tother.com\t# anyFAST\n# END anyFAST\n# BEGIN myFAST\n2.2.2.2\ttest.com\t# myFAST\n# END myFAST";
+
+        let parsed = with_marker(Some("myFAST"), || ParsedHosts::parse(content));
+        assert!(parsed.anyrouter_bindings.contains_key("test.com"));
+        assert!(!parsed.anyrouter_bindings.contains_key("other.com"));
+
+        // 非法名称沿用进程默认标记，且覆盖只在闭包内生效
+        let parsed = with_marker(Some("bad name"), || ParsedHosts::parse(content));
+        assert!(parsed.anyrouter_bindings.contains_key("other.com"));
+        let parsed = ParsedHosts::parse(content);
+        assert!(parsed.anyrouter_bindings.contains_key("other.com"));
+    }
+
     #[test]
     fn test_clear_bindings_batch_empty() {
         let dir = TempDir::new().unwrap();
@@ -762,6 +1789,66 @@ mod tests {
         assert_eq!(count, 0);
     }
 
+    #[test]
+    fn test_ipv6_and_dual_stack_bindings() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(&dir, "127.0.0.1\tlocalhost\n::1\tlocalhost");
+        let manager = TestableHostsManager::new(path.clone());
+
+        // IPv6 binding is stored in canonical form and reads back
+        manager
+            .write_binding("v6.example.com", "2606:4700:0000::0001")
+            .unwrap();
+        assert_eq!(
+            manager.read_binding("v6.example.com"),
+            Some("2606:4700::1".to_string())
+        );
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("2606:4700::1\tv6.example.com\t# anyFAST"));
+        assert!(content.contains("::1\tlocalhost"));
+
+        // A and AAAA bindings for one domain coexist
+        manager.write_binding("v6.example.com", "1.2.3.4").unwrap();
+        assert_eq!(
+            manager.read_binding_ips("v6.example.com"),
+            vec!["1.2.3.4".to_string(), "2606:4700::1".to_string()]
+        );
+
+        // Rewriting one family only replaces that family
+        let changed = manager
+            .write_bindings_batch(&[HostsBinding {
+                domain: "v6.example.com".into(),
+                ip: "2606:4700::2".into(),
+            }])
+            .unwrap();
+        assert_eq!(changed, 1);
+        assert_eq!(
+            manager.read_binding_ips("v6.example.com"),
+            vec!["2606:4700::2".to_string(), "1.2.3.4".to_string()]
+        );
+
+        // A dual-stack batch replaces both, and is a no-op when repeated
+        let pair = [
+            HostsBinding {
+                domain: "v6.example.com".into(),
+                ip: "5.6.7.8".into(),
+            },
+            HostsBinding {
+                domain: "v6.example.com".into(),
+                ip: "2606:4700::3".into(),
+            },
+        ];
+        assert_eq!(manager.write_bindings_batch(&pair).unwrap(), 2);
+        assert_eq!(manager.write_bindings_batch(&pair).unwrap(), 0);
+        assert_eq!(
+            manager.read_binding_ips("v6.example.com"),
+            vec!["5.6.7.8".to_string(), "2606:4700::3".to_string()]
+        );
+
+        manager.clear_binding("v6.example.com").unwrap();
+        assert!(manager.read_binding_ips("v6.example.com").is_empty());
+    }
+
     #[test]
     fn test_bom_handling() {
         let dir = TempDir::new().unwrap();
@@ -786,6 +1873,57 @@ mod tests {
         assert_eq!(ip2, Some("5.6.7.8".to_string()));
     }
 
+    #[test]
+    fn test_utf16_hosts_is_detected_and_left_untouched() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("hosts");
+        let content = "127.0.0.1\tlocalhost\n1.2.3.4\ttest.com\n";
+        let utf16le: Vec<u8> = content
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        let utf16be: Vec<u8> = content
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+
+        let with_bom = |bom: [u8; 2], body: &[u8]| {
+            let mut data = bom.to_vec();
+            data.extend_from_slice(body);
+            data
+        };
+        assert_eq!(
+            HostsEncoding::detect(&with_bom([0xFF, 0xFE], &utf16le)),
+            HostsEncoding::Utf16Le
+        );
+        assert_eq!(
+            HostsEncoding::detect(&with_bom([0xFE, 0xFF], &utf16be)),
+            HostsEncoding::Utf16Be
+        );
+        // Without a BOM the NUL byte pattern gives it away
+        assert_eq!(HostsEncoding::detect(&utf16le), HostsEncoding::Utf16Le);
+        assert_eq!(HostsEncoding::detect(&utf16be), HostsEncoding::Utf16Be);
+        assert_eq!(
+            HostsEncoding::detect(content.as_bytes()),
+            HostsEncoding::Utf8
+        );
+        // GBK-encoded comment
+        assert_eq!(
+            HostsEncoding::detect(b"# \xd6\xd0\xce\xc4\n127.0.0.1 localhost\n"),
+            HostsEncoding::NonUtf8
+        );
+
+        // Writes are refused with a clear error and the file keeps its original bytes
+        let original = with_bom([0xFF, 0xFE], &utf16le);
+        fs::write(&path, &original).unwrap();
+        let manager = TestableHostsManager::new(path.clone());
+        assert_eq!(manager.detect_encoding().unwrap(), HostsEncoding::Utf16Le);
+        let err = manager.write_binding("test2.com", "5.6.7.8").unwrap_err();
+        assert!(matches!(err, HostsError::UnsupportedEncoding("UTF-16 LE")));
+        assert!(err.to_string().contains("convert it to UTF-8"));
+        assert_eq!(fs::read(&path).unwrap(), original);
+    }
+
     #[test]
     fn test_preserves_comments() {
         let dir = TempDir::new().unwrap();
@@ -866,4 +2004,328 @@ mod tests {
         // Block should now be properly closed
         assert!(result.contains(MARKER_END));
     }
+
+    #[test]
+    fn test_export_block() {
+        let dir = TempDir::new().unwrap();
+        let content = "127.0.0.1\tlocalhost\n# BEGIN anyFAST\n2.2.2.2\tb.com\t# anyFAST\n1.1.1.1\ta.com\t# anyFAST\n# END anyFAST\n192.168.1.1\tmyserver.local";
+        let path = create_hosts_file(&dir, content);
+        let manager = TestableHostsManager::new(path.clone());
+        let dest = dir.path().join("export.txt");
+
+        let count = manager.export_block(&dest).unwrap();
+        assert_eq!(count, 2);
+
+        let exported = fs::read_to_string(&dest).unwrap();
+        assert_eq!(
+            exported,
+            "# BEGIN anyFAST\n1.1.1.1\ta.com\t# anyFAST\n2.2.2.2\tb.com\t# anyFAST\n# END anyFAST\n"
+        );
+
+        // System hosts file is untouched
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_snippet_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let source = TestableHostsManager::new(create_hosts_file(
+            &dir,
+            "127.0.0.1\tlocalhost\n# BEGIN anyFAST\n2.2.2.2\tb.com\t# anyFAST\n1.1.1.1\ta.com\t# anyFAST\n3.3.3.3\ta.com\t# anyFAST\n# END anyFAST",
+        ));
+        let block = source.read_block().unwrap();
+
+        let (bindings, rejected) = HostsManager::parse_snippet(&block);
+        assert_eq!(rejected, 0);
+        assert_eq!(bindings.len(), 3);
+
+        let target_path = dir.path().join("target_hosts");
+        fs::write(&target_path, "127.0.0.1\tlocalhost").unwrap();
+        let target = TestableHostsManager::new(target_path);
+        target.write_bindings_batch(&bindings).unwrap();
+        assert_eq!(
+            target.read_all_anyfast_bindings(),
+            source.read_all_anyfast_bindings()
+        );
+        assert_eq!(target.read_block().unwrap(), block);
+    }
+
+    #[test]
+    fn test_parse_snippet_rejects_invalid_entries() {
+        let snippet = "# shared bindings\n\n1.1.1.1 a.com b.com # two names\nnot-an-ip c.com\n2.2.2.2\n3.3.3.3 bad/domain\n2606:4700::1\tv6.com";
+        let (bindings, rejected) = HostsManager::parse_snippet(snippet);
+
+        let pairs: Vec<(&str, &str)> = bindings
+            .iter()
+            .map(|b| (b.domain.as_str(), b.ip.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("a.com", "1.1.1.1"),
+                ("b.com", "1.1.1.1"),
+                ("v6.com", "2606:4700::1")
+            ]
+        );
+        assert_eq!(rejected, 3);
+    }
+
+    #[test]
+    fn test_export_block_without_bindings() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(&dir, "127.0.0.1\tlocalhost");
+        let manager = TestableHostsManager::new(path);
+        let dest = dir.path().join("export.txt");
+
+        assert_eq!(manager.export_block(&dest).unwrap(), 0);
+        assert_eq!(
+            fs::read_to_string(&dest).unwrap(),
+            "# BEGIN anyFAST\n# END anyFAST\n"
+        );
+    }
+
+    #[test]
+    fn test_read_all_anyfast_bindings() {
+        let dir = TempDir::new().unwrap();
+        let content = "127.0.0.1\tlocalhost\n5.5.5.5\tlegacy.com\t# anyFAST\n# BEGIN anyFAST\n2.2.2.2\tb.com\t# anyFAST\n1.1.1.1\ta.com\t# anyFAST\n# END anyFAST\n192.168.1.1\tmyserver.local";
+        let path = create_hosts_file(&dir, content);
+        let manager = TestableHostsManager::new(path);
+
+        assert_eq!(
+            manager.read_all_anyfast_bindings(),
+            vec![
+                ("a.com".to_string(), "1.1.1.1".to_string()),
+                ("b.com".to_string(), "2.2.2.2".to_string()),
+                ("legacy.com".to_string(), "5.5.5.5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_all_anyfast_bindings_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let manager = TestableHostsManager::new(dir.path().join("missing"));
+        assert!(manager.read_all_anyfast_bindings().is_empty());
+    }
+
+    #[test]
+    fn test_preview_matches_post_write_file() {
+        let dir = TempDir::new().unwrap();
+        let original = "127.0.0.1 localhost\n\n# BEGIN anyFAST\n1.1.1.1\told.com\t# anyFAST\n# END anyFAST\n# user line";
+        let path = create_hosts_file(&dir, original);
+        let manager = TestableHostsManager::new(path.clone());
+        let bindings = vec![
+            HostsBinding {
+                domain: "old.com".to_string(),
+                ip: "2.2.2.2".to_string(),
+            },
+            HostsBinding {
+                domain: "new.com".to_string(),
+                ip: "3.3.3.3".to_string(),
+            },
+        ];
+
+        let diff = manager.preview_bindings_batch(&bindings).unwrap();
+        // 预览不写入文件
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(diff.contains("-1.1.1.1\told.com\t# anyFAST"));
+        assert!(diff.contains("+2.2.2.2\told.com\t# anyFAST"));
+        assert!(diff.contains("+3.3.3.3\tnew.com\t# anyFAST"));
+
+        // 按 diff 还原出的新内容应与实际写入后的文件一致
+        manager.write_bindings_batch(&bindings).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        let written_lines: Vec<&str> = written.lines().collect();
+        let hunk = diff.split_once(" @@\n").unwrap().1;
+        let new_side: Vec<&str> = hunk
+            .lines()
+            .filter(|line| !line.starts_with('-'))
+            .map(|line| &line[1..])
+            .collect();
+        assert_eq!(new_side, written_lines);
+    }
+
+    #[test]
+    fn test_preview_without_changes_is_empty() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(
+            &dir,
+            "# BEGIN anyFAST\n1.1.1.1\tsame.com\t# anyFAST\n# END anyFAST",
+        );
+        let manager = TestableHostsManager::new(path);
+        let diff = manager
+            .preview_bindings_batch(&[HostsBinding {
+                domain: "same.com".to_string(),
+                ip: "1.1.1.1".to_string(),
+            }])
+            .unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_unified_diff_context_and_ranges() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh";
+        let new = "a\nb\nc\nd\nX\nf\ng\nh";
+        let diff = unified_diff(old, new);
+        assert!(diff.contains("@@ -2,7 +2,7 @@\n b\n c\n d\n-e\n+X\n f\n g\n h\n"));
+    }
+
+    #[test]
+    fn test_block_tracker_detects_external_edit() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(&dir, "127.0.0.1 localhost");
+        let manager = TestableHostsManager::new(path.clone());
+        let tracker = BlockHashTracker::new();
+
+        // 本次会话尚未写入过：不校验
+        tracker.verify_from_path(&path).unwrap();
+
+        manager.write_binding("a.com", "1.1.1.1").unwrap();
+        tracker.record_from_path(&path);
+        tracker.verify_from_path(&path).unwrap();
+
+        // 块外的修改不影响校验
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("# user comment\n{}", content)).unwrap();
+        tracker.verify_from_path(&path).unwrap();
+
+        // 用户手动修改了块内绑定
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, content.replace("1.1.1.1", "9.9.9.9")).unwrap();
+        assert!(matches!(
+            tracker.verify_from_path(&path),
+            Err(HostsError::ExternalModification)
+        ));
+
+        // 强制写入后重新记录，校验恢复通过
+        manager.write_binding("a.com", "2.2.2.2").unwrap();
+        tracker.record_from_path(&path);
+        tracker.verify_from_path(&path).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_atomic_writes_use_distinct_temp_files() {
+        let dir = TempDir::new().unwrap();
+        assert_ne!(unique_temp_path(dir.path()), unique_temp_path(dir.path()));
+
+        let path = create_hosts_file(&dir, "");
+        let contents: Vec<String> = (0..8)
+            .map(|i| format!("{}.{}.{}.{} example.com\n", i, i, i, i).repeat(2000))
+            .collect();
+        std::thread::scope(|scope| {
+            for content in &contents {
+                let path = &path;
+                scope.spawn(move || atomic_write(path, content).unwrap());
+            }
+        });
+
+        // 最终内容必须是某一次完整写入，且没有残留临时文件
+        let result = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(&result));
+        let leftovers = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".hosts.tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_suspend_resume_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let content = "127.0.0.1\tlocalhost\n\n# BEGIN anyFAST\n1.2.3.4\ta.com\t# anyFAST\n5.6.7.8\tb.com\t# anyFAST\n# END anyFAST\n";
+        let path = create_hosts_file(&dir, content);
+        let manager = TestableHostsManager::new(path.clone());
+
+        assert!(manager.suspend_binding("a.com").unwrap());
+        let suspended = fs::read_to_string(&path).unwrap();
+        assert!(suspended.contains("#1.2.3.4\ta.com\t# anyFAST"));
+        assert!(suspended.contains("# END anyFAST"));
+        // Suspended bindings are invisible to lookups, other domains are untouched
+        assert!(manager.read_binding("a.com").is_none());
+        assert!(manager.read_binding_ips("a.com").is_empty());
+        assert_eq!(manager.read_binding("b.com"), Some("5.6.7.8".to_string()));
+        assert_eq!(manager.read_suspended_domains(), ["a.com"]);
+        // Suspending twice is a no-op
+        assert!(!manager.suspend_binding("a.com").unwrap());
+
+        assert!(manager.resume_binding("a.com").unwrap());
+        assert_eq!(manager.read_binding("a.com"), Some("1.2.3.4".to_string()));
+        assert!(manager.read_suspended_domains().is_empty());
+        assert!(!manager.resume_binding("a.com").unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap().trim_end(),
+            content.trim_end()
+        );
+    }
+
+    #[test]
+    fn test_deconflict_preserves_original_as_comment() {
+        let dir = TempDir::new().unwrap();
+        let content = "127.0.0.1\tlocalhost\n9.9.9.9 Test.com # added by hand\n127.0.0.1 localhost2 test.com\n8.8.8.8 other.com\n\n# BEGIN anyFAST\n1.2.3.4\ttest.com\t# anyFAST\n# END anyFAST\n";
+        let path = create_hosts_file(&dir, content);
+        let manager = TestableHostsManager::new(path.clone());
+
+        // Mixed lines are reported only when every name on them is managed
+        assert_eq!(
+            manager.find_conflicts(),
+            ["9.9.9.9 Test.com # added by hand"]
+        );
+
+        assert_eq!(manager.deconflict_bindings().unwrap(), 1);
+        let deconflicted = fs::read_to_string(&path).unwrap();
+        assert!(deconflicted.contains("# 9.9.9.9 Test.com # added by hand\t# anyFAST-deconflicted"));
+        assert!(deconflicted.contains("127.0.0.1 localhost2 test.com"));
+        assert!(deconflicted.contains("8.8.8.8 other.com"));
+        assert!(manager.find_conflicts().is_empty());
+        assert_eq!(
+            manager.read_binding("test.com"),
+            Some("1.2.3.4".to_string())
+        );
+        // Running again is a no-op
+        assert_eq!(manager.deconflict_bindings().unwrap(), 0);
+
+        assert_eq!(manager.restore_deconflicted().unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap().trim_end(),
+            content.trim_end()
+        );
+    }
+
+    #[test]
+    fn test_suspended_binding_survives_other_writes() {
+        let dir = TempDir::new().unwrap();
+        let path = create_hosts_file(&dir, "127.0.0.1\tlocalhost\n");
+        let manager = TestableHostsManager::new(path.clone());
+        manager.write_binding("a.com", "1.2.3.4").unwrap();
+        manager.suspend_binding("a.com").unwrap();
+
+        // Writes to other domains keep the suspended line; the last active
+        // binding going away must not drop the block holding it
+        manager.write_binding("b.com", "5.6.7.8").unwrap();
+        manager.clear_binding("b.com").unwrap();
+        assert_eq!(manager.read_suspended_domains(), ["a.com"]);
+
+        // Ordinary comments inside the block are not treated as suspended bindings
+        let with_comment = fs::read_to_string(&path)
+            .unwrap()
+            .replace("# END anyFAST", "# just a note\n# END anyFAST");
+        fs::write(&path, with_comment).unwrap();
+        assert_eq!(manager.read_suspended_domains(), ["a.com"]);
+
+        // Re-binding explicitly supersedes the suspended entry
+        manager.write_binding("a.com", "9.9.9.9").unwrap();
+        assert!(manager.read_suspended_domains().is_empty());
+        assert_eq!(manager.read_binding("a.com"), Some("9.9.9.9".to_string()));
+
+        manager.suspend_binding("a.com").unwrap();
+        manager.clear_binding("a.com").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("anyFAST"));
+    }
 }
@@ -5,7 +5,10 @@
 //! - macOS: Uses setuid helper binary for privilege elevation
 //! - Linux: Falls back to direct operations (requires root)
 
-use crate::hosts_manager::{HostsBinding, HostsError, HostsManager};
+use crate::hosts_manager::{
+    BlockHashTracker, HostsBinding, HostsEncoding, HostsError, HostsManager,
+};
+use crate::models::{BindingWriteOutcome, ServicePipeAccess};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::OnceLock;
 
@@ -129,6 +132,52 @@ pub fn is_macos_helper_available() -> bool {
     false
 }
 
+/// 判断已安装 helper 是否与内置 helper 版本不一致
+/// 旧版 helper 不支持 version 子命令（installed 为 None），同样视为需要重新安装
+#[allow(dead_code)]
+pub fn helper_version_mismatch(installed: Option<&str>, bundled: Option<&str>) -> bool {
+    match (installed, bundled) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        (Some(installed), Some(bundled)) => installed.trim() != bundled.trim(),
+    }
+}
+
+/// Query helper version via `<helper> version`
+#[cfg(target_os = "macos")]
+fn query_helper_version(path: &std::path::Path) -> Option<String> {
+    let output = Command::new(path).arg("version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if version.is_empty() {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+/// Check whether the installed setuid helper is outdated compared to the bundled one
+/// (app updates replace the bundled helper but not the installed copy)
+#[cfg(target_os = "macos")]
+pub fn is_macos_helper_outdated() -> bool {
+    let Some(installed_path) = get_macos_helper_path() else {
+        return false;
+    };
+    let Some(bundled_path) = get_bundled_helper_path() else {
+        return false;
+    };
+    let installed = query_helper_version(&installed_path);
+    let bundled = query_helper_version(&bundled_path);
+    helper_version_mismatch(installed.as_deref(), bundled.as_deref())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_macos_helper_outdated() -> bool {
+    false
+}
+
 /// Check if the hosts service is running (cached)
 #[cfg(windows)]
 pub fn is_service_running() -> bool {
@@ -164,6 +213,77 @@ pub fn refresh_service_status() -> bool {
     false
 }
 
+/// Ping the service and report why the pipe is unusable, if it is
+/// Also refreshes the cached service availability
+#[cfg(windows)]
+pub fn check_service_pipe_access() -> ServicePipeAccess {
+    let result = PipeClient::new().ping();
+    let running = result.is_ok();
+    match SERVICE_AVAILABLE.get() {
+        Some(available) => available.store(running, Ordering::Relaxed),
+        None => {
+            SERVICE_AVAILABLE.get_or_init(|| AtomicBool::new(running));
+        }
+    }
+    match result {
+        Ok(ping) => ServicePipeAccess {
+            accessible: true,
+            reason: None,
+            detail: None,
+            service_version: Some(ping.version),
+        },
+        Err(e) => ServicePipeAccess {
+            accessible: false,
+            reason: Some(e.pipe_failure()),
+            detail: Some(e.to_string()),
+            service_version: None,
+        },
+    }
+}
+
+#[cfg(not(windows))]
+pub fn check_service_pipe_access() -> ServicePipeAccess {
+    ServicePipeAccess {
+        accessible: false,
+        reason: Some(crate::models::ServicePipeFailure::NotRunning),
+        detail: Some("The hosts service is only available on Windows".into()),
+        service_version: None,
+    }
+}
+
+/// Error prefix the macOS helper prints when the hosts file is not UTF-8
+/// (must match UNSUPPORTED_ENCODING_PREFIX in anyfast-helper-macos)
+const HELPER_UNSUPPORTED_ENCODING_PREFIX: &str = "unsupported hosts encoding: ";
+
+/// Map a helper failure that a direct write would hit as well
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn helper_hosts_error(stderr: &str) -> Option<HostsError> {
+    let label = stderr
+        .split_once(HELPER_UNSUPPORTED_ENCODING_PREFIX)?
+        .1
+        .trim();
+    [
+        HostsEncoding::Utf16Le,
+        HostsEncoding::Utf16Be,
+        HostsEncoding::NonUtf8,
+    ]
+    .into_iter()
+    .find(|encoding| encoding.label() == label)
+    .map(|encoding| HostsError::UnsupportedEncoding(encoding.label()))
+}
+
+/// Hash of the anyFAST block after our last successful write
+/// Kept in the GUI process so it covers Service, helper and direct writes alike
+static BLOCK_TRACKER: BlockHashTracker = BlockHashTracker::new();
+
+/// Record the block hash after a successful write
+fn track_write<T>(result: Result<T, HostsError>) -> Result<T, HostsError> {
+    if result.is_ok() {
+        BLOCK_TRACKER.record();
+    }
+    result
+}
+
 /// Mark service as unavailable (called on service failure)
 #[allow(dead_code)]
 fn mark_service_unavailable() {
@@ -175,6 +295,10 @@ fn mark_service_unavailable() {
 /// Write a binding using Service if available, otherwise direct
 /// On service failure, automatically falls back to direct operation
 pub fn write_binding(domain: &str, ip: &str) -> Result<(), HostsError> {
+    track_write(write_binding_unchecked(domain, ip))
+}
+
+fn write_binding_unchecked(domain: &str, ip: &str) -> Result<(), HostsError> {
     #[cfg(windows)]
     {
         if is_service_running() {
@@ -207,6 +331,10 @@ pub fn write_binding(domain: &str, ip: &str) -> Result<(), HostsError> {
                     } else {
                         let stderr = String::from_utf8_lossy(&output.stderr);
                         eprintln!("macOS helper write_binding failed: {}", stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
                         // Fall through to direct operation
                     }
                 }
@@ -226,7 +354,110 @@ pub fn write_binding(domain: &str, ip: &str) -> Result<(), HostsError> {
 
 /// Write multiple bindings using Service if available, otherwise direct
 /// On service failure, automatically falls back to direct operation
-pub fn write_bindings_batch(bindings: &[HostsBinding]) -> Result<usize, HostsError> {
+/// Refuses with ExternalModification if the anyFAST block was edited outside anyFAST
+/// since our last write, unless `force` is set
+pub fn write_bindings_batch(bindings: &[HostsBinding], force: bool) -> Result<usize, HostsError> {
+    if !force {
+        BLOCK_TRACKER.verify()?;
+    }
+    track_write(write_bindings_batch_unchecked(bindings))
+}
+
+/// Best-effort variant of `write_bindings_batch`: each domain is validated and written
+/// independently, so one malformed entry (or one write the service/helper rejects)
+/// doesn't block the rest. Returns per-domain outcomes and the number of changed bindings.
+/// Permission and external-modification errors still abort the whole call.
+pub fn write_bindings_best_effort(
+    bindings: Vec<HostsBinding>,
+    force: bool,
+) -> Result<(Vec<BindingWriteOutcome>, usize), HostsError> {
+    if !force {
+        BLOCK_TRACKER.verify()?;
+    }
+    write_best_effort(bindings, |batch| {
+        track_write(write_bindings_batch_unchecked(batch))
+    })
+}
+
+fn write_best_effort<W>(
+    bindings: Vec<HostsBinding>,
+    mut write: W,
+) -> Result<(Vec<BindingWriteOutcome>, usize), HostsError>
+where
+    W: FnMut(&[HostsBinding]) -> Result<usize, HostsError>,
+{
+    // Group by domain (first-seen order); a multi-IP domain with any invalid IP is skipped whole
+    let mut groups: Vec<(String, Vec<HostsBinding>, Option<String>)> = Vec::new();
+    for binding in bindings {
+        let index = match groups.iter().position(|(d, _, _)| *d == binding.domain) {
+            Some(index) => index,
+            None => {
+                groups.push((binding.domain.clone(), Vec::new(), None));
+                groups.len() - 1
+            }
+        };
+        let group = &mut groups[index];
+        if let Err(e) = binding.validate() {
+            group.2.get_or_insert(e.to_string());
+        }
+        group.1.push(binding);
+    }
+
+    let outcome = |domain: &str, error: Option<String>| BindingWriteOutcome {
+        domain: domain.to_string(),
+        ok: error.is_none(),
+        error,
+    };
+
+    // Fast path: all valid domains in a single file operation
+    let valid: Vec<HostsBinding> = groups
+        .iter()
+        .filter(|(_, _, error)| error.is_none())
+        .flat_map(|(_, group, _)| {
+            group.iter().map(|b| HostsBinding {
+                domain: b.domain.clone(),
+                ip: b.ip.clone(),
+            })
+        })
+        .collect();
+    let batch = if valid.is_empty() {
+        Ok(0)
+    } else {
+        write(&valid)
+    };
+    match batch {
+        Ok(changed) => {
+            let outcomes = groups
+                .into_iter()
+                .map(|(domain, _, error)| outcome(&domain, error))
+                .collect();
+            return Ok((outcomes, changed));
+        }
+        Err(HostsError::PermissionDenied) => return Err(HostsError::PermissionDenied),
+        Err(e) => eprintln!("Batch write failed, retrying per domain: {}", e),
+    }
+
+    // Batch rejected: retry each domain on its own and keep going past failures
+    let mut changed = 0;
+    let mut outcomes = Vec::with_capacity(groups.len());
+    for (domain, group, error) in groups {
+        let error = match error {
+            Some(error) => Some(error),
+            None => match write(&group) {
+                Ok(count) => {
+                    changed += count;
+                    None
+                }
+                Err(HostsError::PermissionDenied) => return Err(HostsError::PermissionDenied),
+                Err(e) => Some(e.to_string()),
+            },
+        };
+        outcomes.push(outcome(&domain, error));
+    }
+    Ok((outcomes, changed))
+}
+
+fn write_bindings_batch_unchecked(bindings: &[HostsBinding]) -> Result<usize, HostsError> {
     #[cfg(windows)]
     {
         if is_service_running() {
@@ -267,10 +498,17 @@ pub fn write_bindings_batch(bindings: &[HostsBinding]) -> Result<usize, HostsErr
             {
                 Ok(output) => {
                     if output.status.success() {
-                        return Ok(bindings.len());
+                        // The helper prints the number of changed bindings; an older helper
+                        // prints a sentence instead, so count everything as changed
+                        let stdout = String::from_utf8_lossy(&output.stdout);
+                        return Ok(stdout.trim().parse().unwrap_or(bindings.len()));
                     } else {
                         let stderr = String::from_utf8_lossy(&output.stderr);
                         eprintln!("macOS helper write_bindings_batch failed: {}", stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
                         // Fall through to direct operation
                     }
                 }
@@ -289,6 +527,11 @@ pub fn write_bindings_batch(bindings: &[HostsBinding]) -> Result<usize, HostsErr
 /// On service failure, automatically falls back to direct operation
 #[allow(dead_code)]
 pub fn clear_binding(domain: &str) -> Result<(), HostsError> {
+    track_write(clear_binding_unchecked(domain))
+}
+
+#[allow(dead_code)]
+fn clear_binding_unchecked(domain: &str) -> Result<(), HostsError> {
     #[cfg(windows)]
     {
         if is_service_running() {
@@ -318,6 +561,10 @@ pub fn clear_binding(domain: &str) -> Result<(), HostsError> {
                     } else {
                         let stderr = String::from_utf8_lossy(&output.stderr);
                         eprintln!("macOS helper clear_binding failed: {}", stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
                         // Fall through to direct operation
                     }
                 }
@@ -335,6 +582,10 @@ pub fn clear_binding(domain: &str) -> Result<(), HostsError> {
 /// Clear multiple bindings using Service if available, otherwise direct
 /// On service failure, automatically falls back to direct operation
 pub fn clear_bindings_batch(domains: &[&str]) -> Result<usize, HostsError> {
+    track_write(clear_bindings_batch_unchecked(domains))
+}
+
+fn clear_bindings_batch_unchecked(domains: &[&str]) -> Result<usize, HostsError> {
     #[cfg(windows)]
     {
         if is_service_running() {
@@ -371,6 +622,10 @@ pub fn clear_bindings_batch(domains: &[&str]) -> Result<usize, HostsError> {
                     } else {
                         let stderr = String::from_utf8_lossy(&output.stderr);
                         eprintln!("macOS helper clear_bindings_batch failed: {}", stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
                         // Fall through to direct operation
                     }
                 }
@@ -390,6 +645,11 @@ pub fn clear_bindings_batch(domains: &[&str]) -> Result<usize, HostsError> {
 /// On service failure, automatically falls back to direct operation
 #[allow(dead_code)]
 pub fn clear_all_anyfast_bindings() -> Result<usize, HostsError> {
+    track_write(clear_all_anyfast_bindings_unchecked())
+}
+
+#[allow(dead_code)]
+fn clear_all_anyfast_bindings_unchecked() -> Result<usize, HostsError> {
     #[cfg(windows)]
     {
         if is_service_running() {
@@ -421,6 +681,10 @@ pub fn clear_all_anyfast_bindings() -> Result<usize, HostsError> {
                     } else {
                         let stderr = String::from_utf8_lossy(&output.stderr);
                         eprintln!("macOS helper clear_all_anyfast_bindings failed: {}", stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
                         // Fall through to direct operation
                     }
                 }
@@ -435,11 +699,209 @@ pub fn clear_all_anyfast_bindings() -> Result<usize, HostsError> {
     HostsManager::clear_all_anyfast_bindings()
 }
 
+/// Read all anyFAST bindings in one pass (domain, ip)
+/// Windows: prefer the Service's get_all_bindings, falling back to reading the file directly
+pub fn get_all_anyfast_bindings() -> Vec<(String, String)> {
+    #[cfg(windows)]
+    {
+        if is_service_running() {
+            let client = PipeClient::new();
+            match client.get_all_bindings() {
+                Ok(bindings) => return bindings,
+                Err(e) => {
+                    eprintln!(
+                        "Service get_all_bindings failed, falling back to direct: {}",
+                        e
+                    );
+                    mark_service_unavailable();
+                }
+            }
+        }
+    }
+
+    HostsManager::read_all_anyfast_bindings()
+}
+
+/// Temporarily disable a domain's binding by commenting it out in the anyFAST block
+/// Uses the Service or macOS helper if available, otherwise direct
+pub fn suspend_binding(domain: &str) -> Result<bool, HostsError> {
+    track_write(set_suspended_unchecked(domain, true))
+}
+
+/// Re-enable a binding disabled by suspend_binding
+/// Uses the Service or macOS helper if available, otherwise direct
+pub fn resume_binding(domain: &str) -> Result<bool, HostsError> {
+    track_write(set_suspended_unchecked(domain, false))
+}
+
+fn set_suspended_unchecked(domain: &str, suspend: bool) -> Result<bool, HostsError> {
+    #[cfg(windows)]
+    {
+        if is_service_running() {
+            let client = PipeClient::new();
+            let result = if suspend {
+                client.suspend_binding(domain)
+            } else {
+                client.resume_binding(domain)
+            };
+            match result {
+                Ok(changed) => return Ok(changed),
+                Err(e) => {
+                    // Service failed - mark unavailable and fall back to direct
+                    eprintln!(
+                        "Service {} failed, falling back to direct: {}",
+                        if suspend {
+                            "suspend_binding"
+                        } else {
+                            "resume_binding"
+                        },
+                        e
+                    );
+                    mark_service_unavailable();
+                    // Fall through to direct operation
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(helper_path) = get_macos_helper_path() {
+            let command = if suspend { "suspend" } else { "resume" };
+            match Command::new(&helper_path).args([command, domain]).output() {
+                Ok(output) => {
+                    if output.status.success() {
+                        return Ok(String::from_utf8_lossy(&output.stdout).trim() == "true");
+                    } else {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        eprintln!("macOS helper {} failed: {}", command, stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
+                        // Fall through to direct operation
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to execute macOS helper: {}", e);
+                    // Fall through to direct operation
+                }
+            }
+        }
+    }
+
+    if suspend {
+        HostsManager::suspend_binding(domain)
+    } else {
+        HostsManager::resume_binding(domain)
+    }
+}
+
+/// Encoding of the hosts file on disk (always direct, read-only)
+pub fn detect_hosts_encoding() -> Result<HostsEncoding, HostsError> {
+    HostsManager::detect_encoding()
+}
+
+/// Lines outside the anyFAST block that bind a managed domain (always direct)
+pub fn find_hosts_conflicts() -> Vec<String> {
+    HostsManager::find_conflicts()
+}
+
+/// Comment out conflicting lines outside the anyFAST block, keeping the originals restorable
+/// Uses the Service or macOS helper if available, otherwise direct
+pub fn deconflict_bindings() -> Result<usize, HostsError> {
+    track_write(set_deconflicted_unchecked(true))
+}
+
+/// Restore lines commented out by deconflict_bindings
+/// Uses the Service or macOS helper if available, otherwise direct
+pub fn restore_deconflicted() -> Result<usize, HostsError> {
+    track_write(set_deconflicted_unchecked(false))
+}
+
+fn set_deconflicted_unchecked(deconflict: bool) -> Result<usize, HostsError> {
+    #[cfg(windows)]
+    {
+        if is_service_running() {
+            let client = PipeClient::new();
+            let result = if deconflict {
+                client.deconflict_bindings()
+            } else {
+                client.restore_deconflicted()
+            };
+            match result {
+                Ok(count) => return Ok(count as usize),
+                Err(e) => {
+                    // Service failed - mark unavailable and fall back to direct
+                    eprintln!(
+                        "Service {} failed, falling back to direct: {}",
+                        if deconflict {
+                            "deconflict_bindings"
+                        } else {
+                            "restore_deconflicted"
+                        },
+                        e
+                    );
+                    mark_service_unavailable();
+                    // Fall through to direct operation
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(helper_path) = get_macos_helper_path() {
+            let command = if deconflict {
+                "deconflict"
+            } else {
+                "restore-deconflicted"
+            };
+            match Command::new(&helper_path).arg(command).output() {
+                Ok(output) => {
+                    if output.status.success() {
+                        let stdout = String::from_utf8_lossy(&output.stdout);
+                        return Ok(stdout.trim().parse().unwrap_or(0));
+                    } else {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        eprintln!("macOS helper {} failed: {}", command, stderr);
+                        // Direct writes would fail the same way, so report it instead
+                        if let Some(e) = helper_hosts_error(&stderr) {
+                            return Err(e);
+                        }
+                        // Fall through to direct operation
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to execute macOS helper: {}", e);
+                    // Fall through to direct operation
+                }
+            }
+        }
+    }
+
+    if deconflict {
+        HostsManager::deconflict_bindings()
+    } else {
+        HostsManager::restore_deconflicted()
+    }
+}
+
+/// Domains whose bindings are currently suspended (always direct)
+pub fn get_suspended_domains() -> Vec<String> {
+    HostsManager::read_suspended_domains()
+}
+
 /// Read a binding (always direct, reading doesn't need privileges)
 pub fn read_binding(domain: &str) -> Option<String> {
     HostsManager::read_binding(domain)
 }
 
+/// Read every IP bound to a domain in the anyFAST block (always direct)
+pub fn read_binding_ips(domain: &str) -> Vec<String> {
+    HostsManager::read_binding_ips(domain)
+}
+
 /// Flush DNS using Service if available, otherwise direct
 /// On service failure, automatically falls back to direct operation
 pub fn flush_dns() -> Result<(), HostsError> {
@@ -483,6 +945,59 @@ pub fn flush_dns() -> Result<(), HostsError> {
     HostsManager::flush_dns()
 }
 
+/// Whether this process itself runs elevated (admin token on Windows, root on Unix).
+/// Unlike `get_permission_status`, writes delegated to the service or helper don't count.
+pub fn is_process_elevated() -> bool {
+    #[cfg(windows)]
+    {
+        windows_token_elevated()
+    }
+
+    #[cfg(unix)]
+    {
+        // SAFETY: geteuid has no preconditions and cannot fail
+        is_root_uid(unsafe { libc::geteuid() })
+    }
+
+    #[cfg(not(any(windows, unix)))]
+    {
+        false
+    }
+}
+
+#[cfg(unix)]
+fn is_root_uid(uid: libc::uid_t) -> bool {
+    uid == 0
+}
+
+/// Query TokenElevation on the current process token
+#[cfg(windows)]
+fn windows_token_elevated() -> bool {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut returned = 0u32;
+        let queried = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned,
+        );
+        let _ = CloseHandle(token);
+        queried.is_ok() && elevation.TokenIsElevated != 0
+    }
+}
+
 /// Get permission status
 /// Returns: (has_permission, is_using_service_or_helper)
 pub fn get_permission_status() -> (bool, bool) {
@@ -530,3 +1045,120 @@ pub fn get_permission_status() -> (bool, bool) {
         (has_root, false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(domain: &str, ip: &str) -> HostsBinding {
+        HostsBinding {
+            domain: domain.into(),
+            ip: ip.into(),
+        }
+    }
+
+    #[test]
+    fn test_write_best_effort_skips_invalid_entries() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("hosts");
+        std::fs::write(&path, "127.0.0.1 localhost\n").unwrap();
+        let manager = crate::hosts_manager::TestableHostsManager::new(path);
+
+        let (outcomes, changed) = write_best_effort(
+            vec![
+                binding("a.com", "1.1.1.1"),
+                binding("bad domain.com", "2.2.2.2"),
+                binding("b.com", "not-an-ip"),
+                binding("multi.com", "3.3.3.3"),
+                binding("multi.com", "999.0.0.1"),
+                binding("c.com", "4.4.4.4"),
+            ],
+            |batch| manager.write_bindings_batch(batch),
+        )
+        .unwrap();
+
+        let ok: Vec<(&str, bool)> = outcomes.iter().map(|o| (o.domain.as_str(), o.ok)).collect();
+        assert_eq!(
+            ok,
+            [
+                ("a.com", true),
+                ("bad domain.com", false),
+                ("b.com", false),
+                ("multi.com", false),
+                ("c.com", true)
+            ]
+        );
+        assert!(outcomes[2].error.as_deref().unwrap().contains("not-an-ip"));
+        assert_eq!(changed, 2);
+        assert_eq!(manager.read_binding("a.com").as_deref(), Some("1.1.1.1"));
+        assert_eq!(manager.read_binding("c.com").as_deref(), Some("4.4.4.4"));
+        assert_eq!(manager.read_binding("multi.com"), None);
+    }
+
+    #[test]
+    fn test_write_best_effort_falls_back_per_domain() {
+        // 批量写入被拒绝时逐域名重试，单个失败不影响其他域名
+        let mut written = Vec::new();
+        let (outcomes, changed) = write_best_effort(
+            vec![
+                binding("a.com", "1.1.1.1"),
+                binding("rejected.com", "2.2.2.2"),
+            ],
+            |batch| {
+                if batch.iter().any(|b| b.domain == "rejected.com") {
+                    return Err(HostsError::InvalidDomain("rejected.com".into()));
+                }
+                written.extend(batch.iter().map(|b| b.domain.clone()));
+                Ok(batch.len())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(written, ["a.com"]);
+        assert_eq!(changed, 1);
+        assert!(outcomes[0].ok);
+        assert!(!outcomes[1].ok);
+
+        // 权限不足时整体失败，提示以管理员身份运行
+        let denied = write_best_effort(vec![binding("a.com", "1.1.1.1")], |_| {
+            Err(HostsError::PermissionDenied)
+        });
+        assert!(matches!(denied, Err(HostsError::PermissionDenied)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_root_uid() {
+        assert!(is_root_uid(0));
+        assert!(!is_root_uid(1000));
+        // SAFETY: geteuid has no preconditions
+        let euid = unsafe { libc::geteuid() };
+        assert_eq!(is_process_elevated(), euid == 0);
+    }
+
+    #[test]
+    fn test_helper_hosts_error_maps_unsupported_encoding() {
+        let stderr = format!(
+            "错误: {}{}\n",
+            HELPER_UNSUPPORTED_ENCODING_PREFIX,
+            HostsEncoding::Utf16Le.label()
+        );
+        assert!(matches!(
+            helper_hosts_error(&stderr),
+            Some(HostsError::UnsupportedEncoding("UTF-16 LE"))
+        ));
+        assert!(helper_hosts_error("错误: 无效的 IP 地址: x").is_none());
+        assert!(helper_hosts_error("错误: unsupported hosts encoding: EBCDIC").is_none());
+    }
+
+    #[test]
+    fn test_helper_version_mismatch() {
+        assert!(!helper_version_mismatch(Some("1.2.0"), Some("1.2.0\n")));
+        assert!(helper_version_mismatch(Some("1.1.0"), Some("1.2.0")));
+        // 旧版 helper 不支持 version 子命令
+        assert!(helper_version_mismatch(None, Some("1.2.0")));
+        // 无内置 helper 时无法比较，不提示重装
+        assert!(!helper_version_mismatch(Some("1.1.0"), None));
+        assert!(!helper_version_mismatch(None, None));
+    }
+}
@@ -0,0 +1,67 @@
+//! 最近一次"全部应用"记录
+//! 应用成功后写入配置目录下的 last_apply.json，重启后仍可显示上次优化时间
+
+use crate::models::LastApplyInfo;
+use std::fs;
+use std::path::PathBuf;
+
+pub struct LastApplyStore {
+    path: PathBuf,
+}
+
+impl LastApplyStore {
+    pub fn new() -> Self {
+        Self::with_path(crate::config::data_dir().join("last_apply.json"))
+    }
+
+    pub fn with_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// 读取记录（从未应用过或文件损坏时返回 None）
+    pub fn load(&self) -> Option<LastApplyInfo> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, info: &LastApplyInfo) -> Result<(), String> {
+        let content = serde_json::to_string(info).map_err(|e| e.to_string())?;
+        fs::write(&self.path, content).map_err(|e| e.to_string())
+    }
+
+    /// 记录一次成功的应用（写入失败只打印日志，不影响应用结果）
+    pub fn record(&self, timestamp: i64, count: usize) {
+        let info = LastApplyInfo {
+            timestamp,
+            count: count as u32,
+        };
+        if let Err(e) = self.save(&info) {
+            eprintln!("保存上次应用时间失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_store_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let store = LastApplyStore::with_path(dir.path().join("last_apply.json"));
+        assert_eq!(store.load(), None);
+
+        store.record(1_700_000_000, 3);
+        assert_eq!(
+            store.load(),
+            Some(LastApplyInfo {
+                timestamp: 1_700_000_000,
+                count: 3
+            })
+        );
+
+        fs::write(dir.path().join("last_apply.json"), "not json").unwrap();
+        assert_eq!(store.load(), None);
+    }
+}
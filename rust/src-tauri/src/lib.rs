@@ -1,13 +1,24 @@
 //! anyrouter FAST - Rust Backend
 //! 中转站端点优选工具
 
+mod app_log;
+mod autostart;
+mod baseline_store;
+mod cf_ip_cache;
 mod config;
 mod endpoint_tester;
 mod health_checker;
 mod history;
 mod hosts_manager;
 mod hosts_ops;
+mod last_apply;
 mod models;
+mod network_info;
+mod notification;
+mod proxy;
+mod subscription;
+mod tray;
+mod window_state;
 
 // Service module (Windows only)
 #[cfg(windows)]
@@ -16,16 +27,23 @@ pub mod service;
 // Client module for communicating with the service
 pub mod client;
 
+use baseline_store::BaselineStore;
 use config::ConfigManager;
-use endpoint_tester::{estimate_test_timeout, EndpointTester, TestStrategy};
+use endpoint_tester::{EndpointTester, TestStrategy};
 use health_checker::{BaselineTracker, HealthChecker};
 use history::HistoryManager;
 use hosts_manager::HostsBinding;
 use models::{
-    AppConfig, DiagnosticStep, Endpoint, EndpointResult, HistoryRecord, HistoryStats,
-    PermissionStatus, UpdateInfo,
+    speedup_percent, AppConfig, ApplyAllOutcome, ApplyMeasurement, BindingHealth,
+    BindingWriteOutcome, BindingsImportSummary, CandidateIps, Capabilities,
+    CheckIntervalSuggestion, ConfigImportSummary, ConfigIssue, DailyHistory, DiagnosticStep,
+    DnsBenchmarkResult, DomainHistory, Endpoint, EndpointResult, HistoryInfo, HistoryRecord,
+    HistoryStats, HostsEncodingReport, LastApplyInfo, NetworkInfo, OptimizationStatus,
+    PeriodicRetestEvent, PermissionStatus, ResultSortKey, ServicePipeAccess,
+    SpeedTestFinishedEvent, SpeedTestResultEvent, SubscriptionSummary, UpdateInfo, UrlProbeResult,
+    MAX_IPS_PER_DOMAIN,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
@@ -33,7 +51,7 @@ use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Manager, State, WindowEvent,
+    AppHandle, Emitter, Manager, RunEvent, State, WindowEvent,
 };
 use tokio::sync::Mutex;
 
@@ -48,6 +66,9 @@ pub struct AppState {
     health_checker: Arc<Mutex<Option<HealthChecker>>>,
     /// 上次测速完成时间，用于连续测速冷却（防止快速重复触发 CF 风控）
     last_test_time: Arc<Mutex<Option<Instant>>>,
+    /// 本次会话的退出清理覆盖（优先于 config.clear_hosts_on_quit，不持久化）
+    /// 托盘菜单回调为同步上下文，使用 std Mutex
+    clear_on_quit_override: Arc<std::sync::Mutex<Option<bool>>>,
 }
 
 /// 从端点 URL 中提取目标域名
@@ -87,14 +108,197 @@ fn collect_best_success_by_domain(results: &[EndpointResult]) -> HashMap<String,
     best_by_domain
 }
 
+/// 查找测速结果中与指定 domain + IP 匹配的成功延迟
+fn find_result_latency(results: &[EndpointResult], domain: &str, ip: &str) -> Option<f64> {
+    results
+        .iter()
+        .find(|r| r.success && r.endpoint.domain == domain && r.ip == ip)
+        .map(|r| r.latency)
+}
+
+/// 汇总应用前后两次单 IP 探测的结果
+fn summarize_apply_measurement(
+    domain: &str,
+    before: &EndpointResult,
+    after: &EndpointResult,
+) -> ApplyMeasurement {
+    let before_ms = before.success.then_some(before.latency);
+    let after_ms = after.success.then_some(after.latency);
+    ApplyMeasurement {
+        domain: domain.to_string(),
+        before_ip: before.ip.clone(),
+        applied_ip: after.ip.clone(),
+        before_ms,
+        after_ms,
+        improvement_percent: before_ms
+            .zip(after_ms)
+            .map(|(before, after)| speedup_percent(before, after)),
+    }
+}
+
+/// 生成域名的绑定：最优 IP 在前，其后追加该结果按延迟排序的备用 IP，共最多 ips_per_domain 个
+fn expand_domain_bindings(
+    results: &[EndpointResult],
+    domain: &str,
+    best_ip: &str,
+    ips_per_domain: u32,
+) -> Vec<HostsBinding> {
+    let limit = ips_per_domain.clamp(1, MAX_IPS_PER_DOMAIN) as usize;
+    let backups = results
+        .iter()
+        .find(|r| r.success && r.endpoint.domain.eq_ignore_ascii_case(domain) && r.ip == best_ip)
+        .map(|r| r.backup_ips.as_slice())
+        .unwrap_or_default();
+
+    let mut ips = vec![best_ip];
+    for ip in backups {
+        if ips.len() >= limit {
+            break;
+        }
+        if !ips.contains(&ip.as_str()) {
+            ips.push(ip);
+        }
+    }
+
+    ips.into_iter()
+        .map(|ip| HostsBinding {
+            domain: domain.to_string(),
+            ip: ip.to_string(),
+        })
+        .collect()
+}
+
 /// 仅保留与当前 hosts 不同的绑定，避免无变化写入触发 DNS 刷新
-fn filter_changed_bindings(bindings: Vec<HostsBinding>) -> Vec<HostsBinding> {
-    bindings
+/// 返回 (需要写入的绑定, 因当前绑定已是最优而保持不变的域名数)
+/// 按域名整体比较：多 IP 绑定中任一 IP 或顺序变化都会重写该域名的全部 IP；
+/// 只比较本次写入涉及的地址族（写入 IPv4 时保留的 IPv6 绑定不影响判断）
+fn split_kept_bindings<F>(bindings: Vec<HostsBinding>, current_ips: F) -> (Vec<HostsBinding>, usize)
+where
+    F: Fn(&str) -> Vec<String>,
+{
+    let mut wanted: HashMap<&str, Vec<String>> = HashMap::new();
+    for binding in &bindings {
+        wanted
+            .entry(binding.domain.as_str())
+            .or_default()
+            .push(binding.ip.clone());
+    }
+    let unchanged: HashSet<String> = wanted
         .into_iter()
-        .filter(|binding| {
-            hosts_ops::read_binding(&binding.domain).as_deref() != Some(binding.ip.as_str())
+        .filter(|(domain, ips)| {
+            let writes_v4 = ips.iter().any(|ip| !hosts_manager::is_ipv6(ip));
+            let writes_v6 = ips.iter().any(|ip| hosts_manager::is_ipv6(ip));
+            let current: Vec<String> = current_ips(domain)
+                .into_iter()
+                .filter(|ip| {
+                    if hosts_manager::is_ipv6(ip) {
+                        writes_v6
+                    } else {
+                        writes_v4
+                    }
+                })
+                .collect();
+            current == *ips
         })
-        .collect()
+        .map(|(domain, _)| domain.to_string())
+        .collect();
+
+    let changed = bindings
+        .into_iter()
+        .filter(|binding| !unchanged.contains(&binding.domain))
+        .collect();
+    (changed, unchanged.len())
+}
+
+/// "全部应用"的执行计划：要写入的绑定、保持不变的域名数（含加速不足阈值的）、历史记录与基准更新
+struct ApplyPlan {
+    bindings: Vec<HostsBinding>,
+    kept_count: usize,
+    history_records: Vec<HistoryRecord>,
+    baseline_updates: Vec<(String, f64)>,
+}
+
+/// 根据测速结果计算"全部应用"的计划，不产生任何副作用（dry_run 与正式应用共用）
+fn plan_apply_all<F>(
+    results: &[EndpointResult],
+    ips_per_domain: u32,
+    min_speedup_percent: f64,
+    now: i64,
+    current_ips: F,
+) -> ApplyPlan
+where
+    F: Fn(&str) -> Vec<String>,
+{
+    // 只记录有实际加速效果的历史（跳过原始==优化的无效记录）
+    let history_records = results
+        .iter()
+        .filter(|r| r.success && r.speedup_percent.abs() > 0.1)
+        .map(|r| HistoryRecord {
+            timestamp: now,
+            domain: extract_target_domain(&r.endpoint),
+            original_latency: r.original_latency,
+            optimized_latency: r.latency,
+            speedup_percent: r.speedup_percent,
+            applied: !r.is_below_min_speedup(min_speedup_percent),
+        })
+        .collect();
+
+    // 收集所有成功的端点绑定（按 domain 去重，取最优结果）
+    let best_by_domain = collect_best_success_by_domain(results);
+    let mut bindings: Vec<HostsBinding> = Vec::with_capacity(best_by_domain.len());
+    let mut baseline_updates: Vec<(String, f64)> = Vec::with_capacity(best_by_domain.len());
+    let mut below_threshold = 0;
+    for (domain, (ip, latency)) in best_by_domain {
+        // 加速不足阈值：切换的代价（刷新 DNS、断开连接）不值得，保留当前绑定且不更新基准
+        if results.iter().any(|r| {
+            r.endpoint.domain == domain && r.ip == ip && r.is_below_min_speedup(min_speedup_percent)
+        }) {
+            below_threshold += 1;
+            continue;
+        }
+        bindings.extend(expand_domain_bindings(
+            results,
+            &domain,
+            &ip,
+            ips_per_domain,
+        ));
+        baseline_updates.push((domain, latency));
+    }
+    let (bindings, kept_count) = split_kept_bindings(bindings, current_ips);
+
+    ApplyPlan {
+        bindings,
+        kept_count: kept_count + below_threshold,
+        history_records,
+        baseline_updates,
+    }
+}
+
+/// 执行"全部应用"计划：dry_run 时只返回计划；否则交给 commit 完成全部副作用
+/// （写 hosts、更新基准、记录历史等），返回实际修改的绑定数
+async fn run_apply_plan<F, Fut>(
+    plan: ApplyPlan,
+    dry_run: bool,
+    commit: F,
+) -> Result<ApplyAllOutcome, String>
+where
+    F: FnOnce(ApplyPlan) -> Fut,
+    Fut: std::future::Future<Output = Result<u32, String>>,
+{
+    let mut outcome = ApplyAllOutcome {
+        count: plan.bindings.len() as u32,
+        bindings: plan
+            .bindings
+            .iter()
+            .map(|b| (b.domain.clone(), b.ip.clone()))
+            .collect(),
+        kept_count: plan.kept_count as u32,
+        dry_run,
+    };
+    if !dry_run {
+        outcome.count = commit(plan).await?;
+    }
+    Ok(outcome)
 }
 
 /// 归一化用户配置的优选 IP 列表：去空、校验、去重并保持原有顺序
@@ -119,50 +323,663 @@ fn normalize_preferred_ips(raw_ips: Vec<String>) -> Vec<String> {
     normalized
 }
 
+/// 从 URL 构建端点：提取 host 作为 domain，校验协议、域名格式并拒绝重复域名
+fn endpoint_from_url(name: &str, raw_url: &str, existing: &[Endpoint]) -> Result<Endpoint, String> {
+    let raw_url = raw_url.trim();
+    let parsed = url::Url::parse(raw_url).map_err(|e| format!("URL 无效: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("仅支持 http/https 协议: {}", parsed.scheme()));
+    }
+    let domain = parsed
+        .host_str()
+        .filter(|h| !h.is_empty())
+        .ok_or_else(|| "URL 缺少主机名".to_string())?
+        .to_lowercase();
+    hosts_manager::validate_domain(&domain).map_err(|e| e.to_string())?;
+
+    if existing
+        .iter()
+        .any(|e| e.domain.eq_ignore_ascii_case(&domain))
+    {
+        return Err(format!("端点已存在: {}", domain));
+    }
+
+    let name = name.trim();
+    Ok(Endpoint {
+        name: if name.is_empty() {
+            domain.clone()
+        } else {
+            name.to_string()
+        },
+        url: raw_url.to_string(),
+        domain,
+        port: Some(
+            parsed
+                .port_or_known_default()
+                .unwrap_or(models::DEFAULT_ENDPOINT_PORT),
+        ),
+        ..Default::default()
+    })
+}
+
+/// 将任意 URL 解析为临时探测目标（不检查是否与已有端点重复，也不会保存）
+fn probe_target_from_url(raw_url: &str) -> Result<Endpoint, String> {
+    endpoint_from_url("", raw_url, &[])
+}
+
+/// 为手动测试单个 IP 准备 (端点, 规范化 IP)：
+/// 域名已在配置中时沿用其 URL/端口/超时倍率，否则构造一个临时 https 端点
+fn custom_ip_target(
+    domain: &str,
+    ip: &str,
+    configured: &[Endpoint],
+) -> Result<(Endpoint, String), String> {
+    let ip = ip
+        .trim()
+        .parse::<IpAddr>()
+        .map_err(|_| format!("IP 格式无效: {}", ip.trim()))?
+        .to_string();
+    let domain = domain.trim().to_lowercase();
+    hosts_manager::validate_domain(&domain).map_err(|e| e.to_string())?;
+
+    let endpoint = configured
+        .iter()
+        .find(|e| e.domain.eq_ignore_ascii_case(&domain))
+        .cloned()
+        .unwrap_or_else(|| Endpoint {
+            name: domain.clone(),
+            url: format!("https://{}", domain),
+            domain,
+            port: Some(models::DEFAULT_ENDPOINT_PORT),
+            ..Default::default()
+        });
+    Ok((endpoint, ip))
+}
+
+fn push_issue(
+    issues: &mut Vec<ConfigIssue>,
+    domain: &str,
+    severity: models::IssueSeverity,
+    message: String,
+) {
+    issues.push(ConfigIssue {
+        domain: (!domain.is_empty()).then(|| domain.to_string()),
+        severity,
+        message,
+    });
+}
+
+/// 逐个检查端点（域名、URL、名称、列表内重复及与 existing 重复），只报告不修改
+fn collect_endpoint_issues(endpoints: &[Endpoint], existing: &[Endpoint]) -> Vec<ConfigIssue> {
+    use models::IssueSeverity::{Error, Warning};
+
+    let mut issues = Vec::new();
+    let mut issue = |domain: &str, severity, message: String| {
+        push_issue(&mut issues, domain, severity, message);
+    };
+
+    let existing: HashSet<String> = existing.iter().map(|e| e.domain.to_lowercase()).collect();
+    let mut seen = HashSet::new();
+    for ep in endpoints {
+        let label = if ep.domain.is_empty() {
+            ep.name.as_str()
+        } else {
+            ep.domain.as_str()
+        };
+
+        if ep.name.trim().is_empty() {
+            issue(label, Warning, "端点名称为空".into());
+        }
+
+        if ep.domain.trim().is_empty() {
+            issue(label, Error, format!("端点「{}」的域名为空", ep.name));
+        } else if let Err(e) = hosts_manager::validate_domain(&ep.domain) {
+            issue(label, Error, format!("域名无效: {}", e));
+        } else if !seen.insert(ep.domain.to_lowercase()) {
+            issue(label, Error, "域名重复，仅第一个端点的绑定会生效".into());
+        } else if existing.contains(&ep.domain.to_lowercase()) {
+            issue(label, Error, "端点已存在于当前配置中".into());
+        }
+
+        match url::Url::parse(ep.url.trim()) {
+            Err(e) => issue(label, Error, format!("URL 无效: {}", e)),
+            Ok(url) if !matches!(url.scheme(), "http" | "https") => {
+                issue(
+                    label,
+                    Error,
+                    format!("仅支持 http/https 协议: {}", url.scheme()),
+                );
+            }
+            Ok(url) => {
+                let host = url.host_str().unwrap_or_default();
+                if !ep.domain.is_empty() && !host.eq_ignore_ascii_case(&ep.domain) {
+                    issue(
+                        label,
+                        Warning,
+                        format!("URL 主机名 {} 与域名 {} 不一致", host, ep.domain),
+                    );
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// 检查配置中的问题（只报告，不修改配置）
+fn collect_config_issues(config: &AppConfig) -> Vec<ConfigIssue> {
+    use models::IssueSeverity::{Error, Warning};
+
+    let mut issues = collect_endpoint_issues(&config.endpoints, &[]);
+    if config.endpoints.is_empty() {
+        push_issue(&mut issues, "", Error, "尚未配置任何端点".into());
+    } else if !config.endpoints.iter().any(|e| e.enabled) {
+        push_issue(
+            &mut issues,
+            "",
+            Warning,
+            "没有启用的端点，测速不会执行".into(),
+        );
+    }
+    issues
+}
+
+/// 已注册的全部命令（需与 generate_handler! 保持一致，供前端能力协商）
+const REGISTERED_COMMANDS: &[&str] = &[
+    "get_config",
+    "get_effective_config",
+    "save_config",
+    "validate_config",
+    "validate_endpoints",
+    "add_endpoint_from_url",
+    "set_clear_on_quit_override",
+    "export_config",
+    "import_config",
+    "refresh_subscription",
+    "start_speed_test",
+    "get_estimated_test_duration",
+    "stop_speed_test",
+    "apply_endpoint",
+    "apply_all_endpoints",
+    "apply_all_best_effort",
+    "preview_apply_all",
+    "reset_baselines_to_current",
+    "clear_all_bindings",
+    "unbind_endpoint",
+    "suspend_binding",
+    "resume_binding",
+    "get_suspended_domains",
+    "has_any_bindings",
+    "get_bindings",
+    "get_all_anyfast_bindings",
+    "export_hosts_block",
+    "export_bindings_hosts",
+    "export_bindings_json",
+    "import_bindings_hosts",
+    "get_binding_count",
+    "check_admin",
+    "is_process_elevated",
+    "is_service_running",
+    "get_permission_status",
+    "refresh_service_status",
+    "is_macos_helper_available",
+    "install_macos_helper",
+    "is_macos_helper_outdated",
+    "has_bundled_helper",
+    "get_hosts_path",
+    "open_hosts_file",
+    "get_log_path",
+    "get_data_dir",
+    "open_log_file",
+    "get_history_stats",
+    "get_domain_history",
+    "get_history_daily",
+    "get_history_info",
+    "clear_history",
+    "test_single_endpoint",
+    "probe_url",
+    "retest_endpoint",
+    "get_current_results",
+    "sort_results",
+    "get_last_apply_info",
+    "get_candidate_ips",
+    "verify_bindings_health",
+    "benchmark_cf_ips",
+    "test_custom_ip",
+    "benchmark_dns_servers",
+    "diagnose_multi_dns",
+    "set_autostart",
+    "get_autostart",
+    "restart_as_admin",
+    "install_and_start_service",
+    "fetch_preferred_ips",
+    "check_for_update",
+    "get_current_version",
+    "get_capabilities",
+    "detect_system_proxy",
+    "diagnose_update",
+    "force_download_update",
+    "start_continuous_optimization",
+    "stop_continuous_optimization",
+    "is_continuous_optimization_running",
+    "pause_continuous_optimization",
+    "resume_continuous_optimization",
+    "get_continuous_optimization_status",
+    "cancel_health_check",
+    "check_service_pipe_access",
+    "get_network_info",
+    "apply_and_measure",
+    "suggest_check_interval",
+    "get_hosts_conflicts",
+    "deconflict_hosts",
+    "restore_deconflicted_hosts",
+    "start_speed_test_streaming",
+    "check_hosts_encoding",
+    "schedule_periodic_retest",
+];
+
+/// 仅在 Windows 上可用的命令
+const WINDOWS_ONLY_COMMANDS: &[&str] = &[
+    "restart_as_admin",
+    "install_and_start_service",
+    "check_service_pipe_access",
+];
+
+/// 仅在 macOS 上可用的命令
+const MACOS_ONLY_COMMANDS: &[&str] = &[
+    "is_macos_helper_available",
+    "install_macos_helper",
+    "is_macos_helper_outdated",
+    "has_bundled_helper",
+];
+
+/// 当前平台可用的命令列表（剔除其他平台专属命令）
+fn available_commands(platform: &str) -> Vec<String> {
+    REGISTERED_COMMANDS
+        .iter()
+        .filter(|name| platform == "windows" || !WINDOWS_ONLY_COMMANDS.contains(name))
+        .filter(|name| platform == "macos" || !MACOS_ONLY_COMMANDS.contains(name))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// 找出本次保存中由启用变为禁用的端点域名
+fn newly_disabled_domains(previous: &AppConfig, current: &AppConfig) -> Vec<String> {
+    current
+        .endpoints
+        .iter()
+        .filter(|ep| !ep.enabled)
+        .filter(|ep| {
+            previous
+                .endpoints
+                .iter()
+                .any(|old| old.enabled && old.domain.eq_ignore_ascii_case(&ep.domain))
+        })
+        .map(|ep| ep.domain.clone())
+        .collect()
+}
+
+/// 退出时是否清除 hosts 绑定：本次会话覆盖优先，其次为配置值
+fn should_clear_on_quit(session_override: Option<bool>, config_value: bool) -> bool {
+    session_override.unwrap_or(config_value)
+}
+
+/// 应用退出时的 hosts 清理：默认保留绑定；开启 clear_hosts_on_quit 或本次会话覆盖时清除
+/// 在 RunEvent::Exit 中调用，覆盖托盘退出、窗口关闭退出、以管理员身份重启等所有退出路径
+#[cfg(feature = "tauri-runtime")]
+fn clear_hosts_on_exit(app: &AppHandle) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let override_value = *state
+        .clear_on_quit_override
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let config_value = state
+        .config_manager
+        .load()
+        .map(|c| c.clear_hosts_on_quit)
+        .unwrap_or(false);
+    if should_clear_on_quit(override_value, config_value) {
+        match hosts_ops::clear_all_anyfast_bindings() {
+            Ok(_) => {
+                let _ = hosts_ops::flush_dns();
+            }
+            Err(e) => eprintln!("退出时清除 hosts 绑定失败: {}", e),
+        }
+    }
+}
+
+/// 保存前统一规范化配置（优选 IP、IP 来源、代理地址）
+fn normalize_config(mut config: AppConfig) -> Result<AppConfig, String> {
+    config.preferred_ips = normalize_preferred_ips(config.preferred_ips);
+    config.cf_ip_source_url = match config.cf_ip_source_url.as_deref() {
+        Some(raw) => endpoint_tester::validate_cf_ip_source_url(raw)?,
+        None => None,
+    };
+    config.proxy = match config.proxy.as_deref().map(str::trim) {
+        Some(raw) if !raw.is_empty() => Some(proxy::ProbeProxy::parse(raw)?.to_url()),
+        _ => None,
+    };
+    config.user_agent = match config.user_agent.as_deref() {
+        Some(raw) => models::normalize_user_agent(raw)?,
+        None => None,
+    };
+    config.subscription_url = match config.subscription_url.as_deref() {
+        Some(raw) => subscription::validate_subscription_url(raw)?,
+        None => None,
+    };
+    config.history_retention_days = config
+        .history_retention_days
+        .max(history::MIN_HISTORY_RETENTION_DAYS);
+    config.min_speedup_percent = models::clamp_min_speedup_percent(config.min_speedup_percent);
+    for ep in &mut config.endpoints {
+        ep.normalize_healthy_status_codes();
+    }
+    Ok(config)
+}
+
+/// 应用各模块运行时的修正（下限、范围、回退值）后的配置，即程序实际使用的值
+fn effective_config(mut config: AppConfig) -> AppConfig {
+    endpoint_tester::apply_effective_limits(&mut config);
+    health_checker::apply_effective_limits(&mut config);
+    config.ips_per_domain = config.ips_per_domain.clamp(1, MAX_IPS_PER_DOMAIN);
+    config.subscription_refresh_hours = config.subscription_refresh_hours.clamp(
+        subscription::MIN_REFRESH_HOURS,
+        subscription::MAX_REFRESH_HOURS,
+    );
+    config.history_retention_days = config
+        .history_retention_days
+        .max(history::MIN_HISTORY_RETENTION_DAYS);
+    config.min_speedup_percent = models::clamp_min_speedup_percent(config.min_speedup_percent);
+    for ep in &mut config.endpoints {
+        ep.timeout_multiplier = ep
+            .scaled_timeout(std::time::Duration::from_secs(1))
+            .as_secs_f64();
+        ep.normalize_healthy_status_codes();
+    }
+    config
+}
+
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
 async fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
     state.config_manager.load().map_err(|e| e.to_string())
 }
 
+/// 预检当前配置，返回发现的问题（为空表示无问题）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn validate_config(state: State<'_, AppState>) -> Result<Vec<ConfigIssue>, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    Ok(collect_config_issues(&config))
+}
+
+/// 预检待导入的端点列表（列表内重复及与当前配置重复也会报告），不保存任何内容
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn validate_endpoints(
+    state: State<'_, AppState>,
+    endpoints: Vec<Endpoint>,
+) -> Result<Vec<ConfigIssue>, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    Ok(collect_endpoint_issues(&endpoints, &config.endpoints))
+}
+
+/// 返回程序实际生效的配置（补全默认值并应用各项下限/范围修正），不修改磁盘上的配置
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_effective_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    Ok(effective_config(config))
+}
+
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
 async fn save_config(state: State<'_, AppState>, config: AppConfig) -> Result<(), String> {
-    let mut config = config;
-    config.preferred_ips = normalize_preferred_ips(config.preferred_ips);
+    let config = normalize_config(config)?;
+    let previous = {
+        let _guard = state.config_manager.lock_writes();
+        let previous = state.config_manager.load().ok();
+        state
+            .config_manager
+            .save(&config)
+            .map_err(|e| e.to_string())?;
+        previous
+    };
+
+    // 保留期可能缩短，立即清理过期记录
+    state
+        .history_manager
+        .set_retention_days(config.history_retention_days);
+    if let Err(e) = state.history_manager.clear_old() {
+        eprintln!("Failed to clear old history: {}", e);
+    }
+
+    // 禁用端点后清除其绑定，确保不再走优选 IP
+    if config.clear_binding_on_disable {
+        if let Some(previous) = previous {
+            clear_bindings_for_domains(&newly_disabled_domains(&previous, &config));
+        }
+    }
+    Ok(())
+}
+
+/// 清除指定域名的已有绑定（失败仅记录日志，配置已保存不回滚）
+#[cfg(feature = "tauri-runtime")]
+fn clear_bindings_for_domains(domains: &[String]) {
+    let mut cleared = false;
+    for domain in domains {
+        if hosts_ops::read_binding(domain).is_none() {
+            continue;
+        }
+        match hosts_ops::clear_binding(domain) {
+            Ok(()) => cleared = true,
+            Err(e) => eprintln!("清除已禁用端点 {} 的绑定失败: {}", domain, e),
+        }
+    }
+    if cleared {
+        if let Err(e) = hosts_ops::flush_dns() {
+            eprintln!("刷新 DNS 缓存失败: {}", e);
+        }
+    }
+}
+
+/// 设置本次会话的退出清理覆盖（None 表示恢复使用配置值），不写入配置文件
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn set_clear_on_quit_override(state: State<'_, AppState>, value: Option<bool>) {
+    *state
+        .clear_on_quit_override
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = value;
+}
+
+/// 通过 URL 添加端点（自动提取域名），返回新建的端点
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn add_endpoint_from_url(
+    state: State<'_, AppState>,
+    name: String,
+    url: String,
+) -> Result<Endpoint, String> {
+    let _guard = state.config_manager.lock_writes();
+    let mut config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let endpoint = endpoint_from_url(&name, &url, &config.endpoints)?;
+    config.endpoints.push(endpoint.clone());
     state
         .config_manager
         .save(&config)
+        .map_err(|e| e.to_string())?;
+    Ok(endpoint)
+}
+
+/// 导出完整配置到指定文件（便于迁移到其他机器）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn export_config(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    state
+        .config_manager
+        .export_to(std::path::Path::new(&path))
         .map_err(|e| e.to_string())
 }
 
+/// 从文件导入配置：校验失败或缺少 endpoints 列表时直接报错；端点按域名去重
+/// merge = true 时保留当前设置、端点及其启用状态，只追加新域名
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
-async fn start_speed_test(
+async fn import_config(
     state: State<'_, AppState>,
-    update_baseline: Option<bool>,
-) -> Result<Vec<EndpointResult>, String> {
-    // 连续测速冷却：距上次测速完成不足 3 秒时，自动等待补齐
+    path: String,
+    merge: Option<bool>,
+) -> Result<ConfigImportSummary, String> {
+    let _guard = state.config_manager.lock_writes();
+    let (config, summary) = state
+        .config_manager
+        .prepare_import(std::path::Path::new(&path), merge.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+    let config = normalize_config(config)?;
+    state
+        .config_manager
+        .save(&config)
+        .map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+/// 立即刷新端点订阅（与后台刷新共用限流）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn refresh_subscription(state: State<'_, AppState>) -> Result<SubscriptionSummary, String> {
+    subscription::refresh(&state.app_handle, &state.config_manager)
+        .await
+        .map_err(|e| e.message)
+}
+
+/// 按当前启用的端点数估算整轮测速的超时预算（秒），与 start_speed_test 使用同一估算，
+/// 供前端显示进度条
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_estimated_test_duration(state: State<'_, AppState>) -> Result<u64, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let endpoints: Vec<Endpoint> = config
+        .endpoints
+        .iter()
+        .filter(|e| e.enabled)
+        .cloned()
+        .collect();
+    // 与实际测速使用同样的测速器参数与估算方式（含端点的超时倍数）
+    Ok(EndpointTester::from_config(&config)
+        .estimate_timeout(&endpoints)
+        .as_secs())
+}
+
+/// 占用 tester 槽位（检查与写入在同一次加锁内完成）：已有测速进行中时拒绝，
+/// 避免新测速覆盖旧 tester 导致取消只停掉其中一个
+async fn begin_test_run(
+    slot: &Mutex<Option<EndpointTester>>,
+    tester: &EndpointTester,
+) -> Result<(), String> {
+    let mut current = slot.lock().await;
+    if current.is_some() {
+        return Err("测速进行中，请等待完成或先停止当前测速".into());
+    }
+    *current = Some(tester.clone());
+    Ok(())
+}
+
+/// 测速结束后释放 tester 槽位：只清除属于本次测速的 tester，不影响之后新启动的测速；
+/// 本次测速已被 stop_speed_test 取消时返回 Err，调用方不再写入结果与基准
+async fn finish_test_run(
+    slot: &Mutex<Option<EndpointTester>>,
+    tester: &EndpointTester,
+) -> Result<(), String> {
     {
-        let last = state.last_test_time.lock().await;
-        if let Some(t) = *last {
-            let elapsed = t.elapsed();
-            let cooldown = std::time::Duration::from_secs(3);
-            if elapsed < cooldown {
-                let wait = cooldown - elapsed;
-                drop(last); // 释放锁再 sleep
-                eprintln!(
-                    "[COOLDOWN] 距上次测速仅 {:.1}s，等待 {:.1}s",
-                    elapsed.as_secs_f64(),
-                    wait.as_secs_f64()
-                );
-                tokio::time::sleep(wait).await;
-            }
+        let mut current = slot.lock().await;
+        if current.as_ref().is_some_and(|t| t.is_same_run(tester)) {
+            *current = None;
         }
     }
+    if tester.is_cancelled() {
+        Err("测速已取消".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// 取消进行中的测速：run_id 为 None 时取消当前测速，否则只在运行 ID 匹配时取消；
+/// 返回是否确实取消了一次测速
+async fn cancel_test_run(slot: &Mutex<Option<EndpointTester>>, run_id: Option<u64>) -> bool {
+    let mut current = slot.lock().await;
+    let matches = current
+        .as_ref()
+        .is_some_and(|t| run_id.is_none_or(|id| t.run_id() == id));
+    if !matches {
+        return false;
+    }
+    if let Some(t) = current.take() {
+        t.cancel();
+    }
+    true
+}
+
+/// 连续测速冷却：距上次测速完成不足 3 秒时，自动等待补齐
+async fn wait_test_cooldown(last_test_time: &Mutex<Option<Instant>>) {
+    let last = last_test_time.lock().await;
+    if let Some(t) = *last {
+        let elapsed = t.elapsed();
+        let cooldown = std::time::Duration::from_secs(3);
+        if elapsed < cooldown {
+            let wait = cooldown - elapsed;
+            drop(last); // 释放锁再 sleep
+            eprintln!(
+                "[COOLDOWN] 距上次测速仅 {:.1}s，等待 {:.1}s",
+                elapsed.as_secs_f64(),
+                wait.as_secs_f64()
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// 保存测速结果：按需更新基准延迟，替换当前结果并记录完成时间（用于冷却计算）
+async fn store_test_results(
+    results: &[EndpointResult],
+    update_baseline: bool,
+    baselines: &BaselineTracker,
+    state_results: &Mutex<Vec<EndpointResult>>,
+    last_test_time: &Mutex<Option<Instant>>,
+) {
+    if update_baseline {
+        let best_by_domain = collect_best_success_by_domain(results);
+        baselines
+            .update(
+                best_by_domain
+                    .into_iter()
+                    .map(|(domain, (_, latency))| (domain, latency))
+                    .collect(),
+            )
+            .await;
+    }
+
+    *state_results.lock().await = results.to_vec();
+    *last_test_time.lock().await = Some(Instant::now());
+}
+
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn start_speed_test(
+    state: State<'_, AppState>,
+    update_baseline: Option<bool>,
+) -> Result<Vec<EndpointResult>, String> {
+    wait_test_cooldown(&state.last_test_time).await;
 
     let config = state.config_manager.load().map_err(|e| e.to_string())?;
-    let endpoints: Vec<Endpoint> = config.endpoints.into_iter().filter(|e| e.enabled).collect();
+    let endpoints: Vec<Endpoint> = config
+        .endpoints
+        .iter()
+        .filter(|e| e.enabled)
+        .cloned()
+        .collect();
 
     if endpoints.is_empty() {
         return Err("没有启用的端点".into());
@@ -176,25 +993,21 @@ async fn start_speed_test(
         config.test_count,
         Some(state.app_handle.clone()),
         strategy,
-    );
+    )
+    .with_config_options(&config);
 
-    // 保存 tester 以便取消
-    {
-        let mut t = state.tester.lock().await;
-        *t = Some(tester.clone());
-    }
+    // 保存 tester 以便取消（已有测速进行中时拒绝）
+    begin_test_run(&state.tester, &tester).await?;
 
-    // 使用动态全局超时，避免大量端点时后排任务被过早判失败
-    let workflow_timeout = estimate_test_timeout(endpoints.len());
+    // 每个端点有各自的时限；全局超时仅作兜底，按实际并发与冷却估算，不封顶
+    let workflow_timeout = tester.estimate_timeout(&endpoints);
     let test_future = tester.test_all(&endpoints);
-    let results = match tokio::time::timeout(workflow_timeout, test_future).await {
+    let mut results = match tokio::time::timeout(workflow_timeout, test_future).await {
         Ok(results) => results,
         Err(_) => {
-            // 超时，取消测试
+            // 超时：已被用户取消时按取消处理，否则取消剩余探测
+            finish_test_run(&state.tester, &tester).await?;
             tester.cancel();
-            // 清除 tester
-            let mut t = state.tester.lock().await;
-            *t = None;
             return Err(format!(
                 "测速超时（{}秒），请检查网络连接",
                 workflow_timeout.as_secs()
@@ -202,42 +1015,214 @@ async fn start_speed_test(
         }
     };
 
-    // 清除 tester
-    {
-        let mut t = state.tester.lock().await;
-        *t = None;
+    // 清除 tester；测速中途被取消则不更新结果与基准，避免随后被应用
+    finish_test_run(&state.tester, &tester).await?;
+
+    for r in results.iter_mut() {
+        r.apply_rating(&config.latency_thresholds);
+        r.apply_min_speedup(config.min_speedup_percent);
     }
 
-    if update_baseline {
-        let baselines = state.baselines.get_baselines_arc();
+    store_test_results(
+        &results,
+        update_baseline,
+        &state.baselines,
+        &state.results,
+        &state.last_test_time,
+    )
+    .await;
 
-        let best_by_domain = collect_best_success_by_domain(&results);
-        let mut b = baselines.lock().await;
-        for (domain, (_, latency)) in best_by_domain {
-            b.insert(domain, latency);
-        }
-    }
+    Ok(results)
+}
 
-    let mut state_results = state.results.lock().await;
-    *state_results = results.clone();
+/// 流式测速：立即返回运行 ID，每个端点测完即通过 speed-test-result 事件推送结果，
+/// 全部结束后推送 speed-test-finished；适合端点很多、不宜等待整批结果的列表
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn start_speed_test_streaming(
+    state: State<'_, AppState>,
+    update_baseline: Option<bool>,
+) -> Result<u64, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let endpoints: Vec<Endpoint> = config
+        .endpoints
+        .iter()
+        .filter(|e| e.enabled)
+        .cloned()
+        .collect();
 
-    // 记录测速完成时间（用于冷却计算）
-    {
-        let mut last = state.last_test_time.lock().await;
-        *last = Some(Instant::now());
+    if endpoints.is_empty() {
+        return Err("没有启用的端点".into());
     }
 
-    Ok(results)
+    let update_baseline = update_baseline.unwrap_or(true);
+
+    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
+    let tester = EndpointTester::with_app_handle_and_strategy(
+        config.preferred_ips.clone(),
+        config.test_count,
+        Some(state.app_handle.clone()),
+        strategy,
+    )
+    .with_config_options(&config);
+    let run_id = tester.run_id();
+    let thresholds = config.latency_thresholds;
+    let min_speedup_percent = config.min_speedup_percent;
+    let sink_handle = state.app_handle.clone();
+    let tester = tester.with_result_sink(Arc::new(move |result: &EndpointResult| {
+        let mut result = result.clone();
+        result.apply_rating(&thresholds);
+        result.apply_min_speedup(min_speedup_percent);
+        let _ = sink_handle.emit("speed-test-result", SpeedTestResultEvent { run_id, result });
+    }));
+
+    begin_test_run(&state.tester, &tester).await?;
+
+    let slot = state.tester.clone();
+    let state_results = state.results.clone();
+    let baselines = state.baselines.clone();
+    let last_test_time = state.last_test_time.clone();
+    let app_handle = state.app_handle.clone();
+    tokio::spawn(async move {
+        wait_test_cooldown(&last_test_time).await;
+
+        let workflow_timeout = tester.estimate_timeout(&endpoints);
+        let outcome = tokio::time::timeout(workflow_timeout, tester.test_all(&endpoints)).await;
+        let cancelled = finish_test_run(&slot, &tester).await.is_err();
+        let (result_count, error) = match outcome {
+            Ok(mut results) if !cancelled => {
+                for r in results.iter_mut() {
+                    r.apply_rating(&config.latency_thresholds);
+                    r.apply_min_speedup(config.min_speedup_percent);
+                }
+                store_test_results(
+                    &results,
+                    update_baseline,
+                    &baselines,
+                    &state_results,
+                    &last_test_time,
+                )
+                .await;
+                (results.len(), None)
+            }
+            Ok(_) => (0, None),
+            Err(_) => {
+                tester.cancel();
+                (
+                    0,
+                    Some(format!(
+                        "测速超时（{}秒），请检查网络连接",
+                        workflow_timeout.as_secs()
+                    )),
+                )
+            }
+        };
+        let _ = app_handle.emit(
+            "speed-test-finished",
+            SpeedTestFinishedEvent {
+                run_id,
+                cancelled,
+                result_count,
+                error,
+            },
+        );
+    });
+
+    Ok(run_id)
 }
 
+/// 定时全量重测的检查间隔（配置修改后最迟在一个间隔内生效）
+#[cfg(feature = "tauri-runtime")]
+const PERIODIC_RETEST_POLL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// 定时全量重测是否到期：未开启（0）或已有测速进行中时不运行，避免与手动测速重叠
+fn periodic_retest_due(
+    interval_hours: u32,
+    last_run: Instant,
+    now: Instant,
+    test_in_progress: bool,
+) -> bool {
+    interval_hours > 0
+        && !test_in_progress
+        && now.saturating_duration_since(last_run)
+            >= std::time::Duration::from_secs(u64::from(interval_hours) * 3600)
+}
+
+/// 后台定时全量重测：到期后执行完整测速并应用全部结果，完成后发射 periodic-retest-done
+/// 首次运行在启动一个完整间隔之后；测速被 stop_speed_test 取消时不应用
+#[cfg(feature = "tauri-runtime")]
+fn spawn_periodic_retest_loop(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_run = Instant::now();
+        loop {
+            tokio::time::sleep(PERIODIC_RETEST_POLL).await;
+            let state = app_handle.state::<AppState>();
+            let interval_hours = state
+                .config_manager
+                .load()
+                .map(|c| c.periodic_retest_hours)
+                .unwrap_or(0);
+            if interval_hours == 0 {
+                // 关闭期间不累计，重新开启后从此刻起算一个完整间隔
+                last_run = Instant::now();
+                continue;
+            }
+            let test_in_progress = state.tester.lock().await.is_some();
+            if !periodic_retest_due(interval_hours, last_run, Instant::now(), test_in_progress) {
+                continue;
+            }
+            last_run = Instant::now();
+
+            let outcome = match start_speed_test(app_handle.state::<AppState>(), Some(true)).await {
+                Ok(_) => apply_all_endpoints(app_handle.state::<AppState>(), None, None).await,
+                Err(e) => Err(e),
+            };
+            let event = match outcome {
+                Ok(outcome) => PeriodicRetestEvent {
+                    applied: outcome.count,
+                    error: None,
+                },
+                Err(e) => {
+                    eprintln!("定时全量重测失败: {}", e);
+                    PeriodicRetestEvent {
+                        applied: 0,
+                        error: Some(e),
+                    }
+                }
+            };
+            let _ = app_handle.emit("periodic-retest-done", event);
+        }
+    });
+}
+
+/// 设置定时全量重测间隔（小时，0 表示关闭），后台任务在下一次检查时按新间隔执行
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
-async fn stop_speed_test(state: State<'_, AppState>) -> Result<(), String> {
-    let mut tester = state.tester.lock().await;
-    if let Some(t) = tester.take() {
-        t.cancel();
+async fn schedule_periodic_retest(
+    state: State<'_, AppState>,
+    interval_hours: u32,
+) -> Result<(), String> {
+    let _guard = state.config_manager.lock_writes();
+    let mut config = state.config_manager.load().map_err(|e| e.to_string())?;
+    config.periodic_retest_hours = interval_hours;
+    state
+        .config_manager
+        .save(&config)
+        .map_err(|e| e.to_string())
+}
+
+/// 停止进行中的测速，返回是否确实取消了一次测速
+/// run_id: 只停止指定的流式测速（与当前测速不符时不做任何事）；不传则停止当前测速
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn stop_speed_test(state: State<'_, AppState>, run_id: Option<u64>) -> Result<bool, String> {
+    if !cancel_test_run(&state.tester, run_id).await {
+        // 没有进行中的（匹配的）测速
+        return Ok(false);
     }
-    Ok(())
+    // 通知前端重置进行中的进度显示
+    let _ = state.app_handle.emit("speed-test-cancelled", ());
+    Ok(true)
 }
 
 #[cfg(feature = "tauri-runtime")]
@@ -248,28 +1233,177 @@ async fn apply_endpoint(
     ip: String,
     latency: Option<f64>,
 ) -> Result<(), String> {
-    if hosts_ops::read_binding(&domain).as_deref() == Some(ip.as_str()) {
+    // 未传入延迟时，使用最近测速结果中该 IP 的延迟作为新基准
+    let latency = match latency {
+        Some(l) => Some(l),
+        None => {
+            let results = state.results.lock().await;
+            find_result_latency(&results, &domain, &ip)
+        }
+    };
+
+    if hosts_ops::read_binding_ips(&domain) == [ip.as_str()] {
         if let Some(latency) = latency {
-            let baselines = state.baselines.get_baselines_arc();
-            let mut b = baselines.lock().await;
-            b.insert(domain, latency);
+            state.baselines.update(vec![(domain, latency)]).await;
         }
         return Ok(());
     }
 
     hosts_ops::write_binding(&domain, &ip).map_err(|e| e.to_string())?;
+    let deconflicted = match state.config_manager.load() {
+        Ok(config) => deconflict_if_enabled(&config),
+        Err(_) => Ok(()),
+    };
     hosts_ops::flush_dns().map_err(|e| e.to_string())?;
     if let Some(latency) = latency {
-        let baselines = state.baselines.get_baselines_arc();
-        let mut b = baselines.lock().await;
-        b.insert(domain.clone(), latency);
+        state.baselines.update(vec![(domain, latency)]).await;
     }
-    Ok(())
+    deconflicted
+}
+
+/// 应用单个域名的最优测速 IP 并实测前后对比：
+/// 先探测当前绑定（无绑定时为原始 DNS IP），写入最优 IP 并刷新 DNS 后再次探测，结果记入历史
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn apply_and_measure(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<ApplyMeasurement, String> {
+    let domain = domain.trim().to_lowercase();
+    hosts_manager::validate_domain(&domain).map_err(|e| e.to_string())?;
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+
+    // 与"全部应用"一致：最优 IP 之后按 ips_per_domain 追加备用 IP
+    let (best, bindings) = {
+        let results = state.results.lock().await;
+        let best = results
+            .iter()
+            .filter(|r| r.success && r.endpoint.domain.eq_ignore_ascii_case(&domain))
+            .min_by(|a, b| {
+                a.latency
+                    .partial_cmp(&b.latency)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .cloned()
+            .ok_or_else(|| format!("{} 没有成功的测速结果，请先测速", domain))?;
+        let bindings = expand_domain_bindings(
+            &results,
+            &best.endpoint.domain,
+            &best.ip,
+            config.ips_per_domain,
+        );
+        (best, bindings)
+    };
+    // 之后的 hosts 读写与基准都以端点配置中的域名为准
+    let domain = best.endpoint.domain.clone();
+
+    let before_ip = hosts_ops::read_binding(&domain)
+        .or_else(|| Some(best.original_ip.clone()).filter(|ip| !ip.is_empty()))
+        .ok_or_else(|| format!("无法确定 {} 当前使用的 IP", domain))?;
+
+    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
+    let tester = EndpointTester::with_strategy(vec![], config.test_count, strategy)
+        .with_config_options(&config);
+    let probe_timeout = std::time::Duration::from_secs(30);
+
+    let before = tokio::time::timeout(probe_timeout, tester.test_ip(&best.endpoint, before_ip))
+        .await
+        .map_err(|_| "应用前探测超时（30秒），请检查网络连接".to_string())?;
+
+    hosts_ops::write_bindings_batch(&bindings, false).map_err(|e| e.to_string())?;
+    let deconflicted = deconflict_if_enabled(&config);
+    hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+
+    let after = tokio::time::timeout(probe_timeout, tester.test_ip(&best.endpoint, best.ip))
+        .await
+        .map_err(|_| "应用后探测超时（30秒），绑定已写入".to_string())?;
+
+    let measurement = summarize_apply_measurement(&domain, &before, &after);
+    if let (Some(after_ms), Some(improvement)) =
+        (measurement.after_ms, measurement.improvement_percent)
+    {
+        state.baselines.update(vec![(domain, after_ms)]).await;
+        let record = HistoryRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+            domain: extract_target_domain(&best.endpoint),
+            original_latency: measurement.before_ms.unwrap_or_default(),
+            optimized_latency: after_ms,
+            speedup_percent: improvement,
+            applied: true,
+        };
+        if let Err(e) = state.history_manager.add_records(vec![record]) {
+            eprintln!("Failed to save history: {}", e);
+        }
+    }
+    deconflicted?;
+    Ok(measurement)
 }
 
+/// 将所有基准延迟重置为最近一次测速的延迟
+/// 手动更换绑定后调用，避免健康检查拿旧基准误判新 IP "变慢"
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
-async fn apply_all_endpoints(state: State<'_, AppState>) -> Result<u32, String> {
+async fn reset_baselines_to_current(state: State<'_, AppState>) -> Result<u32, String> {
+    let results_snapshot = {
+        let results = state.results.lock().await;
+        results.clone()
+    };
+
+    let mut updates: Vec<(String, f64)> = Vec::new();
+    for (domain, (_, best_latency)) in collect_best_success_by_domain(&results_snapshot) {
+        // 优先使用当前实际绑定 IP 的延迟
+        let latency = hosts_ops::read_binding(&domain)
+            .and_then(|bound_ip| find_result_latency(&results_snapshot, &domain, &bound_ip))
+            .unwrap_or(best_latency);
+        updates.push((domain, latency));
+    }
+
+    let count = updates.len() as u32;
+    state.baselines.update(updates).await;
+
+    Ok(count)
+}
+
+/// 预览"全部应用"将对 hosts 文件产生的修改（unified diff），不写入任何内容
+/// 无变化时返回空字符串
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn preview_apply_all(state: State<'_, AppState>) -> Result<String, String> {
+    let results_snapshot = {
+        let results = state.results.lock().await;
+        results.clone()
+    };
+
+    // 与 apply_all_endpoints 使用相同的配置和规划，预览的就是实际会写入的绑定
+    let config = state.config_manager.load().ok();
+    let ips_per_domain = config.as_ref().map(|c| c.ips_per_domain).unwrap_or(1);
+    let min_speedup_percent = config.as_ref().map_or(0.0, |c| c.min_speedup_percent);
+    let plan = plan_apply_all(
+        &results_snapshot,
+        ips_per_domain,
+        min_speedup_percent,
+        0,
+        hosts_ops::read_binding_ips,
+    );
+    if plan.bindings.is_empty() {
+        return Ok(String::new());
+    }
+
+    hosts_manager::HostsManager::preview_bindings_batch(&plan.bindings).map_err(|e| e.to_string())
+}
+
+/// force: 覆盖已被外部修改的 anyFAST 区块（用户确认后重试时传入）
+/// dry_run: 仅返回将要写入的绑定（已是最优的域名不计入），不写 hosts、不更新基准和历史
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn apply_all_endpoints(
+    state: State<'_, AppState>,
+    force: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ApplyAllOutcome, String> {
     // 尽早 clone 并释放 results 锁，避免长时间持有
     let results_snapshot = {
         let results = state.results.lock().await;
@@ -282,83 +1416,197 @@ async fn apply_all_endpoints(state: State<'_, AppState>) -> Result<u32, String>
         .map(|d| d.as_secs() as i64)
         .unwrap_or(0);
 
-    // 获取 baselines arc
-    let baselines = state.baselines.get_baselines_arc();
+    let config = state.config_manager.load().ok();
+    let ips_per_domain = config.as_ref().map(|c| c.ips_per_domain).unwrap_or(1);
+    let min_speedup_percent = config.as_ref().map_or(0.0, |c| c.min_speedup_percent);
 
-    // 收集所有成功的端点绑定（按 domain 去重，取最优结果）
-    let best_by_domain = collect_best_success_by_domain(&results_snapshot);
-    let mut bindings: Vec<HostsBinding> = Vec::with_capacity(best_by_domain.len());
-    let mut history_records: Vec<HistoryRecord> = Vec::new();
-    let mut baseline_updates: Vec<(String, f64)> = Vec::with_capacity(best_by_domain.len());
+    let plan = plan_apply_all(
+        &results_snapshot,
+        ips_per_domain,
+        min_speedup_percent,
+        now,
+        hosts_ops::read_binding_ips,
+    );
+    if plan.kept_count > 0 {
+        eprintln!("{} 个域名当前绑定已是最优，保持不变", plan.kept_count);
+    }
 
-    for r in results_snapshot.iter().filter(|r| r.success) {
-        // 只记录有实际加速效果的历史（跳过原始==优化的无效记录）
-        if r.speedup_percent.abs() > 0.1 {
-            history_records.push(HistoryRecord {
-                timestamp: now,
-                domain: extract_target_domain(&r.endpoint),
-                original_latency: r.original_latency,
-                optimized_latency: r.latency,
-                speedup_percent: r.speedup_percent,
-                applied: true,
-            });
+    // dry_run：只返回计划，不写 hosts、不刷新 DNS、不记录历史、不启动持续优化
+    run_apply_plan(plan, dry_run.unwrap_or(false), |plan| async {
+        let ApplyPlan {
+            bindings,
+            history_records,
+            baseline_updates,
+            ..
+        } = plan;
+
+        // 批量更新基准延迟
+        state.baselines.update(baseline_updates).await;
+
+        // 保存历史记录
+        if let Err(e) = state.history_manager.add_records(history_records) {
+            eprintln!("Failed to save history: {}", e);
         }
-    }
 
-    for (domain, (ip, latency)) in best_by_domain {
-        bindings.push(HostsBinding {
-            domain: domain.clone(),
-            ip,
-        });
-        baseline_updates.push((domain, latency));
-    }
-    bindings = filter_changed_bindings(bindings);
+        if bindings.is_empty() {
+            return Ok(0);
+        }
 
-    // 批量更新基准延迟
-    {
-        let mut b = baselines.lock().await;
-        for (domain, latency) in baseline_updates {
-            b.insert(domain, latency);
+        // Apply all bindings in a single file operation
+        // count 为实际新增或修改的绑定数；全部未变时不刷新 DNS，避免打断现有连接
+        let count = hosts_ops::write_bindings_batch(&bindings, force.unwrap_or(false))
+            .map_err(|e| e.to_string())?;
+        let deconflicted = config.as_ref().map_or(Ok(()), deconflict_if_enabled);
+        if count > 0 {
+            hosts_ops::flush_dns().map_err(|e| e.to_string())?;
         }
-    }
+        last_apply::LastApplyStore::new().record(now, count);
 
-    // 保存历史记录
-    if let Err(e) = state.history_manager.add_records(history_records) {
-        eprintln!("Failed to save history: {}", e);
-    }
+        // 如果持续优化模式开启且有绑定，自动启动后台任务
+        if count > 0 {
+            start_continuous_if_enabled(&state).await?;
+        }
 
-    if bindings.is_empty() {
-        return Ok(0);
+        deconflicted.map(|()| count as u32)
+    })
+    .await
+}
+
+/// 开启 deconflict_hosts 时注释掉 anyFAST 区块外的重复绑定
+/// 失败时返回错误（绑定已写入，但被区块外的行抢先生效，需要让用户知道）
+#[cfg(feature = "tauri-runtime")]
+fn deconflict_if_enabled(config: &AppConfig) -> Result<(), String> {
+    if !config.deconflict_hosts {
+        return Ok(());
+    }
+    match hosts_ops::deconflict_bindings() {
+        Ok(0) => Ok(()),
+        Ok(n) => {
+            eprintln!("已注释 {} 行 anyFAST 区块外的重复绑定", n);
+            Ok(())
+        }
+        Err(e) => Err(format!("绑定已写入，但注释区块外的重复绑定失败: {}", e)),
     }
+}
 
-    // Apply all bindings in a single file operation
-    let count = hosts_ops::write_bindings_batch(&bindings).map_err(|e| e.to_string())?;
-    hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+/// 列出 anyFAST 区块外绑定了受管域名的 hosts 行（这些行会抢先生效，导致优选 IP 不起作用）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn get_hosts_conflicts() -> Vec<String> {
+    hosts_ops::find_hosts_conflicts()
+}
 
-    // 如果持续优化模式开启且有绑定，自动启动后台任务
+/// 注释掉 anyFAST 区块外的重复绑定（原行保留为注释），返回处理的行数
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn deconflict_hosts() -> Result<u32, String> {
+    let count = hosts_ops::deconflict_bindings().map_err(|e| e.to_string())?;
     if count > 0 {
-        let config = state.config_manager.load().map_err(|e| e.to_string())?;
-        if config.continuous_mode {
-            let mut hc = state.health_checker.lock().await;
-            // 先停止旧实例（如果存在但已结束也清理掉）
-            if let Some(old) = hc.as_mut() {
-                if !old.is_running() {
-                    // 已结束，清理
-                } else {
-                    old.stop().await;
-                }
+        hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+    }
+    Ok(count as u32)
+}
+
+/// 恢复被 deconflict_hosts 注释掉的原始行，返回恢复的行数
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn restore_deconflicted_hosts() -> Result<u32, String> {
+    let count = hosts_ops::restore_deconflicted().map_err(|e| e.to_string())?;
+    if count > 0 {
+        hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+    }
+    Ok(count as u32)
+}
+
+/// 持续优化模式开启时（重新）启动后台任务
+#[cfg(feature = "tauri-runtime")]
+async fn start_continuous_if_enabled(state: &AppState) -> Result<(), String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    if config.continuous_mode {
+        let mut hc = state.health_checker.lock().await;
+        // 先停止旧实例（如果存在但已结束也清理掉）
+        if let Some(old) = hc.as_mut() {
+            if !old.is_running() {
+                // 已结束，清理
+            } else {
+                old.stop().await;
             }
-            let checker = HealthChecker::start(
-                state.app_handle.clone(),
-                state.config_manager.clone(),
-                state.results.clone(),
-                state.baselines.get_baselines_arc(),
-            );
-            *hc = Some(checker);
         }
+        let checker = HealthChecker::start(
+            state.app_handle.clone(),
+            state.config_manager.clone(),
+            state.results.clone(),
+            state.baselines.clone(),
+        );
+        *hc = Some(checker);
     }
+    Ok(())
+}
 
-    Ok(count as u32)
+/// 逐域名应用全部测速结果：单个域名校验或写入失败不影响其他域名（适合含异常条目的列表）
+/// 返回每个待写入域名的结果；基准延迟与历史记录只针对写入成功的域名
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn apply_all_best_effort(
+    state: State<'_, AppState>,
+    force: Option<bool>,
+) -> Result<Vec<BindingWriteOutcome>, String> {
+    let results_snapshot = {
+        let results = state.results.lock().await;
+        results.clone()
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (ips_per_domain, min_speedup_percent) = state
+        .config_manager
+        .load()
+        .map(|c| (c.ips_per_domain, c.min_speedup_percent))
+        .unwrap_or((1, 0.0));
+
+    let plan = plan_apply_all(
+        &results_snapshot,
+        ips_per_domain,
+        min_speedup_percent,
+        now,
+        hosts_ops::read_binding_ips,
+    );
+    let (outcomes, changed) =
+        hosts_ops::write_bindings_best_effort(plan.bindings, force.unwrap_or(false))
+            .map_err(|e| e.to_string())?;
+    if changed > 0 {
+        hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+    }
+    if outcomes.iter().any(|o| o.ok) {
+        last_apply::LastApplyStore::new().record(now, changed);
+    }
+
+    // 保持不变的域名（未出现在 outcomes 中）视为成功
+    let failed: HashSet<&str> = outcomes
+        .iter()
+        .filter(|o| !o.ok)
+        .map(|o| o.domain.as_str())
+        .collect();
+    let baseline_updates = plan
+        .baseline_updates
+        .into_iter()
+        .filter(|(domain, _)| !failed.contains(domain.as_str()))
+        .collect();
+    state.baselines.update(baseline_updates).await;
+    let history_records = plan
+        .history_records
+        .into_iter()
+        .filter(|r| !failed.contains(r.domain.as_str()))
+        .collect();
+    if let Err(e) = state.history_manager.add_records(history_records) {
+        eprintln!("Failed to save history: {}", e);
+    }
+
+    if changed > 0 {
+        start_continuous_if_enabled(&state).await?;
+    }
+    Ok(outcomes)
 }
 
 #[cfg(feature = "tauri-runtime")]
@@ -402,8 +1650,58 @@ async fn get_bindings(state: State<'_, AppState>) -> Result<Vec<(String, Option<
         let ip = hosts_ops::read_binding(&endpoint.domain);
         bindings.push((endpoint.domain, ip));
     }
-
-    Ok(bindings)
+
+    Ok(bindings)
+}
+
+/// 一次性读取所有 anyFAST 绑定 (domain, ip)，避免逐个域名重复读取 hosts 文件
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_all_anyfast_bindings() -> Result<Vec<(String, String)>, String> {
+    Ok(hosts_ops::get_all_anyfast_bindings())
+}
+
+/// 将当前 anyFAST hosts 块（BEGIN..END）导出到指定文件，不修改系统 hosts
+/// 返回导出的绑定数量
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn export_hosts_block(path: String) -> Result<usize, String> {
+    hosts_manager::HostsManager::export_block(std::path::Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+/// 以 hosts 格式返回当前 anyFAST 块（含起止标记），用于分享或备份
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn export_bindings_hosts() -> Result<String, String> {
+    hosts_manager::HostsManager::read_block().map_err(|e| e.to_string())
+}
+
+/// 以 (域名, IP) 列表返回当前 anyFAST 绑定
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn export_bindings_json() -> Result<Vec<(String, String)>, String> {
+    Ok(hosts_ops::get_all_anyfast_bindings())
+}
+
+/// 导入 hosts 格式片段中的绑定：逐条校验 IP 与域名，跳过无效条目后批量写入
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn import_bindings_hosts(content: String) -> Result<BindingsImportSummary, String> {
+    let (bindings, skipped) = hosts_manager::HostsManager::parse_snippet(&content);
+    if bindings.is_empty() {
+        return Err("片段中没有有效的 hosts 绑定".into());
+    }
+
+    let changed = hosts_ops::write_bindings_batch(&bindings, false).map_err(|e| e.to_string())?;
+    if changed > 0 {
+        hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+    }
+    Ok(BindingsImportSummary {
+        imported_bindings: bindings.len() as u32,
+        changed_bindings: changed as u32,
+        skipped_invalid: skipped as u32,
+    })
 }
 
 #[cfg(feature = "tauri-runtime")]
@@ -428,6 +1726,13 @@ fn check_admin() -> bool {
     has_permission
 }
 
+/// Whether the GUI process itself is elevated (admin / root), regardless of the service or helper
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn is_process_elevated() -> bool {
+    hosts_ops::is_process_elevated()
+}
+
 /// Check if the hosts service is running
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
@@ -435,6 +1740,27 @@ fn is_service_running() -> bool {
     hosts_ops::is_service_running()
 }
 
+/// Ping the hosts service and report why its pipe is unusable (timeout, access denied, not running)
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn check_service_pipe_access() -> ServicePipeAccess {
+    hosts_ops::check_service_pipe_access()
+}
+
+/// 检查 hosts 文件编码：UTF-16 或非 UTF-8 文件会被拒绝写入，需先转换为 UTF-8
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn check_hosts_encoding() -> Result<HostsEncodingReport, String> {
+    let encoding = hosts_ops::detect_hosts_encoding().map_err(|e| e.to_string())?;
+    let message = (!encoding.is_supported())
+        .then(|| hosts_manager::HostsError::UnsupportedEncoding(encoding.label()).to_string());
+    Ok(HostsEncodingReport {
+        encoding: encoding.label().to_string(),
+        supported: encoding.is_supported(),
+        message,
+    })
+}
+
 /// Get permission status as a structured object
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
@@ -513,6 +1839,13 @@ async fn install_macos_helper() -> Result<bool, String> {
     }
 }
 
+/// Check if the installed macOS helper differs from the bundled one (needs reinstall after app update)
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn is_macos_helper_outdated() -> bool {
+    hosts_ops::is_macos_helper_outdated()
+}
+
 /// Check if bundled helper exists (for showing install button)
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
@@ -570,15 +1903,142 @@ async fn open_hosts_file() -> Result<(), String> {
     }
 }
 
+/// 日志文件路径（供前端显示）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_log_path() -> Result<String, String> {
+    Ok(app_log::log_path().to_string_lossy().to_string())
+}
+
+/// 数据目录（配置、历史、缓存与日志所在位置，供前端显示）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_data_dir() -> Result<String, String> {
+    Ok(config::data_dir().to_string_lossy().to_string())
+}
+
+/// 用系统文本编辑器打开日志文件（尚无日志时先创建空文件）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn open_log_file() -> Result<(), String> {
+    let path = app_log::log_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("无法创建日志目录: {}", e))?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("无法打开日志文件: {}", e))?;
+
+    #[cfg(windows)]
+    {
+        use std::process::Command;
+        Command::new("notepad")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| format!("无法打开日志文件: {}", e))?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+        Command::new("open")
+            .arg("-t")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| format!("无法打开日志文件: {}", e))?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::process::Command;
+        Command::new("xdg-open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| format!("无法打开日志文件: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_history_stats(
+    state: State<'_, AppState>,
+    hours: u32,
+    limit: Option<u32>,
+) -> Result<HistoryStats, String> {
+    state
+        .history_manager
+        .get_stats(hours, limit.unwrap_or(history::DEFAULT_STATS_RECORD_LIMIT))
+        .map_err(|e| e.to_string())
+}
+
+/// 根据近 24 小时持续优化的实际切换次数与最近测速的失败率推荐持续优化检查间隔
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
-async fn get_history_stats(state: State<'_, AppState>, hours: u32) -> Result<HistoryStats, String> {
+async fn suggest_check_interval(
+    state: State<'_, AppState>,
+) -> Result<CheckIntervalSuggestion, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let domains = config.endpoints.iter().filter(|e| e.enabled).count().max(1);
+    // 历史记录包含每次测速和未改变 IP 的应用，不能代表切换次数
+    let switches = health_checker::recent_switch_count(chrono::Utc::now().timestamp());
+    let window_hours = health_checker::SWITCH_LOG_WINDOW_SECS as f64 / 3600.0;
+    let switches_per_hour = switches as f64 / (window_hours * domains as f64);
+
+    let failure_rate = {
+        let results = state.results.lock().await;
+        let tested: Vec<&EndpointResult> = results.iter().filter(|r| !r.skipped).collect();
+        if tested.is_empty() {
+            0.0
+        } else {
+            tested.iter().filter(|r| !r.success).count() as f64 / tested.len() as f64
+        }
+    };
+
+    Ok(health_checker::suggest_check_interval(
+        switches_per_hour,
+        failure_rate,
+    ))
+}
+
+/// 单个域名的延迟历史（按时间升序），hours 为 0 表示全部
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_domain_history(
+    state: State<'_, AppState>,
+    domain: String,
+    hours: u32,
+) -> Result<DomainHistory, String> {
+    state
+        .history_manager
+        .get_domain_history(&domain, hours)
+        .map_err(|e| e.to_string())
+}
+
+/// 按 UTC 自然日汇总最近 days 天（含今天）的历史，0 表示全部，供图表使用
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_history_daily(
+    state: State<'_, AppState>,
+    days: u32,
+) -> Result<Vec<DailyHistory>, String> {
     state
         .history_manager
-        .get_stats(hours)
+        .get_daily(days)
         .map_err(|e| e.to_string())
 }
 
+/// 历史记录存储概况：记录数、文件大小与时间范围
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_history_info(state: State<'_, AppState>) -> Result<HistoryInfo, String> {
+    state.history_manager.get_info().map_err(|e| e.to_string())
+}
+
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
 async fn clear_history(state: State<'_, AppState>) -> Result<(), String> {
@@ -614,6 +2074,35 @@ async fn unbind_endpoint(state: State<'_, AppState>, domain: String) -> Result<(
     Ok(())
 }
 
+/// 临时停用某个域名的绑定（在 anyFAST 区块内注释掉，可用 resume_binding 恢复），便于对比效果
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn suspend_binding(domain: String) -> Result<bool, String> {
+    let suspended = hosts_ops::suspend_binding(&domain).map_err(|e| e.to_string())?;
+    if suspended {
+        hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+    }
+    Ok(suspended)
+}
+
+/// 恢复被 suspend_binding 停用的绑定
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn resume_binding(domain: String) -> Result<bool, String> {
+    let resumed = hosts_ops::resume_binding(&domain).map_err(|e| e.to_string())?;
+    if resumed {
+        hosts_ops::flush_dns().map_err(|e| e.to_string())?;
+    }
+    Ok(resumed)
+}
+
+/// 当前被临时停用的域名
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_suspended_domains() -> Result<Vec<String>, String> {
+    Ok(hosts_ops::get_suspended_domains())
+}
+
 /// 检查是否有活跃的 hosts 绑定
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
@@ -645,7 +2134,7 @@ async fn start_continuous_optimization(state: State<'_, AppState>) -> Result<(),
         state.app_handle.clone(),
         state.config_manager.clone(),
         state.results.clone(),
-        state.baselines.get_baselines_arc(),
+        state.baselines.clone(),
     );
     *hc = Some(checker);
     Ok(())
@@ -671,6 +2160,52 @@ async fn is_continuous_optimization_running(state: State<'_, AppState>) -> Resul
     Ok(hc.as_ref().is_some_and(|h| h.is_running()))
 }
 
+/// 暂停持续优化：保留后台任务与失败计数等状态，仅跳过检查
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn pause_continuous_optimization(state: State<'_, AppState>) -> Result<(), String> {
+    let hc = state.health_checker.lock().await;
+    match hc.as_ref().filter(|h| h.is_running()) {
+        Some(checker) => {
+            checker.pause();
+            Ok(())
+        }
+        None => Err("持续优化未运行".into()),
+    }
+}
+
+/// 恢复已暂停的持续优化
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn resume_continuous_optimization(state: State<'_, AppState>) -> Result<(), String> {
+    let hc = state.health_checker.lock().await;
+    match hc.as_ref().filter(|h| h.is_running()) {
+        Some(checker) => {
+            checker.resume();
+            Ok(())
+        }
+        None => Err("持续优化未运行".into()),
+    }
+}
+
+/// 查询持续优化的运行/暂停状态
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_continuous_optimization_status(
+    state: State<'_, AppState>,
+) -> Result<OptimizationStatus, String> {
+    let hc = state.health_checker.lock().await;
+    Ok(hc.as_ref().map(|h| h.status()).unwrap_or_default())
+}
+
+/// 取消持续优化当前这一轮进行中的测速（后台任务继续运行），返回是否有测速被取消
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn cancel_health_check(state: State<'_, AppState>) -> Result<bool, String> {
+    let hc = state.health_checker.lock().await;
+    Ok(hc.as_ref().is_some_and(|h| h.cancel_in_flight()))
+}
+
 // ===== 单端点测速命令 =====
 
 /// 单独测试一个端点，返回测速结果并更新状态
@@ -679,6 +2214,42 @@ async fn is_continuous_optimization_running(state: State<'_, AppState>) -> Resul
 async fn test_single_endpoint(
     state: State<'_, AppState>,
     endpoint: Endpoint,
+) -> Result<EndpointResult, String> {
+    run_single_endpoint_test(&state, endpoint).await
+}
+
+/// 按域名重新测速配置中的单个端点，原位替换结果并刷新基准延迟
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn retest_endpoint(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<EndpointResult, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let endpoint = config
+        .endpoints
+        .into_iter()
+        .find(|e| e.domain == domain)
+        .ok_or_else(|| format!("未找到端点: {}", domain))?;
+
+    run_single_endpoint_test(&state, endpoint).await
+}
+
+/// 诊断任意 URL：解析 DNS 后对首个 IP 测一次连接 / TLS / 首字节耗时，不添加为端点
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn probe_url(state: State<'_, AppState>, url: String) -> Result<UrlProbeResult, String> {
+    let target = probe_target_from_url(&url)?;
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let tester = EndpointTester::new(Vec::new(), 1).with_config_options(&config);
+    tester.probe_once(&target).await
+}
+
+/// 单端点测速：更新全局结果列表与基准延迟
+#[cfg(feature = "tauri-runtime")]
+async fn run_single_endpoint_test(
+    state: &AppState,
+    endpoint: Endpoint,
 ) -> Result<EndpointResult, String> {
     let config = state.config_manager.load().map_err(|e| e.to_string())?;
     let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
@@ -687,42 +2258,197 @@ async fn test_single_endpoint(
         config.test_count,
         Some(state.app_handle.clone()),
         strategy,
-    );
+    )
+    .with_config_options(&config);
 
     // 使用 30 秒超时防止永久卡住
-    let result = match tokio::time::timeout(
+    let mut result = match tokio::time::timeout(
         std::time::Duration::from_secs(30),
         tester.test_endpoint(&endpoint),
     )
-    .await
-    {
-        Ok(result) => result,
-        Err(_) => {
-            return Err("单端点测速超时（30秒），请检查网络连接".into());
-        }
-    };
+    .await
+    {
+        Ok(result) => result,
+        Err(_) => {
+            return Err("单端点测速超时（30秒），请检查网络连接".into());
+        }
+    };
+    result.apply_rating(&config.latency_thresholds);
+    result.apply_min_speedup(config.min_speedup_percent);
+
+    // 更新全局结果列表中该端点的结果
+    {
+        let mut state_results = state.results.lock().await;
+        if let Some(existing) = state_results
+            .iter_mut()
+            .find(|r| r.endpoint.domain == endpoint.domain)
+        {
+            *existing = result.clone();
+        } else {
+            state_results.push(result.clone());
+        }
+    }
+
+    // 如果测速成功，更新基准延迟
+    if result.success {
+        state
+            .baselines
+            .update(vec![(endpoint.domain.clone(), result.latency)])
+            .await;
+    }
+
+    Ok(result)
+}
+
+/// 用指定 IP 测试一个域名（不做优选、不更新结果与基准），用于加入白名单前验证候选 IP
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn test_custom_ip(
+    state: State<'_, AppState>,
+    domain: String,
+    ip: String,
+) -> Result<EndpointResult, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let (endpoint, ip) = custom_ip_target(&domain, &ip, &config.endpoints)?;
+
+    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
+    let tester = EndpointTester::with_strategy(vec![], config.test_count, strategy)
+        .with_config_options(&config);
+
+    let mut result = tokio::time::timeout(
+        std::time::Duration::from_secs(30),
+        tester.test_ip(&endpoint, ip),
+    )
+    .await
+    .map_err(|_| "IP 测试超时（30秒），请检查网络连接".to_string())?;
+    result.apply_rating(&config.latency_thresholds);
+    Ok(result)
+}
+
+// ===== 绑定连通性检查 =====
+
+/// 快速检查每个已应用绑定的 IP 当前是否可用（单 IP 测试，不做优选）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn verify_bindings_health(state: State<'_, AppState>) -> Result<Vec<BindingHealth>, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let bound: Vec<(Endpoint, String)> = config
+        .endpoints
+        .iter()
+        .filter_map(|ep| hosts_ops::read_binding(&ep.domain).map(|ip| (ep.clone(), ip)))
+        .collect();
+
+    if bound.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
+    let tester = EndpointTester::with_strategy(Vec::new(), 1, strategy);
+
+    let mut join_set = tokio::task::JoinSet::new();
+    for (ep, ip) in bound {
+        let tester = tester.clone();
+        join_set.spawn(async move {
+            let result = tester.test_ip(&ep, ip).await;
+            BindingHealth::from_result(ep.domain.clone(), &result)
+        });
+    }
+
+    let mut report = Vec::new();
+    while let Some(joined) = join_set.join_next().await {
+        if let Ok(health) = joined {
+            report.push(health);
+        }
+    }
+    report.sort_by(|a, b| a.domain.cmp(&b.domain));
+    Ok(report)
+}
+
+// ===== 候选 IP 预览 =====
+
+/// 预览某域名将被测试的候选 IP（仅解析与合并，不做探测）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_candidate_ips(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<CandidateIps, String> {
+    let domain = domain.trim().to_string();
+    if domain.is_empty() {
+        return Err("域名不能为空".into());
+    }
+
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
+    let tester =
+        EndpointTester::with_strategy(config.preferred_ips.clone(), config.test_count, strategy)
+            .with_config_options(&config);
+
+    tester.preview_candidate_ips(&domain).await
+}
+
+/// 测试各公共 DNS 解析器对指定域名的响应耗时与返回 IP，按延迟排序返回
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn benchmark_dns_servers(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<Vec<DnsBenchmarkResult>, String> {
+    let domain = domain.trim().to_lowercase();
+    hosts_manager::validate_domain(&domain).map_err(|e| e.to_string())?;
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    Ok(endpoint_tester::benchmark_dns_servers(&domain, config.ip_version_preference).await)
+}
+
+/// 查询各公共 DNS 解析器对指定域名返回的 IP（解析器地址 → IP 列表）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn diagnose_multi_dns(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let domain = domain.trim().to_lowercase();
+    hosts_manager::validate_domain(&domain).map_err(|e| e.to_string())?;
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    Ok(endpoint_tester::diagnose_multi_dns(&domain, config.ip_version_preference).await)
+}
+
+/// 基准测试一组 CF IP（ips 为空时使用当前候选源），按延迟排序返回
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn benchmark_cf_ips(
+    state: State<'_, AppState>,
+    domain: String,
+    ips: Option<Vec<String>>,
+) -> Result<Vec<EndpointResult>, String> {
+    let config = state.config_manager.load().map_err(|e| e.to_string())?;
+    let endpoint = config
+        .endpoints
+        .iter()
+        .find(|e| e.domain == domain)
+        .cloned()
+        .ok_or_else(|| format!("未找到端点: {}", domain))?;
+    let ips = normalize_preferred_ips(ips.unwrap_or_default());
+
+    let strategy = TestStrategy::from_aggressiveness(config.test_aggressiveness);
+    let tester = EndpointTester::with_app_handle_and_strategy(
+        config.preferred_ips.clone(),
+        config.test_count,
+        Some(state.app_handle.clone()),
+        strategy,
+    )
+    .with_config_options(&config);
 
-    // 更新全局结果列表中该端点的结果
-    {
-        let mut state_results = state.results.lock().await;
-        if let Some(existing) = state_results
-            .iter_mut()
-            .find(|r| r.endpoint.domain == endpoint.domain)
-        {
-            *existing = result.clone();
-        } else {
-            state_results.push(result.clone());
-        }
-    }
+    begin_test_run(&state.tester, &tester).await?;
+    let mut results = tester
+        .benchmark_cf_ips(&endpoint, ips, config.benchmark_concurrency)
+        .await;
+    finish_test_run(&state.tester, &tester).await?;
 
-    // 如果测速成功，更新基准延迟
-    if result.success {
-        let baselines = state.baselines.get_baselines_arc();
-        let mut b = baselines.lock().await;
-        b.insert(endpoint.domain.clone(), result.latency);
+    for r in results.iter_mut() {
+        r.apply_rating(&config.latency_thresholds);
     }
-
-    Ok(result)
+    Ok(results)
 }
 
 // ===== 获取当前测速结果 =====
@@ -736,6 +2462,58 @@ async fn get_current_results(state: State<'_, AppState>) -> Result<Vec<EndpointR
     Ok(results.clone())
 }
 
+/// 最近一次成功"全部应用"的时间与写入数量（从未应用过时为 None）
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_last_apply_info() -> Result<Option<LastApplyInfo>, String> {
+    Ok(last_apply::LastApplyStore::new().load())
+}
+
+/// 按指定键重新排序已有测速结果（不重新测速），结果写回 state 并返回
+/// 失败的端点始终排在最后，保持原有相对顺序
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn sort_results(
+    state: State<'_, AppState>,
+    key: ResultSortKey,
+    ascending: bool,
+) -> Result<Vec<EndpointResult>, String> {
+    let mut results = state.results.lock().await;
+    sort_results_by(&mut results, key, ascending);
+    Ok(results.clone())
+}
+
+fn sort_results_by(results: &mut [EndpointResult], key: ResultSortKey, ascending: bool) {
+    results.sort_by(|a, b| {
+        // 失败端点分组到末尾，不参与排序方向
+        match (a.success, b.success) {
+            (true, false) => return std::cmp::Ordering::Less,
+            (false, true) => return std::cmp::Ordering::Greater,
+            (false, false) => return std::cmp::Ordering::Equal,
+            (true, true) => {}
+        }
+        let ordering = match key {
+            ResultSortKey::Latency => a.latency.total_cmp(&b.latency),
+            ResultSortKey::Speedup => a.speedup_percent.total_cmp(&b.speedup_percent),
+            ResultSortKey::Name => a
+                .endpoint
+                .name
+                .to_lowercase()
+                .cmp(&b.endpoint.name.to_lowercase()),
+            ResultSortKey::Domain => a
+                .endpoint
+                .domain
+                .to_lowercase()
+                .cmp(&b.endpoint.domain.to_lowercase()),
+        };
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+}
+
 // 当前版本号（从 tauri.conf.json 读取，通过 build.rs 设置）
 const CURRENT_VERSION: &str = env!("APP_VERSION");
 
@@ -752,12 +2530,12 @@ async fn check_for_update() -> Result<UpdateInfo, String> {
     )];
 
     // 读取代理配置
-    let proxy_setting = {
+    let (proxy_setting, user_agent) = {
         let cfg = ConfigManager::new().load().unwrap_or_default();
-        cfg.update_proxy.clone()
+        (cfg.effective_update_proxy(), cfg.effective_user_agent())
     };
     let builder = reqwest::Client::builder()
-        .user_agent(format!("anyFAST/{}", CURRENT_VERSION))
+        .user_agent(user_agent)
         .timeout(std::time::Duration::from_secs(10));
     let builder = apply_proxy_setting(builder, &proxy_setting);
 
@@ -816,25 +2594,48 @@ async fn check_for_update() -> Result<UpdateInfo, String> {
     Err(format!("所有更新检查端点均失败: {}", last_err))
 }
 
-/// 比较版本号，返回 true 如果 latest > current
-fn compare_versions(latest: &str, current: &str) -> bool {
-    let parse_version =
-        |v: &str| -> Vec<u32> { v.split('.').filter_map(|s| s.parse().ok()).collect() };
+/// 解析版本号（允许 v 前缀，缺省的 minor/patch 补 0，如 "1.2" → 1.2.0）
+fn parse_version(raw: &str) -> Option<semver::Version> {
+    let raw = raw.trim().trim_start_matches(['v', 'V']);
+    if let Ok(version) = semver::Version::parse(raw) {
+        return Some(version);
+    }
 
-    let latest_parts = parse_version(latest);
-    let current_parts = parse_version(current);
+    // 拆出 core 与 -pre/+build 后缀，补齐 core 再解析
+    let suffix_at = raw.find(['-', '+']).unwrap_or(raw.len());
+    let (core, suffix) = raw.split_at(suffix_at);
+    let parts: Vec<&str> = core.split('.').collect();
+    if parts.is_empty() || parts.len() > 3 || parts.iter().any(|p| p.is_empty()) {
+        return None;
+    }
+    let mut padded = parts.join(".");
+    for _ in parts.len()..3 {
+        padded.push_str(".0");
+    }
+    semver::Version::parse(&format!("{}{}", padded, suffix)).ok()
+}
 
-    for i in 0..latest_parts.len().max(current_parts.len()) {
-        let l = latest_parts.get(i).copied().unwrap_or(0);
-        let c = current_parts.get(i).copied().unwrap_or(0);
-        if l > c {
-            return true;
-        }
-        if l < c {
-            return false;
+/// 比较版本号，返回 true 如果 latest > current（按 semver 优先级，预发布版本低于正式版）
+fn compare_versions(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => {
+            latest.cmp_precedence(&current) == std::cmp::Ordering::Greater
         }
+        _ => false,
+    }
+}
+
+/// 获取当前平台能力（可用命令、服务与 helper 状态），前端据此隐藏不支持的功能
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+fn get_capabilities() -> Capabilities {
+    let platform = std::env::consts::OS;
+    Capabilities {
+        platform: platform.to_string(),
+        commands: available_commands(platform),
+        service_available: hosts_ops::is_service_running(),
+        helper_available: hosts_ops::is_macos_helper_available(),
     }
-    false
 }
 
 /// 获取当前版本号
@@ -844,6 +2645,15 @@ fn get_current_version() -> String {
     CURRENT_VERSION.to_string()
 }
 
+/// 收集网络环境诊断信息：默认网关、系统 DNS 服务器与 VPN 网卡
+#[cfg(feature = "tauri-runtime")]
+#[tauri::command]
+async fn get_network_info() -> Result<NetworkInfo, String> {
+    tokio::task::spawn_blocking(network_info::collect)
+        .await
+        .map_err(|e| format!("获取网络信息失败: {}", e))
+}
+
 /// 检测系统 HTTP 代理设置（Windows 从注册表读取，其他从环境变量读取）
 #[cfg(feature = "tauri-runtime")]
 #[tauri::command]
@@ -930,12 +2740,12 @@ async fn force_download_update() -> Result<String, String> {
     use std::io::Write;
 
     // 读取代理配置
-    let proxy_setting = {
+    let (proxy_setting, user_agent) = {
         let cfg = ConfigManager::new().load().unwrap_or_default();
-        cfg.update_proxy.clone()
+        (cfg.effective_update_proxy(), cfg.effective_user_agent())
     };
     let builder = reqwest::Client::builder()
-        .user_agent(format!("anyFAST/{}", CURRENT_VERSION))
+        .user_agent(user_agent)
         .timeout(std::time::Duration::from_secs(120));
     let builder = apply_proxy_setting(builder, &proxy_setting);
 
@@ -1069,12 +2879,12 @@ async fn diagnose_update() -> Result<Vec<DiagnosticStep>, String> {
     let mut steps = Vec::new();
 
     // 读取代理配置
-    let proxy_setting = {
+    let (proxy_setting, user_agent) = {
         let cfg = ConfigManager::new().load().unwrap_or_default();
-        cfg.update_proxy.clone()
+        (cfg.effective_update_proxy(), cfg.effective_user_agent())
     };
     let builder = reqwest::Client::builder()
-        .user_agent(format!("anyFAST/{}", CURRENT_VERSION))
+        .user_agent(user_agent)
         .timeout(std::time::Duration::from_secs(15));
     let builder = apply_proxy_setting(builder, &proxy_setting);
 
@@ -1347,11 +3157,25 @@ async fn set_autostart(enabled: bool) -> Result<(), String> {
         Ok(())
     }
 
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(target_os = "linux")]
+    {
+        // XDG autostart：~/.config/autostart/anyFAST.desktop
+        let dir = autostart::autostart_dir()?;
+        let exe_path = std::env::current_exe().map_err(|e| format!("无法获取程序路径: {}", e))?;
+        autostart::set_enabled_in(&dir, &exe_path, enabled)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // LaunchAgent：~/Library/LaunchAgents/com.anyrouter.fast.plist
+        autostart::set_launch_agent(enabled)
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
     {
-        // 非 Windows 平台暂不支持
+        // 其他平台暂不支持
         let _ = enabled;
-        Err("开机自启动功能仅在 Windows 上可用".to_string())
+        Err("开机自启动功能仅在 Windows、Linux 和 macOS 上可用".to_string())
     }
 }
 
@@ -1378,9 +3202,25 @@ async fn get_autostart() -> Result<bool, String> {
         Ok(result.is_ok())
     }
 
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(target_os = "linux")]
+    {
+        // desktop 文件存在且 Exec 指向当前程序才视为已开启
+        let dir = autostart::autostart_dir()?;
+        let exe_path = std::env::current_exe().map_err(|e| format!("无法获取程序路径: {}", e))?;
+        Ok(autostart::is_enabled_in(&dir, &exe_path))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // plist 存在且 ProgramArguments 指向当前程序才视为已开启
+        let dir = autostart::launch_agents_dir()?;
+        let exe_path = std::env::current_exe().map_err(|e| format!("无法获取程序路径: {}", e))?;
+        Ok(autostart::is_launch_agent_enabled_in(&dir, &exe_path))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
     {
-        // 非 Windows 平台返回 false
+        // 其他平台返回 false
         Ok(false)
     }
 }
@@ -1675,12 +3515,13 @@ pub fn run() {
             let config_manager = ConfigManager::new();
             {
                 let cfg = config_manager.load().unwrap_or_default();
-                let proxy_url = if cfg.update_proxy == "auto" {
+                let update_proxy = cfg.effective_update_proxy();
+                let proxy_url = if update_proxy == "auto" {
                     detect_system_proxy()
-                } else if cfg.update_proxy.is_empty() {
+                } else if update_proxy.is_empty() {
                     None
                 } else {
-                    Some(cfg.update_proxy.clone())
+                    Some(update_proxy)
                 };
                 if let Some(url) = proxy_url {
                     std::env::set_var("HTTPS_PROXY", &url);
@@ -1688,26 +3529,41 @@ pub fn run() {
                 }
             }
 
+            let history_manager = HistoryManager::new();
+            history_manager.set_retention_days(
+                config_manager
+                    .load()
+                    .map(|c| c.history_retention_days)
+                    .unwrap_or(history::DEFAULT_HISTORY_RETENTION_DAYS),
+            );
+
             let state = AppState {
                 config_manager: config_manager.clone(),
-                history_manager: HistoryManager::new(),
+                history_manager,
                 tester: Arc::new(Mutex::new(None)),
                 results: Arc::new(Mutex::new(Vec::new())),
-                baselines: BaselineTracker::new(),
+                baselines: BaselineTracker::with_store(BaselineStore::new()),
                 app_handle: app.handle().clone(),
                 health_checker: Arc::new(Mutex::new(None)),
                 last_test_time: Arc::new(Mutex::new(None)),
+                clear_on_quit_override: Arc::new(std::sync::Mutex::new(None)),
             };
             app.manage(state);
 
+            // 端点订阅后台刷新
+            subscription::spawn_refresh_loop(app.handle().clone(), config_manager.clone());
+            // 定时全量重测（未开启时空转）
+            spawn_periodic_retest_loop(app.handle().clone());
+
             // 创建托盘菜单
             let show_item = MenuItem::with_id(app, "show", "显示窗口", true, None::<&str>)?;
             let quit_item = MenuItem::with_id(app, "quit", "退出", true, None::<&str>)?;
             let menu = Menu::with_items(app, &[&show_item, &quit_item])?;
 
             // 创建托盘图标
-            let _tray = TrayIconBuilder::new()
+            let _tray = TrayIconBuilder::with_id(tray::TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
+                .tooltip(tray::TrayStatus::Idle.tooltip())
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .on_menu_event(|app, event| {
@@ -1721,7 +3577,12 @@ pub fn run() {
                             }
                         }
                         "quit" => {
-                            // 退出时保留 hosts 绑定，用户可通过解绑功能手动清除
+                            // hosts 清理在 RunEvent::Exit 中统一处理
+                            if let Some(window) = app.get_webview_window("main") {
+                                if window.is_visible().unwrap_or(false) {
+                                    window_state::save(&window);
+                                }
+                            }
                             app.exit(0);
                         }
                         _ => {}
@@ -1748,11 +3609,14 @@ pub fn run() {
             // 处理窗口关闭事件 - 始终最小化到托盘
             let app_handle = app.handle().clone();
             if let Some(window) = app.get_webview_window("main") {
+                // 恢复上次的窗口位置与尺寸
+                window_state::restore(&window);
                 window.on_window_event(move |event| {
                     if let WindowEvent::CloseRequested { api, .. } = event {
                         // 阻止关闭，改为隐藏窗口到托盘
                         api.prevent_close();
                         if let Some(win) = app_handle.get_webview_window("main") {
+                            window_state::save(&win);
                             let _ = win.set_skip_taskbar(true);
                             let _ = win.hide();
                         }
@@ -1764,30 +3628,68 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             get_config,
+            get_effective_config,
             save_config,
+            validate_config,
+            validate_endpoints,
+            add_endpoint_from_url,
+            set_clear_on_quit_override,
+            export_config,
+            import_config,
+            refresh_subscription,
             start_speed_test,
+            get_estimated_test_duration,
             stop_speed_test,
             apply_endpoint,
             apply_all_endpoints,
+            apply_all_best_effort,
+            preview_apply_all,
+            reset_baselines_to_current,
             clear_all_bindings,
             unbind_endpoint,
+            suspend_binding,
+            resume_binding,
+            get_suspended_domains,
             has_any_bindings,
             get_bindings,
+            get_all_anyfast_bindings,
+            export_hosts_block,
+            export_bindings_hosts,
+            export_bindings_json,
+            import_bindings_hosts,
             get_binding_count,
             check_admin,
+            is_process_elevated,
             is_service_running,
             get_permission_status,
             refresh_service_status,
             is_macos_helper_available,
             install_macos_helper,
+            is_macos_helper_outdated,
             has_bundled_helper,
             get_hosts_path,
             open_hosts_file,
+            get_log_path,
+            get_data_dir,
+            open_log_file,
             get_history_stats,
+            get_domain_history,
+            get_history_daily,
+            get_history_info,
             clear_history,
             // 单端点测速
             test_single_endpoint,
+            probe_url,
+            retest_endpoint,
             get_current_results,
+            sort_results,
+            get_last_apply_info,
+            get_candidate_ips,
+            verify_bindings_health,
+            benchmark_cf_ips,
+            test_custom_ip,
+            benchmark_dns_servers,
+            diagnose_multi_dns,
             // 开机自启动
             set_autostart,
             get_autostart,
@@ -1798,6 +3700,7 @@ pub fn run() {
             // 更新检查
             check_for_update,
             get_current_version,
+            get_capabilities,
             detect_system_proxy,
             diagnose_update,
             force_download_update,
@@ -1805,15 +3708,636 @@ pub fn run() {
             start_continuous_optimization,
             stop_continuous_optimization,
             is_continuous_optimization_running,
+            pause_continuous_optimization,
+            resume_continuous_optimization,
+            get_continuous_optimization_status,
+            cancel_health_check,
+            check_service_pipe_access,
+            get_network_info,
+            apply_and_measure,
+            suggest_check_interval,
+            get_hosts_conflicts,
+            deconflict_hosts,
+            restore_deconflicted_hosts,
+            start_speed_test_streaming,
+            check_hosts_encoding,
+            schedule_periodic_retest,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                clear_hosts_on_exit(app);
+            }
+        });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn effective_config_should_apply_runtime_limits() {
+        let raw = AppConfig {
+            check_interval: 1,
+            check_jitter_percent: 90,
+            failure_threshold: 0,
+            test_count: 20,
+            test_aggressiveness: 9,
+            benchmark_concurrency: 0,
+            max_endpoint_concurrency: Some(100),
+            max_ip_concurrency: None,
+            max_test_ips: Some(100),
+            full_test_cooldown_secs: 5,
+            switch_min_improvement_percent: 300.0,
+            severe_abs_threshold_ms: -1.0,
+            min_speedup_percent: f64::NAN,
+            ips_per_domain: 10,
+            history_retention_days: 0,
+            endpoints: vec![Endpoint {
+                name: "a".into(),
+                url: "https://a.com".into(),
+                domain: "a.com".into(),
+                timeout_multiplier: 50.0,
+                ..Default::default()
+            }],
+            ..AppConfig::default()
+        };
+
+        let effective = effective_config(raw);
+        assert_eq!(effective.check_interval, 30);
+        assert_eq!(effective.check_jitter_percent, 50);
+        assert_eq!(effective.failure_threshold, 1);
+        assert_eq!(effective.test_count, 5);
+        assert_eq!(effective.test_aggressiveness, 2);
+        assert_eq!(effective.benchmark_concurrency, 1);
+        assert_eq!(effective.max_endpoint_concurrency, Some(32));
+        assert_eq!(effective.max_ip_concurrency, None);
+        assert_eq!(effective.max_test_ips, Some(50));
+        assert_eq!(effective.full_test_cooldown_secs, 60);
+        assert_eq!(effective.switch_min_improvement_percent, 100.0);
+        assert_eq!(effective.severe_abs_threshold_ms, 0.0);
+        assert_eq!(effective.min_speedup_percent, 0.0);
+        assert_eq!(effective.ips_per_domain, MAX_IPS_PER_DOMAIN);
+        assert_eq!(
+            effective.history_retention_days,
+            history::MIN_HISTORY_RETENTION_DAYS
+        );
+        assert_eq!(
+            effective.endpoints[0].timeout_multiplier,
+            models::MAX_TIMEOUT_MULTIPLIER
+        );
+
+        // 默认配置本身就是生效值
+        let defaults = effective_config(AppConfig::default());
+        assert_eq!(
+            serde_json::to_value(&defaults).unwrap(),
+            serde_json::to_value(AppConfig::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn normalize_config_should_sanitize_values_on_save() {
+        let mut config = AppConfig {
+            min_speedup_percent: -20.0,
+            ..AppConfig::default()
+        };
+        config.endpoints[0].healthy_status_codes = vec![700, 204, 42, 200, 204];
+
+        let normalized = normalize_config(config).unwrap();
+        assert_eq!(normalized.min_speedup_percent, 0.0);
+        assert_eq!(normalized.endpoints[0].healthy_status_codes, vec![200, 204]);
+    }
+
+    #[test]
+    fn collect_config_issues_should_report_each_problem() {
+        use models::IssueSeverity;
+
+        let endpoint = |domain: &str, url: &str, enabled: bool| Endpoint {
+            name: format!("ep-{}", domain),
+            url: url.into(),
+            domain: domain.into(),
+            enabled,
+            ..Default::default()
+        };
+        let issues_of = |endpoints: Vec<Endpoint>| {
+            collect_config_issues(&AppConfig {
+                endpoints,
+                ..AppConfig::default()
+            })
+        };
+
+        let ok = issues_of(vec![endpoint("a.com", "https://a.com/v1", true)]);
+        assert!(ok.is_empty(), "{:?}", ok);
+
+        let issues = issues_of(vec![
+            endpoint("", "https://x.com", true),
+            endpoint("bad domain", "https://bad.com", true),
+            endpoint("a.com", "https://a.com", true),
+            endpoint("A.com", "https://a.com", true),
+            endpoint("b.com", "not a url", true),
+            endpoint("c.com", "ftp://c.com", true),
+            endpoint("d.com", "https://other.com", true),
+        ]);
+        let find = |domain: &str| {
+            issues
+                .iter()
+                .filter(|i| i.domain.as_deref() == Some(domain))
+                .collect::<Vec<_>>()
+        };
+        assert!(find("ep-")[0].message.contains("域名为空"));
+        assert!(find("bad domain")[0].message.contains("域名无效"));
+        assert!(find("a.com").is_empty());
+        assert!(find("A.com")[0].message.contains("域名重复"));
+        assert!(find("b.com")[0].message.contains("URL 无效"));
+        assert!(find("c.com")[0].message.contains("http/https"));
+        assert_eq!(find("d.com")[0].severity, IssueSeverity::Warning);
+
+        let issues = issues_of(vec![endpoint("a.com", "https://a.com", false)]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].domain, None);
+        assert_eq!(issues[0].severity, IssueSeverity::Warning);
+
+        let issues = issues_of(vec![]);
+        assert_eq!(issues[0].severity, IssueSeverity::Error);
+    }
+
+    #[test]
+    fn collect_endpoint_issues_should_check_imported_list() {
+        use models::IssueSeverity;
+
+        let endpoint = |name: &str, domain: &str, url: &str| Endpoint {
+            name: name.into(),
+            url: url.into(),
+            domain: domain.into(),
+            ..Default::default()
+        };
+        let existing = vec![endpoint("A", "a.com", "https://a.com")];
+
+        let ok = collect_endpoint_issues(&[endpoint("B", "b.com", "https://b.com")], &existing);
+        assert!(ok.is_empty(), "{:?}", ok);
+
+        let issues = collect_endpoint_issues(
+            &[
+                endpoint("", "noname.com", "https://noname.com"),
+                endpoint("Bad", "bad domain", "https://bad.com"),
+                endpoint("Dup", "A.com", "https://a.com"),
+                endpoint("C", "c.com", "https://c.com"),
+                endpoint("C2", "C.COM", "https://c.com"),
+                endpoint("D", "d.com", "not a url"),
+                endpoint("Empty", "", "https://e.com"),
+            ],
+            &existing,
+        );
+        let find = |domain: &str| {
+            issues
+                .iter()
+                .filter(|i| i.domain.as_deref() == Some(domain))
+                .collect::<Vec<_>>()
+        };
+        assert!(find("noname.com")[0].message.contains("名称为空"));
+        assert_eq!(find("noname.com")[0].severity, IssueSeverity::Warning);
+        assert!(find("bad domain")[0].message.contains("域名无效"));
+        assert!(find("A.com")[0].message.contains("已存在"));
+        assert!(find("c.com").is_empty());
+        assert!(find("C.COM")[0].message.contains("域名重复"));
+        assert!(find("d.com")[0].message.contains("URL 无效"));
+        assert!(find("Empty")[0].message.contains("域名为空"));
+    }
+
+    #[test]
+    fn custom_ip_target_should_validate_and_reuse_config() {
+        let configured = vec![Endpoint {
+            name: "API".into(),
+            url: "https://api.example.com:8443/v1".into(),
+            domain: "api.example.com".into(),
+            port: Some(8443),
+            timeout_multiplier: 2.0,
+            ..Default::default()
+        }];
+
+        let (endpoint, ip) =
+            custom_ip_target(" API.example.com ", " 104.16.1.1 ", &configured).unwrap();
+        assert_eq!(ip, "104.16.1.1");
+        assert_eq!(endpoint.port, Some(8443));
+        assert_eq!(endpoint.timeout_multiplier, 2.0);
+
+        let (endpoint, ip) = custom_ip_target("new.example.com", "2606:4700::1", &[]).unwrap();
+        assert_eq!(ip, "2606:4700::1");
+        assert_eq!(endpoint.url, "https://new.example.com");
+        assert_eq!(endpoint.port, Some(models::DEFAULT_ENDPOINT_PORT));
+
+        let err = custom_ip_target("a.com", "104.16.1", &[]).unwrap_err();
+        assert!(err.contains("IP 格式无效"));
+        assert!(custom_ip_target("bad domain", "1.1.1.1", &[]).is_err());
+    }
+
+    #[test]
+    fn split_kept_bindings_should_skip_unchanged_domains() {
+        let binding = |domain: &str, ip: &str| HostsBinding {
+            domain: domain.into(),
+            ip: ip.into(),
+        };
+        let current = |domain: &str| match domain {
+            "kept.com" => vec!["1.1.1.1".to_string()],
+            "multi.com" => vec!["2.2.2.2".to_string(), "3.3.3.3".to_string()],
+            // 已有 AAAA 绑定不影响 IPv4 的比较
+            "dual.com" => vec!["6.6.6.6".to_string(), "2606:4700::1".to_string()],
+            _ => Vec::new(),
+        };
+
+        let (changed, kept) = split_kept_bindings(
+            vec![
+                binding("dual.com", "6.6.6.6"),
+                binding("kept.com", "1.1.1.1"),
+                binding("multi.com", "2.2.2.2"),
+                binding("multi.com", "4.4.4.4"),
+                binding("new.com", "5.5.5.5"),
+            ],
+            current,
+        );
+
+        assert_eq!(kept, 2);
+        let changed: Vec<(&str, &str)> = changed
+            .iter()
+            .map(|b| (b.domain.as_str(), b.ip.as_str()))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                ("multi.com", "2.2.2.2"),
+                ("multi.com", "4.4.4.4"),
+                ("new.com", "5.5.5.5")
+            ]
+        );
+
+        // 全部保持不变：无需写入（也就不会刷新 DNS）
+        let (changed, kept) = split_kept_bindings(vec![binding("kept.com", "1.1.1.1")], current);
+        assert!(changed.is_empty());
+        assert_eq!(kept, 1);
+    }
+
+    #[tokio::test]
+    async fn finish_test_run_should_reject_cancelled_run() {
+        let slot = Mutex::new(None);
+        let tester = EndpointTester::new(vec![], 1);
+        *slot.lock().await = Some(tester.clone());
+
+        // 测速进行中被 stop_speed_test 取消（取走并取消 tester）
+        slot.lock().await.take().unwrap().cancel();
+        assert!(finish_test_run(&slot, &tester).await.is_err());
+
+        // 正常完成：清除自己的 tester
+        let tester = EndpointTester::new(vec![], 1);
+        *slot.lock().await = Some(tester.clone());
+        assert!(finish_test_run(&slot, &tester).await.is_ok());
+        assert!(slot.lock().await.is_none());
+
+        // 旧测速结束时不清除新启动的 tester
+        let stale = EndpointTester::new(vec![], 1);
+        let newer = EndpointTester::new(vec![], 1);
+        *slot.lock().await = Some(newer.clone());
+        assert!(finish_test_run(&slot, &stale).await.is_ok());
+        assert!(slot.lock().await.as_ref().unwrap().is_same_run(&newer));
+    }
+
+    #[tokio::test]
+    async fn begin_test_run_should_reject_concurrent_start() {
+        let slot = Arc::new(Mutex::new(None));
+        let first = EndpointTester::new(vec![], 1);
+        let second = EndpointTester::new(vec![], 1);
+
+        // 两次几乎同时启动：只有一个能占用槽位
+        let (a, b) = tokio::join!(
+            begin_test_run(&slot, &first),
+            begin_test_run(&slot, &second)
+        );
+        assert!(a.is_ok() != b.is_ok());
+        let winner = if a.is_ok() { &first } else { &second };
+        assert!(slot.lock().await.as_ref().unwrap().is_same_run(winner));
+
+        // 结束后可以再次启动
+        finish_test_run(&slot, winner).await.unwrap();
+        assert!(begin_test_run(&slot, &second).await.is_ok());
+    }
+
+    #[test]
+    fn periodic_retest_due_should_respect_interval_and_running_tests() {
+        let last_run = Instant::now();
+        let hour = std::time::Duration::from_secs(3600);
+
+        assert!(!periodic_retest_due(
+            0,
+            last_run,
+            last_run + 100 * hour,
+            false
+        ));
+        assert!(!periodic_retest_due(
+            24,
+            last_run,
+            last_run + 23 * hour,
+            false
+        ));
+        assert!(periodic_retest_due(
+            24,
+            last_run,
+            last_run + 24 * hour,
+            false
+        ));
+        // 手动测速进行中时推迟到下一次检查
+        assert!(!periodic_retest_due(
+            24,
+            last_run,
+            last_run + 25 * hour,
+            true
+        ));
+        // 时钟倒退不会触发
+        assert!(!periodic_retest_due(1, last_run + hour, last_run, false));
+    }
+
+    #[tokio::test]
+    async fn cancel_test_run_should_only_stop_matching_run() {
+        let slot = Arc::new(Mutex::new(None));
+        let stale = EndpointTester::new(vec![], 1);
+        let current = EndpointTester::new(vec![], 1);
+        assert_ne!(stale.run_id(), current.run_id());
+        // clone 共享运行 ID
+        assert_eq!(current.clone().run_id(), current.run_id());
+
+        begin_test_run(&slot, &current).await.unwrap();
+
+        // 已结束的旧运行 ID 不影响当前测速
+        assert!(!cancel_test_run(&slot, Some(stale.run_id())).await);
+        assert!(!current.is_cancelled());
+        assert!(slot.lock().await.is_some());
+
+        assert!(cancel_test_run(&slot, Some(current.run_id())).await);
+        assert!(current.is_cancelled());
+        assert!(finish_test_run(&slot, &current).await.is_err());
+
+        // 槽位已空：不带运行 ID 的停止也返回 false
+        assert!(!cancel_test_run(&slot, None).await);
+    }
+
+    fn result_for(domain: &str, ip: &str, latency: f64) -> EndpointResult {
+        let ep = Endpoint {
+            name: domain.into(),
+            url: format!("https://{}", domain),
+            domain: domain.into(),
+            ..Default::default()
+        };
+        EndpointResult::success(ep, ip.into(), latency, latency)
+    }
+
+    #[test]
+    fn plan_apply_all_should_be_side_effect_free() {
+        let mut faster = result_for("b.com", "2.2.2.2", 60.0);
+        faster.original_latency = 120.0;
+        faster.speedup_percent = 50.0;
+        let results = vec![
+            result_for("a.com", "1.1.1.1", 80.0),
+            result_for("a.com", "9.9.9.9", 150.0),
+            faster,
+            EndpointResult::failure(
+                result_for("c.com", "", 0.0).endpoint,
+                "3.3.3.3".into(),
+                "超时".into(),
+            ),
+        ];
+        let current = |domain: &str| match domain {
+            "a.com" => vec!["1.1.1.1".to_string()],
+            _ => Vec::new(),
+        };
+
+        let plan = plan_apply_all(&results, 1, 0.0, 1_700_000_000, current);
+
+        // a.com 已是最优保持不变，只有 b.com 需要写入；失败端点不参与
+        assert_eq!(plan.kept_count, 1);
+        let bindings: Vec<(&str, &str)> = plan
+            .bindings
+            .iter()
+            .map(|b| (b.domain.as_str(), b.ip.as_str()))
+            .collect();
+        assert_eq!(bindings, [("b.com", "2.2.2.2")]);
+        assert_eq!(plan.history_records.len(), 1);
+        assert_eq!(plan.history_records[0].domain, "b.com");
+        assert_eq!(plan.history_records[0].timestamp, 1_700_000_000);
+        let mut baselines = plan.baseline_updates.clone();
+        baselines.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            baselines,
+            [("a.com".to_string(), 80.0), ("b.com".to_string(), 60.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_all_dry_run_should_leave_everything_untouched() {
+        let mut faster = result_for("b.com", "2.2.2.2", 60.0);
+        faster.original_latency = 120.0;
+        faster.speedup_percent = 50.0;
+        let results = vec![result_for("a.com", "1.1.1.1", 80.0), faster];
+        let current = |domain: &str| match domain {
+            "a.com" => vec!["1.1.1.1".to_string()],
+            _ => Vec::new(),
+        };
+
+        // 写 hosts、更新基准与历史都在 commit 中完成，dry_run 不会调用它
+        let committed = &std::cell::Cell::new(false);
+        let plan = plan_apply_all(&results, 1, 0.0, 1_700_000_000, current);
+        let outcome = run_apply_plan(plan, true, |_| async {
+            committed.set(true);
+            Ok(0)
+        })
+        .await
+        .unwrap();
+        assert!(!committed.get());
+        assert_eq!(
+            outcome,
+            ApplyAllOutcome {
+                count: 1,
+                bindings: vec![("b.com".into(), "2.2.2.2".into())],
+                kept_count: 1,
+                dry_run: true,
+            }
+        );
+
+        // 正式应用时 count 取 commit 返回的实际修改数
+        let plan = plan_apply_all(&results, 1, 0.0, 1_700_000_000, current);
+        let outcome = run_apply_plan(plan, false, |plan| async move {
+            committed.set(true);
+            Ok(plan.bindings.len() as u32 + 1)
+        })
+        .await
+        .unwrap();
+        assert!(committed.get());
+        assert_eq!(outcome.count, 2);
+        assert!(!outcome.dry_run);
+    }
+
+    #[test]
+    fn plan_apply_all_should_keep_binding_below_min_speedup() {
+        let mut marginal = result_for("a.com", "1.1.1.1", 98.0);
+        marginal.original_latency = 100.0;
+        marginal.speedup_percent = 2.0;
+        let mut faster = result_for("b.com", "2.2.2.2", 60.0);
+        faster.original_latency = 120.0;
+        faster.speedup_percent = 50.0;
+        let results = vec![marginal, faster];
+
+        let plan = plan_apply_all(&results, 1, 5.0, 1_700_000_000, |_| Vec::new());
+
+        // a.com 只快 2%，低于 5% 阈值：不写入、计入保持不变、不更新基准
+        assert_eq!(plan.kept_count, 1);
+        let domains: Vec<&str> = plan.bindings.iter().map(|b| b.domain.as_str()).collect();
+        assert_eq!(domains, ["b.com"]);
+        assert_eq!(plan.baseline_updates, [("b.com".to_string(), 60.0)]);
+        let mut applied: Vec<(&str, bool)> = plan
+            .history_records
+            .iter()
+            .map(|r| (r.domain.as_str(), r.applied))
+            .collect();
+        applied.sort();
+        assert_eq!(applied, [("a.com", false), ("b.com", true)]);
+
+        // 阈值为 0 时照常写入
+        let plan = plan_apply_all(&results, 1, 0.0, 1_700_000_000, |_| Vec::new());
+        assert_eq!(plan.bindings.len(), 2);
+        assert_eq!(plan.kept_count, 0);
+    }
+
+    #[test]
+    fn sort_results_by_should_keep_failures_last() {
+        let mut fast = result_for("b.com", "1.1.1.1", 50.0);
+        fast.endpoint.name = "Beta".into();
+        fast.speedup_percent = 10.0;
+        let mut slow = result_for("a.com", "2.2.2.2", 200.0);
+        slow.endpoint.name = "alpha".into();
+        slow.speedup_percent = 40.0;
+        let mut mid = result_for("c.com", "3.3.3.3", 100.0);
+        mid.endpoint.name = "Gamma".into();
+        mid.speedup_percent = -5.0;
+        let failed = |domain: &str| {
+            EndpointResult::failure(
+                result_for(domain, "", 0.0).endpoint,
+                String::new(),
+                "超时".into(),
+            )
+        };
+        let mut results = vec![failed("z.com"), fast, failed("y.com"), slow, mid];
+
+        let domains = |results: &[EndpointResult]| -> Vec<String> {
+            results.iter().map(|r| r.endpoint.domain.clone()).collect()
+        };
+
+        sort_results_by(&mut results, ResultSortKey::Latency, true);
+        assert_eq!(
+            domains(&results),
+            ["b.com", "c.com", "a.com", "z.com", "y.com"]
+        );
+
+        sort_results_by(&mut results, ResultSortKey::Speedup, false);
+        assert_eq!(
+            domains(&results),
+            ["a.com", "b.com", "c.com", "z.com", "y.com"]
+        );
+
+        // 名称排序不区分大小写
+        sort_results_by(&mut results, ResultSortKey::Name, true);
+        assert_eq!(
+            domains(&results),
+            ["a.com", "b.com", "c.com", "z.com", "y.com"]
+        );
+
+        // 降序时失败端点仍在最后，且保持原有相对顺序
+        sort_results_by(&mut results, ResultSortKey::Domain, false);
+        assert_eq!(
+            domains(&results),
+            ["c.com", "b.com", "a.com", "z.com", "y.com"]
+        );
+
+        let key: ResultSortKey = serde_json::from_str("\"speedup\"").unwrap();
+        assert_eq!(key, ResultSortKey::Speedup);
+    }
+
+    #[test]
+    fn summarize_apply_measurement_should_compute_improvement() {
+        let before = result_for("a.com", "1.1.1.1", 200.0);
+        let after = result_for("a.com", "2.2.2.2", 150.0);
+        let measurement = summarize_apply_measurement("a.com", &before, &after);
+        assert_eq!(measurement.before_ip, "1.1.1.1");
+        assert_eq!(measurement.applied_ip, "2.2.2.2");
+        assert_eq!(measurement.before_ms, Some(200.0));
+        assert_eq!(measurement.after_ms, Some(150.0));
+        assert_eq!(measurement.improvement_percent, Some(25.0));
+
+        // 变慢时为负
+        let slower = summarize_apply_measurement("a.com", &after, &before);
+        assert!((slower.improvement_percent.unwrap() + 33.33).abs() < 0.01);
+
+        // 任一侧探测失败时不给出加速百分比
+        let failed = EndpointResult::failure(
+            before.endpoint.clone(),
+            "1.1.1.1".into(),
+            "TCP_TIMEOUT".into(),
+        );
+        let measurement = summarize_apply_measurement("a.com", &failed, &after);
+        assert_eq!(measurement.before_ms, None);
+        assert_eq!(measurement.after_ms, Some(150.0));
+        assert_eq!(measurement.improvement_percent, None);
+    }
+
+    #[test]
+    fn find_result_latency_should_match_domain_and_ip() {
+        let results = vec![
+            result_for("a.com", "1.1.1.1", 120.0),
+            result_for("a.com", "2.2.2.2", 80.0),
+            result_for("b.com", "1.1.1.1", 50.0),
+        ];
+
+        assert_eq!(
+            find_result_latency(&results, "a.com", "1.1.1.1"),
+            Some(120.0)
+        );
+        assert_eq!(
+            find_result_latency(&results, "a.com", "2.2.2.2"),
+            Some(80.0)
+        );
+        assert_eq!(find_result_latency(&results, "a.com", "3.3.3.3"), None);
+        assert_eq!(find_result_latency(&results, "c.com", "1.1.1.1"), None);
+    }
+
+    #[test]
+    fn expand_domain_bindings_should_take_top_n_ips() {
+        let results = vec![result_for("a.com", "1.1.1.1", 80.0).with_backup_ips(vec![
+            "2.2.2.2".into(),
+            "1.1.1.1".into(),
+            "3.3.3.3".into(),
+            "4.4.4.4".into(),
+        ])];
+        let ips = |n: u32| -> Vec<String> {
+            expand_domain_bindings(&results, "a.com", "1.1.1.1", n)
+                .into_iter()
+                .map(|b| b.ip)
+                .collect()
+        };
+
+        assert_eq!(ips(1), ["1.1.1.1"]);
+        assert_eq!(ips(0), ["1.1.1.1"]);
+        assert_eq!(ips(3), ["1.1.1.1", "2.2.2.2", "3.3.3.3"]);
+        // 上限为 MAX_IPS_PER_DOMAIN
+        assert_eq!(ips(10).len(), MAX_IPS_PER_DOMAIN as usize);
+        // 找不到对应结果时只绑定最优 IP
+        assert_eq!(
+            expand_domain_bindings(&results, "b.com", "5.5.5.5", 3).len(),
+            1
+        );
+        // 域名大小写不影响匹配备用 IP
+        assert_eq!(
+            expand_domain_bindings(&results, "A.COM", "1.1.1.1", 3).len(),
+            3
+        );
+    }
+
     #[test]
     fn normalize_preferred_ips_should_trim_dedupe_and_drop_invalid() {
         let input = vec![
@@ -1837,4 +4361,133 @@ mod tests {
         let got = normalize_preferred_ips(input);
         assert_eq!(got, vec!["::1".to_string()]);
     }
+
+    #[test]
+    fn endpoint_from_url_should_extract_domain_and_port() {
+        let ep = endpoint_from_url("", " https://API.Example.com:8443/v1 ", &[]).unwrap();
+        assert_eq!(ep.domain, "api.example.com");
+        assert_eq!(ep.name, "api.example.com");
+        assert_eq!(ep.url, "https://API.Example.com:8443/v1");
+        assert_eq!(ep.port, Some(8443));
+        assert!(ep.enabled);
+
+        let ep = endpoint_from_url("My API", "https://relay.example.com", &[]).unwrap();
+        assert_eq!(ep.name, "My API");
+        assert_eq!(ep.effective_port(), 443);
+    }
+
+    #[test]
+    fn endpoint_from_url_should_reject_invalid_input() {
+        assert!(endpoint_from_url("x", "ftp://example.com", &[]).is_err());
+        assert!(endpoint_from_url("x", "not a url", &[]).is_err());
+        assert!(endpoint_from_url("x", "https://exa$mple.com", &[]).is_err());
+
+        let existing = vec![result_for("example.com", "1.1.1.1", 1.0).endpoint];
+        assert!(endpoint_from_url("x", "https://EXAMPLE.com/v1", &existing).is_err());
+    }
+
+    #[test]
+    fn probe_target_from_url_should_derive_scheme_and_port() {
+        let target = probe_target_from_url("http://status.example.com/health").unwrap();
+        assert_eq!(target.domain, "status.example.com");
+        assert!(!target.uses_tls());
+        assert_eq!(target.effective_port(), 80);
+
+        let target = probe_target_from_url("https://api.example.com:8443/v1").unwrap();
+        assert!(target.uses_tls());
+        assert_eq!(target.effective_port(), 8443);
+
+        assert!(probe_target_from_url("ftp://example.com").is_err());
+        assert!(probe_target_from_url("example.com").is_err());
+    }
+
+    #[test]
+    fn should_clear_on_quit_prefers_session_override() {
+        assert!(!should_clear_on_quit(None, false));
+        assert!(should_clear_on_quit(None, true));
+        assert!(!should_clear_on_quit(Some(false), true));
+        assert!(should_clear_on_quit(Some(true), false));
+    }
+
+    #[test]
+    fn registered_commands_should_match_invoke_handler() {
+        let source = include_str!("lib.rs");
+        let start = source.find("tauri::generate_handler![").unwrap();
+        let block = &source[start..];
+        let block = &block[block.find('[').unwrap() + 1..block.find("])").unwrap()];
+        let handler: Vec<&str> = block
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(|line| line.trim_end_matches(','))
+            .collect();
+
+        assert_eq!(handler, REGISTERED_COMMANDS);
+    }
+
+    #[test]
+    fn available_commands_should_hide_other_platform_commands() {
+        let windows = available_commands("windows");
+        assert!(windows.contains(&"install_and_start_service".to_string()));
+        assert!(!windows.contains(&"install_macos_helper".to_string()));
+
+        let macos = available_commands("macos");
+        assert!(macos.contains(&"install_macos_helper".to_string()));
+        assert!(!macos.contains(&"install_and_start_service".to_string()));
+
+        let linux = available_commands("linux");
+        assert!(!linux.contains(&"restart_as_admin".to_string()));
+        assert!(!linux.contains(&"is_macos_helper_available".to_string()));
+        assert!(linux.contains(&"get_capabilities".to_string()));
+    }
+
+    #[test]
+    fn newly_disabled_domains_should_only_include_transitions() {
+        let endpoint = |domain: &str, enabled: bool| Endpoint {
+            enabled,
+            ..result_for(domain, "1.1.1.1", 1.0).endpoint
+        };
+        let previous = AppConfig {
+            endpoints: vec![
+                endpoint("a.com", true),
+                endpoint("b.com", false),
+                endpoint("c.com", true),
+            ],
+            ..AppConfig::default()
+        };
+        let current = AppConfig {
+            endpoints: vec![
+                endpoint("A.com", false),
+                endpoint("b.com", false),
+                endpoint("c.com", true),
+                endpoint("new.com", false),
+            ],
+            ..AppConfig::default()
+        };
+
+        assert_eq!(newly_disabled_domains(&previous, &current), vec!["A.com"]);
+    }
+
+    #[test]
+    fn compare_versions_should_follow_semver_precedence() {
+        // 常规递增
+        assert!(compare_versions("1.2.1", "1.2.0"));
+        assert!(compare_versions("1.10.0", "1.9.9"));
+        assert!(compare_versions("v2.0.0", "1.99.0"));
+        assert!(!compare_versions("1.2.0", "1.2.1"));
+
+        // 缺省补零视为相等
+        assert!(!compare_versions("1.2", "1.2.0"));
+        assert!(!compare_versions("1.2.0", "1.2"));
+
+        // 预发布版本低于正式版
+        assert!(compare_versions("1.2.0", "1.2.0-beta"));
+        assert!(!compare_versions("1.2.0-beta", "1.2.0"));
+        assert!(compare_versions("1.2.0-beta.2", "1.2.0-beta.1"));
+        assert!(!compare_versions("1.2-beta", "1.2.0"));
+
+        // build 元数据不参与比较，无法解析时不提示更新
+        assert!(!compare_versions("1.2.0+build.5", "1.2.0"));
+        assert!(!compare_versions("latest", "1.2.0"));
+    }
 }
@@ -1,6 +1,8 @@
 //! Data models
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Endpoint {
@@ -8,6 +10,147 @@ pub struct Endpoint {
     pub url: String,
     pub domain: String,
     pub enabled: bool,
+    /// 测速端口（旧配置缺失该字段时默认 443）
+    #[serde(default = "default_endpoint_port")]
+    pub port: Option<u16>,
+    /// 收藏（置顶显示）
+    #[serde(default)]
+    pub favorite: bool,
+    /// 超时倍数（已知较慢的中转站可调大，作用于 DNS 解析与单 IP 测速超时）
+    #[serde(default = "default_timeout_multiplier")]
+    pub timeout_multiplier: f64,
+    /// 来自端点订阅（手动添加的端点为 false，订阅刷新不会改动）
+    #[serde(default)]
+    pub subscribed: bool,
+    /// 订阅源已不再包含该端点（保留在列表中，由用户决定是否删除）
+    #[serde(default)]
+    pub removed_upstream: bool,
+    /// 视为可用的 HTTP 状态码（为空时 1xx-4xx 可用、5xx 视为故障）
+    #[serde(default)]
+    pub healthy_status_codes: Vec<u16>,
+}
+
+/// 手动构造端点时的默认值：启用、端口按 URL 协议推断、超时倍数 1、默认健康状态码规则
+impl Default for Endpoint {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            url: String::new(),
+            domain: String::new(),
+            enabled: true,
+            port: None,
+            favorite: false,
+            timeout_multiplier: default_timeout_multiplier(),
+            subscribed: false,
+            removed_upstream: false,
+            healthy_status_codes: Vec::new(),
+        }
+    }
+}
+
+/// 端点默认 HTTPS 端口
+pub const DEFAULT_ENDPOINT_PORT: u16 = 443;
+/// 明文 HTTP 端点默认端口
+pub const DEFAULT_HTTP_PORT: u16 = 80;
+
+/// 超时倍数允许范围（避免误配置导致测速过快失败或长时间卡住）
+pub const MIN_TIMEOUT_MULTIPLIER: f64 = 0.5;
+pub const MAX_TIMEOUT_MULTIPLIER: f64 = 5.0;
+
+/// 最小加速百分比上限（加速幅度不可能达到 100%）
+pub const MAX_MIN_SPEEDUP_PERCENT: f64 = 100.0;
+
+/// 将最小加速百分比限制在 [0, MAX_MIN_SPEEDUP_PERCENT]，负数和非有限值视为不限制
+pub fn clamp_min_speedup_percent(value: f64) -> f64 {
+    if value.is_finite() {
+        value.clamp(0.0, MAX_MIN_SPEEDUP_PERCENT)
+    } else {
+        0.0
+    }
+}
+
+/// 内置 User-Agent（Chrome 风格，平台与探测请求的 sec-ch-ua-platform 及 TLS 指纹一致）
+#[cfg(target_os = "macos")]
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+
+/// 规范化自定义 User-Agent：空白视为未配置；含换行等控制字符（可注入请求头）时报错
+pub fn normalize_user_agent(raw: &str) -> Result<Option<String>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    if trimmed.chars().any(char::is_control) {
+        return Err("User-Agent 不能包含换行或控制字符".into());
+    }
+    Ok(Some(trimmed.to_string()))
+}
+
+/// 每个域名最多绑定的 IP 数（多 IP 轮询）
+pub const MAX_IPS_PER_DOMAIN: u32 = 4;
+
+impl Endpoint {
+    /// 实际使用的测速端口（未配置时 HTTPS 为 443，HTTP 为 80）
+    /// 旧配置缺失 port 时统一填入 443，因此 http:// 端点上的 443 也按未配置处理
+    pub fn effective_port(&self) -> u16 {
+        match self.port {
+            Some(port) if self.uses_tls() || port != DEFAULT_ENDPOINT_PORT => port,
+            _ if self.uses_tls() => DEFAULT_ENDPOINT_PORT,
+            _ => DEFAULT_HTTP_PORT,
+        }
+    }
+
+    /// 是否通过 TLS 测速：仅 URL 明确为 http:// 时走明文 HTTP，其余（含缺省）均为 HTTPS
+    pub fn uses_tls(&self) -> bool {
+        !self
+            .url
+            .trim_start()
+            .get(..7)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+    }
+
+    /// 探测响应状态码是否表示端点可用
+    pub fn is_healthy_status(&self, status: u16) -> bool {
+        if self.healthy_status_codes.is_empty() {
+            status < 500
+        } else {
+            self.healthy_status_codes.contains(&status)
+        }
+    }
+
+    /// 丢弃不合法的状态码（100-599 之外），并排序去重
+    pub fn normalize_healthy_status_codes(&mut self) {
+        self.healthy_status_codes
+            .retain(|code| (100..=599).contains(code));
+        self.healthy_status_codes.sort_unstable();
+        self.healthy_status_codes.dedup();
+    }
+
+    /// 按端点超时倍数缩放基础超时（非法值按 1.0 处理，并限制在允许范围内）
+    pub fn scaled_timeout(&self, base: Duration) -> Duration {
+        let multiplier = if self.timeout_multiplier.is_finite() {
+            self.timeout_multiplier
+                .clamp(MIN_TIMEOUT_MULTIPLIER, MAX_TIMEOUT_MULTIPLIER)
+        } else {
+            1.0
+        };
+        base.mul_f64(multiplier)
+    }
+}
+
+/// 单 IP 探测失败原因分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeErrorKind {
+    /// TCP 连接失败（超时、拒绝、重置）
+    Connect,
+    /// TLS 握手失败（非证书原因）
+    TlsHandshake,
+    /// 证书校验失败（不受信任、过期、非 CF IP 的域名不匹配等）
+    CertInvalid,
+    /// IP 可达但证书与 SNI 域名不匹配（CF 边缘 IP 常见，并非 IP 不可用）
+    CertMismatch,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,15 +173,78 @@ pub struct EndpointResult {
     pub speedup_percent: f64,
     #[serde(default)]
     pub use_original: bool,
+    /// 下载吞吐量（KB/s），未开启吞吐量测量时为 0
+    #[serde(default)]
+    pub throughput_kbps: f64,
+    /// 延迟评级（按 AppConfig.latency_thresholds 计算，失败结果为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<LatencyRating>,
+    /// 失败原因分类（区分连接失败、握手失败与证书问题）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ProbeErrorKind>,
+    /// 探测实际使用的应用层协议（"h2" / "http/1.1"）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// 按延迟排序的其余可用 IP（不含 ip），用于多 IP 绑定
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backup_ips: Vec<String>,
+    /// 选择该 IP 的原因（如"在 12 个候选 IP 中最快"），仅完整优选的结果带有
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub selection_reason: String,
+    /// 本轮未实际测试（端点在 skip_recently_failed_secs 内刚失败过）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+    /// 加速幅度低于 min_speedup_percent，应用时保留当前绑定
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub below_min_speedup: bool,
+}
+
+/// 延迟评级：Good / Ok / Poor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LatencyRating {
+    Good,
+    Ok,
+    Poor,
+}
+
+/// 延迟评级阈值（毫秒）：< good_ms 为 Good，< ok_ms 为 Ok，其余为 Poor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencyThresholds {
+    pub good_ms: f64,
+    pub ok_ms: f64,
+}
+
+impl Default for LatencyThresholds {
+    fn default() -> Self {
+        Self {
+            good_ms: 200.0,
+            ok_ms: 500.0,
+        }
+    }
+}
+
+impl LatencyThresholds {
+    /// 按阈值评级延迟
+    pub fn rate(&self, latency: f64) -> LatencyRating {
+        if latency < self.good_ms {
+            LatencyRating::Good
+        } else if latency < self.ok_ms {
+            LatencyRating::Ok
+        } else {
+            LatencyRating::Poor
+        }
+    }
 }
 
 impl EndpointResult {
-    pub fn success(endpoint: Endpoint, ip: String, latency: f64) -> Self {
+    /// latency 为连接延迟（TCP + TLS 握手），ttfb 为首字节时间
+    pub fn success(endpoint: Endpoint, ip: String, latency: f64, ttfb: f64) -> Self {
         Self {
             endpoint,
             ip,
             latency,
-            ttfb: latency,
+            ttfb,
             success: true,
             error: None,
             warning: None,
@@ -46,6 +252,14 @@ impl EndpointResult {
             original_latency: 0.0,
             speedup_percent: 0.0,
             use_original: false,
+            throughput_kbps: 0.0,
+            rating: None,
+            error_kind: None,
+            protocol: None,
+            backup_ips: Vec::new(),
+            selection_reason: String::new(),
+            skipped: false,
+            below_min_speedup: false,
         }
     }
 
@@ -57,11 +271,7 @@ impl EndpointResult {
         original_latency: f64,
     ) -> Self {
         // 计算加速百分比（始终和原始 DNS IP 对比）
-        let speedup_percent = if original_latency > 0.0 && latency < 9999.0 {
-            (original_latency - latency) / original_latency * 100.0
-        } else {
-            0.0
-        };
+        let speedup_percent = speedup_percent(original_latency, latency);
 
         // 始终使用测试中最快的 IP，不回退到原始 IP
         // use_original 仅用于标记当前使用的 IP 是否恰好是原始 IP
@@ -79,9 +289,66 @@ impl EndpointResult {
             original_latency,
             speedup_percent,
             use_original,
+            throughput_kbps: 0.0,
+            rating: None,
+            error_kind: None,
+            protocol: None,
+            backup_ips: Vec::new(),
+            selection_reason: String::new(),
+            skipped: false,
+            below_min_speedup: false,
         }
     }
 
+    /// 从同一 IP 的单次测试结果复制 TTFB、吞吐量与协议
+    pub fn with_metrics_from(mut self, measured: &EndpointResult) -> Self {
+        self.ttfb = measured.ttfb;
+        self.throughput_kbps = measured.throughput_kbps;
+        self.protocol = measured.protocol.clone();
+        self
+    }
+
+    /// 附带其余可用 IP
+    pub fn with_backup_ips(mut self, backup_ips: Vec<String>) -> Self {
+        self.backup_ips = backup_ips;
+        self
+    }
+
+    /// 附带选择该 IP 的原因
+    pub fn with_selection_reason(mut self, reason: String) -> Self {
+        self.selection_reason = reason;
+        self
+    }
+
+    /// 标注失败原因分类
+    pub fn with_error_kind(mut self, kind: Option<ProbeErrorKind>) -> Self {
+        self.error_kind = kind;
+        self
+    }
+
+    /// 加速幅度是否低于阈值（阈值 <= 0、原始延迟未知或应使用原始 IP 时不算）
+    pub fn is_below_min_speedup(&self, min_speedup_percent: f64) -> bool {
+        min_speedup_percent > 0.0
+            && self.success
+            && !self.use_original
+            && self.original_latency > 0.0
+            && self.speedup_percent < min_speedup_percent
+    }
+
+    /// 根据最小加速阈值标记 below_min_speedup
+    pub fn apply_min_speedup(&mut self, min_speedup_percent: f64) {
+        self.below_min_speedup = self.is_below_min_speedup(min_speedup_percent);
+    }
+
+    /// 根据阈值填充评级（仅成功结果）
+    pub fn apply_rating(&mut self, thresholds: &LatencyThresholds) {
+        self.rating = if self.success {
+            Some(thresholds.rate(self.latency))
+        } else {
+            None
+        };
+    }
+
     pub fn failure(endpoint: Endpoint, ip: String, error: String) -> Self {
         Self {
             endpoint,
@@ -95,6 +362,178 @@ impl EndpointResult {
             original_latency: 0.0,
             speedup_percent: 0.0,
             use_original: false,
+            throughput_kbps: 0.0,
+            rating: None,
+            error_kind: None,
+            protocol: None,
+            backup_ips: Vec::new(),
+            selection_reason: String::new(),
+            skipped: false,
+            below_min_speedup: false,
+        }
+    }
+
+    /// 因最近测速失败而跳过的端点（记为失败，不参与应用）
+    pub fn skipped_recent_failure(endpoint: Endpoint, failed_secs_ago: u64) -> Self {
+        Self {
+            skipped: true,
+            ..Self::failure(
+                endpoint,
+                String::new(),
+                format!("已跳过（{} 秒前测速失败）", failed_secs_ago),
+            )
+        }
+    }
+}
+
+/// IP 协议族偏好（影响多 DNS 解析请求的记录类型）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IpVersionPreference {
+    /// 自动：优先 A 记录，无 A 记录时再查 AAAA
+    #[default]
+    Auto,
+    /// 仅 IPv4（A 记录）
+    Ipv4,
+    /// 仅 IPv6（AAAA 记录）
+    Ipv6,
+}
+
+/// 最近一次成功"全部应用"的时间与写入数量（持久化，供界面显示"上次优化于"）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastApplyInfo {
+    /// Unix 时间戳（秒）
+    pub timestamp: i64,
+    /// 实际新增或修改的绑定数
+    pub count: u32,
+}
+
+/// 已有测速结果的排序键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultSortKey {
+    /// 连接延迟
+    Latency,
+    /// 相对原始 IP 的加速百分比
+    Speedup,
+    /// 端点名称
+    Name,
+    /// 域名
+    Domain,
+}
+
+/// hosts 片段导入结果汇总
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BindingsImportSummary {
+    /// 片段中有效的绑定数
+    pub imported_bindings: u32,
+    /// 实际新增或修改的绑定数（与当前 hosts 相同的不计）
+    pub changed_bindings: u32,
+    /// 格式无效被跳过的条目数
+    pub skipped_invalid: u32,
+}
+
+/// "全部应用"的结果；dry_run 时为将要执行的计划，未做任何修改
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApplyAllOutcome {
+    /// 实际新增或修改的绑定数（dry_run 时为将要写入的绑定数）
+    pub count: u32,
+    /// 计划写入的绑定 (domain, ip)
+    pub bindings: Vec<(String, String)>,
+    /// 当前绑定已是最优或加速不足阈值而保持不变的域名数
+    pub kept_count: u32,
+    pub dry_run: bool,
+}
+
+/// 逐域名写入 hosts 的结果（部分失败时不影响其他域名）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BindingWriteOutcome {
+    pub domain: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 配置导入结果汇总
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigImportSummary {
+    /// 实际导入的端点数
+    pub imported_endpoints: u32,
+    /// 因域名重复被跳过的端点数
+    pub skipped_duplicates: u32,
+}
+
+/// 候选 IP 预览（DNS 解析 + 合并结果，不含探测）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandidateIps {
+    pub dns_ips: Vec<String>,
+    pub cf_ips: Vec<String>,
+    pub merged: Vec<String>,
+    pub is_cf: bool,
+}
+
+/// 单个 DNS 解析器的测速结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsBenchmarkResult {
+    pub server: String,
+    /// 解析耗时（ms），失败时为超时前实际等待的时间
+    pub latency_ms: f64,
+    pub ips: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 任意 URL 的单次诊断探测结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlProbeResult {
+    /// 实际探测的 IP（DNS 返回的第一个）
+    pub ip: String,
+    pub dns_ms: f64,
+    /// 连接耗时：TCP 建连（https 含 TLS 握手）
+    pub connect_ms: f64,
+    pub ttfb_ms: f64,
+    /// HTTP 状态码（无法解析响应状态行时为 None）
+    pub status: Option<u16>,
+}
+
+/// 配置检查问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// 会导致测速或绑定失败
+    Error,
+    /// 可以运行，但结果可能不符合预期
+    Warning,
+}
+
+/// 配置检查发现的问题（domain 为空表示整体配置问题）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    pub domain: Option<String>,
+    pub severity: IssueSeverity,
+    pub message: String,
+}
+
+/// 已应用绑定的连通性检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BindingHealth {
+    pub domain: String,
+    pub ip: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BindingHealth {
+    pub fn from_result(domain: String, result: &EndpointResult) -> Self {
+        Self {
+            domain,
+            ip: result.ip.clone(),
+            reachable: result.success,
+            latency: result.success.then_some(result.latency),
+            error: result.error.clone(),
         }
     }
 }
@@ -118,6 +557,78 @@ pub struct HistoryStats {
     pub records: Vec<HistoryRecord>,
 }
 
+/// 相对原始延迟的加速百分比（变慢时为负；任一延迟无效时为 0）
+pub fn speedup_percent(original_latency: f64, latency: f64) -> f64 {
+    if original_latency > 0.0 && latency < 9999.0 {
+        (original_latency - latency) / original_latency * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// 应用单个域名最优 IP 前后的实测对比（探测失败的一侧为 None）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApplyMeasurement {
+    pub domain: String,
+    /// 应用前使用的 IP（当前绑定或原始 DNS IP）
+    pub before_ip: String,
+    pub applied_ip: String,
+    pub before_ms: Option<f64>,
+    pub after_ms: Option<f64>,
+    /// 前后都探测成功时的加速百分比
+    pub improvement_percent: Option<f64>,
+}
+
+/// 根据近期稳定性推荐的持续优化检查间隔
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckIntervalSuggestion {
+    pub suggested_secs: u64,
+    /// 推荐理由（含所依据的切换频率与失败率）
+    pub rationale: String,
+}
+
+/// 历史记录存储概况（清空前查看数据量、排查文件膨胀）
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct HistoryInfo {
+    pub record_count: u32,
+    pub file_size_bytes: u64,
+    /// 最早 / 最新记录的时间戳（无记录时为 None）
+    pub oldest_timestamp: Option<i64>,
+    pub newest_timestamp: Option<i64>,
+}
+
+/// 网络环境诊断信息（VPN 或自定义 DNS 可能导致 hosts 优选不生效）
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct NetworkInfo {
+    /// 默认网关及其所在网卡（无法获取时为 None）
+    pub default_gateway: Option<String>,
+    pub default_interface: Option<String>,
+    /// 系统配置的 DNS 服务器（按优先级去重）
+    pub dns_servers: Vec<String>,
+    pub vpn_detected: bool,
+    /// 识别为 VPN / 隧道的网卡
+    pub vpn_interfaces: Vec<String>,
+}
+
+/// 单个域名的历史记录（按时间升序）及优化后延迟统计，无记录时统计为 0
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DomainHistory {
+    pub domain: String,
+    pub min_latency: f64,
+    pub max_latency: f64,
+    pub avg_latency: f64,
+    pub records: Vec<HistoryRecord>,
+}
+
+/// 按 UTC 自然日汇总的历史数据（date 为 YYYY-MM-DD）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DailyHistory {
+    pub date: String,
+    pub test_count: u32,
+    pub avg_speedup_percent: f64,
+    pub total_speedup_ms: f64,
+}
+
 /// Permission status for hosts file operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -126,6 +637,52 @@ pub struct PermissionStatus {
     pub is_using_service: bool,
 }
 
+/// 服务管道不可用的原因（前端据此给出修复建议）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServicePipeFailure {
+    /// 等待管道超时（服务繁忙或无响应）
+    Timeout,
+    /// 管道存在但安全描述符拒绝当前用户访问
+    AccessDenied,
+    /// 服务未安装或未运行
+    NotRunning,
+    /// 已连接但请求失败（IO、协议错误等）
+    Other,
+}
+
+/// 服务管道访问检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServicePipeAccess {
+    pub accessible: bool,
+    pub reason: Option<ServicePipeFailure>,
+    /// 原始错误信息
+    pub detail: Option<String>,
+    /// 服务版本（可访问时）
+    pub service_version: Option<String>,
+}
+
+/// hosts 文件编码检查结果（UTF-16 / 非 UTF-8 文件无法安全改写）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostsEncodingReport {
+    /// 检测到的编码（如 "UTF-8"、"UTF-16 LE"）
+    pub encoding: String,
+    pub supported: bool,
+    /// 不支持时的处理建议
+    pub message: Option<String>,
+}
+
+/// 平台能力信息（前端据此隐藏当前平台不支持的功能）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub platform: String,
+    pub commands: Vec<String>,
+    pub service_available: bool,
+    pub helper_available: bool,
+}
+
 /// 更新排查诊断步骤
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -170,6 +727,122 @@ pub struct AppConfig {
     /// 更新代理地址: "auto" = 自动检测系统代理, "" = 不使用代理, 其他 = 手动指定
     #[serde(default = "default_update_proxy")]
     pub update_proxy: String,
+    /// 延迟评级阈值（UI 着色与导出共用）
+    #[serde(default = "default_latency_thresholds")]
+    pub latency_thresholds: LatencyThresholds,
+    /// 在线优选 IP 磁盘缓存有效期（秒）
+    #[serde(default = "default_cf_ip_cache_ttl_secs")]
+    pub cf_ip_cache_ttl_secs: u64,
+    /// CF IP 基准测试并发数
+    #[serde(default = "default_benchmark_concurrency")]
+    pub benchmark_concurrency: u32,
+    /// 测速时最大端点并发数（None 使用测速强度预设，范围 1-32）
+    #[serde(default)]
+    pub max_endpoint_concurrency: Option<u32>,
+    /// 每个端点内最大 IP 并发数（None 使用测速强度预设，范围 1-32）
+    #[serde(default)]
+    pub max_ip_concurrency: Option<u32>,
+    /// 每个端点最多测试的候选 IP 数（None 使用测速强度预设，范围 1-50）
+    #[serde(default)]
+    pub max_test_ips: Option<u32>,
+    /// 自定义优选 IP 来源 URL（为空时使用内置来源）
+    #[serde(default)]
+    pub cf_ip_source_url: Option<String>,
+    /// 持续优化每日汇总通知（默认关闭）
+    #[serde(default)]
+    pub daily_summary: bool,
+    /// 测速时额外测量下载吞吐量（GET 请求，默认关闭以保持 HEAD 快速路径）
+    #[serde(default)]
+    pub measure_throughput: bool,
+    /// 吞吐量测量请求路径
+    #[serde(default = "default_throughput_path")]
+    pub throughput_path: String,
+    /// IP 协议族偏好：auto / ipv4 / ipv6
+    #[serde(default)]
+    pub ip_version_preference: IpVersionPreference,
+    /// 测速代理（http:// 或 socks5://），同时作为在线优选 IP 与更新检查的默认代理
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// 测速时通过 ALPN 协商 HTTP/2（默认关闭，仅 HTTP/1.1；开启吞吐量测量时仍走 HTTP/1.1）
+    #[serde(default)]
+    pub enable_http2: bool,
+    /// 严格 CF 模式：所有端点都按 CF 站点测速（只测优选 IP，跳过多 DNS 解析），
+    /// 适用于 DNS 返回非 CF 代理但源站实际在 CF 后面的情况
+    #[serde(default)]
+    pub cf_only: bool,
+    /// 测速时跳过在此秒数内刚失败过的端点（0 表示不跳过）
+    #[serde(default)]
+    pub skip_recently_failed_secs: u64,
+    /// 最小加速百分比：优选 IP 相对原始延迟的加速低于此值时保留当前绑定（0 表示不限制）
+    #[serde(default)]
+    pub min_speedup_percent: f64,
+    /// 定时全量重测并应用的间隔（小时，0 表示关闭），与持续优化的轻量健康检查相互独立
+    #[serde(default)]
+    pub periodic_retest_hours: u32,
+    /// 写入绑定后注释掉 anyFAST 区块外对同一域名的重复绑定（原行保留为注释，可恢复）
+    #[serde(default)]
+    pub deconflict_hosts: bool,
+    /// 退出应用时清除所有 anyFAST hosts 绑定（默认保留）
+    #[serde(default)]
+    pub clear_hosts_on_quit: bool,
+    /// 禁用端点时同时清除其 hosts 绑定（默认开启）
+    #[serde(default = "default_clear_binding_on_disable")]
+    pub clear_binding_on_disable: bool,
+    /// 全部应用时每个域名绑定的最快 IP 数（1 = 单 IP，上限 MAX_IPS_PER_DOMAIN）
+    #[serde(default = "default_ips_per_domain")]
+    pub ips_per_domain: u32,
+    /// 历史记录保留天数（最少 1 天）
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u32,
+    /// 持续优化自动切换 IP 时发送系统通知（默认开启）
+    #[serde(default = "default_notify_on_switch")]
+    pub notify_on_switch: bool,
+    /// 严重变慢判定：除比基准慢 slow_threshold% 外，绝对增加还需超过该值（ms）
+    #[serde(default = "default_severe_abs_threshold_ms")]
+    pub severe_abs_threshold_ms: f64,
+    /// 同一域名两次全量优选的最小间隔（秒，最少 60）
+    #[serde(default = "default_full_test_cooldown_secs")]
+    pub full_test_cooldown_secs: u64,
+    /// 当前 IP 仍可用时，候选 IP 需至少快多少百分比才切换
+    #[serde(default = "default_switch_min_improvement_percent")]
+    pub switch_min_improvement_percent: f64,
+    /// 当前 IP 仍可用时，候选 IP 需至少快多少 ms 才切换
+    #[serde(default = "default_switch_min_improvement_ms")]
+    pub switch_min_improvement_ms: f64,
+    /// 检查间隔随机抖动比例（±%，0 表示不抖动，最大 50）
+    #[serde(default = "default_check_jitter_percent")]
+    pub check_jitter_percent: u32,
+    /// 持续优化任务崩溃后看门狗最多自动重启的次数（0 表示不重启）
+    #[serde(default = "default_health_loop_max_restarts")]
+    pub health_loop_max_restarts: u32,
+    /// 端点订阅地址（社区维护的端点列表，为空表示不订阅）
+    #[serde(default)]
+    pub subscription_url: Option<String>,
+    /// 订阅自动刷新间隔（小时，范围 1-168）
+    #[serde(default = "default_subscription_refresh_hours")]
+    pub subscription_refresh_hours: u32,
+    /// 探测请求与在线 IP、更新检查等 HTTP 请求使用的 User-Agent（为空时使用内置浏览器 UA）
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+impl AppConfig {
+    /// 实际使用的 User-Agent：配置值无效或为空时回退到内置浏览器 UA
+    pub fn effective_user_agent(&self) -> String {
+        match self.user_agent.as_deref().map(normalize_user_agent) {
+            Some(Ok(Some(ua))) => ua,
+            _ => DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+
+    /// 更新检查实际使用的代理设置：
+    /// update_proxy 为 "auto" 且配置了测速代理时复用测速代理，否则保持原设置
+    pub fn effective_update_proxy(&self) -> String {
+        match self.proxy.as_deref().map(str::trim) {
+            Some(proxy) if self.update_proxy == "auto" && !proxy.is_empty() => proxy.to_string(),
+            _ => self.update_proxy.clone(),
+        }
+    }
 }
 
 impl Default for AppConfig {
@@ -185,6 +858,38 @@ impl Default for AppConfig {
             continuous_mode: default_continuous_mode(),
             test_aggressiveness: default_test_aggressiveness(),
             update_proxy: default_update_proxy(),
+            latency_thresholds: default_latency_thresholds(),
+            cf_ip_cache_ttl_secs: default_cf_ip_cache_ttl_secs(),
+            benchmark_concurrency: default_benchmark_concurrency(),
+            cf_ip_source_url: None,
+            max_endpoint_concurrency: None,
+            max_ip_concurrency: None,
+            max_test_ips: None,
+            daily_summary: false,
+            measure_throughput: false,
+            throughput_path: default_throughput_path(),
+            ip_version_preference: IpVersionPreference::default(),
+            proxy: None,
+            enable_http2: false,
+            cf_only: false,
+            skip_recently_failed_secs: 0,
+            min_speedup_percent: 0.0,
+            periodic_retest_hours: 0,
+            deconflict_hosts: false,
+            clear_hosts_on_quit: false,
+            clear_binding_on_disable: default_clear_binding_on_disable(),
+            ips_per_domain: default_ips_per_domain(),
+            history_retention_days: default_history_retention_days(),
+            notify_on_switch: default_notify_on_switch(),
+            severe_abs_threshold_ms: default_severe_abs_threshold_ms(),
+            full_test_cooldown_secs: default_full_test_cooldown_secs(),
+            switch_min_improvement_percent: default_switch_min_improvement_percent(),
+            switch_min_improvement_ms: default_switch_min_improvement_ms(),
+            check_jitter_percent: default_check_jitter_percent(),
+            health_loop_max_restarts: default_health_loop_max_restarts(),
+            subscription_url: None,
+            subscription_refresh_hours: default_subscription_refresh_hours(),
+            user_agent: None,
         }
     }
 }
@@ -212,17 +917,27 @@ fn default_endpoints() -> Vec<Endpoint> {
             name: "anyrouter".into(),
             url: "https://cf.betterclau.de/claude/anyrouter.top".into(),
             domain: "cf.betterclau.de".into(),
-            enabled: true,
+            port: default_endpoint_port(),
+            ..Default::default()
         },
         Endpoint {
             name: "WONG公益站".into(),
             url: "https://wzw.pp.ua".into(),
             domain: "wzw.pp.ua".into(),
-            enabled: true,
+            port: default_endpoint_port(),
+            ..Default::default()
         },
     ]
 }
 
+fn default_timeout_multiplier() -> f64 {
+    1.0
+}
+
+fn default_endpoint_port() -> Option<u16> {
+    Some(DEFAULT_ENDPOINT_PORT)
+}
+
 fn default_preferred_ips() -> Vec<String> {
     Vec::new()
 }
@@ -231,6 +946,50 @@ fn default_continuous_mode() -> bool {
     true
 }
 
+fn default_clear_binding_on_disable() -> bool {
+    true
+}
+
+fn default_severe_abs_threshold_ms() -> f64 {
+    300.0
+}
+
+fn default_full_test_cooldown_secs() -> u64 {
+    600
+} // 10 分钟
+
+fn default_switch_min_improvement_percent() -> f64 {
+    20.0
+}
+
+fn default_switch_min_improvement_ms() -> f64 {
+    50.0
+}
+
+fn default_check_jitter_percent() -> u32 {
+    10
+}
+
+fn default_health_loop_max_restarts() -> u32 {
+    5
+}
+
+fn default_subscription_refresh_hours() -> u32 {
+    24
+}
+
+fn default_notify_on_switch() -> bool {
+    true
+}
+
+fn default_history_retention_days() -> u32 {
+    7
+}
+
+fn default_ips_per_domain() -> u32 {
+    1
+}
+
 fn default_test_aggressiveness() -> u32 {
     2
 } // 1=保守, 2=标准, 3=激进
@@ -239,6 +998,22 @@ fn default_update_proxy() -> String {
     "auto".into()
 } // "auto" = 自动检测, "" = 不使用, 其他 = 手动指定
 
+fn default_latency_thresholds() -> LatencyThresholds {
+    LatencyThresholds::default()
+}
+
+fn default_cf_ip_cache_ttl_secs() -> u64 {
+    crate::cf_ip_cache::DEFAULT_CF_IP_CACHE_TTL_SECS
+} // 在线优选 IP 缓存 6 小时
+
+fn default_benchmark_concurrency() -> u32 {
+    12
+}
+
+fn default_throughput_path() -> String {
+    "/".into()
+}
+
 /// 测速进度事件类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -254,6 +1029,7 @@ pub enum TestProgressEventType {
     TcpProbeComplete,
     EndpointComplete,
     TestFinished,
+    BenchmarkIpComplete,
 }
 
 /// 测速进度事件（后端 → 前端实时日志）
@@ -266,6 +1042,34 @@ pub struct TestProgressEvent {
     pub message: String,
 }
 
+/// 流式测速的单个端点结果事件（speed-test-result）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedTestResultEvent {
+    pub run_id: u64,
+    pub result: EndpointResult,
+}
+
+/// 流式测速结束事件（speed-test-finished）；error 为超时等异常结束原因
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedTestFinishedEvent {
+    pub run_id: u64,
+    pub cancelled: bool,
+    pub result_count: usize,
+    pub error: Option<String>,
+}
+
+/// 定时全量重测完成事件（periodic-retest-done）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodicRetestEvent {
+    /// 实际写入的绑定数
+    pub applied: u32,
+    /// 测速或应用失败（含被取消）时的原因
+    pub error: Option<String>,
+}
+
 /// 持续优化事件类型
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
@@ -275,6 +1079,8 @@ pub enum OptimizationEventType {
     Started,
     #[default]
     Stopped,
+    /// anyFAST 区块被外部修改，持续优化已自动暂停
+    ExternalModification,
 }
 
 /// 持续优化事件（后端 → 前端通知）
@@ -290,6 +1096,65 @@ pub struct OptimizationEvent {
     pub message: String,
 }
 
+/// 持续优化运行状态
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizationStatus {
+    pub is_running: bool,
+    /// 已暂停：后台任务仍在运行但跳过检查
+    pub is_paused: bool,
+}
+
+/// 持续优化任务被看门狗重启（后端 → 前端 health-checker-restarted 事件）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthCheckerRestart {
+    pub reason: String,
+    /// 本次启动以来的累计重启次数
+    pub restart_count: u32,
+}
+
+/// 持续优化任务异常退出且不再重启（后端 → 前端 health-loop-stopped 事件）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthLoopStopped {
+    pub reason: String,
+    /// 停止前已自动重启的次数
+    pub restart_count: u32,
+}
+
+/// 持续优化每日汇总（后端 → 前端 daily-summary 事件）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DailySummary {
+    /// 汇总日期（本地时区，YYYY-MM-DD）
+    pub date: String,
+    pub checks: u32,
+    pub switches: u32,
+    /// 域名 → 当日平均延迟（ms）
+    pub avg_latency: HashMap<String, f64>,
+}
+
+/// 端点订阅刷新结果（后端 → 前端 endpoints-updated 事件）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionSummary {
+    /// 订阅源中的有效端点数
+    pub upstream_count: u32,
+    /// 新增的端点数
+    pub added: u32,
+    /// 新标记为"订阅源已移除"的端点数
+    pub marked_removed: u32,
+    /// 重新出现在订阅源中、取消标记的端点数
+    pub restored: u32,
+}
+
+impl SubscriptionSummary {
+    pub fn has_changes(&self) -> bool {
+        self.added + self.marked_removed + self.restored > 0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -300,22 +1165,80 @@ mod tests {
             name: "Test".into(),
             url: "https://test.com/api".into(),
             domain: "test.com".into(),
-            enabled: true,
+            ..Default::default()
         };
         assert_eq!(ep.name, "Test");
         assert_eq!(ep.domain, "test.com");
         assert!(ep.enabled);
     }
 
+    #[test]
+    fn test_endpoint_port_default() {
+        // 旧配置没有 port 字段，反序列化后默认 443
+        let json = r#"{"name":"Test","url":"https://test.com","domain":"test.com","enabled":true}"#;
+        let ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert_eq!(ep.port, Some(443));
+        assert_eq!(ep.effective_port(), 443);
+        assert!(!ep.favorite);
+
+        let json = r#"{"name":"Test","url":"https://test.com","domain":"test.com","enabled":true,"port":8443}"#;
+        let ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert_eq!(ep.effective_port(), 8443);
+
+        let json = r#"{"name":"Test","url":"https://test.com","domain":"test.com","enabled":true,"port":null}"#;
+        let ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert_eq!(ep.effective_port(), 443);
+    }
+
+    #[test]
+    fn test_endpoint_plain_http_defaults() {
+        // http:// 端点走明文探测，旧配置默认填入的 443 视为未配置
+        let json = r#"{"name":"Test","url":"HTTP://test.com","domain":"test.com","enabled":true}"#;
+        let ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert!(!ep.uses_tls());
+        assert_eq!(ep.effective_port(), 80);
+
+        let json = r#"{"name":"Test","url":"http://test.com:8080","domain":"test.com","enabled":true,"port":8080}"#;
+        let ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert_eq!(ep.effective_port(), 8080);
+
+        let json =
+            r#"{"name":"Test","url":"test.com","domain":"test.com","enabled":true,"port":null}"#;
+        let ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert!(ep.uses_tls());
+        assert_eq!(ep.effective_port(), 443);
+    }
+
+    #[test]
+    fn test_endpoint_scaled_timeout() {
+        // 旧配置缺失倍数字段时默认 1.0，超时不变
+        let json = r#"{"name":"Test","url":"https://test.com","domain":"test.com","enabled":true}"#;
+        let mut ep: Endpoint = serde_json::from_str(json).unwrap();
+        assert_eq!(ep.timeout_multiplier, 1.0);
+        let base = Duration::from_secs(8);
+        assert_eq!(ep.scaled_timeout(base), base);
+
+        ep.timeout_multiplier = 2.5;
+        assert_eq!(ep.scaled_timeout(base), Duration::from_secs(20));
+
+        // 超出范围的值被钳制，非法值回退为 1.0
+        ep.timeout_multiplier = 100.0;
+        assert_eq!(ep.scaled_timeout(base), Duration::from_secs(40));
+        ep.timeout_multiplier = 0.0;
+        assert_eq!(ep.scaled_timeout(base), Duration::from_secs(4));
+        ep.timeout_multiplier = f64::NAN;
+        assert_eq!(ep.scaled_timeout(base), base);
+    }
+
     #[test]
     fn test_endpoint_result_success() {
         let ep = Endpoint {
             name: "Test".into(),
             url: "https://test.com".into(),
             domain: "test.com".into(),
-            enabled: true,
+            ..Default::default()
         };
-        let result = EndpointResult::success(ep.clone(), "1.2.3.4".into(), 100.0);
+        let result = EndpointResult::success(ep.clone(), "1.2.3.4".into(), 100.0, 100.0);
 
         assert!(result.success);
         assert_eq!(result.ip, "1.2.3.4");
@@ -323,13 +1246,60 @@ mod tests {
         assert!(result.error.is_none());
     }
 
+    #[test]
+    fn test_endpoint_result_ttfb_differs_from_latency() {
+        let ep = Endpoint {
+            name: "Test".into(),
+            url: "https://test.com".into(),
+            domain: "test.com".into(),
+            ..Default::default()
+        };
+        let mut result = EndpointResult::success(ep.clone(), "1.2.3.4".into(), 80.0, 230.0);
+        result.throughput_kbps = 512.0;
+        assert_eq!(result.latency, 80.0);
+        assert_eq!(result.ttfb, 230.0);
+
+        let compared = EndpointResult::success_with_comparison(
+            ep,
+            "1.2.3.4".into(),
+            80.0,
+            "5.6.7.8".into(),
+            160.0,
+        )
+        .with_metrics_from(&result);
+        assert_eq!(compared.throughput_kbps, 512.0);
+        assert_eq!(compared.latency, 80.0);
+        assert_eq!(compared.ttfb, 230.0);
+    }
+
+    #[test]
+    fn test_binding_health_from_result() {
+        let ep = Endpoint {
+            name: "Test".into(),
+            url: "https://test.com".into(),
+            domain: "test.com".into(),
+            ..Default::default()
+        };
+        let ok = EndpointResult::success(ep.clone(), "1.2.3.4".into(), 90.0, 120.0);
+        let health = BindingHealth::from_result("test.com".into(), &ok);
+        assert!(health.reachable);
+        assert_eq!(health.latency, Some(90.0));
+        assert!(health.error.is_none());
+
+        let failed = EndpointResult::failure(ep, "1.2.3.4".into(), "TCP_TIMEOUT".into());
+        let health = BindingHealth::from_result("test.com".into(), &failed);
+        assert!(!health.reachable);
+        assert_eq!(health.latency, None);
+        assert_eq!(health.error.as_deref(), Some("TCP_TIMEOUT"));
+    }
+
     #[test]
     fn test_endpoint_result_failure() {
         let ep = Endpoint {
             name: "Test".into(),
             url: "https://test.com".into(),
             domain: "test.com".into(),
-            enabled: true,
+            ..Default::default()
         };
         let result = EndpointResult::failure(ep.clone(), "1.2.3.4".into(), "Timeout".into());
 
@@ -344,7 +1314,7 @@ mod tests {
             name: "Test".into(),
             url: "https://test.com".into(),
             domain: "test.com".into(),
-            enabled: true,
+            ..Default::default()
         };
         // Original: 200ms, Optimized: 100ms -> 50% speedup
         let result = EndpointResult::success_with_comparison(
@@ -370,7 +1340,7 @@ mod tests {
             name: "Test".into(),
             url: "https://test.com".into(),
             domain: "test.com".into(),
-            enabled: true,
+            ..Default::default()
         };
         // 新逻辑：传入的 IP 就是最优 IP（调用方已经选好了）
         // 这里模拟原始 IP 就是最优的情况
@@ -394,7 +1364,7 @@ mod tests {
             name: "Test".into(),
             url: "https://test.com".into(),
             domain: "test.com".into(),
-            enabled: true,
+            ..Default::default()
         };
         // 传入的 IP 恰好等于原始 IP
         let result = EndpointResult::success_with_comparison(
@@ -437,6 +1407,88 @@ mod tests {
         assert_eq!(config.preferred_ips, parsed.preferred_ips);
     }
 
+    #[test]
+    fn test_latency_thresholds_rate() {
+        let t = LatencyThresholds::default();
+        assert_eq!(t.rate(50.0), LatencyRating::Good);
+        assert_eq!(t.rate(200.0), LatencyRating::Ok);
+        assert_eq!(t.rate(499.0), LatencyRating::Ok);
+        assert_eq!(t.rate(500.0), LatencyRating::Poor);
+
+        let custom = LatencyThresholds {
+            good_ms: 80.0,
+            ok_ms: 150.0,
+        };
+        assert_eq!(custom.rate(100.0), LatencyRating::Ok);
+        assert_eq!(custom.rate(200.0), LatencyRating::Poor);
+    }
+
+    #[test]
+    fn test_endpoint_result_apply_rating() {
+        let ep = Endpoint {
+            name: "Test".into(),
+            url: "https://test.com".into(),
+            domain: "test.com".into(),
+            ..Default::default()
+        };
+        let thresholds = LatencyThresholds::default();
+
+        let mut ok = EndpointResult::success(ep.clone(), "1.2.3.4".into(), 120.0, 180.0);
+        ok.apply_rating(&thresholds);
+        assert_eq!(ok.rating, Some(LatencyRating::Good));
+
+        let mut failed = EndpointResult::failure(ep, "1.2.3.4".into(), "Timeout".into());
+        failed.apply_rating(&thresholds);
+        assert_eq!(failed.rating, None);
+    }
+
+    #[test]
+    fn test_latency_thresholds_default_deserialization() {
+        let json = r#"{"check_interval":120}"#;
+        let parsed: AppConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.latency_thresholds, LatencyThresholds::default());
+    }
+
+    #[test]
+    fn test_ip_version_preference_serde() {
+        let parsed: AppConfig = serde_json::from_str(r#"{"check_interval":120}"#).unwrap();
+        assert_eq!(parsed.ip_version_preference, IpVersionPreference::Auto);
+
+        let parsed: AppConfig =
+            serde_json::from_str(r#"{"ip_version_preference":"ipv4"}"#).unwrap();
+        assert_eq!(parsed.ip_version_preference, IpVersionPreference::Ipv4);
+    }
+
+    #[test]
+    fn test_effective_user_agent() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.effective_user_agent(), DEFAULT_USER_AGENT);
+
+        config.user_agent = Some("  MyClient/2.0 ".into());
+        assert_eq!(config.effective_user_agent(), "MyClient/2.0");
+
+        // 可注入请求头的值不生效
+        config.user_agent = Some("evil\r\nX-Injected: 1".into());
+        assert_eq!(config.effective_user_agent(), DEFAULT_USER_AGENT);
+        assert!(normalize_user_agent("evil\r\nX-Injected: 1").is_err());
+        assert_eq!(normalize_user_agent("   "), Ok(None));
+    }
+
+    #[test]
+    fn test_effective_update_proxy() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.effective_update_proxy(), "auto");
+
+        config.proxy = Some("socks5://127.0.0.1:1080".into());
+        assert_eq!(config.effective_update_proxy(), "socks5://127.0.0.1:1080");
+
+        // 手动指定或禁用更新代理时不受测速代理影响
+        config.update_proxy = "".into();
+        assert_eq!(config.effective_update_proxy(), "");
+        config.update_proxy = "http://10.0.0.1:3128".into();
+        assert_eq!(config.effective_update_proxy(), "http://10.0.0.1:3128");
+    }
+
     #[test]
     fn test_history_record() {
         let record = HistoryRecord {
@@ -0,0 +1,425 @@
+//! 网络环境诊断
+//! 收集默认网关、系统 DNS 服务器与 VPN 网卡信息，用于排查"优选后没有效果"
+//! （VPN 或自定义 DNS 可能绕过 hosts 文件）：
+//! Windows 通过 iphlpapi 枚举网卡，macOS 使用 scutil --dns 与 route，
+//! Linux 读取 /etc/resolv.conf、ip route 与 /sys/class/net
+
+use crate::models::NetworkInfo;
+
+/// VPN / 隧道网卡的常见名称前缀（Linux / macOS 网卡名）
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+const VPN_INTERFACE_PREFIXES: &[&str] = &[
+    "tun",
+    "tap",
+    "wg",
+    "utun",
+    "ppp",
+    "ipsec",
+    "zt",
+    "tailscale",
+    "nordlynx",
+];
+
+/// VPN 网卡描述中的常见关键字（Windows 网卡描述）
+#[cfg_attr(not(windows), allow(dead_code))]
+const VPN_DESCRIPTION_KEYWORDS: &[&str] = &[
+    "vpn",
+    "tap-windows",
+    "wintun",
+    "wireguard",
+    "tailscale",
+    "zerotier",
+    "openvpn",
+    "anyconnect",
+    "fortinet",
+];
+
+/// 收集当前网络环境信息（各项获取失败时留空，不报错）
+pub fn collect() -> NetworkInfo {
+    platform::collect()
+}
+
+/// 按网卡名判断是否为 VPN / 隧道网卡
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+pub fn is_vpn_interface_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    VPN_INTERFACE_PREFIXES
+        .iter()
+        .any(|prefix| lower.starts_with(prefix))
+}
+
+/// 按网卡描述判断是否为 VPN 网卡
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn is_vpn_description(description: &str) -> bool {
+    let lower = description.to_lowercase();
+    VPN_DESCRIPTION_KEYWORDS
+        .iter()
+        .any(|keyword| lower.contains(keyword))
+}
+
+/// 解析 resolv.conf 中的 nameserver 行（忽略注释，按出现顺序去重）
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_resolv_conf(content: &str) -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some("nameserver") {
+            continue;
+        }
+        if let Some(server) = parts.next() {
+            if !servers.iter().any(|s| s == server) {
+                servers.push(server.to_string());
+            }
+        }
+    }
+    servers
+}
+
+/// 解析 `ip route show default` 输出，返回 (网关, 网卡)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_ip_route_default(output: &str) -> Option<(String, String)> {
+    output.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() != Some(&"default") {
+            return None;
+        }
+        let value_after = |key: &str| {
+            fields
+                .windows(2)
+                .find(|pair| pair[0] == key)
+                .map(|pair| pair[1].to_string())
+        };
+        Some((value_after("via")?, value_after("dev").unwrap_or_default()))
+    })
+}
+
+/// 解析 `scutil --dns` 输出中的 nameserver[n] 行（按出现顺序去重）
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn parse_scutil_dns(output: &str) -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.starts_with("nameserver[") {
+            continue;
+        }
+        if let Some((_, server)) = line.split_once(':') {
+            let server = server.trim();
+            if !server.is_empty() && !servers.iter().any(|s| s == server) {
+                servers.push(server.to_string());
+            }
+        }
+    }
+    servers
+}
+
+/// 解析 `route -n get default` 输出，返回 (网关, 网卡)
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn parse_route_get_default(output: &str) -> Option<(String, String)> {
+    let value_of = |key: &str| {
+        output.lines().find_map(|line| {
+            let (name, value) = line.trim().split_once(':')?;
+            (name.trim() == key).then(|| value.trim().to_string())
+        })
+    };
+    Some((
+        value_of("gateway")?,
+        value_of("interface").unwrap_or_default(),
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    pub fn collect() -> NetworkInfo {
+        let dns_servers = std::fs::read_to_string("/etc/resolv.conf")
+            .map(|content| parse_resolv_conf(&content))
+            .unwrap_or_default();
+        let default_route = command_output("ip", &["route", "show", "default"])
+            .and_then(|output| parse_ip_route_default(&output));
+
+        // 已建立的隧道网卡都会出现在 /sys/class/net 中
+        let mut vpn_interfaces: Vec<String> = std::fs::read_dir("/sys/class/net")
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .filter(|name| is_vpn_interface_name(name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        vpn_interfaces.sort();
+
+        NetworkInfo {
+            default_gateway: default_route.as_ref().map(|(gateway, _)| gateway.clone()),
+            default_interface: default_route.map(|(_, interface)| interface),
+            dns_servers,
+            vpn_detected: !vpn_interfaces.is_empty(),
+            vpn_interfaces,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    pub fn collect() -> NetworkInfo {
+        let dns_servers = command_output("scutil", &["--dns"])
+            .map(|output| parse_scutil_dns(&output))
+            .unwrap_or_default();
+        let default_route = command_output("route", &["-n", "get", "default"])
+            .and_then(|output| parse_route_get_default(&output));
+
+        // macOS 默认就有若干 utun 网卡（iCloud 等系统服务使用），只看默认路由是否走隧道
+        let vpn_interfaces: Vec<String> = default_route
+            .iter()
+            .map(|(_, interface)| interface.clone())
+            .filter(|interface| is_vpn_interface_name(interface))
+            .collect();
+
+        NetworkInfo {
+            default_gateway: default_route.as_ref().map(|(gateway, _)| gateway.clone()),
+            default_interface: default_route.map(|(_, interface)| interface),
+            dns_servers,
+            vpn_detected: !vpn_interfaces.is_empty(),
+            vpn_interfaces,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use windows::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS};
+    use windows::Win32::NetworkManagement::IpHelper::{
+        GetAdaptersAddresses, GAA_FLAG_INCLUDE_GATEWAYS, IP_ADAPTER_ADDRESSES_LH,
+    };
+    use windows::Win32::NetworkManagement::Ndis::IfOperStatusUp;
+    use windows::Win32::Networking::WinSock::{
+        AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN, SOCKADDR_IN6, SOCKET_ADDRESS,
+    };
+
+    /// IF_TYPE_SOFTWARE_LOOPBACK
+    const IF_TYPE_LOOPBACK: u32 = 24;
+
+    pub fn collect() -> NetworkInfo {
+        let mut info = NetworkInfo::default();
+        // 多块网卡都有网关时，系统优先走接口跃点数（metric）最小的那块
+        let mut best_gateway: Option<(u32, String, String)> = None;
+        for adapter in adapters() {
+            if adapter.is_vpn {
+                info.vpn_interfaces.push(adapter.name.clone());
+            }
+            for server in adapter.dns_servers {
+                if !info.dns_servers.contains(&server) {
+                    info.dns_servers.push(server);
+                }
+            }
+            for (gateway, metric) in adapter.gateways {
+                if best_gateway
+                    .as_ref()
+                    .is_none_or(|(best, _, _)| metric < *best)
+                {
+                    best_gateway = Some((metric, gateway, adapter.name.clone()));
+                }
+            }
+        }
+        if let Some((_, gateway, interface)) = best_gateway {
+            info.default_gateway = Some(gateway);
+            info.default_interface = Some(interface);
+        }
+        info.vpn_detected = !info.vpn_interfaces.is_empty();
+        info
+    }
+
+    struct Adapter {
+        name: String,
+        is_vpn: bool,
+        dns_servers: Vec<String>,
+        /// (网关地址, 对应地址族的接口跃点数)
+        gateways: Vec<(String, u32)>,
+    }
+
+    /// 枚举处于连接状态的非回环网卡
+    fn adapters() -> Vec<Adapter> {
+        // 先按常见大小分配，缓冲区不足时按系统给出的大小重试
+        let mut size: u32 = 16 * 1024;
+        let mut buffer: Vec<u64> = Vec::new();
+        let mut loaded = false;
+        for _ in 0..3 {
+            // u64 元素保证结构体所需的 8 字节对齐
+            buffer = vec![0u64; (size as usize).div_ceil(8)];
+            let result = unsafe {
+                GetAdaptersAddresses(
+                    AF_UNSPEC.0 as u32,
+                    GAA_FLAG_INCLUDE_GATEWAYS,
+                    None,
+                    Some(buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH),
+                    &mut size,
+                )
+            };
+            if result == ERROR_SUCCESS.0 {
+                loaded = true;
+                break;
+            }
+            if result != ERROR_BUFFER_OVERFLOW.0 {
+                break;
+            }
+        }
+        if !loaded {
+            return Vec::new();
+        }
+
+        let mut adapters = Vec::new();
+        let mut current = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+        while !current.is_null() {
+            let adapter = unsafe { &*current };
+            current = adapter.Next;
+            if adapter.OperStatus != IfOperStatusUp || adapter.IfType == IF_TYPE_LOOPBACK {
+                continue;
+            }
+
+            let name = unsafe { adapter.FriendlyName.to_string() }.unwrap_or_default();
+            let description = unsafe { adapter.Description.to_string() }.unwrap_or_default();
+
+            let mut dns_servers = Vec::new();
+            let mut dns = adapter.FirstDnsServerAddress;
+            while !dns.is_null() {
+                let entry = unsafe { &*dns };
+                dns_servers.extend(socket_address_to_ip(&entry.Address));
+                dns = entry.Next;
+            }
+
+            let mut gateways = Vec::new();
+            let mut gateway = adapter.FirstGatewayAddress;
+            while !gateway.is_null() {
+                let entry = unsafe { &*gateway };
+                if let Some(ip) = socket_address_to_ip(&entry.Address) {
+                    let metric = if ip.contains(':') {
+                        adapter.Ipv6Metric
+                    } else {
+                        adapter.Ipv4Metric
+                    };
+                    gateways.push((ip, metric));
+                }
+                gateway = entry.Next;
+            }
+
+            adapters.push(Adapter {
+                is_vpn: is_vpn_description(&description) || is_vpn_description(&name),
+                name,
+                dns_servers,
+                gateways,
+            });
+        }
+        adapters
+    }
+
+    fn socket_address_to_ip(address: &SOCKET_ADDRESS) -> Option<String> {
+        if address.lpSockaddr.is_null() {
+            return None;
+        }
+        let family = unsafe { (*address.lpSockaddr).sa_family };
+        let ip = if family == AF_INET {
+            let addr = unsafe { &*(address.lpSockaddr as *const SOCKADDR_IN) };
+            let raw = unsafe { addr.sin_addr.S_un.S_addr };
+            IpAddr::V4(Ipv4Addr::from(u32::from_be(raw)))
+        } else if family == AF_INET6 {
+            let addr = unsafe { &*(address.lpSockaddr as *const SOCKADDR_IN6) };
+            let octets = unsafe { addr.sin6_addr.u.Byte };
+            IpAddr::V6(Ipv6Addr::from(octets))
+        } else {
+            return None;
+        };
+        Some(ip.to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    pub fn collect() -> NetworkInfo {
+        NetworkInfo::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let content = "\
+# Generated by NetworkManager
+search lan
+nameserver 192.168.1.1
+;nameserver 10.0.0.1
+nameserver   8.8.8.8   # trailing comment
+nameserver 192.168.1.1
+nameserver
+options edns0
+nameserver fe80::1%eth0
+";
+        assert_eq!(
+            parse_resolv_conf(content),
+            vec!["192.168.1.1", "8.8.8.8", "fe80::1%eth0"]
+        );
+        assert!(parse_resolv_conf("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_default_routes() {
+        let linux = "default via 192.168.1.1 dev wlp2s0 proto dhcp metric 600\n";
+        assert_eq!(
+            parse_ip_route_default(linux),
+            Some(("192.168.1.1".to_string(), "wlp2s0".to_string()))
+        );
+        // 点对点隧道的默认路由没有网关
+        assert_eq!(
+            parse_ip_route_default("default dev tun0 scope link\n"),
+            None
+        );
+
+        let macos =
+            "   route to: default\ndestination: default\n    gateway: 10.0.0.1\n  interface: en0\n";
+        assert_eq!(
+            parse_route_get_default(macos),
+            Some(("10.0.0.1".to_string(), "en0".to_string()))
+        );
+
+        let scutil = "resolver #1\n  nameserver[0] : 1.1.1.1\n  nameserver[1] : 2606:4700::1111\nresolver #2\n  nameserver[0] : 1.1.1.1\n";
+        assert_eq!(parse_scutil_dns(scutil), vec!["1.1.1.1", "2606:4700::1111"]);
+    }
+
+    #[test]
+    fn test_vpn_detection() {
+        assert!(is_vpn_interface_name("wg0"));
+        assert!(is_vpn_interface_name("tun0"));
+        assert!(is_vpn_interface_name("utun3"));
+        assert!(!is_vpn_interface_name("eth0"));
+        assert!(!is_vpn_interface_name("en0"));
+
+        assert!(is_vpn_description("TAP-Windows Adapter V9"));
+        assert!(is_vpn_description("WireGuard Tunnel"));
+        assert!(!is_vpn_description("Intel(R) Wi-Fi 6 AX201 160MHz"));
+    }
+}
@@ -0,0 +1,161 @@
+//! 系统桌面通知
+//! 通过各平台自带命令发送（Windows PowerShell Toast / macOS osascript / Linux notify-send），
+//! 命令不可用或执行失败时仅记录日志，不影响调用方
+
+/// 单条通知最多列出的切换条数，其余以"等 N 个"汇总
+const MAX_SWITCH_LINES: usize = 5;
+
+/// Windows Toast 使用的 AppUserModelID（与 tauri.conf.json 的 identifier 一致）
+#[cfg(windows)]
+const APP_USER_MODEL_ID: &str = "com.anyfast.app";
+
+/// 一次自动切换的摘要
+pub struct SwitchNotice {
+    pub domain: String,
+    pub old_ip: String,
+    pub new_ip: String,
+    pub new_latency: f64,
+}
+
+/// 将同一轮的多个切换合并为一条通知的 (标题, 正文)，无切换时返回 None
+pub fn switch_summary(switches: &[SwitchNotice]) -> Option<(String, String)> {
+    if switches.is_empty() {
+        return None;
+    }
+
+    let title = if switches.len() == 1 {
+        "anyFAST 已自动切换 IP".to_string()
+    } else {
+        format!("anyFAST 已自动切换 {} 个端点", switches.len())
+    };
+
+    let mut lines: Vec<String> = switches
+        .iter()
+        .take(MAX_SWITCH_LINES)
+        .map(|s| {
+            format!(
+                "{}: {} → {} ({:.0}ms)",
+                s.domain, s.old_ip, s.new_ip, s.new_latency
+            )
+        })
+        .collect();
+    if switches.len() > MAX_SWITCH_LINES {
+        lines.push(format!("等 {} 个端点", switches.len()));
+    }
+
+    Some((title, lines.join("\n")))
+}
+
+/// 在后台线程发送桌面通知（不阻塞调用方）
+pub fn notify(title: String, body: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = send(&title, &body) {
+            eprintln!("发送系统通知失败: {}", e);
+        }
+    });
+}
+
+/// PowerShell 单引号字符串转义
+#[cfg_attr(not(windows), allow(dead_code))]
+fn powershell_quote(raw: &str) -> String {
+    format!("'{}'", raw.replace('\'', "''"))
+}
+
+/// AppleScript 双引号字符串转义
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_quote(raw: &str) -> String {
+    format!("\"{}\"", raw.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(windows)]
+fn send(title: &str, body: &str) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let script = format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null\n\
+         $t = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)\n\
+         $x = $t.GetElementsByTagName('text')\n\
+         $x.Item(0).AppendChild($t.CreateTextNode({})) | Out-Null\n\
+         $x.Item(1).AppendChild($t.CreateTextNode({})) | Out-Null\n\
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier({}).Show([Windows.UI.Notifications.ToastNotification]::new($t))",
+        powershell_quote(title),
+        powershell_quote(body),
+        powershell_quote(APP_USER_MODEL_ID)
+    );
+    let output =
+        std::process::Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| e.to_string())?;
+    check_status(output)
+}
+
+#[cfg(target_os = "macos")]
+fn send(title: &str, body: &str) -> Result<(), String> {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_quote(body),
+        applescript_quote(title)
+    );
+    let output = std::process::Command::new("/usr/bin/osascript")
+        .args(["-e", &script])
+        .output()
+        .map_err(|e| e.to_string())?;
+    check_status(output)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn send(title: &str, body: &str) -> Result<(), String> {
+    let output = std::process::Command::new("notify-send")
+        .args(["--app-name=anyFAST", title, body])
+        .output()
+        .map_err(|e| e.to_string())?;
+    check_status(output)
+}
+
+fn check_status(output: std::process::Output) -> Result<(), String> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notice(domain: &str, new_ip: &str, new_latency: f64) -> SwitchNotice {
+        SwitchNotice {
+            domain: domain.into(),
+            old_ip: "1.1.1.1".into(),
+            new_ip: new_ip.into(),
+            new_latency,
+        }
+    }
+
+    #[test]
+    fn test_switch_summary_batches_switches() {
+        assert!(switch_summary(&[]).is_none());
+
+        let (title, body) = switch_summary(&[notice("a.com", "2.2.2.2", 85.4)]).unwrap();
+        assert_eq!(title, "anyFAST 已自动切换 IP");
+        assert_eq!(body, "a.com: 1.1.1.1 → 2.2.2.2 (85ms)");
+
+        let many: Vec<SwitchNotice> = (0..7)
+            .map(|i| notice(&format!("d{}.com", i), "3.3.3.3", 100.0))
+            .collect();
+        let (title, body) = switch_summary(&many).unwrap();
+        assert_eq!(title, "anyFAST 已自动切换 7 个端点");
+        assert_eq!(body.lines().count(), MAX_SWITCH_LINES + 1);
+        assert!(body.ends_with("等 7 个端点"));
+    }
+
+    #[test]
+    fn test_quote_escaping() {
+        assert_eq!(powershell_quote("it's"), "'it''s'");
+        assert_eq!(applescript_quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+    }
+}
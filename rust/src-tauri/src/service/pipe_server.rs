@@ -34,7 +34,7 @@ pub const PIPE_NAME: &str = r"\\.\pipe\anyfast-hosts-service";
 /// Service version
 const SERVICE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Buffer size for pipe communication (64KB max message size)
+/// Buffer size for pipe communication (larger messages are framed and split into chunks)
 const BUFFER_SIZE: u32 = 65536;
 
 /// PIPE_ACCESS_DUPLEX constant
@@ -306,116 +306,161 @@ impl PipeServer {
         let _event_guard = HandleGuard(io_event);
 
         loop {
-            // Check if we should stop
-            if !self.running.load(Ordering::SeqCst) {
-                return Ok(());
-            }
-
-            // Read request with overlapped I/O
-            let mut overlapped = OVERLAPPED {
-                hEvent: io_event,
-                ..Default::default()
-            };
+            // Read a full request, looping until all frame chunks have arrived
+            let mut assembler = framing::FrameAssembler::new();
+            let message = loop {
+                // Check if we should stop
+                if !self.running.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
 
-            let mut bytes_read: u32 = 0;
-            let read_result = unsafe {
-                ReadFile(
-                    pipe,
-                    Some(&mut buffer),
-                    Some(&mut bytes_read),
-                    Some(&mut overlapped),
-                )
-            };
+                let bytes_read = match self.read_chunk(pipe, &mut buffer, io_event)? {
+                    Some(n) => n,
+                    None => return Ok(()), // Client disconnected, idle or stop requested
+                };
 
-            if read_result.is_err() {
-                let err = std::io::Error::last_os_error();
-                let err_code = err.raw_os_error().unwrap_or(0);
-
-                // ERROR_IO_PENDING means async read started
-                if err_code == 997 {
-                    // Wait for read or stop
-                    let handles = [io_event, self.stop_event];
-                    let wait_result = unsafe {
-                        windows::Win32::System::Threading::WaitForMultipleObjects(
-                            &handles, false, 30000, // 30 second timeout for read
-                        )
-                    };
-
-                    match wait_result {
-                        WAIT_OBJECT_0 => {
-                            // Read completed
-                            let get_result = unsafe {
-                                GetOverlappedResult(pipe, &overlapped, &mut bytes_read, false)
-                            };
-                            if get_result.is_err() {
-                                let err = std::io::Error::last_os_error();
-                                // ERROR_BROKEN_PIPE or ERROR_PIPE_NOT_CONNECTED
-                                if err.raw_os_error() == Some(109)
-                                    || err.raw_os_error() == Some(233)
-                                {
-                                    return Ok(()); // Client disconnected
-                                }
-                                return Err(format!("GetOverlappedResult error: {}", err));
-                            }
-                        }
-                        w if w.0 == WAIT_OBJECT_0.0 + 1 => {
-                            // Stop signal
-                            unsafe { windows::Win32::System::IO::CancelIo(pipe) }.ok();
-                            return Ok(());
-                        }
-                        w if w.0 == 258 => {
-                            // Timeout - client idle too long, disconnect
-                            return Ok(());
-                        }
-                        _ => {
-                            return Err(format!(
-                                "Read wait failed: {}",
-                                std::io::Error::last_os_error()
-                            ));
-                        }
+                match assembler.push(&buffer[..bytes_read as usize]) {
+                    Ok(Some(message)) => break message,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        // Framing is out of sync; drop the connection
+                        eprintln!("Invalid request frame: {}", e);
+                        return Ok(());
                     }
-                } else if err_code == 109 || err_code == 233 {
-                    // ERROR_BROKEN_PIPE or ERROR_PIPE_NOT_CONNECTED
-                    return Ok(()); // Client disconnected normally
-                } else {
-                    return Err(format!("Read error: {}", err));
                 }
-            }
-
-            if bytes_read == 0 {
-                return Ok(()); // Client disconnected
-            }
-
-            // Validate message size
-            if bytes_read > BUFFER_SIZE {
-                eprintln!("Message too large: {} bytes", bytes_read);
-                continue;
-            }
+            };
 
             // Parse and handle request
-            let request_data = &buffer[..bytes_read as usize];
-            let response = self.handle_request(request_data);
+            let response = self.handle_request(&message.payload);
 
             // Send response
             let response_json = serde_json::to_vec(&response)
                 .map_err(|e| format!("Failed to serialize response: {}", e))?;
 
-            // Validate response size
-            if response_json.len() > BUFFER_SIZE as usize {
+            let data = if message.framed {
+                framing::encode(&response_json)?
+            } else if response_json.len() > BUFFER_SIZE as usize {
+                // Legacy clients read a single bare message, which cannot exceed the buffer
                 eprintln!("Response too large: {} bytes", response_json.len());
                 let error_response =
                     RpcResponse::error(0, error_codes::INTERNAL_ERROR, "Response too large");
-                let error_json = serde_json::to_vec(&error_response).unwrap_or_default();
-                self.write_response(pipe, &error_json, io_event)?;
-                continue;
+                serde_json::to_vec(&error_response).unwrap_or_default()
+            } else {
+                response_json
+            };
+            self.write_response(pipe, &data, io_event)?;
+        }
+    }
+
+    /// Read one chunk with overlapped I/O
+    ///
+    /// Returns `None` when the client disconnected, stayed idle too long or the
+    /// server is stopping. A message larger than the buffer yields its first
+    /// part here (ERROR_MORE_DATA); the rest arrives on subsequent calls.
+    fn read_chunk(
+        &self,
+        pipe: HANDLE,
+        buffer: &mut [u8],
+        io_event: HANDLE,
+    ) -> Result<Option<u32>, String> {
+        let mut overlapped = OVERLAPPED {
+            hEvent: io_event,
+            ..Default::default()
+        };
+
+        let mut bytes_read: u32 = 0;
+        let read_result = unsafe {
+            ReadFile(
+                pipe,
+                Some(buffer),
+                Some(&mut bytes_read),
+                Some(&mut overlapped),
+            )
+        };
+
+        if read_result.is_err() {
+            let err = std::io::Error::last_os_error();
+            let err_code = err.raw_os_error().unwrap_or(0);
+
+            // ERROR_IO_PENDING means async read started
+            if err_code == 997 {
+                // Wait for read or stop
+                let handles = [io_event, self.stop_event];
+                let wait_result = unsafe {
+                    windows::Win32::System::Threading::WaitForMultipleObjects(
+                        &handles, false, 30000, // 30 second timeout for read
+                    )
+                };
+
+                match wait_result {
+                    WAIT_OBJECT_0 => {
+                        // Read completed
+                        let get_result = unsafe {
+                            GetOverlappedResult(pipe, &overlapped, &mut bytes_read, false)
+                        };
+                        if get_result.is_err() {
+                            let err = std::io::Error::last_os_error();
+                            match err.raw_os_error() {
+                                // ERROR_MORE_DATA: partial message, keep reading
+                                Some(234) => {}
+                                // ERROR_BROKEN_PIPE or ERROR_PIPE_NOT_CONNECTED
+                                Some(109) | Some(233) => return Ok(None), // Client disconnected
+                                _ => return Err(format!("GetOverlappedResult error: {}", err)),
+                            }
+                        }
+                    }
+                    w if w.0 == WAIT_OBJECT_0.0 + 1 => {
+                        // Stop signal
+                        unsafe { windows::Win32::System::IO::CancelIo(pipe) }.ok();
+                        return Ok(None);
+                    }
+                    w if w.0 == 258 => {
+                        // Timeout - client idle too long, disconnect
+                        return Ok(None);
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Read wait failed: {}",
+                            std::io::Error::last_os_error()
+                        ));
+                    }
+                }
+            } else if err_code == 234 {
+                // ERROR_MORE_DATA: partial message, keep reading
+            } else if err_code == 109 || err_code == 233 {
+                // ERROR_BROKEN_PIPE or ERROR_PIPE_NOT_CONNECTED
+                return Ok(None); // Client disconnected normally
+            } else {
+                return Err(format!("Read error: {}", err));
             }
+        }
 
-            self.write_response(pipe, &response_json, io_event)?;
+        if bytes_read == 0 {
+            return Ok(None); // Client disconnected
         }
+
+        Ok(Some(bytes_read))
     }
 
-    /// Write response with overlapped I/O
+    /// Write response with overlapped I/O, looping until all bytes are written
     fn write_response(&self, pipe: HANDLE, data: &[u8], io_event: HANDLE) -> Result<(), String> {
+        for chunk in data.chunks(BUFFER_SIZE as usize) {
+            let mut offset = 0;
+            while offset < chunk.len() {
+                let written = self.write_chunk(pipe, &chunk[offset..], io_event)?;
+                if written == 0 {
+                    return Err("Write error: no bytes written".to_string());
+                }
+                offset += written as usize;
+            }
+        }
+
+        unsafe { FlushFileBuffers(pipe) }.ok();
+        Ok(())
+    }
+
+    /// Write a single chunk with overlapped I/O
+    fn write_chunk(&self, pipe: HANDLE, data: &[u8], io_event: HANDLE) -> Result<u32, String> {
         let mut overlapped = OVERLAPPED {
             hEvent: io_event,
             ..Default::default()
@@ -448,8 +493,7 @@ impl PipeServer {
             }
         }
 
-        unsafe { FlushFileBuffers(pipe) }.ok();
-        Ok(())
+        Ok(bytes_written)
     }
 
     /// Parse and handle a JSON-RPC request
@@ -475,7 +519,11 @@ impl PipeServer {
             );
         }
 
-        // Dispatch method
+        // Hosts operations use the client's marker for the duration of this request
+        crate::hosts_manager::with_marker(request.marker.as_deref(), || self.dispatch(&request))
+    }
+
+    fn dispatch(&self, request: &RpcRequest) -> RpcResponse {
         match request.method.as_str() {
             methods::PING => self.handle_ping(request.id),
             methods::WRITE_BINDING => self.handle_write_binding(request.id, &request.params),
@@ -490,6 +538,14 @@ impl PipeServer {
             methods::READ_BINDING => self.handle_read_binding(request.id, &request.params),
             methods::GET_ALL_BINDINGS => self.handle_get_all_bindings(request.id),
             methods::FLUSH_DNS => self.handle_flush_dns(request.id),
+            methods::SUSPEND_BINDING => {
+                self.handle_set_suspended(request.id, &request.params, true)
+            }
+            methods::RESUME_BINDING => {
+                self.handle_set_suspended(request.id, &request.params, false)
+            }
+            methods::DECONFLICT_BINDINGS => self.handle_set_deconflicted(request.id, true),
+            methods::RESTORE_DECONFLICTED => self.handle_set_deconflicted(request.id, false),
             _ => RpcResponse::error(
                 request.id,
                 error_codes::METHOD_NOT_FOUND,
@@ -502,6 +558,7 @@ impl PipeServer {
         let result = PingResult {
             pong: true,
             version: SERVICE_VERSION.to_string(),
+            framing: true,
         };
         RpcResponse::success(id, serde_json::to_value(result).unwrap())
     }
@@ -635,46 +692,62 @@ impl PipeServer {
     }
 
     fn handle_get_all_bindings(&self, id: u64) -> RpcResponse {
-        // Read hosts file and extract all anyFAST bindings
-        let content = match std::fs::read_to_string(r"C:\Windows\System32\drivers\etc\hosts") {
-            Ok(c) => c,
+        // Same parser as every other operation, so custom markers and multi-IP domains match
+        let bindings = HostsManager::read_all_anyfast_bindings()
+            .into_iter()
+            .map(|(domain, ip)| BindingEntry { domain, ip })
+            .collect();
+
+        let result = AllBindingsResult { bindings };
+        RpcResponse::success(id, serde_json::to_value(result).unwrap())
+    }
+
+    fn handle_set_suspended(
+        &self,
+        id: u64,
+        params: &serde_json::Value,
+        suspend: bool,
+    ) -> RpcResponse {
+        let params: SuspendBindingParams = match serde_json::from_value(params.clone()) {
+            Ok(p) => p,
             Err(e) => {
                 return RpcResponse::error(
                     id,
-                    error_codes::IO_ERROR,
-                    &format!("Failed to read hosts file: {}", e),
+                    error_codes::INVALID_PARAMS,
+                    &format!("Invalid params: {}", e),
                 );
             }
         };
 
-        let mut bindings = Vec::new();
-        let mut in_block = false;
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-
-            if trimmed == "# BEGIN anyFAST" {
-                in_block = true;
-                continue;
-            }
-            if trimmed == "# END anyFAST" {
-                in_block = false;
-                continue;
+        let result = if suspend {
+            HostsManager::suspend_binding(&params.domain)
+        } else {
+            HostsManager::resume_binding(&params.domain)
+        };
+        match result {
+            Ok(changed) => {
+                let result = ChangedResult { changed };
+                RpcResponse::success(id, serde_json::to_value(result).unwrap())
             }
+            Err(e) => self.hosts_error_to_response(id, e),
+        }
+    }
 
-            if in_block && !trimmed.is_empty() && !trimmed.starts_with('#') {
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    bindings.push(BindingEntry {
-                        ip: parts[0].to_string(),
-                        domain: parts[1].to_string(),
-                    });
-                }
+    fn handle_set_deconflicted(&self, id: u64, deconflict: bool) -> RpcResponse {
+        let result = if deconflict {
+            HostsManager::deconflict_bindings()
+        } else {
+            HostsManager::restore_deconflicted()
+        };
+        match result {
+            Ok(count) => {
+                let result = CountResult {
+                    count: count as u32,
+                };
+                RpcResponse::success(id, serde_json::to_value(result).unwrap())
             }
+            Err(e) => self.hosts_error_to_response(id, e),
         }
-
-        let result = AllBindingsResult { bindings };
-        RpcResponse::success(id, serde_json::to_value(result).unwrap())
     }
 
     fn handle_flush_dns(&self, id: u64) -> RpcResponse {
@@ -709,6 +782,14 @@ impl PipeServer {
             HostsError::Io(e) => {
                 RpcResponse::error(id, error_codes::IO_ERROR, &format!("IO error: {}", e))
             }
+            HostsError::ExternalModification => RpcResponse::error(
+                id,
+                error_codes::INTERNAL_ERROR,
+                "anyFAST block was modified externally",
+            ),
+            HostsError::UnsupportedEncoding(_) => {
+                RpcResponse::error(id, error_codes::IO_ERROR, &error.to_string())
+            }
         }
     }
 }
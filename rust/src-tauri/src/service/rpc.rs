@@ -13,6 +13,10 @@ pub struct RpcRequest {
     pub method: String,
     #[serde(default)]
     pub params: serde_json::Value,
+    /// Hosts marker name used by the client; the Service runs in its own
+    /// environment and cannot see the GUI's marker setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
 }
 
 impl RpcRequest {
@@ -22,8 +26,14 @@ impl RpcRequest {
             id,
             method: method.to_string(),
             params,
+            marker: None,
         }
     }
+
+    pub fn with_marker(mut self, marker: &str) -> Self {
+        self.marker = Some(marker.to_string());
+        self
+    }
 }
 
 /// JSON-RPC 2.0 response
@@ -93,6 +103,10 @@ pub mod methods {
     pub const READ_BINDING: &str = "read_binding";
     pub const GET_ALL_BINDINGS: &str = "get_all_bindings";
     pub const FLUSH_DNS: &str = "flush_dns";
+    pub const SUSPEND_BINDING: &str = "suspend_binding";
+    pub const RESUME_BINDING: &str = "resume_binding";
+    pub const DECONFLICT_BINDINGS: &str = "deconflict_bindings";
+    pub const RESTORE_DECONFLICTED: &str = "restore_deconflicted";
 }
 
 // ============ Request parameter types ============
@@ -135,6 +149,12 @@ pub struct ReadBindingParams {
     pub domain: String,
 }
 
+/// Parameters for suspend_binding and resume_binding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuspendBindingParams {
+    pub domain: String,
+}
+
 // ============ Response result types ============
 
 /// Result for write_binding, clear_binding, flush_dns
@@ -143,12 +163,19 @@ pub struct SuccessResult {
     pub success: bool,
 }
 
-/// Result for write_bindings_batch, clear_bindings_batch
+/// Result for write_bindings_batch, clear_bindings_batch, deconflict_bindings, restore_deconflicted
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CountResult {
     pub count: u32,
 }
 
+/// Result for suspend_binding and resume_binding
+/// changed is false when the domain was not in the expected state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedResult {
+    pub changed: bool,
+}
+
 /// Result for read_binding
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadBindingResult {
@@ -166,6 +193,96 @@ pub struct AllBindingsResult {
 pub struct PingResult {
     pub pong: bool,
     pub version: String,
+    /// Whether the service accepts length-prefixed frames (services before framing omit it)
+    #[serde(default)]
+    pub framing: bool,
+}
+
+/// Message framing for pipe transport
+///
+/// Each message is prefixed with a 4-byte big-endian payload length so that
+/// payloads larger than the pipe buffer can be split across several reads and
+/// writes. Legacy peers send a bare JSON object in a single message; since the
+/// length prefix of any allowed frame starts with a zero byte, a message whose
+/// first byte is `{` is treated as an unframed legacy message.
+pub mod framing {
+    /// Length prefix size in bytes
+    pub const HEADER_LEN: usize = 4;
+
+    /// Upper bound on a single payload (guards against corrupt length prefixes)
+    pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Prefix a payload with its length
+    pub fn encode(payload: &[u8]) -> Result<Vec<u8>, String> {
+        if payload.len() > MAX_MESSAGE_SIZE {
+            return Err(format!("Message too large: {} bytes", payload.len()));
+        }
+        let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        Ok(frame)
+    }
+
+    /// A fully received message
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Message {
+        pub payload: Vec<u8>,
+        /// Whether the peer used length-prefixed framing (reply in the same format)
+        pub framed: bool,
+    }
+
+    /// Accumulates chunks from successive reads until a whole message is available
+    #[derive(Debug, Default)]
+    pub struct FrameAssembler {
+        buffer: Vec<u8>,
+    }
+
+    impl FrameAssembler {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Feed one read chunk; returns the message once it is complete
+        pub fn push(&mut self, chunk: &[u8]) -> Result<Option<Message>, String> {
+            if self.buffer.is_empty() && chunk.first() == Some(&b'{') {
+                // Legacy single-message read
+                return Ok(Some(Message {
+                    payload: chunk.to_vec(),
+                    framed: false,
+                }));
+            }
+
+            self.buffer.extend_from_slice(chunk);
+            if self.buffer.len() < HEADER_LEN {
+                return Ok(None);
+            }
+
+            let mut header = [0u8; HEADER_LEN];
+            header.copy_from_slice(&self.buffer[..HEADER_LEN]);
+            let len = u32::from_be_bytes(header) as usize;
+            if len > MAX_MESSAGE_SIZE {
+                return Err(format!("Message too large: {} bytes", len));
+            }
+
+            let total = HEADER_LEN + len;
+            if self.buffer.len() < total {
+                return Ok(None);
+            }
+            if self.buffer.len() > total {
+                return Err(format!(
+                    "Unexpected trailing data: {} bytes",
+                    self.buffer.len() - total
+                ));
+            }
+
+            let payload = self.buffer.split_off(HEADER_LEN);
+            self.buffer.clear();
+            Ok(Some(Message {
+                payload,
+                framed: true,
+            }))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -192,6 +309,47 @@ mod tests {
         assert_eq!(parsed.method, methods::WRITE_BINDING);
     }
 
+    #[test]
+    fn test_clear_all_anyfast_round_trip() {
+        let req = RpcRequest::new(7, methods::CLEAR_ALL_ANYFAST, serde_json::Value::Null)
+            .with_marker("myFAST");
+        let json = serde_json::to_string(&req).unwrap();
+        let parsed: RpcRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.id, 7);
+        assert_eq!(parsed.marker.as_deref(), Some("myFAST"));
+        assert_eq!(parsed.method, methods::CLEAR_ALL_ANYFAST);
+        assert!(parsed.params.is_null());
+
+        let resp = RpcResponse::success(7, serde_json::to_value(CountResult { count: 3 }).unwrap());
+        let json = serde_json::to_string(&resp).unwrap();
+        let parsed: RpcResponse = serde_json::from_str(&json).unwrap();
+        let count: CountResult = serde_json::from_value(parsed.result.unwrap()).unwrap();
+        assert_eq!(count.count, 3);
+    }
+
+    #[test]
+    fn test_suspend_binding_round_trip() {
+        let params = serde_json::to_value(SuspendBindingParams {
+            domain: "example.com".into(),
+        })
+        .unwrap();
+        let req = RpcRequest::new(9, methods::SUSPEND_BINDING, params);
+        let json = serde_json::to_string(&req).unwrap();
+        let parsed: RpcRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.method, methods::SUSPEND_BINDING);
+        let params: SuspendBindingParams = serde_json::from_value(parsed.params).unwrap();
+        assert_eq!(params.domain, "example.com");
+
+        let resp = RpcResponse::success(
+            9,
+            serde_json::to_value(ChangedResult { changed: true }).unwrap(),
+        );
+        let json = serde_json::to_string(&resp).unwrap();
+        let parsed: RpcResponse = serde_json::from_str(&json).unwrap();
+        let result: ChangedResult = serde_json::from_value(parsed.result.unwrap()).unwrap();
+        assert!(result.changed);
+    }
+
     #[test]
     fn test_response_success() {
         let resp = RpcResponse::success(
@@ -212,4 +370,64 @@ mod tests {
         assert!(json.contains("Access denied"));
         assert!(json.contains("-1"));
     }
+
+    #[test]
+    fn test_ping_result_framing_defaults_to_legacy() {
+        // Services that predate framing reply without the flag
+        let old: PingResult = serde_json::from_str(r#"{"pong":true,"version":"2.6.3"}"#).unwrap();
+        assert!(!old.framing);
+        let new: PingResult =
+            serde_json::from_str(r#"{"pong":true,"version":"2.6.4","framing":true}"#).unwrap();
+        assert!(new.framing);
+    }
+
+    #[test]
+    fn test_framing_round_trip_large_payload() {
+        // 超过 64KB 管道缓冲区的 get_all_bindings 响应
+        let bindings: Vec<BindingEntry> = (0..3000)
+            .map(|i| BindingEntry {
+                domain: format!("relay-{}.example.com", i),
+                ip: format!("104.16.{}.{}", i / 256, i % 256),
+            })
+            .collect();
+        let resp = RpcResponse::success(
+            9,
+            serde_json::to_value(AllBindingsResult { bindings }).unwrap(),
+        );
+        let payload = serde_json::to_vec(&resp).unwrap();
+        assert!(payload.len() > 65536);
+
+        let frame = framing::encode(&payload).unwrap();
+        let mut assembler = framing::FrameAssembler::new();
+        let mut received = None;
+        for chunk in frame.chunks(65536) {
+            assert!(received.is_none());
+            received = assembler.push(chunk).unwrap();
+        }
+
+        let message = received.expect("message should be complete");
+        assert!(message.framed);
+        assert_eq!(message.payload, payload);
+        let parsed: RpcResponse = serde_json::from_slice(&message.payload).unwrap();
+        let result: AllBindingsResult = serde_json::from_value(parsed.result.unwrap()).unwrap();
+        assert_eq!(result.bindings.len(), 3000);
+    }
+
+    #[test]
+    fn test_framing_accepts_legacy_single_message() {
+        let req = RpcRequest::new(1, methods::PING, serde_json::Value::Null);
+        let payload = serde_json::to_vec(&req).unwrap();
+
+        let mut assembler = framing::FrameAssembler::new();
+        let message = assembler.push(&payload).unwrap().unwrap();
+        assert!(!message.framed);
+        assert_eq!(message.payload, payload);
+    }
+
+    #[test]
+    fn test_framing_rejects_oversized_length() {
+        let mut assembler = framing::FrameAssembler::new();
+        let header = ((framing::MAX_MESSAGE_SIZE + 1) as u32).to_be_bytes();
+        assert!(assembler.push(&header).is_err());
+    }
 }
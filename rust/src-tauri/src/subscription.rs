@@ -0,0 +1,422 @@
+//! 端点订阅
+//! 定期拉取社区维护的端点列表并合并到本地配置：按域名去重追加新端点，
+//! 订阅源移除的端点只做标记，不删除用户手动添加或禁用的端点
+
+#[cfg(feature = "tauri-runtime")]
+use crate::config::ConfigManager;
+use crate::models::{Endpoint, SubscriptionSummary, DEFAULT_ENDPOINT_PORT};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+#[cfg(feature = "tauri-runtime")]
+use tauri::{AppHandle, Emitter};
+
+/// 自动刷新间隔范围（小时）
+pub const MIN_REFRESH_HOURS: u32 = 1;
+pub const MAX_REFRESH_HOURS: u32 = 168;
+/// 两次拉取订阅源的最小间隔（手动刷新也受限，避免频繁请求社区源）
+pub const MIN_FETCH_GAP: Duration = Duration::from_secs(60);
+/// 订阅列表大小上限，防止异常响应撑爆内存
+const MAX_LIST_BYTES: usize = 1024 * 1024;
+/// 启动后首次自动刷新的延迟（避开启动时的测速与更新检查）
+const STARTUP_DELAY: Duration = Duration::from_secs(60);
+/// 未配置订阅时重新检查配置的间隔
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 上次拉取订阅源的时间（自动与手动刷新共用）
+static LAST_FETCH: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// 订阅源中的端点条目（domain 缺失时取 URL 主机名）
+#[derive(Debug, Deserialize)]
+struct UpstreamEntry {
+    #[serde(default)]
+    name: String,
+    url: String,
+    #[serde(default)]
+    domain: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+}
+
+/// 订阅列表格式：端点数组，或含 endpoints 字段的对象（兼容导出的配置文件）
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum UpstreamList {
+    Entries(Vec<UpstreamEntry>),
+    Wrapped { endpoints: Vec<UpstreamEntry> },
+}
+
+/// 校验订阅地址（仅支持 http/https），空白视为不订阅
+pub fn validate_subscription_url(raw: &str) -> Result<Option<String>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    let parsed = url::Url::parse(trimmed).map_err(|e| format!("订阅地址无效: {}", e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(Some(trimmed.to_string())),
+        other => Err(format!("订阅地址仅支持 http/https，当前为 {}", other)),
+    }
+}
+
+/// 实际生效的刷新间隔
+pub fn refresh_interval(hours: u32) -> Duration {
+    Duration::from_secs(hours.clamp(MIN_REFRESH_HOURS, MAX_REFRESH_HOURS) as u64 * 3600)
+}
+
+/// 解析订阅列表，跳过无效条目并按域名去重；没有任何有效端点时报错
+/// （避免订阅源临时故障返回空列表时把全部订阅端点标记为已移除）
+pub fn parse_list(content: &str) -> Result<Vec<Endpoint>, String> {
+    let list: UpstreamList = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("订阅列表格式无效: {}", e))?;
+    let entries = match list {
+        UpstreamList::Entries(entries) | UpstreamList::Wrapped { endpoints: entries } => entries,
+    };
+
+    let mut seen = HashSet::new();
+    let endpoints: Vec<Endpoint> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let parsed = url::Url::parse(entry.url.trim()).ok()?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                return None;
+            }
+            let domain = match entry.domain.as_deref().map(str::trim) {
+                Some(domain) if !domain.is_empty() => domain.to_lowercase(),
+                _ => parsed.host_str()?.to_lowercase(),
+            };
+            if crate::hosts_manager::validate_domain(&domain).is_err()
+                || !seen.insert(domain.clone())
+            {
+                return None;
+            }
+            let name = match entry.name.trim() {
+                "" => domain.clone(),
+                name => name.to_string(),
+            };
+            Some(Endpoint {
+                name,
+                url: entry.url.trim().to_string(),
+                domain,
+                // 新订阅的端点默认禁用，由用户确认后启用（已有域名保持用户设置，见 merge_endpoints）
+                enabled: false,
+                port: entry
+                    .port
+                    .or(parsed.port_or_known_default())
+                    .or(Some(DEFAULT_ENDPOINT_PORT)),
+                subscribed: true,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    if endpoints.is_empty() {
+        return Err("订阅列表中没有有效端点".into());
+    }
+    Ok(endpoints)
+}
+
+/// 将订阅源端点合并到本地列表：
+/// - 本地没有的域名追加为订阅端点（默认禁用，不会未经确认就参与测速和写入 hosts）
+/// - 本地已有的域名保持用户的设置（启用、收藏、超时倍数等）不变，仅清除"已移除"标记
+/// - 订阅源不再包含的订阅端点标记为已移除，手动添加的端点从不改动
+pub fn merge_endpoints(local: &mut Vec<Endpoint>, upstream: Vec<Endpoint>) -> SubscriptionSummary {
+    let mut summary = SubscriptionSummary {
+        upstream_count: upstream.len() as u32,
+        ..Default::default()
+    };
+    let upstream_domains: HashSet<String> =
+        upstream.iter().map(|e| e.domain.to_lowercase()).collect();
+
+    for endpoint in local.iter_mut().filter(|e| e.subscribed) {
+        let listed = upstream_domains.contains(&endpoint.domain.trim().to_lowercase());
+        if listed && endpoint.removed_upstream {
+            endpoint.removed_upstream = false;
+            summary.restored += 1;
+        } else if !listed && !endpoint.removed_upstream {
+            endpoint.removed_upstream = true;
+            summary.marked_removed += 1;
+        }
+    }
+
+    let mut known: HashSet<String> = local
+        .iter()
+        .map(|e| e.domain.trim().to_lowercase())
+        .collect();
+    for endpoint in upstream {
+        if known.insert(endpoint.domain.to_lowercase()) {
+            local.push(endpoint);
+            summary.added += 1;
+        }
+    }
+
+    summary
+}
+
+/// 距上次拉取不足 MIN_FETCH_GAP 时返回还需等待的时间，否则记录本次拉取
+fn reserve_fetch_slot(now: Instant) -> Result<(), Duration> {
+    let mut last = LAST_FETCH.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(elapsed) = last.map(|t| now.saturating_duration_since(t)) {
+        if elapsed < MIN_FETCH_GAP {
+            return Err(MIN_FETCH_GAP - elapsed);
+        }
+    }
+    *last = Some(now);
+    Ok(())
+}
+
+/// 拉取订阅并合并到配置；有变化时保存并发送 endpoints-updated 事件
+#[cfg(feature = "tauri-runtime")]
+pub async fn refresh(
+    app_handle: &AppHandle,
+    config_manager: &ConfigManager,
+) -> Result<SubscriptionSummary, FetchError> {
+    let config = config_manager
+        .load()
+        .map_err(|e| FetchError::other(e.to_string()))?;
+    let Some(url) = config.subscription_url.clone() else {
+        return Err(FetchError::other("未配置端点订阅地址".into()));
+    };
+    if let Err(wait) = reserve_fetch_slot(Instant::now()) {
+        return Err(FetchError {
+            message: format!("刷新过于频繁，请 {} 秒后重试", wait.as_secs().max(1)),
+            retry_after: Some(wait),
+        });
+    }
+
+    let upstream = fetch_list(
+        &url,
+        &config.effective_update_proxy(),
+        &config.effective_user_agent(),
+    )
+    .await?;
+
+    // 拉取期间用户可能修改了配置，持有写锁基于最新配置合并，避免与 save_config 互相覆盖
+    let summary = {
+        let _guard = config_manager.lock_writes();
+        let mut config = config_manager
+            .load()
+            .map_err(|e| FetchError::other(e.to_string()))?;
+        let summary = merge_endpoints(&mut config.endpoints, upstream);
+        if summary.has_changes() {
+            config_manager
+                .save(&config)
+                .map_err(|e| FetchError::other(e.to_string()))?;
+        }
+        summary
+    };
+    if summary.has_changes() {
+        let _ = app_handle.emit("endpoints-updated", summary.clone());
+    }
+    Ok(summary)
+}
+
+/// 后台定期刷新订阅（未配置订阅时空转，配置后自动开始）
+#[cfg(feature = "tauri-runtime")]
+pub fn spawn_refresh_loop(app_handle: AppHandle, config_manager: ConfigManager) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        loop {
+            let config = config_manager.load().unwrap_or_default();
+            let wait = if config.subscription_url.is_none() {
+                IDLE_POLL_INTERVAL
+            } else {
+                match refresh(&app_handle, &config_manager).await {
+                    Ok(_) => refresh_interval(config.subscription_refresh_hours),
+                    Err(e) => {
+                        eprintln!("端点订阅刷新失败: {}", e.message);
+                        // 失败后按订阅源要求或最短间隔重试，不等满整个刷新周期
+                        e.retry_after
+                            .unwrap_or_else(|| refresh_interval(MIN_REFRESH_HOURS))
+                            .max(MIN_FETCH_GAP)
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    });
+}
+
+/// 拉取订阅列表（走更新检查的代理设置，HTTP 429/503 时返回服务端要求的等待时间）
+#[cfg(feature = "tauri-runtime")]
+pub async fn fetch_list(
+    url: &str,
+    proxy_setting: &str,
+    user_agent: &str,
+) -> Result<Vec<Endpoint>, FetchError> {
+    let builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .timeout(Duration::from_secs(15));
+    let client = crate::apply_proxy_setting(builder, proxy_setting)
+        .build()
+        .map_err(|e| FetchError::other(format!("创建 HTTP 客户端失败: {}", e)))?;
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| FetchError::other(format!("拉取订阅失败: {}", e)))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+    {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        return Err(FetchError {
+            message: format!("订阅源限流（HTTP {}），稍后重试", status.as_u16()),
+            retry_after,
+        });
+    }
+    if !status.is_success() {
+        return Err(FetchError::other(format!("订阅源返回 HTTP {}", status)));
+    }
+
+    // 先按 Content-Length 拒绝，再边读边累计，不会把超大响应整体读入内存
+    let too_large = || FetchError::other("订阅列表过大".into());
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_LIST_BYTES as u64)
+    {
+        return Err(too_large());
+    }
+    let mut response = response;
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| FetchError::other(format!("读取订阅内容失败: {}", e)))?
+    {
+        if body.len() + chunk.len() > MAX_LIST_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    parse_list(&String::from_utf8_lossy(&body)).map_err(FetchError::other)
+}
+
+/// 拉取订阅失败
+#[derive(Debug)]
+pub struct FetchError {
+    pub message: String,
+    /// 订阅源要求的重试等待时间（Retry-After）
+    pub retry_after: Option<Duration>,
+}
+
+impl FetchError {
+    fn other(message: String) -> Self {
+        Self {
+            message,
+            retry_after: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(domain: &str, subscribed: bool) -> Endpoint {
+        Endpoint {
+            name: domain.into(),
+            url: format!("https://{}", domain),
+            domain: domain.into(),
+            port: Some(DEFAULT_ENDPOINT_PORT),
+            subscribed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_list_accepts_both_formats_and_dedupes() {
+        let list = parse_list(
+            r#"[
+                {"name": "A", "url": "https://a.com/v1"},
+                {"url": "https://b.com", "domain": "B.com"},
+                {"url": "https://a.com"},
+                {"url": "ftp://c.com"},
+                {"url": "not a url"}
+            ]"#,
+        )
+        .unwrap();
+        let domains: Vec<&str> = list.iter().map(|e| e.domain.as_str()).collect();
+        assert_eq!(domains, ["a.com", "b.com"]);
+        assert!(list.iter().all(|e| e.subscribed && !e.enabled));
+        assert_eq!(list[1].name, "b.com");
+
+        let wrapped = parse_list(r#"{"endpoints": [{"url": "https://d.com"}]}"#).unwrap();
+        assert_eq!(wrapped[0].domain, "d.com");
+
+        assert!(parse_list("[]").is_err());
+        assert!(parse_list(r#"[{"url": "ftp://c.com"}]"#).is_err());
+        assert!(parse_list("<html>").is_err());
+    }
+
+    #[test]
+    fn test_merge_preserves_user_choices() {
+        let mut disabled = endpoint("kept.com", true);
+        disabled.enabled = false;
+        disabled.favorite = true;
+        disabled.timeout_multiplier = 2.0;
+        let mut local = vec![
+            endpoint("manual.com", false),
+            disabled,
+            endpoint("gone.com", true),
+        ];
+
+        let summary = merge_endpoints(
+            &mut local,
+            vec![
+                endpoint("kept.com", true),
+                endpoint("MANUAL.com", true),
+                endpoint("new.com", true),
+            ],
+        );
+
+        assert_eq!(summary.upstream_count, 3);
+        assert_eq!(summary.added, 1);
+        assert_eq!(summary.marked_removed, 1);
+        assert_eq!(summary.restored, 0);
+
+        let domains: Vec<&str> = local.iter().map(|e| e.domain.as_str()).collect();
+        assert_eq!(domains, ["manual.com", "kept.com", "gone.com", "new.com"]);
+        // 手动添加的端点保持为用户所有
+        assert!(!local[0].subscribed);
+        // 用户禁用、收藏与超时倍数不被订阅覆盖
+        assert!(!local[1].enabled && local[1].favorite);
+        assert_eq!(local[1].timeout_multiplier, 2.0);
+        // 订阅源移除的端点只标记不删除
+        assert!(local[2].removed_upstream);
+
+        // 再次出现时取消标记；重复刷新不重复计数
+        let summary = merge_endpoints(&mut local, vec![endpoint("gone.com", true)]);
+        assert_eq!(summary.restored, 1);
+        assert_eq!(summary.marked_removed, 2);
+        assert!(!local[2].removed_upstream);
+        assert!(local[1].removed_upstream && local[3].removed_upstream);
+        assert!(!local[0].removed_upstream);
+
+        let summary = merge_endpoints(&mut local, vec![endpoint("gone.com", true)]);
+        assert!(!summary.has_changes());
+    }
+
+    #[test]
+    fn test_fetch_slot_is_rate_limited() {
+        let start = Instant::now() + Duration::from_secs(3600);
+        reserve_fetch_slot(start).unwrap();
+        let wait = reserve_fetch_slot(start + Duration::from_secs(20)).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(40));
+        reserve_fetch_slot(start + MIN_FETCH_GAP).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_interval_is_clamped() {
+        assert_eq!(refresh_interval(0), Duration::from_secs(3600));
+        assert_eq!(refresh_interval(24), Duration::from_secs(24 * 3600));
+        assert_eq!(refresh_interval(10_000), Duration::from_secs(168 * 3600));
+    }
+}
@@ -0,0 +1,137 @@
+//! 托盘图标状态
+//! 根据持续优化状态在应用图标右下角叠加状态圆点，并更新提示文字：
+//! 未运行 = 原图标，运行且全部健康 = 绿点，运行但有端点失败 = 橙点，已暂停 = 灰点
+
+/// 托盘图标 ID（创建与更新时共用）
+pub const TRAY_ID: &str = "main";
+
+const HEALTHY_COLOR: [u8; 4] = [0x22, 0xc5, 0x5e, 0xff];
+const DEGRADED_COLOR: [u8; 4] = [0xf5, 0x9e, 0x0b, 0xff];
+const PAUSED_COLOR: [u8; 4] = [0x9c, 0xa3, 0xaf, 0xff];
+const BADGE_BORDER: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+/// 持续优化的健康状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayStatus {
+    /// 持续优化未运行
+    Idle,
+    /// 持续优化运行中：最近一轮检查中健康的端点数 / 检查的端点数
+    Running { healthy: usize, total: usize },
+    /// 持续优化已暂停（任务仍在，跳过检查）
+    Paused,
+}
+
+impl TrayStatus {
+    /// 状态圆点颜色（未运行时不叠加）
+    fn badge_color(self) -> Option<[u8; 4]> {
+        match self {
+            TrayStatus::Idle => None,
+            TrayStatus::Running { healthy, total } if healthy < total => Some(DEGRADED_COLOR),
+            TrayStatus::Running { .. } => Some(HEALTHY_COLOR),
+            TrayStatus::Paused => Some(PAUSED_COLOR),
+        }
+    }
+
+    pub fn tooltip(self) -> String {
+        match self {
+            TrayStatus::Idle => "anyFAST".to_string(),
+            TrayStatus::Running { total: 0, .. } => "anyFAST - 持续优化运行中".to_string(),
+            TrayStatus::Running { healthy, total } => {
+                format!("anyFAST - {}/{} 个端点健康", healthy, total)
+            }
+            TrayStatus::Paused => "anyFAST - 持续优化已暂停".to_string(),
+        }
+    }
+}
+
+/// 在 RGBA 图像右下角绘制带白边的圆点，返回新的像素数据
+fn overlay_badge(rgba: &[u8], width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+    let mut out = rgba.to_vec();
+    let size = width.min(height) as f64;
+    let radius = size * 0.22;
+    let border = (size * 0.05).max(1.0);
+    let cx = width as f64 - radius - border;
+    let cy = height as f64 - radius - border;
+
+    for y in 0..height {
+        for x in 0..width {
+            let dx = x as f64 + 0.5 - cx;
+            let dy = y as f64 + 0.5 - cy;
+            let dist = (dx * dx + dy * dy).sqrt();
+            let pixel = if dist <= radius {
+                color
+            } else if dist <= radius + border {
+                BADGE_BORDER
+            } else {
+                continue;
+            };
+            let offset = ((y * width + x) * 4) as usize;
+            if let Some(slot) = out.get_mut(offset..offset + 4) {
+                slot.copy_from_slice(&pixel);
+            }
+        }
+    }
+    out
+}
+
+/// 按状态更新托盘图标与提示文字（托盘不存在时忽略）
+#[cfg(feature = "tauri-runtime")]
+pub fn update(app: &tauri::AppHandle, status: TrayStatus) {
+    use tauri::image::Image;
+
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    if let Some(base) = app.default_window_icon() {
+        let icon = match status.badge_color() {
+            Some(color) => Image::new_owned(
+                overlay_badge(base.rgba(), base.width(), base.height(), color),
+                base.width(),
+                base.height(),
+            ),
+            None => base.clone().to_owned(),
+        };
+        if let Err(e) = tray.set_icon(Some(icon)) {
+            eprintln!("更新托盘图标失败: {}", e);
+        }
+    }
+    let _ = tray.set_tooltip(Some(status.tooltip()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_maps_to_badge_and_tooltip() {
+        assert_eq!(TrayStatus::Idle.badge_color(), None);
+        assert_eq!(TrayStatus::Idle.tooltip(), "anyFAST");
+
+        let healthy = TrayStatus::Running {
+            healthy: 3,
+            total: 3,
+        };
+        assert_eq!(healthy.badge_color(), Some(HEALTHY_COLOR));
+        assert_eq!(healthy.tooltip(), "anyFAST - 3/3 个端点健康");
+
+        let degraded = TrayStatus::Running {
+            healthy: 1,
+            total: 3,
+        };
+        assert_eq!(degraded.badge_color(), Some(DEGRADED_COLOR));
+        assert_eq!(degraded.tooltip(), "anyFAST - 1/3 个端点健康");
+
+        assert_eq!(TrayStatus::Paused.badge_color(), Some(PAUSED_COLOR));
+    }
+
+    #[test]
+    fn test_overlay_badge_only_touches_corner() {
+        let base = vec![0u8; 32 * 32 * 4];
+        let out = overlay_badge(&base, 32, 32, DEGRADED_COLOR);
+        assert_eq!(out.len(), base.len());
+
+        let pixel = |x: usize, y: usize| &out[(y * 32 + x) * 4..(y * 32 + x) * 4 + 4];
+        assert_eq!(pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(24, 24), DEGRADED_COLOR);
+    }
+}
@@ -0,0 +1,211 @@
+//! 主窗口位置与尺寸持久化
+//! 关闭到托盘或退出时记录窗口几何信息，启动时恢复；保存的位置已不在任何显示器上
+//! （如拔掉外接显示器）时移回主显示器居中
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// 标题栏至少要有这么大的区域落在某个显示器内，才认为窗口可见（可被拖动）
+const MIN_VISIBLE_WIDTH: i64 = 100;
+const MIN_VISIBLE_HEIGHT: i64 = 40;
+
+/// 窗口外框的物理像素位置与尺寸
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 显示器的物理像素区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonitorRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MonitorRect {
+    /// 与窗口标题栏区域的重叠是否足以操作窗口
+    fn shows_title_bar(&self, geometry: &WindowGeometry) -> bool {
+        let left = (geometry.x as i64).max(self.x as i64);
+        let right =
+            (geometry.x as i64 + geometry.width as i64).min(self.x as i64 + self.width as i64);
+        let top = (geometry.y as i64).max(self.y as i64);
+        let bottom =
+            (geometry.y as i64 + MIN_VISIBLE_HEIGHT).min(self.y as i64 + self.height as i64);
+        right - left >= MIN_VISIBLE_WIDTH && bottom - top >= MIN_VISIBLE_HEIGHT
+    }
+}
+
+/// 将保存的几何信息修正到可见区域：
+/// 标题栏仍在某个显示器内时原样返回，否则缩放到不超过第一个（主）显示器并居中
+pub fn clamp_to_monitors(geometry: WindowGeometry, monitors: &[MonitorRect]) -> WindowGeometry {
+    if monitors.is_empty() || monitors.iter().any(|m| m.shows_title_bar(&geometry)) {
+        return geometry;
+    }
+
+    let primary = monitors[0];
+    let width = geometry.width.min(primary.width);
+    let height = geometry.height.min(primary.height);
+    WindowGeometry {
+        x: primary.x + ((primary.width - width) / 2) as i32,
+        y: primary.y + ((primary.height - height) / 2) as i32,
+        width,
+        height,
+    }
+}
+
+pub struct WindowStateStore {
+    path: PathBuf,
+}
+
+impl WindowStateStore {
+    pub fn new() -> Self {
+        let path = crate::config::data_dir().join("window_state.json");
+
+        Self::with_path(path)
+    }
+
+    pub fn with_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// 读取保存的几何信息（文件缺失、损坏或尺寸为 0 时返回 None）
+    pub fn load(&self) -> Option<WindowGeometry> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str::<WindowGeometry>(&content)
+            .ok()
+            .filter(|g| g.width > 0 && g.height > 0)
+    }
+
+    pub fn save(&self, geometry: &WindowGeometry) -> Result<(), String> {
+        let content = serde_json::to_string(geometry).map_err(|e| e.to_string())?;
+        fs::write(&self.path, content).map_err(|e| e.to_string())
+    }
+}
+
+/// 记录窗口当前位置与尺寸（最小化时坐标无意义，跳过）
+#[cfg(feature = "tauri-runtime")]
+pub fn save(window: &tauri::WebviewWindow) {
+    if window.is_minimized().unwrap_or(false) {
+        return;
+    }
+    let (Ok(position), Ok(size)) = (window.outer_position(), window.outer_size()) else {
+        return;
+    };
+    let geometry = WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    if let Err(e) = WindowStateStore::new().save(&geometry) {
+        eprintln!("保存窗口位置失败: {}", e);
+    }
+}
+
+/// 按保存的几何信息恢复窗口（无记录时保持 tauri.conf.json 的默认值）
+#[cfg(feature = "tauri-runtime")]
+pub fn restore(window: &tauri::WebviewWindow) {
+    use tauri::{PhysicalPosition, PhysicalSize};
+
+    let Some(saved) = WindowStateStore::new().load() else {
+        return;
+    };
+    let mut monitors: Vec<MonitorRect> = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| MonitorRect {
+            x: m.position().x,
+            y: m.position().y,
+            width: m.size().width,
+            height: m.size().height,
+        })
+        .collect();
+    // 主显示器放在最前，作为离屏时的回退位置
+    if let Ok(Some(primary)) = window.primary_monitor() {
+        let position = *primary.position();
+        if let Some(index) = monitors
+            .iter()
+            .position(|m| m.x == position.x && m.y == position.y)
+        {
+            monitors.swap(0, index);
+        }
+    }
+
+    let geometry = clamp_to_monitors(saved, &monitors);
+    let _ = window.set_size(PhysicalSize::new(geometry.width, geometry.height));
+    let _ = window.set_position(PhysicalPosition::new(geometry.x, geometry.y));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PRIMARY: MonitorRect = MonitorRect {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+
+    fn geometry(x: i32, y: i32, width: u32, height: u32) -> WindowGeometry {
+        WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_clamp_keeps_visible_and_recenters_offscreen() {
+        let secondary = MonitorRect {
+            x: 1920,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        };
+
+        // 在任一显示器上可见：保持原样（部分超出屏幕边缘也允许）
+        let on_secondary = geometry(2000, 100, 960, 640);
+        assert_eq!(
+            clamp_to_monitors(on_secondary, &[PRIMARY, secondary]),
+            on_secondary
+        );
+        let partly_off = geometry(1500, 900, 960, 640);
+        assert_eq!(clamp_to_monitors(partly_off, &[PRIMARY]), partly_off);
+
+        // 外接显示器已拔掉：移回主显示器居中
+        assert_eq!(
+            clamp_to_monitors(on_secondary, &[PRIMARY]),
+            geometry(480, 220, 960, 640)
+        );
+
+        // 标题栏在屏幕上方之外也视为不可见；过大的窗口缩到显示器尺寸
+        assert_eq!(
+            clamp_to_monitors(geometry(100, -500, 2560, 1440), &[PRIMARY]),
+            geometry(0, 0, 1920, 1080)
+        );
+
+        // 无法获取显示器信息时不做修改
+        assert_eq!(clamp_to_monitors(on_secondary, &[]), on_secondary);
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let store = WindowStateStore::with_path(dir.path().join("window_state.json"));
+        assert_eq!(store.load(), None);
+
+        let saved = geometry(-1200, 50, 1024, 700);
+        store.save(&saved).unwrap();
+        assert_eq!(store.load(), Some(saved));
+    }
+}
@@ -42,6 +42,8 @@ describe('App', () => {
           return []
         case 'check_admin':
           return true
+        case 'is_process_elevated':
+          return true
         case 'get_permission_status':
           return { hasPermission: true, isUsingService: false }
         case 'refresh_service_status':
@@ -51,7 +53,7 @@ describe('App', () => {
         case 'start_speed_test':
           return mockResults
         case 'apply_all_endpoints':
-          return 1
+          return { count: 1, bindings: [], kept_count: 0, dry_run: false }
         case 'clear_all_bindings':
           return 0
         case 'get_current_results':
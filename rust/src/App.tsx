@@ -7,7 +7,7 @@ import { Settings } from './components/Settings'
 import { Logs } from './components/Logs'
 import { HistoryView } from './components/HistoryView'
 import { ToastContainer, ToastData, ToastType } from './components'
-import { Endpoint, EndpointResult, AppConfig, LogEntry, OptimizationEvent, TestProgressEvent } from './types'
+import { ApplyAllOutcome, Endpoint, EndpointResult, AppConfig, LogEntry, OptimizationEvent, TestProgressEvent, HealthCheckerRestart, HealthLoopStopped, SubscriptionSummary } from './types'
 
 type View = 'dashboard' | 'settings' | 'logs' | 'history'
 
@@ -181,10 +181,10 @@ function App() {
         }
       }
 
-      // Windows: check if running as admin (for showing install-service option)
+      // Windows: check if the GUI itself is elevated (for showing install-service option)
       if (!isMacOS) {
         try {
-          const adminStatus = await invoke<boolean>('check_admin')
+          const adminStatus = await invoke<boolean>('is_process_elevated')
           setIsRunningAsAdmin(adminStatus)
 
           // Auto-try installing service if running as admin but service not available
@@ -282,11 +282,46 @@ function App() {
         case 'check_complete':
           addLog('info', data.message)
           break
+        case 'external_modification':
+          showToast('warning', data.message)
+          addLog('warning', data.message)
+          break
       }
     })
     return () => { unlisten.then(fn => fn()) }
   }, [addLog, showToast, refreshBindingCount])
 
+  // 持续优化任务异常退出后被自动重启
+  useEffect(() => {
+    const unlisten = listen<HealthCheckerRestart>('health-checker-restarted', (event) => {
+      addLog('warning', `持续优化已自动重启（第 ${event.payload.restartCount} 次）：${event.payload.reason}`)
+    })
+    return () => { unlisten.then(fn => fn()) }
+  }, [addLog])
+
+  // 持续优化任务多次崩溃后停止
+  useEffect(() => {
+    const unlisten = listen<HealthLoopStopped>('health-loop-stopped', (event) => {
+      addLog('error', `持续优化已停止（已自动重启 ${event.payload.restartCount} 次）：${event.payload.reason}`)
+    })
+    return () => { unlisten.then(fn => fn()) }
+  }, [addLog])
+
+  // 端点订阅刷新后重新加载端点列表
+  useEffect(() => {
+    const unlisten = listen<SubscriptionSummary>('endpoints-updated', (event) => {
+      const { added, markedRemoved, restored } = event.payload
+      addLog('info', `端点订阅已更新：新增 ${added} 个（默认禁用），订阅源移除 ${markedRemoved} 个，恢复 ${restored} 个`)
+      invoke<AppConfig>('get_config')
+        .then((cfg) => {
+          setConfig(cfg)
+          setEndpoints(cfg.endpoints)
+        })
+        .catch(console.error)
+    })
+    return () => { unlisten.then(fn => fn()) }
+  }, [addLog])
+
   // 监听测速进度事件
   useEffect(() => {
     const unlisten = listen<TestProgressEvent>('test-progress', (event) => {
@@ -295,6 +330,17 @@ function App() {
     return () => { unlisten.then(fn => fn()) }
   }, [addLog])
 
+  // 测速被取消时重置进度显示
+  useEffect(() => {
+    const unlisten = listen('speed-test-cancelled', () => {
+      setIsRunning(false)
+      setTestingDomains(new Set())
+      setProgress({ current: 0, total: 0, message: '已取消' })
+      addLog('info', '测速已取消')
+    })
+    return () => { unlisten.then(fn => fn()) }
+  }, [addLog])
+
   const initializeApp = async () => {
     await loadConfig()
     await refreshBindingCount()
@@ -308,6 +354,19 @@ function App() {
       return
     }
 
+    // 应用更新后已安装的 Helper 可能与当前版本不一致，提示重新安装
+    if (isMacOS) {
+      try {
+        const outdated = await invoke<boolean>('is_macos_helper_outdated')
+        if (outdated) {
+          setShowAdminDialog(true)
+          addLog('warning', '已安装的 Helper 与当前版本不一致，请重新安装')
+        }
+      } catch (e) {
+        console.error('Failed to check helper version:', e)
+      }
+    }
+
     // 恢复已有的测速结果
     try {
       const currentResults = await invoke<EndpointResult[]>('get_current_results')
@@ -364,6 +423,8 @@ function App() {
       addLog('success', `测速完成：成功 ${successCount}/${enabledCount} 个`)
       showToast('success', `测速完成: ${successCount} 个可用`)
     } catch (e) {
+      // 用户取消：speed-test-cancelled 事件已处理提示
+      if (String(e) === '测速已取消') return
       console.error('Speed test failed:', e)
       setProgress({ current: 0, total: 0, message: `测速失败: ${e}` })
       addLog('error', `测速失败: ${e}`)
@@ -382,7 +443,18 @@ function App() {
     }
 
     try {
-      const count = await invoke<number>('apply_all_endpoints')
+      let count: number
+      try {
+        count = (await invoke<ApplyAllOutcome>('apply_all_endpoints')).count
+      } catch (e) {
+        // hosts 中的 anyFAST 区块被手动修改过，确认后强制覆盖
+        if (!String(e).includes('modified externally')) throw e
+        if (!confirm('hosts 中的 anyFAST 区块已被外部修改，是否覆盖？')) {
+          addLog('warning', '已取消绑定：anyFAST 区块被外部修改')
+          return
+        }
+        count = (await invoke<ApplyAllOutcome>('apply_all_endpoints', { force: true })).count
+      }
       await refreshBindingCount()
       addLog('success', `已绑定 ${count} 个端点`)
       showToast('success', `已绑定 ${count} 个端点`)
@@ -622,8 +622,14 @@ function LatencyComparison({ result, isTesting }: { result: EndpointResult; isTe
   }
 
   const latency = result.latency || 0
-  const latencyColor =
-    latency < 200
+  // 优先使用后端按配置阈值计算的评级
+  const latencyColor = result.rating
+    ? result.rating === 'good'
+      ? 'text-apple-green'
+      : result.rating === 'ok'
+        ? 'text-apple-gray-600'
+        : 'text-apple-orange'
+    : latency < 200
       ? 'text-apple-green'
       : latency < 500
         ? 'text-apple-gray-600'
@@ -76,6 +76,7 @@ export function Settings({
   const [customProxy, setCustomProxy] = useState('')
   const [proxyLoading, setProxyLoading] = useState(false)
   const [detectedProxy, setDetectedProxy] = useState<string | null>(null)
+  const [dataDir, setDataDir] = useState<string | null>(null)
 
   const initializedRef = useRef(false)
 
@@ -108,6 +109,11 @@ export function Settings({
     invoke<string>('get_current_version').then(setCurrentVersion).catch(console.error)
   }, [])
 
+  // 获取数据目录（配置、历史、日志所在位置）
+  useEffect(() => {
+    invoke<string>('get_data_dir').then(setDataDir).catch(console.error)
+  }, [])
+
   // 获取自启动状态
   useEffect(() => {
     invoke<boolean>('get_autostart')
@@ -482,6 +488,30 @@ export function Settings({
                 打开
               </button>
             </div>
+            <div className="flex items-center justify-between p-3 bg-apple-gray-50 rounded-xl">
+              <div className="flex-1 min-w-0 mr-3">
+                <span className="text-sm text-apple-gray-600">日志文件</span>
+                <p className="text-xs text-apple-gray-400 mt-0.5">测速日志，反馈问题时可附上</p>
+                {dataDir && (
+                  <p className="text-xs text-apple-gray-400 mt-0.5 truncate" title={dataDir}>
+                    数据目录：{dataDir}
+                  </p>
+                )}
+              </div>
+              <button
+                onClick={async () => {
+                  try {
+                    await invoke('open_log_file')
+                  } catch (e) {
+                    console.error('Failed to open log file:', e)
+                  }
+                }}
+                className="flex items-center gap-1.5 px-3 py-1.5 bg-apple-gray-200 text-apple-gray-600 text-sm font-medium rounded-xl hover:bg-apple-gray-300 transition-colors flex-shrink-0"
+              >
+                <ExternalLink className="w-4 h-4" />
+                查看
+              </button>
+            </div>
           </div>
         </Section>
 
@@ -98,11 +98,16 @@ export async function setupMockInvoke() {
       case 'start_speed_test':
         return mockResults
       case 'stop_speed_test':
-        return undefined
+        return false
       case 'apply_endpoint':
         return undefined
       case 'apply_all_endpoints':
-        return mockResults.filter((r) => r.success).length
+        return {
+          count: mockResults.filter((r) => r.success).length,
+          bindings: [],
+          kept_count: 0,
+          dry_run: false,
+        }
       case 'clear_all_bindings':
         return 2
       case 'get_bindings':
@@ -114,6 +119,8 @@ export async function setupMockInvoke() {
         return 2
       case 'check_admin':
         return true
+      case 'is_process_elevated':
+        return true
       case 'get_history_stats':
         return mockHistoryStats
       default:
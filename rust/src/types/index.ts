@@ -3,6 +3,12 @@ export interface Endpoint {
   url: string
   domain: string
   enabled: boolean
+  port?: number | null
+  favorite?: boolean
+  timeout_multiplier?: number
+  subscribed?: boolean
+  removed_upstream?: boolean
+  healthy_status_codes?: number[]
 }
 
 export interface EndpointResult {
@@ -17,6 +23,23 @@ export interface EndpointResult {
   original_latency: number
   speedup_percent: number
   use_original: boolean
+  throughput_kbps?: number
+  rating?: LatencyRating
+  error_kind?: ProbeErrorKind
+  protocol?: 'h2' | 'http/1.1'
+  backup_ips?: string[]
+  selection_reason?: string
+  skipped?: boolean
+  below_min_speedup?: boolean
+}
+
+export type LatencyRating = 'good' | 'ok' | 'poor'
+
+export type ProbeErrorKind = 'connect' | 'tls_handshake' | 'cert_invalid' | 'cert_mismatch'
+
+export interface LatencyThresholds {
+  good_ms: number
+  ok_ms: number
 }
 
 export interface AppConfig {
@@ -30,8 +53,87 @@ export interface AppConfig {
   continuous_mode: boolean
   test_aggressiveness: number
   update_proxy: string
+  latency_thresholds?: LatencyThresholds
+  cf_ip_cache_ttl_secs?: number
+  benchmark_concurrency?: number
+  max_endpoint_concurrency?: number | null
+  max_ip_concurrency?: number | null
+  max_test_ips?: number | null
+  cf_ip_source_url?: string | null
+  daily_summary?: boolean
+  measure_throughput?: boolean
+  throughput_path?: string
+  ip_version_preference?: 'auto' | 'ipv4' | 'ipv6'
+  proxy?: string | null
+  enable_http2?: boolean
+  cf_only?: boolean
+  skip_recently_failed_secs?: number
+  min_speedup_percent?: number
+  periodic_retest_hours?: number
+  deconflict_hosts?: boolean
+  clear_hosts_on_quit?: boolean
+  clear_binding_on_disable?: boolean
+  ips_per_domain?: number
+  history_retention_days?: number
+  notify_on_switch?: boolean
+  severe_abs_threshold_ms?: number
+  full_test_cooldown_secs?: number
+  switch_min_improvement_percent?: number
+  switch_min_improvement_ms?: number
+  check_jitter_percent?: number
+  health_loop_max_restarts?: number
+  subscription_url?: string | null
+  subscription_refresh_hours?: number
+  user_agent?: string | null
+}
+
+export interface ConfigImportSummary {
+  imported_endpoints: number
+  skipped_duplicates: number
+}
+
+export interface LastApplyInfo {
+  timestamp: number
+  count: number
+}
+
+export type ResultSortKey = 'latency' | 'speedup' | 'name' | 'domain'
+
+export interface BindingWriteOutcome {
+  domain: string
+  ok: boolean
+  error?: string
+}
+
+export interface ApplyAllOutcome {
+  count: number
+  bindings: [string, string][]
+  kept_count: number
+  dry_run: boolean
+}
+
+export interface BindingsImportSummary {
+  imported_bindings: number
+  changed_bindings: number
+  skipped_invalid: number
 }
 
+export interface ConfigIssue {
+  domain?: string | null
+  severity: 'error' | 'warning'
+  message: string
+}
+
+export interface DnsBenchmarkResult {
+  server: string
+  latency_ms: number
+  ips: string[]
+  error?: string
+}
+
+/** 解析器地址 → 该解析器返回的 IP 列表（无响应时为空数组） */
+export type MultiDnsDiagnosis = Record<string, string[]>
+
 export interface Progress {
   current: number
   total: number
@@ -61,13 +163,87 @@ export interface HistoryStats {
   records: HistoryRecord[]
 }
 
+export interface UrlProbeResult {
+  ip: string
+  dns_ms: number
+  connect_ms: number
+  ttfb_ms: number
+  status?: number | null
+}
+
+export interface HistoryInfo {
+  record_count: number
+  file_size_bytes: number
+  oldest_timestamp?: number | null
+  newest_timestamp?: number | null
+}
+
+export interface DomainHistory {
+  domain: string
+  min_latency: number
+  max_latency: number
+  avg_latency: number
+  records: HistoryRecord[]
+}
+
+export interface DailyHistory {
+  date: string
+  test_count: number
+  avg_speedup_percent: number
+  total_speedup_ms: number
+}
+
 // ===== Service 相关类型 =====
 
+export interface CheckIntervalSuggestion {
+  suggested_secs: number
+  rationale: string
+}
+
+export interface ApplyMeasurement {
+  domain: string
+  before_ip: string
+  applied_ip: string
+  before_ms: number | null
+  after_ms: number | null
+  improvement_percent: number | null
+}
+
+export interface NetworkInfo {
+  default_gateway?: string | null
+  default_interface?: string | null
+  dns_servers: string[]
+  vpn_detected: boolean
+  vpn_interfaces: string[]
+}
+
 export interface PermissionStatus {
   hasPermission: boolean
   isUsingService: boolean
 }
 
+export type ServicePipeFailure = 'timeout' | 'access_denied' | 'not_running' | 'other'
+
+export interface ServicePipeAccess {
+  accessible: boolean
+  reason?: ServicePipeFailure | null
+  detail?: string | null
+  serviceVersion?: string | null
+}
+
+export interface HostsEncodingReport {
+  encoding: string
+  supported: boolean
+  message?: string | null
+}
+
+export interface Capabilities {
+  platform: string
+  commands: string[]
+  service_available: boolean
+  helper_available: boolean
+}
+
 // ===== 更新排查诊断 =====
 
 export interface DiagnosticStep {
@@ -90,7 +266,7 @@ export interface UpdateInfo {
 // ===== 持续优化事件 =====
 
 export interface OptimizationEvent {
-  eventType: 'auto_switch' | 'check_complete' | 'started' | 'stopped'
+  eventType: 'auto_switch' | 'check_complete' | 'started' | 'stopped' | 'external_modification'
   domain?: string
   oldIp?: string
   newIp?: string
@@ -107,3 +283,57 @@ export interface TestProgressEvent {
   endpointName?: string
   message: string
 }
+
+// 流式测速：单个端点结果（speed-test-result 事件）
+export interface SpeedTestResultEvent {
+  runId: number
+  result: EndpointResult
+}
+
+// 流式测速结束（speed-test-finished 事件）
+export interface SpeedTestFinishedEvent {
+  runId: number
+  cancelled: boolean
+  resultCount: number
+  error?: string | null
+}
+
+// 定时全量重测完成（periodic-retest-done 事件）
+export interface PeriodicRetestEvent {
+  applied: number
+  error?: string | null
+}
+
+// 持续优化运行/暂停状态
+export interface OptimizationStatus {
+  isRunning: boolean
+  isPaused: boolean
+}
+
+// 持续优化任务被看门狗重启（health-checker-restarted 事件）
+export interface HealthCheckerRestart {
+  reason: string
+  restartCount: number
+}
+
+// 持续优化任务异常退出且不再重启（health-loop-stopped 事件）
+export interface HealthLoopStopped {
+  reason: string
+  restartCount: number
+}
+
+// 持续优化每日汇总（daily-summary 事件）
+export interface DailySummary {
+  date: string
+  checks: number
+  switches: number
+  avgLatency: Record<string, number>
+}
+
+// 端点订阅刷新结果（endpoints-updated 事件）
+export interface SubscriptionSummary {
+  upstreamCount: number
+  added: number
+  markedRemoved: number
+  restored: number
+}